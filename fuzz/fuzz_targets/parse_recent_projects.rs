@@ -0,0 +1,23 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// The main crate is a binary, so include the parser module directly.
+#[path = "../../src/recents.rs"]
+#[allow(dead_code)]
+mod recents;
+
+fuzz_target!(|data: &[u8]| {
+    // We only care about panics; errors on malformed input are fine.
+    let _ = recents::parse_recent_jetbrains_projects(
+        "/home/fuzz",
+        data,
+        &recents::Limits::default(),
+    );
+});
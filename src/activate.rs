@@ -0,0 +1,146 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Activate search results with additional options.
+//!
+//! Gnome Shell only passes terms and a timestamp when activating a result; companion extensions
+//! can call the extended method in this module instead, to pass additional options.
+
+use std::collections::HashMap;
+
+use tracing::{event, instrument, Level};
+use zbus::{interface, message::Header, zvariant::OwnedValue, ObjectServer};
+
+use crate::searchprovider::provider_at;
+
+/// Options for activating a result.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ActivationOptions {
+    /// Whether to open the project in a new window.
+    pub new_window: bool,
+    /// A file within the project to open, and a line in this file.
+    pub target_line: Option<(String, u32)>,
+    /// The timestamp of the user interaction which activated the result.
+    pub timestamp: Option<u32>,
+    /// The search terms which found the result.
+    pub terms: Vec<String>,
+}
+
+impl TryFrom<HashMap<String, OwnedValue>> for ActivationOptions {
+    type Error = zbus::fdo::Error;
+
+    /// Parse activation options from a DBus dictionary.
+    ///
+    /// Supports `new_window` of type `b`, `target_line` of type `(su)`, `timestamp` of type `u`,
+    /// and `terms` of type `as`; fail on all other options, to let callers detect when they use options we don't
+    /// support yet.
+    fn try_from(options: HashMap<String, OwnedValue>) -> Result<Self, Self::Error> {
+        let mut parsed = Self::default();
+        for (name, value) in options {
+            let invalid = |error: zbus::zvariant::Error| {
+                zbus::fdo::Error::InvalidArgs(format!("Invalid value for option {name}: {error}"))
+            };
+            match name.as_str() {
+                "new_window" => parsed.new_window = bool::try_from(value).map_err(invalid)?,
+                "target_line" => {
+                    parsed.target_line = Some(<(String, u32)>::try_from(value).map_err(invalid)?)
+                }
+                "timestamp" => parsed.timestamp = Some(u32::try_from(value).map_err(invalid)?),
+                "terms" => parsed.terms = <Vec<String>>::try_from(value).map_err(invalid)?,
+                _ => {
+                    return Err(zbus::fdo::Error::InvalidArgs(format!(
+                        "Unsupported option {name}"
+                    )))
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Activate results of the search provider at the same object path with additional options.
+#[derive(Debug, Default)]
+pub struct ActivateResultEx;
+
+#[interface(name = "de.swsnr.searchprovider.ActivateResultEx")]
+impl ActivateResultEx {
+    /// Activate the result with the given `item_id`, with additional `options`.
+    ///
+    /// See [`ActivationOptions`] for supported options.
+    #[instrument(skip(self, server, connection, header))]
+    async fn activate_result_ex(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: Header<'_>,
+        item_id: &str,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<()> {
+        let options = ActivationOptions::try_from(options)?;
+        let interface = provider_at(server, &header).await?;
+        let mut provider = interface.get_mut().await;
+        event!(
+            Level::DEBUG,
+            item_id,
+            "Activating result {item_id} of {} with {options:?}",
+            provider.app().id()
+        );
+        provider.activate_result_with_options(connection, item_id, &options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+    use zbus::zvariant::Value;
+
+    fn options(options: Vec<(&str, Value<'_>)>) -> HashMap<String, OwnedValue> {
+        options
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_activation_options() {
+        assert_eq!(
+            ActivationOptions::try_from(HashMap::new()).unwrap(),
+            ActivationOptions::default()
+        );
+        assert_eq!(
+            ActivationOptions::try_from(options(vec![
+                ("new_window", Value::from(true)),
+                (
+                    "target_line",
+                    Value::from(("/home/foo/mdcat/src/main.rs", 42u32))
+                ),
+                ("timestamp", Value::from(1234u32)),
+                ("terms", Value::from(vec!["mdcat", "render"])),
+            ]))
+            .unwrap(),
+            ActivationOptions {
+                new_window: true,
+                target_line: Some(("/home/foo/mdcat/src/main.rs".to_string(), 42)),
+                timestamp: Some(1234),
+                terms: vec!["mdcat".to_string(), "render".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn reject_invalid_activation_options() {
+        let error = ActivationOptions::try_from(options(vec![("new_window", Value::from(1u32))]))
+            .unwrap_err();
+        assert!(matches!(error, zbus::fdo::Error::InvalidArgs(_)));
+        let error =
+            ActivationOptions::try_from(options(vec![("foo", Value::from(true))])).unwrap_err();
+        assert_eq!(
+            error,
+            zbus::fdo::Error::InvalidArgs("Unsupported option foo".to_string())
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Versions of our own DBus interfaces.
+//!
+//! Besides the standard search provider interface this service has a few interfaces of its own,
+//! for extensions and scripts.  To let these consumers evolve without breakage we follow these
+//! rules:
+//!
+//! - Adding methods, properties or options to an interface increments the version of the
+//!   interface in [`INTERFACES`].
+//! - Renaming a method keeps the old name as a shim which calls the new method, and records the
+//!   rename in [`DEPRECATIONS`].
+//! - Removing deprecated methods, or changing signatures of existing methods, increments
+//!   [`API_VERSION`].
+//!
+//! Consumers check the properties of `de.swsnr.searchprovider.Manager` on `/` to find out what
+//! this service supports.
+
+use zbus::interface;
+
+/// The version of the API of this service.
+///
+/// Changes incompatibly only if we remove deprecated methods or change method signatures.
+pub const API_VERSION: u32 = 1;
+
+/// Our own interfaces, with their current version.
+pub const INTERFACES: &[(&str, u32)] = &[
+    ("de.swsnr.searchprovider.Manager", 1),
+    ("de.swsnr.searchprovider.ReloadAll", 1),
+    ("de.swsnr.searchprovider.Capabilities", 1),
+    ("de.swsnr.searchprovider.ActivateResultEx", 1),
+    ("de.swsnr.searchprovider.Score", 1),
+    ("de.swsnr.searchprovider.Stats", 1),
+];
+
+/// A deprecated member of one of our interfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The interface of the deprecated member.
+    pub interface: &'static str,
+    /// The name of the deprecated member.
+    pub member: &'static str,
+    /// The member which replaces the deprecated member.
+    pub replacement: &'static str,
+    /// The API version which removes the deprecated member.
+    pub removed_in: u32,
+}
+
+/// All deprecated members which we still support.
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// Describe the API of this service.
+#[derive(Debug, Default)]
+pub struct Manager;
+
+#[interface(name = "de.swsnr.searchprovider.Manager")]
+impl Manager {
+    /// The version of the API of this service.
+    #[zbus(property)]
+    fn api_version(&self) -> u32 {
+        API_VERSION
+    }
+
+    /// The versions of all interfaces of this service, by interface name.
+    #[zbus(property)]
+    fn interface_versions(&self) -> std::collections::HashMap<&str, u32> {
+        INTERFACES.iter().copied().collect()
+    }
+
+    /// Deprecated members with interface name, member name, replacement, and the API version
+    /// which removes the member.
+    #[zbus(property)]
+    fn deprecations(&self) -> Vec<(&str, &str, &str, u32)> {
+        DEPRECATIONS
+            .iter()
+            .map(|d| (d.interface, d.member, d.replacement, d.removed_in))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deprecations_refer_to_known_interfaces_and_future_versions() {
+        for deprecation in DEPRECATIONS {
+            assert!(
+                INTERFACES
+                    .iter()
+                    .any(|(name, _)| *name == deprecation.interface),
+                "{deprecation:?}"
+            );
+            assert!(API_VERSION < deprecation.removed_in, "{deprecation:?}");
+        }
+    }
+
+    #[test]
+    fn interfaces_are_unique() {
+        let mut names = INTERFACES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), INTERFACES.len());
+    }
+}
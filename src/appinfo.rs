@@ -0,0 +1,117 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Cached lookups of desktop app infos.
+//!
+//! Looking up a desktop app info parses its desktop file, so we cache app infos and their
+//! icons per app ID.  We drop the whole cache whenever Gio tells us that the set of installed
+//! apps changed, e.g. after an IDE update through the Toolbox, and whenever the user switches
+//! icon themes, to look up icons afresh.
+//!
+//! App infos are not thread-safe, so the cache is per thread.  In practice we only ever look up
+//! apps on the main thread, because the DBus connection runs on the glib mainloop.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use tracing::{event, Level};
+
+use crate::searchprovider::AppId;
+
+/// A cached app info.
+#[derive(Debug, Clone)]
+struct CachedApp {
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    info: gio::DesktopAppInfo,
+    icon: Option<String>,
+}
+
+/// The app info cache of a thread.
+#[derive(Debug)]
+struct Cache {
+    /// The monitor to invalidate the cache; we must keep it alive to receive change signals.
+    _monitor: gio::AppInfoMonitor,
+    apps: HashMap<String, CachedApp>,
+}
+
+thread_local! {
+    static CACHE: RefCell<Option<Cache>> = const { RefCell::new(None) };
+}
+
+/// The settings schema of Gnome for the desktop interface, including the icon theme.
+const INTERFACE_SCHEMA: &str = "org.gnome.desktop.interface";
+
+/// Clear the app info cache of the current thread, because of `reason`.
+fn clear_cache(reason: &str) {
+    event!(Level::DEBUG, "{reason}, clearing app info cache");
+    CACHE.with(|cache| {
+        if let Some(cache) = cache.borrow_mut().as_mut() {
+            cache.apps.clear();
+        }
+    });
+}
+
+/// Get the cached app for `app_id`, or look it up.
+fn get_or_lookup(app_id: &AppId) -> Result<CachedApp, glib::Error> {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let cache = cache.get_or_insert_with(|| {
+            let monitor = gio::AppInfoMonitor::get();
+            monitor.connect_changed(|_| clear_cache("Installed apps changed"));
+            Cache {
+                _monitor: monitor,
+                apps: HashMap::new(),
+            }
+        });
+        if let Some(app) = cache.apps.get(&app_id.to_string()) {
+            event!(Level::TRACE, %app_id, "Using cached app info for {app_id}");
+            Ok(app.clone())
+        } else {
+            event!(Level::DEBUG, %app_id, "Looking up app info for {app_id}");
+            let info = gio::DesktopAppInfo::try_from(app_id)?;
+            let icon = info
+                .icon()
+                .and_then(|icon| IconExt::to_string(&icon))
+                .map(|icon| icon.to_string());
+            let app = CachedApp { info, icon };
+            cache.apps.insert(app_id.to_string(), app.clone());
+            Ok(app)
+        }
+    })
+}
+
+/// Look up the desktop app info for `app_id`.
+#[cfg(feature = "launch")]
+pub fn lookup(app_id: &AppId) -> Result<gio::DesktopAppInfo, glib::Error> {
+    get_or_lookup(app_id).map(|app| app.info)
+}
+
+/// Look up the icon of the app with `app_id`, as serialized string.
+///
+/// Return `None` if the app doesn't exist or has no icon.
+pub fn icon(app_id: &AppId) -> Option<String> {
+    get_or_lookup(app_id).ok().and_then(|app| app.icon)
+}
+
+/// Look up icons afresh whenever the user switches icon themes.
+///
+/// Return the underlying settings; the caller must keep them alive as long as it wants to track
+/// the icon theme.  Return `None` if the settings schema of Gnome is not installed.
+pub fn track_icon_theme() -> Option<gio::Settings> {
+    let Some(schema) = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(INTERFACE_SCHEMA, true))
+    else {
+        event!(
+            Level::DEBUG,
+            "Settings schema {INTERFACE_SCHEMA} not found, not tracking icon theme"
+        );
+        return None;
+    };
+    let settings = gio::Settings::new_full(&schema, None::<&gio::SettingsBackend>, None);
+    settings.connect_changed(Some("icon-theme"), |_, _| clear_cache("Icon theme changed"));
+    Some(settings)
+}
@@ -0,0 +1,192 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Show this service in the background apps of Gnome.
+//!
+//! The background portal lets apps running without windows tell the user what they do, and
+//! Gnome Shell quits background apps through the `quit` action of their `org.gtk.Actions`
+//! interface.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tracing::instrument;
+use zbus::zvariant::{OwnedValue, Signature, Value};
+use zbus::{interface, proxy};
+
+use crate::shutdown::Shutdown;
+use crate::timeout::with_timeout;
+
+/// The maximum length of a status message, according to the portal documentation.
+const MAX_STATUS_LENGTH: usize = 96;
+
+/// The background portal.
+///
+/// See <https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Background.html>.
+#[proxy(
+    interface = "org.freedesktop.portal.Background",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Background {
+    /// Set the status of the calling app.
+    fn set_status(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    /// The version of the portal.
+    #[zbus(property)]
+    fn version(&self) -> zbus::Result<u32>;
+}
+
+/// The status message for a service with `providers` search providers.
+pub fn status_message(providers: usize) -> String {
+    let message = match providers {
+        1 => "Serving recent projects of 1 IDE".to_string(),
+        n => format!("Serving recent projects of {n} IDEs"),
+    };
+    message.chars().take(MAX_STATUS_LENGTH).collect()
+}
+
+/// Set the status `message` of this service in the background portal.
+///
+/// Fail if the portal is not available, e.g. because Gnome only shows the status of sandboxed
+/// apps, or if it doesn't reply within `call_timeout`.
+#[instrument(skip(connection))]
+pub async fn set_status(
+    connection: &zbus::Connection,
+    message: &str,
+    call_timeout: Duration,
+) -> zbus::Result<()> {
+    with_timeout(call_timeout, async {
+        let portal = BackgroundProxy::new(connection).await?;
+        // SetStatus appeared in version 2 of the portal
+        let version = portal.version().await?;
+        if version < 2 {
+            return Err(zbus::Error::Unsupported);
+        }
+        portal
+            .set_status(HashMap::from([("message", Value::from(message))]))
+            .await
+    })
+    .await
+}
+
+/// The object path for the actions of the service with the given `busname`.
+///
+/// Gnome Shell looks for actions of an app at the object path corresponding to its bus name.
+pub fn actions_path(busname: &str) -> String {
+    format!("/{}", busname.replace('.', "/"))
+}
+
+/// The `org.gtk.Actions` interface of this service.
+///
+/// Provides a single `quit` action, which shuts the service down, just like `SIGTERM` does.
+#[derive(Debug)]
+pub struct Actions {
+    shutdown: Shutdown,
+}
+
+impl Actions {
+    /// The only action we provide.
+    const QUIT: &'static str = "quit";
+
+    /// Create actions to `shutdown` the service.
+    pub fn new(shutdown: Shutdown) -> Self {
+        Self { shutdown }
+    }
+}
+
+#[interface(name = "org.gtk.Actions")]
+impl Actions {
+    /// List the names of all actions.
+    fn list(&self) -> Vec<&str> {
+        vec![Self::QUIT]
+    }
+
+    /// Describe the action `action_name`.
+    ///
+    /// Return whether the action is enabled, the type of its parameter, and its state.
+    fn describe(
+        &self,
+        action_name: &str,
+    ) -> zbus::fdo::Result<(bool, Signature<'static>, Vec<OwnedValue>)> {
+        if action_name == Self::QUIT {
+            Ok((true, Signature::from_static_str_unchecked(""), Vec::new()))
+        } else {
+            Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Unknown action {action_name}"
+            )))
+        }
+    }
+
+    /// Describe all actions.
+    fn describe_all(&self) -> HashMap<&str, (bool, Signature<'static>, Vec<OwnedValue>)> {
+        HashMap::from([(
+            Self::QUIT,
+            (true, Signature::from_static_str_unchecked(""), Vec::new()),
+        )])
+    }
+
+    /// Activate the action `action_name`.
+    #[instrument(skip(self, _parameter, _platform_data))]
+    fn activate(
+        &self,
+        action_name: &str,
+        _parameter: Vec<OwnedValue>,
+        _platform_data: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<()> {
+        if action_name == Self::QUIT {
+            self.shutdown.request("Quit action activated");
+            Ok(())
+        } else {
+            Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Unknown action {action_name}"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn status_message_for_providers() {
+        assert_eq!(status_message(1), "Serving recent projects of 1 IDE");
+        assert_eq!(status_message(5), "Serving recent projects of 5 IDEs");
+        assert!(status_message(usize::MAX).chars().count() <= MAX_STATUS_LENGTH);
+    }
+
+    #[test]
+    fn actions_path_of_busname() {
+        assert_eq!(
+            actions_path("de.swsnr.searchprovider.Jetbrains"),
+            "/de/swsnr/searchprovider/Jetbrains"
+        );
+    }
+
+    #[test]
+    fn quit_action_quits_mainloop() {
+        let context = glib::MainContext::new();
+        let mainloop = glib::MainLoop::new(Some(&context), false);
+        let actions = Actions::new(Shutdown::new(
+            mainloop.clone(),
+            "de.swsnr.searchprovider.Test".to_string(),
+            crate::shutdown::SHUTDOWN_TIMEOUT,
+        ));
+        assert_eq!(actions.list(), vec!["quit"]);
+        assert!(actions.describe("quit").unwrap().0);
+        assert!(actions.describe("foo").is_err());
+        assert!(actions.activate("foo", Vec::new(), HashMap::new()).is_err());
+        context.spawn_local(async move {
+            actions
+                .activate("quit", Vec::new(), HashMap::new())
+                .unwrap();
+        });
+        // Returns only if the action quit the mainloop
+        mainloop.run();
+    }
+}
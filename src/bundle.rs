@@ -0,0 +1,442 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Collect information for bug reports into a debug bundle.
+//!
+//! The bundle contains no project names or paths: we replace them with hashes, so that
+//! maintainers can still tell projects apart.  We also hash all other paths below the home
+//! directory, and replace the name of the user.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use regex::{Captures, Regex};
+
+use crate::dump::{to_table, DumpedProvider};
+use crate::validate::{Report, SYSTEMD_UNIT};
+
+/// The number of recent journal lines of the service to include.
+const JOURNAL_LINES: u32 = 500;
+
+/// The maximum number of bytes to include of every recent projects file.
+const MAX_SNIPPET_SIZE: u64 = 64 * 1024;
+
+/// The files in a debug bundle, with a description for each.
+pub const CONTENTS: &[(&str, &str)] = &[
+    ("version.txt", "the version and features of this service"),
+    ("validate-install.txt", "the output of validate-install"),
+    (
+        "projects.txt",
+        "the recent projects of all providers, with names and paths hashed",
+    ),
+    (
+        "recent-projects.txt",
+        "the beginning of all recent projects files, with names and paths hashed",
+    ),
+    (
+        "journal.txt",
+        "recent log messages of the service, with names and paths hashed",
+    ),
+];
+
+/// Hash `s` into a short identifier.
+fn hash(s: &str) -> String {
+    let mut digest = glib::compute_checksum_for_data(glib::ChecksumType::Sha256, s.as_bytes())
+        .unwrap()
+        .to_string();
+    digest.truncate(12);
+    digest
+}
+
+/// Replace names and directories of projects of all `providers` with hashes.
+///
+/// Replace warnings with their number, since they contain paths of recent projects.  Replace the
+/// home directory in paths of recent projects files with `~`, to omit the name of the user.
+pub fn sanitize_providers(providers: &mut [DumpedProvider]) {
+    let home = glib::home_dir();
+    for provider in providers {
+        for file in &mut provider.files {
+            if let Ok(rest) = Path::new(file).strip_prefix(&home) {
+                *file = Path::new("~").join(rest).display().to_string();
+            }
+        }
+        for project in &mut provider.projects {
+            project.id = hash(&project.id);
+            project.name = hash(&project.name);
+            project.directory = hash(&project.directory);
+        }
+        let warnings = provider.warnings.len();
+        provider.warnings = if warnings == 0 {
+            Vec::new()
+        } else {
+            vec![format!("{warnings} warning(s) omitted")]
+        };
+    }
+}
+
+/// Redacts names and paths of the current user in text.
+#[derive(Debug)]
+struct Redactions {
+    /// Project directories, longest first.
+    directories: Vec<String>,
+    /// Well-known directories below the home directory, with their replacements.
+    known_directories: Vec<(String, &'static str)>,
+    /// Matches all paths in the home directory.
+    home: Regex,
+    /// Matches the user name and all project names.
+    names: Option<Regex>,
+    /// The user name.
+    user: String,
+}
+
+impl Redactions {
+    /// Redact project names and directories of `providers` and paths below `home`, as well as
+    /// the `user` name.
+    fn new(providers: &[DumpedProvider], home: &Path, user: &str) -> Self {
+        let mut directories = providers
+            .iter()
+            .flat_map(|p| p.projects.iter().map(|project| project.directory.clone()))
+            .collect::<Vec<_>>();
+        // Replace longer directories first, so that nested directories get their own hash.
+        directories.sort_unstable_by_key(|d| std::cmp::Reverse(d.len()));
+        let known_directories = [
+            (glib::user_config_dir(), "$XDG_CONFIG_HOME"),
+            (glib::user_data_dir(), "$XDG_DATA_HOME"),
+            (glib::user_cache_dir(), "$XDG_CACHE_HOME"),
+        ]
+        .into_iter()
+        .filter(|(directory, _)| directory.starts_with(home) && directory != home)
+        .map(|(directory, name)| (directory.display().to_string(), name))
+        .collect();
+        let home = Regex::new(&format!(
+            "{}([^ \t\r\n\"'<>,;()]*)",
+            regex::escape(&home.display().to_string())
+        ))
+        .unwrap();
+        let mut names = providers
+            .iter()
+            .flat_map(|p| p.projects.iter().map(|project| project.name.as_str()))
+            .chain(std::iter::once(user))
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        // Match longer names first, so that names containing other names get their own hash.
+        names.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        names.dedup();
+        let names = (!names.is_empty()).then(|| {
+            let names = names
+                .into_iter()
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join("|");
+            Regex::new(&format!("(?-u:\\b)(?:{names})(?-u:\\b)")).unwrap()
+        });
+        Self {
+            directories,
+            known_directories,
+            home,
+            names,
+            user: user.to_string(),
+        }
+    }
+
+    /// Redact `text`.
+    ///
+    /// Replace project directories and all other paths below the home directory with hashes, but
+    /// keep paths in well-known directories such as `$XDG_CONFIG_HOME`, since they are useful for
+    /// diagnosis and contain no names of the user.  Then replace the user name and project names.
+    fn redact(&self, text: &str) -> String {
+        let text = self
+            .directories
+            .iter()
+            .fold(text.to_string(), |text, directory| {
+                text.replace(directory, &format!("<{}>", hash(directory)))
+            });
+        let text = self
+            .known_directories
+            .iter()
+            .fold(text, |text, (directory, name)| {
+                text.replace(directory, name)
+            });
+        let text = self.home.replace_all(&text, |captures: &Captures| {
+            let rest = &captures[1];
+            if rest.is_empty() {
+                "~".to_string()
+            } else if rest.starts_with('/') {
+                format!("~/<{}>", hash(rest))
+            } else {
+                // Another directory which just starts with the home directory
+                format!("<{}>", hash(&captures[0]))
+            }
+        });
+        match &self.names {
+            Some(names) => names
+                .replace_all(&text, |captures: &Captures| {
+                    if captures[0] == self.user {
+                        "<user>".to_string()
+                    } else {
+                        format!("<{}>", hash(&captures[0]))
+                    }
+                })
+                .into_owned(),
+            None => text.into_owned(),
+        }
+    }
+}
+
+/// Redact names and paths in `text`.
+///
+/// Replace all project names and directories of `providers` and all other paths below the home
+/// directory with hashes, and replace the name of the user.
+///
+/// Expects providers which were not sanitized yet.
+pub fn sanitize_text(text: &str, providers: &[DumpedProvider]) -> String {
+    Redactions::new(
+        providers,
+        &glib::home_dir(),
+        &glib::user_name().to_string_lossy(),
+    )
+    .redact(text)
+}
+
+/// Get the beginning of all recent projects files of `providers`, redacted.
+///
+/// Expand `$USER_HOME$` in these files, so that hashes of project directories match the hashes
+/// in the other files of the bundle.
+///
+/// Expects providers which were not sanitized yet.
+fn recent_projects_snippets(providers: &[DumpedProvider]) -> String {
+    let home = glib::home_dir();
+    let redactions = Redactions::new(providers, &home, &glib::user_name().to_string_lossy());
+    let mut files = providers
+        .iter()
+        .flat_map(|provider| provider.files.iter().map(PathBuf::from))
+        .collect::<Vec<_>>();
+    files.dedup();
+    files
+        .into_iter()
+        .map(|file| {
+            let mut contents = Vec::new();
+            let result = std::fs::File::open(&file)
+                .and_then(|source| source.take(MAX_SNIPPET_SIZE).read_to_end(&mut contents));
+            let contents = match result {
+                Ok(_) => String::from_utf8_lossy(&contents)
+                    .replace("$USER_HOME$", &home.display().to_string()),
+                Err(error) => format!("Failed to read file: {error}\n"),
+            };
+            format!(
+                "==> {} <==\n{}\n",
+                redactions.redact(&file.display().to_string()),
+                redactions.redact(&contents)
+            )
+        })
+        .collect()
+}
+
+/// Describe the version and enabled features of this service.
+fn version_info() -> String {
+    let features = [
+        ("launch", cfg!(feature = "launch")),
+        ("log-bridge", cfg!(feature = "log-bridge")),
+        ("prometheus", cfg!(feature = "prometheus")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| feature)
+    .collect::<Vec<_>>();
+    format!(
+        "{} {}\nfeatures: {}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        features.join(", ")
+    )
+}
+
+/// Get recent journal lines of the service.
+///
+/// Return the error instead, if reading the journal failed.
+fn recent_journal() -> String {
+    let output = Command::new("journalctl")
+        .args(["--user", "--unit", SYSTEMD_UNIT, "--no-pager", "--lines"])
+        .arg(JOURNAL_LINES.to_string())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(output) => format!(
+            "journalctl failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(error) => format!("Failed to run journalctl: {error}"),
+    }
+}
+
+/// Write a debug bundle with the given installation `report` and `providers` to `target`.
+///
+/// Create a gzipped tarball with `tar`.
+pub fn write_bundle(
+    target: &Path,
+    report: &Report,
+    mut providers: Vec<DumpedProvider>,
+) -> Result<()> {
+    let directory = glib::mkdtemp(std::env::temp_dir().join("debug-bundle-XXXXXX"))
+        .ok_or_else(|| anyhow!("Failed to create temporary directory"))?;
+    let journal = sanitize_text(&recent_journal(), &providers);
+    let recent_projects = recent_projects_snippets(&providers);
+    sanitize_providers(&mut providers);
+    let validation = report
+        .findings
+        .iter()
+        .map(|finding| format!("{finding}\n"))
+        .collect::<String>();
+    let files = [
+        ("version.txt", version_info()),
+        ("validate-install.txt", validation),
+        ("projects.txt", to_table(&providers)),
+        ("recent-projects.txt", recent_projects),
+        ("journal.txt", journal),
+    ];
+    let result = files
+        .iter()
+        .try_for_each(|(name, contents)| {
+            std::fs::write(directory.join(name), contents)
+                .with_context(|| format!("Failed to write {name}"))
+        })
+        .and_then(|_| {
+            let status = Command::new("tar")
+                .arg("-czf")
+                .arg(target)
+                .arg("-C")
+                .arg(&directory)
+                .args(files.iter().map(|(name, _)| name))
+                .status()
+                .with_context(|| "Failed to run tar")?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(anyhow!("tar failed with {status}"))
+            }
+        });
+    std::fs::remove_dir_all(&directory)?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::DumpedProject;
+    use similar_asserts::assert_eq;
+
+    fn providers() -> Vec<DumpedProvider> {
+        vec![DumpedProvider {
+            label: "IDEA (toolbox)".to_string(),
+            desktop_id: "jetbrains-idea.desktop".to_string(),
+            files: vec![glib::home_dir()
+                .join(".config/JetBrains/IntelliJIdea2024.2/options/recentProjects.xml")
+                .display()
+                .to_string()],
+            projects: vec![
+                DumpedProject {
+                    id: "jetbrains-recent-project-idea-/home/foo/secret".to_string(),
+                    name: "secret".to_string(),
+                    directory: "/home/foo/secret".to_string(),
+                    exists: false,
+                    opened_at: Some(1705312234567),
+                },
+                DumpedProject {
+                    id: "jetbrains-recent-project-idea-/home/foo/secret/nested".to_string(),
+                    name: "nested".to_string(),
+                    directory: "/home/foo/secret/nested".to_string(),
+                    exists: false,
+                    opened_at: None,
+                },
+            ],
+            warnings: vec!["Skipping /home/foo/gone".to_string()],
+        }]
+    }
+
+    #[test]
+    fn sanitize_projects_and_warnings() {
+        let mut providers = providers();
+        sanitize_providers(&mut providers);
+        let project = &providers[0].projects[0];
+        assert_eq!(project.name, hash("secret"));
+        assert_eq!(project.directory, hash("/home/foo/secret"));
+        assert_eq!(project.opened_at, Some(1705312234567));
+        assert_eq!(project.name.len(), 12);
+        assert_eq!(providers[0].warnings, vec!["1 warning(s) omitted"]);
+        assert_eq!(
+            providers[0].files,
+            vec!["~/.config/JetBrains/IntelliJIdea2024.2/options/recentProjects.xml"]
+        );
+    }
+
+    #[test]
+    fn sanitize_project_directories_in_text() {
+        let text = "Launching /home/foo/secret/nested\nReading /home/foo/secret\n";
+        assert_eq!(
+            Redactions::new(&providers(), Path::new("/home/foo"), "foo").redact(text),
+            format!(
+                "Launching <{}>\nReading <{}>\n",
+                hash("/home/foo/secret/nested"),
+                hash("/home/foo/secret")
+            )
+        );
+    }
+
+    #[test]
+    fn sanitize_home_user_and_project_names_in_text() {
+        let redactions = Redactions::new(&providers(), Path::new("/home/foo"), "foo");
+        assert_eq!(
+            redactions.redact("Opened nested for foo in /home/foo/other/file.txt"),
+            format!(
+                "Opened <{}> for <user> in ~/<{}>",
+                hash("nested"),
+                hash("/other/file.txt")
+            )
+        );
+        assert_eq!(
+            redactions.redact("Home is /home/foo, not /home/foobar; foobar is no user"),
+            format!(
+                "Home is ~, not <{}>; foobar is no user",
+                hash("/home/foobar")
+            )
+        );
+    }
+
+    #[test]
+    fn sanitize_recent_projects_file() {
+        let home = glib::home_dir();
+        let directory = home.join("secret").display().to_string();
+        let providers = vec![DumpedProvider {
+            label: "IDEA (toolbox)".to_string(),
+            desktop_id: "jetbrains-idea.desktop".to_string(),
+            files: Vec::new(),
+            projects: vec![DumpedProject {
+                id: format!("jetbrains-recent-project-idea-{directory}"),
+                name: "secret".to_string(),
+                directory: directory.clone(),
+                exists: false,
+                opened_at: None,
+            }],
+            warnings: Vec::new(),
+        }];
+        let redactions = Redactions::new(&providers, &home, "nobody");
+        let snippet = r#"<entry key="$USER_HOME$/secret"><value><RecentProjectMetaInfo frameTitle="secret – README.md" /></value></entry>"#
+            .replace("$USER_HOME$", &home.display().to_string());
+        assert_eq!(
+            redactions.redact(&snippet),
+            format!(
+                r#"<entry key="<{}>"><value><RecentProjectMetaInfo frameTitle="<{}> – README.md" /></value></entry>"#,
+                hash(&directory),
+                hash("secret")
+            )
+        );
+    }
+}
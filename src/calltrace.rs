@@ -0,0 +1,176 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Trace DBus calls to search providers.
+//!
+//! zbus has no middleware for interfaces, so interface methods wrap their work in a [`CallTrace`]
+//! which measures how long the call took and how large its arguments and reply were.  Calls in
+//! progress also count as in flight, so that shutdown can wait for their replies.
+//!
+//! We sum up finished calls per provider and method, for `de.swsnr.searchprovider.Stats`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{event, Level};
+use zbus::export::serde::Serialize;
+use zbus::zvariant::{self, DynamicType};
+
+use crate::metrics;
+use crate::searchprovider::AppId;
+
+/// The number of calls in progress.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Get the number of calls in progress.
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+/// Totals of all finished calls to a method.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CallTotals {
+    /// The number of calls.
+    pub calls: u64,
+    /// The total size of arguments of all calls, in bytes.
+    pub request_bytes: u64,
+    /// The total size of replies of all calls, in bytes.
+    pub reply_bytes: u64,
+    /// The total duration of all calls.
+    pub duration: Duration,
+}
+
+/// Totals of finished calls, by desktop ID of the provider and method.
+static TOTALS: Mutex<BTreeMap<(String, &str), CallTotals>> = Mutex::new(BTreeMap::new());
+
+/// Get totals of finished calls to the provider for `app_id`, by method.
+pub fn totals(app_id: &AppId) -> Vec<(&'static str, CallTotals)> {
+    let app_id = app_id.to_string();
+    TOTALS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((id, _), _)| *id == app_id)
+        .map(|((_, method), totals)| (*method, *totals))
+        .collect()
+}
+
+/// Get the size of `value` in bytes when encoded for DBus.
+fn encoded_size<T: Serialize + DynamicType + ?Sized>(value: &T) -> usize {
+    let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+    zvariant::serialized_size(ctxt, value).map_or(0, |size| size.size())
+}
+
+/// A DBus call in progress.
+#[derive(Debug)]
+pub struct CallTrace {
+    app_id: AppId,
+    method: &'static str,
+    request_bytes: usize,
+    started: Instant,
+}
+
+impl CallTrace {
+    /// Start tracing a call to `method` of the provider for `app_id` with the given `arguments`.
+    pub fn start<T: Serialize + DynamicType + ?Sized>(
+        app_id: &AppId,
+        method: &'static str,
+        arguments: &T,
+    ) -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        Self {
+            app_id: app_id.clone(),
+            method,
+            request_bytes: encoded_size(arguments),
+            started: Instant::now(),
+        }
+    }
+
+    /// Finish this call with the given `reply`, and return the `reply`.
+    ///
+    /// Log the duration of the call and the sizes of its arguments and reply at debug level, add
+    /// them to the totals of the method, and record them in metrics.
+    pub fn finish<T: Serialize + DynamicType>(self, reply: T) -> T {
+        let duration = self.started.elapsed();
+        let reply_bytes = encoded_size(&reply);
+        event!(
+            Level::DEBUG,
+            app_id = %self.app_id,
+            method = self.method,
+            request_bytes = self.request_bytes,
+            reply_bytes,
+            duration_us = duration.as_micros(),
+            "{} of {} took {}µs, with {} bytes of arguments and {} bytes of reply",
+            self.method,
+            self.app_id,
+            duration.as_micros(),
+            self.request_bytes,
+            reply_bytes
+        );
+        {
+            let mut totals = TOTALS.lock().unwrap();
+            let totals = totals
+                .entry((self.app_id.to_string(), self.method))
+                .or_default();
+            totals.calls += 1;
+            totals.request_bytes += self.request_bytes as u64;
+            totals.reply_bytes += reply_bytes as u64;
+            totals.duration += duration;
+        }
+        metrics::record_call(
+            &self.app_id,
+            self.method,
+            self.request_bytes,
+            reply_bytes,
+            duration,
+        );
+        reply
+    }
+}
+
+impl Drop for CallTrace {
+    /// The call is no longer in flight, whether it finished or failed.
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn encoded_size_of_arguments() {
+        // A u32 length, the bytes and a NUL byte
+        assert_eq!(encoded_size("hello world"), 16);
+        assert_eq!(encoded_size(&(vec!["foo", "bar"],)), 20);
+        assert_eq!(encoded_size(&()), 0);
+    }
+
+    #[test]
+    fn sum_up_calls_per_provider_and_method() {
+        let app_id = AppId::try_from("calltrace-test.desktop").unwrap();
+        assert_eq!(totals(&app_id), Vec::new());
+        CallTrace::start(&app_id, "GetInitialResultSet", &(vec!["foo"],)).finish(vec!["bar"]);
+        CallTrace::start(&app_id, "GetInitialResultSet", &(vec!["foo"],))
+            .finish(Vec::<&str>::new());
+        CallTrace::start(&app_id, "ActivateResult", "bar").finish(());
+        let totals = totals(&app_id);
+        assert_eq!(
+            totals
+                .iter()
+                .map(|(method, t)| (*method, t.calls, t.request_bytes, t.reply_bytes))
+                .collect::<Vec<_>>(),
+            vec![
+                ("ActivateResult", 1, 8, 0),
+                ("GetInitialResultSet", 2, 24, 16)
+            ]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Advertise capabilities of search providers.
+
+use zbus::interface;
+
+/// The version of the `org.gnome.Shell.SearchProvider2` interface we implement.
+pub const PROVIDER_VERSION: u32 = 2;
+
+/// Extensions supported by our search providers beyond the standard interface.
+///
+/// - `path-terms`: Search terms containing `/` filter by project directory.
+/// - `activate-result-ex`: Activate results with options through
+///   `de.swsnr.searchprovider.ActivateResultEx`; not available without the `launch` feature.
+/// - `score`: Query scores of recent projects through `de.swsnr.searchprovider.Score`.
+/// - `stats`: Query the state of the provider through `de.swsnr.searchprovider.Stats`.
+#[cfg(feature = "launch")]
+pub const CAPABILITIES: &[&str] = &["path-terms", "activate-result-ex", "score", "stats"];
+
+/// Extensions supported by our search providers beyond the standard interface.
+#[cfg(not(feature = "launch"))]
+pub const CAPABILITIES: &[&str] = &["path-terms", "score", "stats"];
+
+/// Advertise the version and capabilities of the search provider at the same object path.
+///
+/// This lets other consumers than Gnome Shell detect our extensions without guesswork.
+#[derive(Debug, Default)]
+pub struct ProviderCapabilities;
+
+#[interface(name = "de.swsnr.searchprovider.Capabilities")]
+impl ProviderCapabilities {
+    /// The version of the search provider interface.
+    #[zbus(property)]
+    fn provider_version(&self) -> u32 {
+        PROVIDER_VERSION
+    }
+
+    /// The extensions this search provider supports.
+    #[zbus(property)]
+    fn capabilities(&self) -> Vec<&str> {
+        CAPABILITIES.to_vec()
+    }
+}
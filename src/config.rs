@@ -11,7 +11,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::OnceLock;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use regex::Regex;
 use tracing::{event, instrument, Level};
 
@@ -60,6 +60,52 @@ impl VersionedPath {
     }
 }
 
+/// Directories inside a product configuration directory which can hold the recent projects file.
+///
+/// `settingsSync/options` holds options shared across machines with JetBrains Settings Sync.
+const OPTIONS_DIRS: &[&str] = &["options", "settingsSync/options"];
+
+/// The environment variable with additional directories to look for configuration directories in.
+pub const CONFIG_ROOTS_VARIABLE: &str = "JETBRAINS_CONFIG_DIR";
+
+/// Get additional directories to look for configuration directories in.
+///
+/// Return `settings` followed by all directories in `$JETBRAINS_CONFIG_DIR`, separated by `:`,
+/// for IDEs whose `idea.config.path` points outside of `$XDG_CONFIG_HOME`.  Each directory holds
+/// configuration directories of products, like `~/.config/JetBrains`.
+pub fn extra_config_roots(settings: &[PathBuf]) -> Vec<PathBuf> {
+    let from_env = std::env::var_os(CONFIG_ROOTS_VARIABLE).unwrap_or_default();
+    settings
+        .iter()
+        .cloned()
+        .chain(std::env::split_paths(&from_env).filter(|path| !path.as_os_str().is_empty()))
+        .collect()
+}
+
+/// Find the name of the configuration directory of the product installed at `executable`.
+///
+/// Look for `product-info.json` in the parents of `executable`, after resolving symlinks, and
+/// return its `dataDirectoryName`.
+pub fn find_data_directory_name(executable: &Path) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r#""dataDirectoryName"\s*:\s*"([^"]+)""#).unwrap());
+    let executable = std::fs::canonicalize(executable).ok()?;
+    let product_info = executable
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("product-info.json"))
+        .find(|file| file.is_file())?;
+    let contents = std::fs::read_to_string(&product_info).ok()?;
+    let name = re.captures(&contents).map(|m| m[1].to_string());
+    event!(
+        Level::DEBUG,
+        "Found data directory name {:?} in {}",
+        name,
+        product_info.display()
+    );
+    name
+}
+
 /// A location for configuration of a Jetbrains product.
 #[derive(Debug)]
 pub struct ConfigLocation<'a> {
@@ -69,54 +115,181 @@ pub struct ConfigLocation<'a> {
     pub config_prefix: &'a str,
     /// The file name for recent projects
     pub projects_filename: &'a str,
+    /// Channels of configuration directories which belong to a different product.
+    ///
+    /// Android Studio Preview, for instance, keeps its configuration in
+    /// `AndroidStudioPreview2024.3`, which would otherwise look like the `Preview` channel of
+    /// Android Studio.
+    pub excluded_channels: &'a [&'a str],
 }
 
 impl ConfigLocation<'_> {
-    /// Find the configuration directory of the latest installed product version.
-    fn find_config_dir_of_latest_version(&self, config_home: &Path) -> Result<VersionedPath> {
-        let vendor_dir = config_home.join(self.vendor_dir);
-        let dir = std::fs::read_dir(&vendor_dir)
-            .with_context(|| format!("Failed to open directory {}", vendor_dir.display()))?
+    /// Get the channel of the configuration directory `name`.
+    ///
+    /// The channel is the part between the config prefix and the version, e.g. `U` in
+    /// `IntelliJIdeaU2024.2`; the channel of `IntelliJIdea2024.1` is empty.
+    ///
+    /// Return `None` if `name` isn't a configuration directory of this location, or if its
+    /// channel is excluded.
+    pub fn channel<'b>(&self, name: &'b str) -> Option<&'b str> {
+        let rest = name.strip_prefix(self.config_prefix)?;
+        let channel = &rest[..rest.find(|c: char| c.is_ascii_digit())?];
+        (channel.chars().all(|c| c.is_ascii_alphabetic())
+            && !self.excluded_channels.contains(&channel))
+        .then_some(channel)
+    }
+
+    /// Get all directories to look for configuration directories in.
+    ///
+    /// Return the vendor directory in `config_home`, followed by `extra_roots`, e.g. from
+    /// [`extra_config_roots`].
+    pub fn config_roots(&self, config_home: &Path, extra_roots: &[PathBuf]) -> Vec<PathBuf> {
+        std::iter::once(config_home.join(self.vendor_dir))
+            .chain(extra_roots.iter().cloned())
+            .collect()
+    }
+
+    /// Find all configuration directories of installed product versions in `roots`.
+    ///
+    /// Return configuration directories with their channels, latest first.  If given, put
+    /// directories of `preferred_channel` first, so that we fall back to other channels only if
+    /// there's no directory for `preferred_channel`.  Among directories of the same version put
+    /// those in later roots first.  Skip roots which we fail to read.
+    fn find_config_dirs(
+        &self,
+        roots: &[PathBuf],
+        preferred_channel: Option<&str>,
+    ) -> Result<Vec<(String, VersionedPath)>> {
+        let mut dirs = roots
+            .iter()
+            .filter_map(|root| match std::fs::read_dir(root) {
+                Ok(entries) => Some(entries),
+                Err(error) => {
+                    event!(
+                        Level::DEBUG,
+                        %error,
+                        "Failed to open directory {}: {error}",
+                        root.display()
+                    );
+                    None
+                }
+            })
+            .flatten()
             .filter_map(|entry| entry.ok())
             .map(|entry| entry.path())
-            .filter(|entry| {
-                if let Some(name) = entry.file_name().and_then(|name| name.to_str()) {
-                    name.starts_with(self.config_prefix)
-                } else {
-                    false
-                }
+            .filter_map(|entry| {
+                let channel = entry
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| self.channel(name))?
+                    .to_string();
+                VersionedPath::extract_version(entry).map(|p| (channel, p))
             })
-            .filter_map(VersionedPath::extract_version)
-            .max_by_key(|p| p.version);
+            .collect::<Vec<_>>();
+        dirs.sort_by_key(|(channel, p)| (preferred_channel == Some(channel), p.version));
+        dirs.reverse();
+        event!(Level::DEBUG, "Found config dirs {:?} in {:?}", dirs, roots);
+        if dirs.is_empty() {
+            Err(anyhow!(
+                "Failed to find configuration directory in {}",
+                roots
+                    .iter()
+                    .map(|root| root.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        } else {
+            Ok(dirs)
+        }
+    }
+
+    /// Find all existing recent projects files in the options directories of `config_dirs`.
+    ///
+    /// Return files in order of `config_dirs`, and for each config dir in order of precedence,
+    /// i.e. local options before synced options.
+    fn find_recent_projects_files_in<I>(&self, config_dirs: I) -> Vec<PathBuf>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        config_dirs
+            .into_iter()
+            .flat_map(|config_dir| {
+                OPTIONS_DIRS.iter().map(move |options_dir| {
+                    config_dir.join(options_dir).join(self.projects_filename)
+                })
+            })
+            .filter(|file| {
+                let exists = file.is_file();
+                event!(
+                    Level::TRACE,
+                    "Recent projects file at {} exists? {}",
+                    file.display(),
+                    exists
+                );
+                exists
+            })
+            .collect()
+    }
+
+    /// Find all recent projects files of the latest product version.
+    ///
+    /// Return all existing recent projects files in the options directories of the latest
+    /// config directory in `roots`, in order of precedence, i.e. local options before synced
+    /// options.
+    ///
+    /// See [`Self::channel`] for `preferred_channel`.
+    #[instrument]
+    pub fn find_latest_recent_projects_files(
+        &self,
+        roots: &[PathBuf],
+        preferred_channel: Option<&str>,
+    ) -> Result<Vec<PathBuf>> {
+        let (_, latest) = self
+            .find_config_dirs(roots, preferred_channel)?
+            .into_iter()
+            .next()
+            .unwrap();
+        let files = self.find_recent_projects_files_in([latest.into_path()]);
         event!(
-            Level::DEBUG,
-            "Found config dir {:?} in {}",
-            dir,
-            config_home.display()
+            Level::TRACE,
+            "Using recent projects files at {:?} in {:?}",
+            files,
+            roots
         );
-        dir.ok_or_else(|| {
-            anyhow!(
-                "Failed to find configuration directory in {}",
-                config_home.display(),
-            )
-        })
+        Ok(files)
     }
 
-    /// Find the latest recent projects file.
+    /// Find recent projects files of all installed versions of the product.
+    ///
+    /// After an upgrade the IDE may not have migrated all recent projects to the configuration
+    /// directory of the new version yet, so the previous versions may hold projects which the
+    /// latest version lacks.
+    ///
+    /// Return all existing recent projects files of all config directories in `roots` which have
+    /// the channel of the latest config directory, newest version first, and for each version in
+    /// order of precedence, i.e. local options before synced options.
+    ///
+    /// See [`Self::channel`] for `preferred_channel`.
     #[instrument]
-    pub fn find_latest_recent_projects_file(&self, config_home: &Path) -> Result<PathBuf> {
-        let file = self
-            .find_config_dir_of_latest_version(config_home)?
-            .into_path()
-            .join("options")
-            .join(self.projects_filename);
+    pub fn find_all_recent_projects_files(
+        &self,
+        roots: &[PathBuf],
+        preferred_channel: Option<&str>,
+    ) -> Result<Vec<PathBuf>> {
+        let dirs = self.find_config_dirs(roots, preferred_channel)?;
+        let channel = dirs[0].0.clone();
+        let files = self.find_recent_projects_files_in(
+            dirs.into_iter()
+                .filter(|(c, _)| *c == channel)
+                .map(|(_, p)| p.into_path()),
+        );
         event!(
             Level::TRACE,
-            "Using recent projects file at {:?} in {}",
-            file,
-            config_home.display()
+            "Using recent projects files at {:?} of all versions in {:?}",
+            files,
+            roots
         );
-        Ok(file)
+        Ok(files)
     }
 }
 
@@ -134,4 +307,217 @@ mod tests {
         let versioned_path = VersionedPath::extract_version(path).unwrap();
         assert_eq!(versioned_path.version, (2021, 1))
     }
+
+    #[test]
+    fn find_latest_recent_projects_files_with_settings_sync() {
+        let config_home = glib::mkdtemp(std::env::temp_dir().join("config-XXXXXX")).unwrap();
+        let config_dir = config_home.join("JetBrains").join("IdeaIC2023.2");
+        for options_dir in ["options", "settingsSync/options"] {
+            let dir = config_dir.join(options_dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("recentProjects.xml"), "<application />").unwrap();
+        }
+        std::fs::create_dir_all(config_home.join("JetBrains").join("IdeaIC2023.1")).unwrap();
+
+        let location = ConfigLocation {
+            vendor_dir: "JetBrains",
+            config_prefix: "IdeaIC",
+            projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
+        };
+        let files = location
+            .find_latest_recent_projects_files(&location.config_roots(&config_home, &[]), None);
+        std::fs::remove_dir_all(&config_home).unwrap();
+        assert_eq!(
+            files.unwrap(),
+            vec![
+                config_dir.join("options").join("recentProjects.xml"),
+                config_dir
+                    .join("settingsSync")
+                    .join("options")
+                    .join("recentProjects.xml"),
+            ]
+        );
+    }
+
+    fn idea_location() -> ConfigLocation<'static> {
+        ConfigLocation {
+            vendor_dir: "JetBrains",
+            config_prefix: "IntelliJIdea",
+            projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
+        }
+    }
+
+    #[test]
+    fn channel_of_config_dirs() {
+        let location = idea_location();
+        assert_eq!(location.channel("IntelliJIdea2024.1"), Some(""));
+        assert_eq!(location.channel("IntelliJIdeaU2024.2"), Some("U"));
+        assert_eq!(location.channel("IntelliJIdea"), None);
+        assert_eq!(location.channel("IntelliJIdea-backup2024.1"), None);
+        assert_eq!(location.channel("IdeaIC2024.1"), None);
+    }
+
+    #[test]
+    fn find_recent_projects_files_of_preferred_channel() {
+        let config_home = glib::mkdtemp(std::env::temp_dir().join("config-XXXXXX")).unwrap();
+        let stable_dir = config_home.join("JetBrains").join("IntelliJIdea2024.1");
+        let eap_dir = config_home.join("JetBrains").join("IntelliJIdeaU2024.2");
+        for config_dir in [&stable_dir, &eap_dir] {
+            let dir = config_dir.join("options");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("recentProjects.xml"), "<application />").unwrap();
+        }
+
+        let location = idea_location();
+        let files = [None, Some(""), Some("U"), Some("X")].map(|channel| {
+            location.find_latest_recent_projects_files(
+                &location.config_roots(&config_home, &[]),
+                channel,
+            )
+        });
+        std::fs::remove_dir_all(&config_home).unwrap();
+        let [latest, stable, eap, unknown] = files.map(|files| files.unwrap());
+        let stable_file = stable_dir.join("options").join("recentProjects.xml");
+        let eap_file = eap_dir.join("options").join("recentProjects.xml");
+        assert_eq!(latest, vec![eap_file.clone()]);
+        assert_eq!(stable, vec![stable_file]);
+        assert_eq!(eap, vec![eap_file.clone()]);
+        assert_eq!(unknown, vec![eap_file]);
+    }
+
+    #[test]
+    fn separate_android_studio_preview_from_stable() {
+        let config_home = glib::mkdtemp(std::env::temp_dir().join("config-XXXXXX")).unwrap();
+        let stable_dir = config_home.join("Google").join("AndroidStudio2024.2");
+        let canary_dir = config_home
+            .join("Google")
+            .join("AndroidStudioPreview2024.3");
+        for config_dir in [&stable_dir, &canary_dir] {
+            let dir = config_dir.join("options");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("recentProjects.xml"), "<application />").unwrap();
+        }
+
+        let stable = ConfigLocation {
+            vendor_dir: "Google",
+            config_prefix: "AndroidStudio",
+            projects_filename: "recentProjects.xml",
+            excluded_channels: &["Preview"],
+        };
+        let preview = ConfigLocation {
+            vendor_dir: "Google",
+            config_prefix: "AndroidStudioPreview",
+            projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
+        };
+        assert_eq!(stable.channel("AndroidStudio2024.2"), Some(""));
+        assert_eq!(stable.channel("AndroidStudioPreview2024.3"), None);
+        assert_eq!(preview.channel("AndroidStudioPreview2024.3"), Some(""));
+        assert_eq!(
+            VersionedPath::extract_version(canary_dir.clone())
+                .unwrap()
+                .version,
+            (2024, 3)
+        );
+
+        let stable_files =
+            stable.find_latest_recent_projects_files(&stable.config_roots(&config_home, &[]), None);
+        let preview_files = preview
+            .find_latest_recent_projects_files(&preview.config_roots(&config_home, &[]), None);
+        std::fs::remove_dir_all(&config_home).unwrap();
+        assert_eq!(
+            stable_files.unwrap(),
+            vec![stable_dir.join("options").join("recentProjects.xml")]
+        );
+        assert_eq!(
+            preview_files.unwrap(),
+            vec![canary_dir.join("options").join("recentProjects.xml")]
+        );
+    }
+
+    #[test]
+    fn find_latest_recent_projects_files_across_config_roots() {
+        let config_home = glib::mkdtemp(std::env::temp_dir().join("config-XXXXXX")).unwrap();
+        let custom_root = glib::mkdtemp(std::env::temp_dir().join("custom-XXXXXX")).unwrap();
+        let default_dir = config_home.join("JetBrains").join("IntelliJIdea2024.1");
+        let custom_dir = custom_root.join("IntelliJIdea2024.2");
+        let older_custom_dir = custom_root.join("IntelliJIdea2023.3");
+        for config_dir in [&default_dir, &custom_dir, &older_custom_dir] {
+            let dir = config_dir.join("options");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("recentProjects.xml"), "<application />").unwrap();
+        }
+
+        let location = idea_location();
+        let roots = location.config_roots(&config_home, std::slice::from_ref(&custom_root));
+        let default_only = location.config_roots(&config_home, &[]);
+        let missing_root = location.config_roots(&config_home, &[custom_root.join("missing")]);
+        let files = [&roots, &default_only, &missing_root]
+            .map(|roots| location.find_latest_recent_projects_files(roots, None));
+        std::fs::remove_dir_all(&config_home).unwrap();
+        std::fs::remove_dir_all(&custom_root).unwrap();
+        assert_eq!(roots[0], config_home.join("JetBrains"));
+        assert_eq!(roots[1], custom_root);
+        let [custom, default, missing] = files.map(|files| files.unwrap());
+        let default_file = default_dir.join("options").join("recentProjects.xml");
+        assert_eq!(
+            custom,
+            vec![custom_dir.join("options").join("recentProjects.xml")]
+        );
+        assert_eq!(default, vec![default_file.clone()]);
+        assert_eq!(missing, vec![default_file]);
+    }
+
+    #[test]
+    fn find_all_recent_projects_files_newest_first() {
+        let config_home = glib::mkdtemp(std::env::temp_dir().join("config-XXXXXX")).unwrap();
+        let vendor_dir = config_home.join("JetBrains");
+        let dirs = [
+            "IntelliJIdea2023.3",
+            "IntelliJIdea2024.2",
+            "IntelliJIdea2024.1",
+        ]
+        .map(|name| vendor_dir.join(name));
+        for config_dir in &dirs {
+            let dir = config_dir.join("options");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("recentProjects.xml"), "<application />").unwrap();
+        }
+        let synced = dirs[1].join("settingsSync").join("options");
+        std::fs::create_dir_all(&synced).unwrap();
+        std::fs::write(synced.join("recentProjects.xml"), "<application />").unwrap();
+        // Other channels and versions without recent projects
+        std::fs::create_dir_all(vendor_dir.join("IntelliJIdeaU2024.3").join("options")).unwrap();
+        std::fs::create_dir_all(vendor_dir.join("IntelliJIdea2024.3")).unwrap();
+
+        let location = idea_location();
+        let files = location
+            .find_all_recent_projects_files(&location.config_roots(&config_home, &[]), Some(""));
+        std::fs::remove_dir_all(&config_home).unwrap();
+        let [oldest, newest, middle] =
+            dirs.map(|dir| dir.join("options").join("recentProjects.xml"));
+        assert_eq!(
+            files.unwrap(),
+            vec![newest, synced.join("recentProjects.xml"), middle, oldest]
+        );
+    }
+
+    #[test]
+    fn find_data_directory_name_of_installation() {
+        let install_dir = glib::mkdtemp(std::env::temp_dir().join("idea-XXXXXX")).unwrap();
+        std::fs::create_dir_all(install_dir.join("bin")).unwrap();
+        std::fs::write(install_dir.join("bin").join("idea"), "").unwrap();
+        std::fs::write(
+            install_dir.join("product-info.json"),
+            include_str!("tests/product-info.json"),
+        )
+        .unwrap();
+        let name = find_data_directory_name(&install_dir.join("bin").join("idea"));
+        let missing = find_data_directory_name(&install_dir.join("bin").join("missing"));
+        std::fs::remove_dir_all(&install_dir).unwrap();
+        assert_eq!(name.as_deref(), Some("IntelliJIdeaU2024.2"));
+        assert_eq!(missing, None);
+    }
 }
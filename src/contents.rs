@@ -0,0 +1,237 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! An index of the contents of project directories.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tracing::{event, Level};
+
+use crate::searchprovider::CacheSize;
+use crate::tracebatch::TraceBatch;
+
+/// The maximum number of entries to index per project directory.
+const MAX_ENTRIES: usize = 256;
+
+/// How long to keep indexed entries of a project directory before reading it again.
+const MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Read the names of at most [`MAX_ENTRIES`] entries of `directory`, in lowercase.
+///
+/// Skip hidden entries.
+fn read_entries(directory: &Path) -> Vec<String> {
+    match std::fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+            .filter(|name| !name.starts_with('.'))
+            .take(MAX_ENTRIES)
+            .collect(),
+        Err(error) => {
+            event!(
+                Level::DEBUG,
+                "Failed to index contents of {}: {error}",
+                directory.display()
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Find the top-level entry of `directory` which `term` refers to.
+///
+/// Return the first entry in order of names whose name starts with `term`, ignoring case, just
+/// like terms match indexed entries.  Skip hidden entries, and return `None` if no entry matches
+/// or `directory` can't be read.
+#[cfg_attr(not(feature = "launch"), allow(dead_code))]
+pub fn find_entry(directory: &Path, term: &str) -> Option<PathBuf> {
+    let term = term.to_lowercase();
+    if term.is_empty() || term.contains('/') {
+        return None;
+    }
+    let mut matches = std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.') && name.to_lowercase().starts_with(&term))
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.first().map(|name| directory.join(name))
+}
+
+/// Read the top-level entries of all `directories` at `now`.
+///
+/// This reads from the file system, so call it off the main loop, e.g. with
+/// [`gio::spawn_blocking`].
+pub fn index_directories(directories: Vec<String>, now: Instant) -> Vec<IndexedDirectory> {
+    let mut indexed = TraceBatch::new("Indexed contents of directories", |message| {
+        event!(Level::TRACE, "{message}")
+    });
+    directories
+        .into_iter()
+        .map(|directory| {
+            indexed.add(&directory);
+            let entries = read_entries(Path::new(&directory));
+            (directory, now, entries)
+        })
+        .collect()
+}
+
+/// The entries of a directory, with the time they were read at.
+pub type IndexedDirectory = (String, Instant, Vec<String>);
+
+/// Cached names of the top-level entries of project directories.
+#[derive(Debug, Default)]
+pub struct ContentIndex {
+    entries: HashMap<String, (Instant, Vec<String>)>,
+    /// Directories being read in the background, see [`Self::insert`].
+    pending: HashSet<String>,
+}
+
+impl ContentIndex {
+    /// Update the index to the given project `directories` at `now`.
+    ///
+    /// Forget all other directories, and return directories to read, i.e. directories which are
+    /// neither indexed yet nor being read already, or whose index is older than [`MAX_AGE`].  Read
+    /// these with [`index_directories`] and add them with [`Self::insert`]; until then keep
+    /// outdated entries.
+    pub fn update<'a, I: IntoIterator<Item = &'a str>>(
+        &mut self,
+        directories: I,
+        now: Instant,
+    ) -> Vec<String> {
+        let directories = directories.into_iter().collect::<Vec<_>>();
+        let wanted = directories.iter().copied().collect::<HashSet<_>>();
+        self.entries
+            .retain(|directory, _| wanted.contains(directory.as_str()));
+        self.pending
+            .retain(|directory| wanted.contains(directory.as_str()));
+        let mut stale = Vec::new();
+        for directory in directories {
+            let is_fresh = self.entries.get(directory).is_some_and(|(indexed_at, _)| {
+                now.saturating_duration_since(*indexed_at) < MAX_AGE
+            });
+            if !is_fresh && self.pending.insert(directory.to_string()) {
+                stale.push(directory.to_string());
+            }
+        }
+        stale
+    }
+
+    /// Add directories read with [`index_directories`] to this index.
+    ///
+    /// Skip directories which the index forgot meanwhile.
+    pub fn insert<I: IntoIterator<Item = IndexedDirectory>>(&mut self, indexed: I) {
+        for (directory, indexed_at, entries) in indexed {
+            if self.pending.remove(&directory) {
+                self.entries.insert(directory, (indexed_at, entries));
+            }
+        }
+    }
+
+    /// Add the indexed directories to `size`.
+    pub fn add_size(&self, size: &mut CacheSize) {
+        for (directory, (_, entries)) in &self.entries {
+            size.add_entry(
+                std::iter::once(directory.as_str()).chain(entries.iter().map(String::as_str)),
+            );
+        }
+    }
+
+    /// Get the indexed entries of `directory`.
+    ///
+    /// Return an empty slice if `directory` is not indexed.
+    pub fn entries(&self, directory: &str) -> &[String] {
+        self.entries
+            .get(directory)
+            .map_or(&[], |(_, entries)| entries.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn index_and_cache_directory_contents() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("contents-XXXXXX")).unwrap();
+        std::fs::create_dir(directory.join("Docs")).unwrap();
+        std::fs::create_dir(directory.join(".git")).unwrap();
+        let directory_s = directory.to_str().unwrap();
+
+        let mut index = ContentIndex::default();
+        let start = Instant::now();
+        let stale = index.update([directory_s], start);
+        assert_eq!(stale, [directory_s]);
+        // Do not read directories again while they're being read
+        assert!(index.update([directory_s], start).is_empty());
+        assert!(index.entries(directory_s).is_empty());
+        index.insert(index_directories(stale, start));
+        assert_eq!(index.entries(directory_s), ["docs"]);
+
+        std::fs::write(directory.join("README.md"), "").unwrap();
+        assert!(index
+            .update([directory_s], start + Duration::from_secs(1))
+            .is_empty());
+        assert_eq!(index.entries(directory_s), ["docs"]);
+
+        let stale = index.update([directory_s], start + MAX_AGE);
+        assert_eq!(stale, [directory_s]);
+        // Keep outdated entries until the directory was read again
+        assert_eq!(index.entries(directory_s), ["docs"]);
+        index.insert(index_directories(stale, start + MAX_AGE));
+        let mut entries = index.entries(directory_s).to_vec();
+        entries.sort();
+        assert_eq!(entries, ["docs", "readme.md"]);
+
+        assert!(index.update([], start).is_empty());
+        assert!(index.entries(directory_s).is_empty());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn skip_forgotten_directories_when_inserting() {
+        let mut index = ContentIndex::default();
+        let start = Instant::now();
+        assert_eq!(
+            index.update(["/a", "/b"], start),
+            ["/a", "/b"].map(String::from)
+        );
+        index.update(["/a"], start);
+        index.insert([
+            ("/a".to_string(), start, vec!["docs".to_string()]),
+            ("/b".to_string(), start, vec!["src".to_string()]),
+        ]);
+        assert_eq!(index.entries("/a"), ["docs"]);
+        assert!(index.entries("/b").is_empty());
+    }
+
+    #[test]
+    fn find_entries_for_terms() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("contents-XXXXXX")).unwrap();
+        std::fs::write(directory.join("README.md"), "").unwrap();
+        std::fs::write(directory.join("Render.rs"), "").unwrap();
+        std::fs::write(directory.join("renderer.rs"), "").unwrap();
+        std::fs::write(directory.join(".readme"), "").unwrap();
+
+        assert_eq!(
+            find_entry(&directory, "readme"),
+            Some(directory.join("README.md"))
+        );
+        assert_eq!(
+            find_entry(&directory, "render"),
+            Some(directory.join("Render.rs"))
+        );
+        assert_eq!(find_entry(&directory, "docs"), None);
+        assert_eq!(find_entry(&directory, "../"), None);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
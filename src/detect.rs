@@ -0,0 +1,406 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Detect installed Jetbrains apps without a provider.
+
+use std::path::{Path, PathBuf};
+
+use gio::prelude::*;
+
+use crate::config::find_data_directory_name;
+use crate::providers::{ProviderDefinition, OBJECT_PATH_PREFIX, PROVIDERS};
+use crate::settings::{
+    custom_obj_path, DEFAULT_PROJECTS_FILENAME, DEFAULT_VENDOR_DIR, PROVIDER_GROUP_PREFIX,
+};
+
+/// Whether `app` looks like a Jetbrains IDE.
+///
+/// Toolbox creates desktop files named `jetbrains-*.desktop`; for other installations we check
+/// whether the app launches something from Jetbrains.
+fn is_jetbrains_app(app: &gio::AppInfo) -> bool {
+    let id = app.id().map(|id| id.to_lowercase()).unwrap_or_default();
+    let commandline = app
+        .commandline()
+        .map(|cmd| cmd.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    id.starts_with("jetbrains-")
+        || commandline.contains("jetbrains")
+        || commandline.contains("android-studio")
+}
+
+/// An installed Jetbrains app which no provider covers.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DetectedApp {
+    /// The desktop ID of the app.
+    pub desktop_id: String,
+    /// The name of the app.
+    pub name: String,
+    /// The executable of the app, if any.
+    pub executable: Option<PathBuf>,
+}
+
+/// Find all installed Jetbrains apps which none of `providers` covers.
+pub fn find_apps_without_provider(providers: &[&ProviderDefinition]) -> Vec<DetectedApp> {
+    let mut apps = gio::AppInfo::all()
+        .into_iter()
+        .filter(is_jetbrains_app)
+        .filter_map(|app| {
+            Some(DetectedApp {
+                desktop_id: app.id()?.to_string(),
+                name: app.name().to_string(),
+                executable: Some(app.executable())
+                    .filter(|executable| !executable.as_os_str().is_empty())
+                    .and_then(|executable| {
+                        if executable.is_absolute() {
+                            Some(executable)
+                        } else {
+                            glib::find_program_in_path(executable)
+                        }
+                    }),
+            })
+        })
+        .filter(|app| !providers.iter().any(|p| p.desktop_id == app.desktop_id))
+        .collect::<Vec<_>>();
+    apps.sort_unstable();
+    apps
+}
+
+/// The directory where Toolbox puts shell scripts to launch IDEs.
+pub fn toolbox_scripts_dir() -> PathBuf {
+    glib::user_data_dir()
+        .join("JetBrains")
+        .join("Toolbox")
+        .join("scripts")
+}
+
+/// Find Toolbox scripts in `scripts_dir` for providers whose app has no desktop entry.
+///
+/// Gnome Shell ignores search providers of apps without desktop entry, so these providers
+/// never show results, even though the IDE is installed.  `has_desktop_entry` tells whether
+/// a desktop ID exists.
+///
+/// Return pairs of script name and provider.
+pub fn find_scripts_without_desktop_entry<F: Fn(&str) -> bool>(
+    scripts_dir: &Path,
+    has_desktop_entry: F,
+) -> Vec<(String, &'static ProviderDefinition<'static>)> {
+    let mut scripts = std::fs::read_dir(scripts_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|script| {
+            let desktop_id = format!("jetbrains-{script}.desktop");
+            PROVIDERS
+                .iter()
+                .find(|p| p.desktop_id == desktop_id)
+                .filter(|p| !has_desktop_entry(p.desktop_id))
+                .map(|p| (script, p))
+        })
+        .collect::<Vec<_>>();
+    scripts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    scripts
+}
+
+/// Normalize `desktop_id` for comparison with desktop IDs of known providers.
+///
+/// Toolbox names desktop files `jetbrains-*.desktop`, whereas distribution packages often omit
+/// the `jetbrains-` prefix, e.g. `idea-ce.desktop` instead of `jetbrains-idea-ce.desktop`.
+fn normalize_desktop_id(desktop_id: &str) -> String {
+    desktop_id
+        .trim_end_matches(".desktop")
+        .trim_start_matches("jetbrains-")
+        .to_lowercase()
+}
+
+/// Strip the version from the name of a configuration directory, e.g. `DataSpell2024.1`.
+///
+/// Return `None` if `name` has no version.
+fn strip_version(name: &str) -> Option<&str> {
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (prefix.len() < name.len() && !prefix.is_empty()).then_some(prefix)
+}
+
+/// Find the configuration prefix of an app called `name` in `config_roots`.
+///
+/// Look for configuration directories whose name without version is `name` without spaces,
+/// e.g. `DataSpell2024.1` for `DataSpell`, or `RustRover2024.1` for `Rust Rover`.
+fn find_config_prefix_in(name: &str, config_roots: &[PathBuf]) -> Option<String> {
+    let name = name.replace(' ', "");
+    config_roots
+        .iter()
+        .filter_map(|root| std::fs::read_dir(root).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find_map(|dir| {
+            strip_version(&dir)
+                .filter(|prefix| prefix.eq_ignore_ascii_case(&name))
+                .map(ToString::to_string)
+        })
+}
+
+/// A suggested provider definition for an app without provider.
+pub struct SuggestedProvider {
+    /// The name of the app.
+    pub label: String,
+    /// The desktop ID of the app.
+    pub desktop_id: String,
+    /// The vendor configuration directory.
+    pub vendor_dir: &'static str,
+    /// The prefix of configuration directories, if we found out where the app keeps its
+    /// configuration.
+    pub config_prefix: Option<String>,
+    /// The file name of recent projects.
+    pub projects_filename: &'static str,
+    /// The known provider whose app has a different desktop ID, if any.
+    pub renamed_from: Option<&'static ProviderDefinition<'static>>,
+}
+
+impl SuggestedProvider {
+    /// The object path of the suggested provider as `[Provider …]` group, relative to the object
+    /// path prefix.
+    pub fn config_obj_path(&self) -> String {
+        custom_obj_path(&self.desktop_id)
+    }
+
+    /// Format the suggested provider as `[Provider …]` group for the configuration file.
+    ///
+    /// Only include `VendorDir` and `ProjectsFilename` if they differ from their defaults.  If we
+    /// don't know the configuration prefix, the group has a placeholder which the user needs to
+    /// fill in.
+    pub fn to_config_group(&self) -> String {
+        let key_file = glib::KeyFile::new();
+        let group = format!("{PROVIDER_GROUP_PREFIX}{}", self.desktop_id);
+        key_file.set_string(&group, "Label", &self.label);
+        key_file.set_string(
+            &group,
+            "ConfigPrefix",
+            self.config_prefix.as_deref().unwrap_or("TODO"),
+        );
+        if self.vendor_dir != DEFAULT_VENDOR_DIR {
+            key_file.set_string(&group, "VendorDir", self.vendor_dir);
+        }
+        if self.projects_filename != DEFAULT_PROJECTS_FILENAME {
+            key_file.set_string(&group, "ProjectsFilename", self.projects_filename);
+        }
+        key_file.to_data().to_string()
+    }
+
+    /// The object path of the suggested provider as built-in provider, relative to the object
+    /// path prefix.
+    pub fn relative_obj_path(&self) -> String {
+        let name = self
+            .desktop_id
+            .trim_end_matches(".desktop")
+            .trim_start_matches("jetbrains-")
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>();
+        format!("toolbox/{name}")
+    }
+
+    /// Format the suggested provider as a provider definition for [`PROVIDERS`].
+    ///
+    /// If we don't know the configuration prefix, the definition has a placeholder which the
+    /// user needs to fill in.
+    pub fn to_definition(&self) -> String {
+        format!(
+            r#"ProviderDefinition {{
+    label: "{label}",
+    desktop_id: "{desktop_id}",
+    relative_obj_path: "{relative_obj_path}",
+    config: ConfigLocation {{
+        vendor_dir: "{vendor_dir}",
+        config_prefix: "{config_prefix}",
+        projects_filename: "{projects_filename}",
+        excluded_channels: &[],
+    }},
+}},"#,
+            label = self.label.escape_default(),
+            desktop_id = self.desktop_id.escape_default(),
+            relative_obj_path = self.relative_obj_path(),
+            vendor_dir = self.vendor_dir,
+            config_prefix = self
+                .config_prefix
+                .as_deref()
+                .unwrap_or("TODO")
+                .escape_default(),
+            projects_filename = self.projects_filename,
+        )
+    }
+
+    /// Format a search provider file for the suggested provider, for the given `busname` and
+    /// `relative_obj_path`.
+    ///
+    /// Gnome Shell only asks this service for results of the provider if this file exists.
+    pub fn search_provider_file(&self, busname: &str, relative_obj_path: &str) -> String {
+        let key_file = glib::KeyFile::new();
+        let group = "Shell Search Provider";
+        key_file.set_string(group, "DesktopId", &self.desktop_id);
+        key_file.set_string(group, "BusName", busname);
+        key_file.set_string(
+            group,
+            "ObjectPath",
+            &format!("{OBJECT_PATH_PREFIX}/{relative_obj_path}"),
+        );
+        key_file.set_integer(group, "Version", 2);
+        key_file.to_data().to_string()
+    }
+}
+
+/// Suggest a provider definition for the detected `app`.
+///
+/// If the app is a known IDE with a different desktop ID, suggest the configuration of the known
+/// provider.  Otherwise take the configuration prefix from `product-info.json` of the app, or
+/// from configuration directories in `config_roots` which match the name of the app.
+pub fn suggest_provider(app: &DetectedApp, config_roots: &[PathBuf]) -> SuggestedProvider {
+    let normalized = normalize_desktop_id(&app.desktop_id);
+    let renamed_from = PROVIDERS
+        .iter()
+        .find(|p| normalize_desktop_id(p.desktop_id) == normalized);
+    let (vendor_dir, config_prefix, projects_filename) = match renamed_from {
+        Some(known) => (
+            known.config.vendor_dir,
+            Some(known.config.config_prefix.to_string()),
+            known.config.projects_filename,
+        ),
+        None => {
+            let config_prefix = app
+                .executable
+                .as_deref()
+                .and_then(find_data_directory_name)
+                .and_then(|name| strip_version(&name).map(ToString::to_string))
+                .or_else(|| find_config_prefix_in(&app.name, config_roots));
+            (DEFAULT_VENDOR_DIR, config_prefix, DEFAULT_PROJECTS_FILENAME)
+        }
+    };
+    SuggestedProvider {
+        label: app.name.clone(),
+        desktop_id: app.desktop_id.clone(),
+        vendor_dir,
+        config_prefix,
+        projects_filename,
+        renamed_from,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn find_toolbox_scripts_without_desktop_entry() {
+        let scripts_dir = glib::mkdtemp(std::env::temp_dir().join("scripts-XXXXXX")).unwrap();
+        for script in ["idea", "clion", "jetbrains-toolbox"] {
+            std::fs::write(scripts_dir.join(script), "#!/bin/sh\n").unwrap();
+        }
+        let scripts =
+            find_scripts_without_desktop_entry(&scripts_dir, |id| id == "jetbrains-clion.desktop");
+        assert_eq!(
+            scripts
+                .iter()
+                .map(|(script, p)| (script.as_str(), p.desktop_id))
+                .collect::<Vec<_>>(),
+            vec![("idea", "jetbrains-idea.desktop")]
+        );
+        assert!(
+            find_scripts_without_desktop_entry(&scripts_dir.join("missing"), |_| false).is_empty()
+        );
+        std::fs::remove_dir_all(scripts_dir).unwrap();
+    }
+
+    fn app(desktop_id: &str, name: &str) -> DetectedApp {
+        DetectedApp {
+            desktop_id: desktop_id.to_string(),
+            name: name.to_string(),
+            executable: None,
+        }
+    }
+
+    #[test]
+    fn suggest_provider_for_renamed_desktop_id() {
+        let suggestion = suggest_provider(&app("goland.desktop", "GoLand"), &[]);
+        assert_eq!(
+            suggestion.renamed_from.map(|p| p.desktop_id),
+            Some("jetbrains-goland.desktop")
+        );
+        assert_eq!(suggestion.config_prefix.as_deref(), Some("GoLand"));
+        assert_eq!(
+            suggestion.to_definition(),
+            r#"ProviderDefinition {
+    label: "GoLand",
+    desktop_id: "goland.desktop",
+    relative_obj_path: "toolbox/goland",
+    config: ConfigLocation {
+        vendor_dir: "JetBrains",
+        config_prefix: "GoLand",
+        projects_filename: "recentProjects.xml",
+        excluded_channels: &[],
+    },
+},"#
+        );
+    }
+
+    #[test]
+    fn suggest_provider_from_config_directories() {
+        let root = glib::mkdtemp(std::env::temp_dir().join("detect-XXXXXX")).unwrap();
+        std::fs::create_dir(root.join("DataSpell2024.1")).unwrap();
+        let suggestion = suggest_provider(
+            &app("jetbrains-dataspell.desktop", "Data Spell"),
+            std::slice::from_ref(&root),
+        );
+        assert_eq!(suggestion.renamed_from.map(|p| p.desktop_id), None);
+        assert_eq!(suggestion.config_prefix.as_deref(), Some("DataSpell"));
+        assert_eq!(
+            suggestion.to_config_group(),
+            "[Provider jetbrains-dataspell.desktop]
+Label=Data Spell
+ConfigPrefix=DataSpell
+"
+        );
+        assert_eq!(suggestion.config_obj_path(), "custom/jetbrains_dataspell");
+        assert_eq!(
+            suggestion.search_provider_file(
+                "de.swsnr.searchprovider.Jetbrains",
+                &suggestion.config_obj_path()
+            ),
+            "[Shell Search Provider]
+DesktopId=jetbrains-dataspell.desktop
+BusName=de.swsnr.searchprovider.Jetbrains
+ObjectPath=/de/swsnr/searchprovider/jetbrains/custom/jetbrains_dataspell
+Version=2
+"
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn suggest_provider_definition_with_placeholder() {
+        let suggestion = suggest_provider(&app("acme.desktop", "Acme \"IDE\""), &[]);
+        assert_eq!(suggestion.config_prefix, None);
+        assert_eq!(
+            suggestion.to_config_group(),
+            "[Provider acme.desktop]
+Label=Acme \"IDE\"
+ConfigPrefix=TODO
+"
+        );
+        assert_eq!(
+            suggestion.to_definition(),
+            r#"ProviderDefinition {
+    label: "Acme \"IDE\"",
+    desktop_id: "acme.desktop",
+    relative_obj_path: "toolbox/acme",
+    config: ConfigLocation {
+        vendor_dir: "JetBrains",
+        config_prefix: "TODO",
+        projects_filename: "recentProjects.xml",
+        excluded_channels: &[],
+    },
+},"#
+        );
+    }
+}
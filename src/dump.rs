@@ -0,0 +1,311 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Dump parsed recent projects for debugging.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::providers::ProviderDefinition;
+use crate::recents::Limits;
+use crate::searchprovider::{read_recent_projects, AppId, ProjectNameCache, ReadOptions};
+
+/// A recent project as dumped.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DumpedProject {
+    /// The ID of the search result for this project.
+    pub id: String,
+    /// The human readable name of the project.
+    pub name: String,
+    /// The project directory.
+    pub directory: String,
+    /// Whether the project directory exists.
+    pub exists: bool,
+    /// When the project was last opened, in milliseconds since the Unix epoch.
+    pub opened_at: Option<i64>,
+}
+
+/// All recent projects of a provider, as dumped.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DumpedProvider {
+    /// The label of the provider.
+    pub label: String,
+    /// The desktop ID of the provider's app.
+    pub desktop_id: String,
+    /// The recent projects files we read projects from, in order of precedence.
+    pub files: Vec<String>,
+    /// All recent projects of the provider.
+    pub projects: Vec<DumpedProject>,
+    /// Warnings from reading the recent projects of the provider.
+    pub warnings: Vec<String>,
+}
+
+/// Read and dump the recent projects of `provider`.
+///
+/// Look for configuration directories in `config_roots` as well, read recent projects of all
+/// versions if `all_versions` is true, skip recent projects files and entries which exceed
+/// `limits`, and look for projects in alternative `home_roots`.
+///
+/// Like search results this includes projects whose directory doesn't exist, e.g. on unmounted
+/// drives, and tells whether the directory exists for each project.
+///
+/// If reading recent projects fails, return no projects, and add the error to the warnings.
+pub fn dump_provider(
+    provider: &ProviderDefinition<'_>,
+    config_roots: &[PathBuf],
+    all_versions: bool,
+    limits: &Limits,
+    home_roots: &[String],
+) -> DumpedProvider {
+    let mut warnings = Vec::new();
+    let mut sources = Vec::new();
+    let projects = match AppId::try_from(provider.desktop_id)
+        .map_err(anyhow::Error::from)
+        .and_then(|app_id| {
+            let options = ReadOptions {
+                config_roots,
+                all_versions,
+                limits,
+                home_roots,
+                find_trashed: false,
+            };
+            read_recent_projects(
+                &provider.config,
+                &app_id,
+                &options,
+                &mut warnings,
+                &mut sources,
+                &mut ProjectNameCache::default(),
+            )
+        }) {
+        Ok(projects) => projects
+            .into_iter()
+            .map(|(id, project)| DumpedProject {
+                id,
+                name: project.name().to_string(),
+                directory: project.directory().to_string(),
+                exists: Path::new(project.directory()).is_dir(),
+                opened_at: project.opened_at(),
+            })
+            .collect(),
+        Err(error) => {
+            warnings.push(format!("Failed to read recent projects: {error:#}"));
+            Vec::new()
+        }
+    };
+    DumpedProvider {
+        label: provider.display_label(),
+        desktop_id: provider.desktop_id.to_string(),
+        files: sources
+            .iter()
+            .map(|source| source.path.display().to_string())
+            .collect(),
+        projects,
+        warnings,
+    }
+}
+
+/// Format a timestamp in milliseconds since the Unix epoch as ISO 8601 string in UTC.
+fn format_timestamp(millis: i64) -> Option<String> {
+    glib::DateTime::from_unix_utc(millis.div_euclid(1000))
+        .and_then(|datetime| datetime.format("%FT%TZ"))
+        .ok()
+        .map(|s| s.to_string())
+}
+
+/// Quote `s` as JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Convert an optional value to JSON, with `null` for `None`.
+fn json_option(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+/// Render `providers` as a JSON array.
+pub fn to_json(providers: &[DumpedProvider]) -> String {
+    let providers = providers
+        .iter()
+        .map(|provider| {
+            let projects = provider
+                .projects
+                .iter()
+                .map(|project| {
+                    format!(
+                        r#"{{"id":{},"name":{},"directory":{},"exists":{},"opened_at":{}}}"#,
+                        json_string(&project.id),
+                        json_string(&project.name),
+                        json_string(&project.directory),
+                        project.exists,
+                        json_option(
+                            project
+                                .opened_at
+                                .and_then(format_timestamp)
+                                .map(|s| json_string(&s))
+                        )
+                    )
+                })
+                .collect::<Vec<_>>();
+            let files = provider
+                .files
+                .iter()
+                .map(|f| json_string(f))
+                .collect::<Vec<_>>();
+            let warnings = provider
+                .warnings
+                .iter()
+                .map(|w| json_string(w))
+                .collect::<Vec<_>>();
+            format!(
+                r#"{{"label":{},"desktop_id":{},"files":[{}],"projects":[{}],"warnings":[{}]}}"#,
+                json_string(&provider.label),
+                json_string(&provider.desktop_id),
+                files.join(","),
+                projects.join(","),
+                warnings.join(",")
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", providers.join(","))
+}
+
+/// Render `providers` as human readable tables.
+///
+/// Render one table per provider, preceded by the recent projects files of the provider, and
+/// followed by all warnings for the provider.
+pub fn to_table(providers: &[DumpedProvider]) -> String {
+    let mut output = String::new();
+    for provider in providers {
+        writeln!(output, "{} ({})", provider.label, provider.desktop_id).unwrap();
+        for file in &provider.files {
+            writeln!(output, "  file: {file}").unwrap();
+        }
+        let rows = provider
+            .projects
+            .iter()
+            .map(|project| {
+                [
+                    project.id.clone(),
+                    project.name.clone(),
+                    project.directory.clone(),
+                    if project.exists { "yes" } else { "no" }.to_string(),
+                    project
+                        .opened_at
+                        .and_then(format_timestamp)
+                        .unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let header = ["ID", "NAME", "DIRECTORY", "EXISTS", "OPENED"].map(ToString::to_string);
+        let mut widths = [0; 5];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(output, "  {}", line.trim_end()).unwrap();
+        }
+        for warning in &provider.warnings {
+            writeln!(output, "  warning: {warning}").unwrap();
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    fn provider() -> DumpedProvider {
+        DumpedProvider {
+            label: "IDEA (toolbox)".to_string(),
+            desktop_id: "jetbrains-idea.desktop".to_string(),
+            files: vec![
+                "/home/foo/.config/JetBrains/IntelliJIdea2024.2/options/recentProjects.xml"
+                    .to_string(),
+            ],
+            projects: vec![
+                DumpedProject {
+                    id: "jetbrains-recent-project-idea-/home/foo/mdcat".to_string(),
+                    name: "mdcat".to_string(),
+                    directory: "/home/foo/mdcat".to_string(),
+                    exists: true,
+                    opened_at: Some(1705312234567),
+                },
+                DumpedProject {
+                    id: "jetbrains-recent-project-idea-/home/foo/\"quoted\"".to_string(),
+                    name: "\"quoted\"".to_string(),
+                    directory: "/home/foo/\"quoted\"".to_string(),
+                    exists: false,
+                    opened_at: None,
+                },
+            ],
+            warnings: vec!["Skipping /home/foo/gone, failed to determine project name".to_string()],
+        }
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+    }
+
+    #[test]
+    fn dump_to_json() {
+        assert_eq!(
+            to_json(&[provider()]),
+            concat!(
+                r#"[{"label":"IDEA (toolbox)","desktop_id":"jetbrains-idea.desktop","#,
+                r#""files":["/home/foo/.config/JetBrains/IntelliJIdea2024.2/options/recentProjects.xml"],"#,
+                r#""projects":["#,
+                r#"{"id":"jetbrains-recent-project-idea-/home/foo/mdcat","name":"mdcat","#,
+                r#""directory":"/home/foo/mdcat","exists":true,"opened_at":"2024-01-15T09:50:34Z"},"#,
+                r#"{"id":"jetbrains-recent-project-idea-/home/foo/\"quoted\"","name":"\"quoted\"","#,
+                r#""directory":"/home/foo/\"quoted\"","exists":false,"opened_at":null}],"#,
+                r#""warnings":["Skipping /home/foo/gone, failed to determine project name"]}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn dump_to_table() {
+        assert_eq!(
+            to_table(&[provider()]),
+            "\
+IDEA (toolbox) (jetbrains-idea.desktop)
+  file: /home/foo/.config/JetBrains/IntelliJIdea2024.2/options/recentProjects.xml
+  ID                                                NAME      DIRECTORY           EXISTS  OPENED
+  jetbrains-recent-project-idea-/home/foo/mdcat     mdcat     /home/foo/mdcat     yes     2024-01-15T09:50:34Z
+  jetbrains-recent-project-idea-/home/foo/\"quoted\"  \"quoted\"  /home/foo/\"quoted\"  no      -
+  warning: Skipping /home/foo/gone, failed to determine project name
+
+"
+        );
+    }
+}
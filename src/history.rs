@@ -0,0 +1,85 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! History of activated results.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The maximum number of activations to remember.
+const MAX_ACTIVATIONS: usize = 100;
+
+/// An activated result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activation {
+    /// The desktop ID of the app which opened the project.
+    pub app_id: String,
+    /// The name of the project.
+    pub name: String,
+    /// The directory of the project.
+    pub directory: String,
+    /// When the project was activated, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Shared history of activated results, most recent first.
+///
+/// Clones share the same history.
+#[derive(Debug, Clone, Default)]
+pub struct ActivationHistory(Arc<Mutex<VecDeque<Activation>>>);
+
+impl ActivationHistory {
+    /// Record that the project at `directory` with `name` was opened with `app_id` right now.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub fn record(&self, app_id: &str, name: &str, directory: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.push(Activation {
+            app_id: app_id.to_string(),
+            name: name.to_string(),
+            directory: directory.to_string(),
+            timestamp,
+        });
+    }
+
+    fn push(&self, activation: Activation) {
+        let mut activations = self.0.lock().unwrap();
+        activations.push_front(activation);
+        activations.truncate(MAX_ACTIVATIONS);
+    }
+
+    /// Get at most `limit` most recent activations.
+    pub fn recent(&self, limit: usize) -> Vec<Activation> {
+        self.0.lock().unwrap().iter().take(limit).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn recent_activations_first() {
+        let history = ActivationHistory::default();
+        for i in 0..(MAX_ACTIVATIONS + 5) {
+            history.clone().push(Activation {
+                app_id: "jetbrains-idea.desktop".to_string(),
+                name: format!("project{i}"),
+                directory: format!("/home/foo/project{i}"),
+                timestamp: i as u64,
+            });
+        }
+        let recent = history.recent(2);
+        assert_eq!(
+            recent.iter().map(|a| a.timestamp).collect::<Vec<_>>(),
+            vec![104, 103]
+        );
+        assert_eq!(history.recent(usize::MAX).len(), MAX_ACTIVATIONS);
+    }
+}
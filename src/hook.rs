@@ -0,0 +1,121 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Run a command after launching an app for an activated result.
+//!
+//! Where a launched IDE puts the project window depends on the compositor; most put it on the
+//! workspace of the last IDE window.  We can't move windows ourselves, but users can configure a
+//! hook to do so, e.g. with `wmctrl` on X11, or with `gdbus` and a shell extension on Wayland.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+#[cfg(feature = "launch")]
+use tracing::{event, Level};
+
+/// A command to run after launching an app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationHook {
+    argv: Vec<String>,
+}
+
+impl FromStr for ActivationHook {
+    type Err = anyhow::Error;
+
+    /// Parse a hook from a command line with shell quoting.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let argv = glib::shell_parse_argv(s)
+            .with_context(|| format!("Invalid activation hook {s}"))?
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        if argv.is_empty() {
+            Err(anyhow!("Empty activation hook"))
+        } else {
+            Ok(Self { argv })
+        }
+    }
+}
+
+impl ActivationHook {
+    /// Get the command line of this hook for a launch of `app_id`.
+    ///
+    /// Replace `{app_id}` with `app_id`, `{wm_class}` with the `wm_class` of the app, or the
+    /// empty string, and `{timestamp}` with the `timestamp` of the activation, or 0 if unknown.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    fn expand(&self, app_id: &str, wm_class: Option<&str>, timestamp: Option<u32>) -> Vec<String> {
+        let timestamp = timestamp.unwrap_or(0).to_string();
+        self.argv
+            .iter()
+            .map(|arg| {
+                arg.replace("{app_id}", app_id)
+                    .replace("{wm_class}", wm_class.unwrap_or_default())
+                    .replace("{timestamp}", &timestamp)
+            })
+            .collect()
+    }
+
+    /// Run this hook after a launch of the app with `app_id` at `timestamp`.
+    ///
+    /// Wait for the hook to finish, and fail if it fails.
+    #[cfg(feature = "launch")]
+    pub async fn run(
+        &self,
+        app_id: &crate::searchprovider::AppId,
+        timestamp: Option<u32>,
+    ) -> anyhow::Result<()> {
+        let wm_class = crate::appinfo::lookup(app_id)
+            .ok()
+            .and_then(|app| app.startup_wm_class());
+        let argv = self.expand(&app_id.to_string(), wm_class.as_deref(), timestamp);
+        event!(Level::DEBUG, %app_id, "Running activation hook {:?}", argv);
+        let argv = argv.iter().map(std::ffi::OsStr::new).collect::<Vec<_>>();
+        gio::Subprocess::newv(&argv, gio::SubprocessFlags::NONE)
+            .with_context(|| format!("Failed to start activation hook {argv:?}"))?
+            .wait_check_future()
+            .await
+            .with_context(|| format!("Activation hook {argv:?} failed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn parse_hooks() {
+        let hook: ActivationHook = "sh -c 'wmctrl -x -r \"$1\" -t 0' hook {wm_class}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            hook.argv,
+            vec!["sh", "-c", "wmctrl -x -r \"$1\" -t 0", "hook", "{wm_class}"]
+        );
+        assert!("".parse::<ActivationHook>().is_err());
+        assert!("'unterminated".parse::<ActivationHook>().is_err());
+    }
+
+    #[test]
+    fn expand_placeholders() {
+        let hook: ActivationHook = "move-window {app_id} --class={wm_class} {timestamp}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            hook.expand("jetbrains-idea.desktop", Some("jetbrains-idea"), Some(42)),
+            vec![
+                "move-window",
+                "jetbrains-idea.desktop",
+                "--class=jetbrains-idea",
+                "42"
+            ]
+        );
+        assert_eq!(
+            hook.expand("jetbrains-idea.desktop", None, None),
+            vec!["move-window", "jetbrains-idea.desktop", "--class=", "0"]
+        );
+    }
+}
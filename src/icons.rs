@@ -0,0 +1,90 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Override the icons of providers.
+//!
+//! By default results show the icon of the app of their provider, but users with custom icon
+//! themes may prefer a different icon, e.g. a symbolic icon which matches the rest of their
+//! shell.
+
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use tracing::{event, Level};
+
+use crate::searchprovider::AppId;
+
+/// Icons to use instead of the icons of apps, by desktop ID.
+#[derive(Debug, Default, Clone)]
+pub struct Icons {
+    icons: HashMap<String, String>,
+}
+
+impl Icons {
+    /// Create icon overrides from pairs of desktop IDs and icons.
+    ///
+    /// An icon is either the name of an icon in the current icon theme, or an absolute path to
+    /// an image file.  Skip icons for invalid desktop IDs.
+    pub fn new<'a, I>(icons: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let icons = icons
+            .into_iter()
+            .filter_map(|(desktop_id, icon)| {
+                let app_id = match AppId::try_from(desktop_id) {
+                    Ok(app_id) => app_id,
+                    Err(error) => {
+                        event!(Level::WARN, "Skipping icon {icon}: {error}");
+                        return None;
+                    }
+                };
+                let icon = if icon.starts_with('/') {
+                    IconExt::to_string(&gio::FileIcon::new(&gio::File::for_path(icon)))
+                        .map_or_else(|| icon.to_string(), |icon| icon.to_string())
+                } else {
+                    icon.to_string()
+                };
+                Some((app_id.to_string(), icon))
+            })
+            .collect();
+        Self { icons }
+    }
+
+    /// Get the icon to use for the app with `app_id`, as serialized string.
+    ///
+    /// Return `None` if the icon of the app isn't overridden.
+    pub fn get(&self, app_id: &AppId) -> Option<&str> {
+        self.icons.get(&app_id.to_string()).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn override_icons_by_name_and_path() {
+        let icons = Icons::new([
+            ("jetbrains-idea", "idea-symbolic"),
+            ("jetbrains-clion.desktop", "/usr/share/pixmaps/clion.svg"),
+            ("apps/jetbrains-goland", "goland"),
+        ]);
+        assert_eq!(
+            icons.get(&AppId::try_from("jetbrains-idea.desktop").unwrap()),
+            Some("idea-symbolic")
+        );
+        assert_eq!(
+            icons.get(&AppId::try_from("jetbrains-clion.desktop").unwrap()),
+            Some("/usr/share/pixmaps/clion.svg")
+        );
+        assert_eq!(
+            icons.get(&AppId::try_from("jetbrains-goland.desktop").unwrap()),
+            None
+        );
+    }
+}
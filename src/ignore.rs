@@ -0,0 +1,135 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Ignore recent projects by name or path.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use tracing::{event, Level};
+
+/// The default location of the ignore list of the current user.
+pub fn user_ignore_file() -> PathBuf {
+    glib::user_config_dir()
+        .join(env!("CARGO_PKG_NAME"))
+        .join("ignore")
+}
+
+/// Convert a `glob` to an anchored regular expression.
+///
+/// `**` matches anything, `*` anything but `/`, and `?` any single character but `/`.
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.next_if_eq(&'*').is_some() => regex.push_str(".*"),
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// A list of patterns for projects to ignore.
+#[derive(Debug, Default, Clone)]
+pub struct IgnoreList {
+    patterns: Vec<Regex>,
+}
+
+impl IgnoreList {
+    /// Parse an ignore list.
+    ///
+    /// Each non-empty line which doesn't start with `#` holds a pattern.  Patterns starting with
+    /// `re:` are regular expressions which may match anywhere in the name or directory of a
+    /// project; all other patterns are globs which must match the whole name or directory, see
+    /// [`glob_to_regex`].
+    pub fn parse(s: &str) -> Result<Self> {
+        let patterns = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(lineno, line)| {
+                let regex = match line.strip_prefix("re:") {
+                    Some(regex) => regex.to_string(),
+                    None => glob_to_regex(line),
+                };
+                Regex::new(&regex).with_context(|| format!("Invalid pattern in line {lineno}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Load an ignore list from the file at `path`.
+    ///
+    /// Return an empty list if `path` does not exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                let list = Self::parse(&contents)
+                    .with_context(|| format!("Failed to parse ignore list {}", path.display()))?;
+                event!(
+                    Level::INFO,
+                    "Loaded {} ignore pattern(s) from {}",
+                    list.patterns.len(),
+                    path.display()
+                );
+                Ok(list)
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                event!(Level::DEBUG, "No ignore list at {}", path.display());
+                Ok(Self::default())
+            }
+            Err(error) => {
+                Err(error).with_context(|| format!("Failed to read ignore list {}", path.display()))
+            }
+        }
+    }
+
+    /// Whether to ignore a project with the given `name` and `directory`.
+    pub fn is_ignored(&self, name: &str, directory: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.is_match(name) || pattern.is_match(directory))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignore_by_glob_and_regex() {
+        let list = IgnoreList::parse(
+            "\
+# Scratch projects
+**/tmp-*
+
+Acme*
+re:[Ss]ecret
+",
+        )
+        .unwrap();
+        assert!(list.is_ignored("foo", "/home/foo/Code/tmp-foo"));
+        assert!(!list.is_ignored("foo", "/home/foo/Code/tmp-foo/bar"));
+        assert!(list.is_ignored("AcmeShop", "/home/foo/Code/shop"));
+        assert!(!list.is_ignored("NotAcme", "/home/foo/Code/acme"));
+        assert!(list.is_ignored("foo", "/home/foo/Code/TopSecretThing"));
+        assert!(!list.is_ignored("mdcat", "/home/foo/Code/gh/mdcat"));
+    }
+
+    #[test]
+    fn reject_invalid_regex() {
+        let error = IgnoreList::parse("foo\nre:(unclosed\n").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid pattern in line 2");
+    }
+}
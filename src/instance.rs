@@ -0,0 +1,116 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Run only a single instance of the service.
+//!
+//! Only one connection can own our bus name, so a second instance, e.g. started manually while
+//! the systemd unit runs, would only fail after loading all recent projects.  Instead we check
+//! for a running instance first, and fail right away unless the user asked to replace it.
+//!
+//! Every instance allows others to replace it; on replacement the new instance takes over the
+//! name right away, and the old instance shuts down in an orderly fashion once it lost the name.
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{event, Level};
+use zbus::export::futures_util::StreamExt;
+use zbus::fdo::{DBusProxy, NameLostStream, RequestNameFlags, RequestNameReply};
+use zbus::names::BusName;
+
+use crate::shutdown::Shutdown;
+use crate::validate::SYSTEMD_UNIT;
+
+/// The error for an instance already running at `busname`.
+fn already_running(busname: &str) -> anyhow::Error {
+    anyhow!(
+        "Another instance of the service already owns {busname}, e.g. the systemd user unit \
+         {SYSTEMD_UNIT}; stop it with `systemctl --user stop {SYSTEMD_UNIT}`, or pass --replace \
+         to take over from it"
+    )
+}
+
+/// Check whether another instance of the service owns `busname`.
+///
+/// Fail if so, unless we shall `replace` the other instance.
+pub async fn check_not_running(busname: &str, replace: bool) -> Result<()> {
+    let connection = zbus::Connection::session()
+        .await
+        .with_context(|| "Failed to connect to session bus")?;
+    let has_owner = DBusProxy::new(&connection)
+        .await?
+        .name_has_owner(BusName::try_from(busname)?)
+        .await
+        .with_context(|| format!("Failed to check for owner of {busname}"))?;
+    match (has_owner, replace) {
+        (false, _) => Ok(()),
+        (true, true) => {
+            event!(Level::INFO, "Replacing running instance at {busname}");
+            Ok(())
+        }
+        (true, false) => Err(already_running(busname)),
+    }
+}
+
+/// Acquire `busname` on `connection`, and allow other instances to replace us.
+///
+/// If `replace` take over `busname` from a running instance, otherwise fail if another instance
+/// owns `busname`.
+///
+/// Return a stream of lost names, for [`shutdown_on_name_lost`].
+pub async fn acquire_name(
+    connection: &zbus::Connection,
+    busname: &str,
+    replace: bool,
+) -> Result<NameLostStream<'static>> {
+    // Listen for lost names before we request the name, to not miss a replacement right away
+    let name_lost = DBusProxy::new(connection)
+        .await?
+        .receive_name_lost()
+        .await?;
+    let mut flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
+    if replace {
+        flags |= RequestNameFlags::ReplaceExisting;
+    }
+    let reply = connection
+        .request_name_with_flags(busname, flags)
+        .await
+        .with_context(|| format!("Failed to acquire {busname}"))?;
+    match reply {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(name_lost),
+        RequestNameReply::Exists | RequestNameReply::InQueue => Err(already_running(busname)),
+    }
+}
+
+/// Shut down once we lost `busname` to another instance.
+pub async fn shutdown_on_name_lost(
+    mut name_lost: NameLostStream<'static>,
+    busname: String,
+    shutdown: Shutdown,
+) {
+    while let Some(signal) = name_lost.next().await {
+        match signal.args() {
+            Ok(args) if args.name() == busname.as_str() => {
+                shutdown.request(&format!("Another instance took over {busname}"));
+                return;
+            }
+            Ok(_) => (),
+            Err(error) => {
+                event!(Level::WARN, %error, "Failed to parse NameLost signal: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offer_replace_if_already_running() {
+        let message = already_running("de.swsnr.searchprovider.Jetbrains").to_string();
+        assert!(message.contains("owns de.swsnr.searchprovider.Jetbrains"));
+        assert!(message.contains("--replace"));
+    }
+}
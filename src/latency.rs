@@ -0,0 +1,107 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Skip providers whose searches or reloads take too long.
+//!
+//! Gnome Shell waits for all search providers before it shows results, so a single provider
+//! which takes seconds to answer delays the entire search.  Searches themselves only score
+//! projects in memory; the IO happens when a provider reloads recent projects, e.g. from a
+//! configuration directory on a hung network mount.  We therefore track two budgets: if a search
+//! exceeds the latency budget, or a reload exceeds the reload budget, we suspend the provider for
+//! a while, and then probe it again with the next search.  Reload budgets are more generous,
+//! because the first load reads project names from every project directory.
+
+use std::time::{Duration, Instant};
+
+/// How long to suspend a provider after a search exceeded the latency budget.
+pub const REPROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Track the search latency of a provider.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LatencyTracker {
+    /// Until when to skip the provider.
+    suspended_until: Option<Instant>,
+}
+
+impl LatencyTracker {
+    /// Record a search which took `latency` and finished at `now`.
+    ///
+    /// If `latency` exceeds a non-zero `budget` suspend the provider until [`REPROBE_INTERVAL`]
+    /// after `now`, otherwise resume it.  Return whether the provider is suspended.
+    pub fn record(&mut self, latency: Duration, budget: Duration, now: Instant) -> bool {
+        if !budget.is_zero() && budget < latency {
+            self.suspended_until = Some(now + REPROBE_INTERVAL);
+            true
+        } else {
+            self.suspended_until = None;
+            false
+        }
+    }
+
+    /// Suspend the provider until [`REPROBE_INTERVAL`] after `now`, e.g. after a slow reload.
+    pub fn suspend(&mut self, now: Instant) {
+        self.suspended_until = Some(now + REPROBE_INTERVAL);
+    }
+
+    /// How much longer the provider is suspended at `now`, if at all.
+    pub fn suspended_for(&self, now: Instant) -> Option<Duration> {
+        self.suspended_until
+            .filter(|suspended_until| now < *suspended_until)
+            .map(|suspended_until| suspended_until - now)
+    }
+
+    /// Whether the provider is suspended at `now`.
+    ///
+    /// Once the reprobe interval passed, the provider is no longer suspended, and the next search
+    /// probes it again.
+    pub fn is_suspended(&self, now: Instant) -> bool {
+        self.suspended_until
+            .is_some_and(|suspended_until| now < suspended_until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspend_and_reprobe() {
+        let budget = Duration::from_secs(1);
+        let now = Instant::now();
+        let mut tracker = LatencyTracker::default();
+        assert!(!tracker.is_suspended(now));
+        assert!(!tracker.record(Duration::from_millis(200), budget, now));
+        assert!(!tracker.is_suspended(now));
+        assert!(tracker.record(Duration::from_secs(3), budget, now));
+        assert!(tracker.is_suspended(now + Duration::from_secs(30)));
+        assert!(!tracker.is_suspended(now + REPROBE_INTERVAL));
+        let later = now + REPROBE_INTERVAL;
+        assert!(!tracker.record(Duration::from_millis(100), budget, later));
+        assert!(!tracker.is_suspended(later));
+    }
+
+    #[test]
+    fn suspend_until_reprobe() {
+        let now = Instant::now();
+        let mut tracker = LatencyTracker::default();
+        assert_eq!(tracker.suspended_for(now), None);
+        tracker.suspend(now);
+        assert_eq!(tracker.suspended_for(now), Some(REPROBE_INTERVAL));
+        assert_eq!(
+            tracker.suspended_for(now + Duration::from_secs(20)),
+            Some(REPROBE_INTERVAL - Duration::from_secs(20))
+        );
+        assert_eq!(tracker.suspended_for(now + REPROBE_INTERVAL), None);
+    }
+
+    #[test]
+    fn zero_budget_never_suspends() {
+        let now = Instant::now();
+        let mut tracker = LatencyTracker::default();
+        assert!(!tracker.record(Duration::from_secs(60), Duration::ZERO, now));
+        assert!(!tracker.is_suspended(now));
+    }
+}
@@ -6,21 +6,33 @@
 
 //! Launching apps.
 
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::time::Duration;
+
 use gio::prelude::*;
 use glib::{Variant, VariantDict};
 use tracing::{event, instrument, span, Level};
 use tracing_futures::Instrument;
-use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::zvariant::OwnedObjectPath;
+
+use zbus::export::futures_util::StreamExt;
 
-use crate::systemd::{self, Systemd1ManagerProxy};
+use crate::notifications::NotificationsProxy;
+use crate::private::PrivateMode;
+use crate::redact;
+use crate::systemd::{
+    self, ScopeProperties, Systemd1ManagerProxy, Systemd1ScopeProxy, Systemd1UnitProxy,
+};
+use crate::timeout::with_timeout;
 
 fn get_pid(platform_data: &Variant) -> Option<i32> {
     match platform_data.get::<VariantDict>() {
         None => {
             event!(
                 Level::ERROR,
-                "platform_data not a dictionary, but {:?}",
-                platform_data
+                "platform_data not a dictionary, but {}",
+                redact::platform_data(platform_data)
             );
             None
         }
@@ -41,8 +53,8 @@ fn get_pid(platform_data: &Variant) -> Option<i32> {
             Ok(None) => {
                 event!(
                     Level::WARN,
-                    "pid missing in platform_data {:?}",
-                    platform_data
+                    "pid missing in platform_data {}",
+                    redact::platform_data(platform_data)
                 );
                 None
             }
@@ -51,70 +63,432 @@ fn get_pid(platform_data: &Variant) -> Option<i32> {
     }
 }
 
+/// What an app was launched for.
+///
+/// We record this information in the systemd scope of the launched app, to make it easier
+/// to identify scopes with `systemctl --user status`.
+#[derive(Debug, Clone)]
+pub struct LaunchProvenance {
+    /// The name of the project the app was launched for.
+    pub project_name: String,
+    /// The directory of the project the app was launched for.
+    pub project_directory: String,
+}
+
+/// Environment variables we remove from the environment of launched apps.
+///
+/// These variables either configure this service, or describe the systemd service we run in,
+/// and make no sense for launched apps.
+pub const SCRUBBED_ENVIRONMENT: &[&str] = &[
+    "RUST_LOG",
+    "RUST_BACKTRACE",
+    "RUST_LIB_BACKTRACE",
+    "INVOCATION_ID",
+    "JOURNAL_STREAM",
+    "NOTIFY_SOCKET",
+    "MANAGERPID",
+    "SYSTEMD_EXEC_PID",
+    "LISTEN_PID",
+    "LISTEN_FDS",
+    "LISTEN_FDNAMES",
+    "WATCHDOG_PID",
+    "WATCHDOG_USEC",
+];
+
+/// Remove all variables in [`SCRUBBED_ENVIRONMENT`] from the environment of `context`.
+///
+/// Keep all variables listed in `keep`.
+pub fn scrub_environment<S: AsRef<str>>(context: &gio::AppLaunchContext, keep: &[S]) {
+    for variable in SCRUBBED_ENVIRONMENT {
+        if keep.iter().any(|k| k.as_ref() == *variable) {
+            event!(Level::TRACE, "Keeping {variable} in environment");
+        } else {
+            context.unsetenv(variable);
+        }
+    }
+}
+
+/// Environment variables of the graphical session.
+///
+/// systemd may start this service before the graphical session imports these variables into the
+/// activation environment, so we refresh them before every launch.
+pub const SESSION_ENVIRONMENT: &[&str] = &[
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XAUTHORITY",
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_TYPE",
+];
+
+/// Get all variables of the graphical session from `environment`.
+///
+/// `environment` holds `NAME=VALUE` pairs; return all pairs whose name is in
+/// [`SESSION_ENVIRONMENT`].
+fn session_variables(environment: &[String]) -> Vec<(&str, &str)> {
+    environment
+        .iter()
+        .filter_map(|variable| variable.split_once('='))
+        .filter(|(name, _)| SESSION_ENVIRONMENT.contains(name))
+        .collect()
+}
+
+/// Update the graphical session variables in the environment of `context`.
+///
+/// Get the current activation environment from the systemd user manager, and copy all variables
+/// of the graphical session, see [`SESSION_ENVIRONMENT`], to `context`.  Keep the environment of
+/// `context` if the manager doesn't reply within `call_timeout`.
+#[instrument(skip(connection, context))]
+pub async fn refresh_session_environment(
+    connection: &zbus::Connection,
+    context: &gio::AppLaunchContext,
+    call_timeout: Duration,
+) {
+    let environment = with_timeout(call_timeout, async {
+        // The activation environment can change anytime, so don't cache it.
+        let manager = Systemd1ManagerProxy::builder(connection)
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await?;
+        manager.environment().await
+    })
+    .await;
+    match environment {
+        Ok(environment) => {
+            for (name, value) in session_variables(&environment) {
+                let current = context.environment().into_iter().find_map(|variable| {
+                    variable
+                        .to_str()
+                        .and_then(|v| v.strip_prefix(name)?.strip_prefix('='))
+                        .map(ToOwned::to_owned)
+                });
+                if current.as_deref() != Some(value) {
+                    event!(
+                        Level::INFO,
+                        "Updating {name} to {value} from activation environment"
+                    );
+                    context.setenv(name, value);
+                }
+            }
+        }
+        Err(error) => {
+            event!(
+                Level::WARN,
+                %error,
+                "Failed to get activation environment, keeping current environment: {error}"
+            );
+        }
+    }
+}
+
+/// Parse the state out of the contents of `/proc/<pid>/stat`.
+///
+/// See `proc_pid_stat(5)` for the format.  The command name in the second field can contain
+/// whitespace and parenthesis, so we look for the last closing parenthesis.
+fn parse_proc_stat(stat: &str) -> Option<char> {
+    stat[stat.rfind(')')? + 1..]
+        .split_whitespace()
+        .next()?
+        .chars()
+        .next()
+}
+
+/// Whether `pid` refers to a running process launched by this service.
+///
+/// The launched signal can refer to a process which already exited, e.g. if the launcher script
+/// of an IDE exits quickly, and the kernel can then reuse its PID for an unrelated process.  GIO
+/// double-forks launched apps, so they are no children of our process; but they inherit the
+/// cgroup of this service until we move them to their own scope, whereas unrelated processes
+/// live in other cgroups.  Hence we detect reuse by comparing the cgroup of `pid` with our own.
+fn is_launched_process(pid: i32) -> bool {
+    let read = |file: &str| {
+        std::fs::read_to_string(format!("/proc/{pid}/{file}"))
+            .map_err(|error| {
+                event!(Level::WARN, "Failed to read {file} of PID {pid}: {error}");
+            })
+            .ok()
+    };
+    let own_cgroup = match std::fs::read_to_string("/proc/self/cgroup") {
+        Ok(cgroup) => cgroup,
+        Err(error) => {
+            event!(Level::WARN, "Failed to read our own cgroup: {error}");
+            return false;
+        }
+    };
+    let Some(stat) = read("stat") else {
+        return false;
+    };
+    match parse_proc_stat(&stat) {
+        None => {
+            event!(Level::WARN, "Failed to parse status of PID {pid}: {stat}");
+            false
+        }
+        Some('Z') => {
+            event!(Level::WARN, "Process {pid} already exited");
+            false
+        }
+        Some(_) => match read("cgroup") {
+            None => false,
+            Some(cgroup) if cgroup != own_cgroup => {
+                event!(
+                    Level::WARN,
+                    "Process {pid} in cgroup {}, not ours; PID reused by another process?",
+                    cgroup.trim()
+                );
+                false
+            }
+            Some(_) => true,
+        },
+    }
+}
+
 #[instrument(skip(connection))]
 async fn move_to_scope(
     connection: &zbus::Connection,
     app_name: &str,
+    app_label: &str,
     pid: u32,
+    provenance: Option<&LaunchProvenance>,
 ) -> Result<(String, OwnedObjectPath), zbus::Error> {
     let manager = Systemd1ManagerProxy::new(connection).await?;
-    // See https://gitlab.gnome.org/jf/start-transient-unit/-/blob/117c6f32c8dc0d1f28686408f698632aa71880bc/rust/src/main.rs#L94
-    // for inspiration.
-    // See https://www.freedesktop.org/wiki/Software/systemd/ControlGroupInterface/ for background.
-    let props = &[
-        // I haven't found any documentation for the type of the PIDs property directly, but elsewhere
-        // in its DBus interface system always used u32 for PIDs.
-        ("PIDs", Value::Array(vec![pid].into())),
-        // libgnome passes this property too, see
-        // https://gitlab.gnome.org/GNOME/gnome-desktop/-/blob/106a729c3f98b8ee56823a0a49fa8504f78dd355/libgnome-desktop/gnome-systemd.c#L100
-        //
-        // I'm not entirely sure how it's relevant but it seems a good idea to do what Gnome does.
-        ("CollectMode", Value::Str("inactive-or-failed".into())),
-    ];
-    let name = format!(
-        "app-{}-{}-{}.scope",
-        env!("CARGO_BIN_NAME"),
-        systemd::escape_name(app_name.trim_end_matches(".desktop")),
-        pid
-    );
-    event!(
-        Level::DEBUG,
-        "Creating new scope {name} for PID {pid} of {app_name} with {props:?}"
-    );
-    let scope_object_path = manager
-        .start_transient_unit(&name, "fail", props, &[])
+    let description =
+        provenance.map(|provenance| format!("{} – {}", provenance.project_name, app_label));
+    let documentation = provenance.and_then(|provenance| {
+        glib::filename_to_uri(&provenance.project_directory, None)
+            .map_err(|error| {
+                event!(
+                    Level::WARN,
+                    "Failed to convert {} to URI: {error}",
+                    redact::path(&provenance.project_directory)
+                );
+            })
+            .ok()
+    });
+    let properties = ScopeProperties {
+        prefix: concat!("app-", env!("CARGO_BIN_NAME")),
+        name: app_name.trim_end_matches(".desktop"),
+        description: description.as_deref(),
+        documentation: documentation.iter().map(|uri| uri.as_str()).collect(),
+    };
+    systemd::start_app_scope(&manager, properties, pid).await
+}
+
+/// How long to watch the scope of a launched app for an immediate exit.
+///
+/// IDEs take a while to start, so an IDE whose scope stops within this window most likely crashed
+/// on startup, e.g. because of invalid JVM options.
+const LAUNCH_FAILURE_WINDOW: Duration = Duration::from_secs(10);
+
+/// How the scope of a launched app ended.
+#[derive(Debug, PartialEq, Eq)]
+struct ScopeEnd {
+    /// The final state of the scope, i.e. `inactive` or `failed`.
+    active_state: String,
+    /// The result of the scope, e.g. `success` or `oom-kill`.
+    result: String,
+}
+
+impl ScopeEnd {
+    /// Whether the scope ended with a failure.
+    ///
+    /// Apps may also end successfully right away, e.g. when an IDE hands the project over to an
+    /// instance which is already running.
+    fn is_failure(&self) -> bool {
+        self.active_state == "failed" || self.result != "success"
+    }
+}
+
+/// Wait at most `window` for the scope `name` to end.
+///
+/// Return how the scope ended, or `None` if it still runs after `window`.
+#[instrument(skip(connection))]
+async fn watch_scope(
+    connection: &zbus::Connection,
+    name: &str,
+    window: Duration,
+) -> zbus::Result<Option<ScopeEnd>> {
+    let manager = Systemd1ManagerProxy::new(connection).await?;
+    // Systemd refuses a second subscription of the same client, which is fine for us
+    if let Err(error) = manager.subscribe().await {
+        event!(Level::DEBUG, %error, "Failed to subscribe to systemd: {error}");
+    }
+    let path = manager.get_unit(name).await?;
+    let unit = Systemd1UnitProxy::builder(connection)
+        .path(path.clone())?
+        .build()
+        .await?;
+    let scope = Systemd1ScopeProxy::builder(connection)
+        .path(path)?
+        .build()
+        .await?;
+    let mut states = unit.receive_active_state_changed().await;
+    let ended = async {
+        // Check the current state first, in case the scope ended before we subscribed
+        let mut active_state = unit.active_state().await?;
+        loop {
+            if active_state == "inactive" || active_state == "failed" {
+                let result = scope.result().await?;
+                return Ok(Some(ScopeEnd {
+                    active_state,
+                    result,
+                }));
+            }
+            match states.next().await {
+                Some(change) => active_state = change.get().await?,
+                None => return Ok(None),
+            }
+        }
+    };
+    glib::future_with_timeout(window, ended)
+        .await
+        .unwrap_or(Ok(None))
+}
+
+/// Tell the user that the app `app_label` launched in scope `name` failed right away.
+async fn notify_launch_failure(
+    connection: &zbus::Connection,
+    app_icon: &str,
+    app_label: &str,
+    name: &str,
+    end: &ScopeEnd,
+) -> zbus::Result<()> {
+    let notifications = NotificationsProxy::new(connection).await?;
+    notifications
+        .notify(
+            env!("CARGO_PKG_NAME"),
+            0,
+            app_icon,
+            &format!("{app_label} failed to start"),
+            &format!(
+                "{app_label} exited right after launch with result {}; run \"journalctl --user -u {name}\" for details.",
+                end.result
+            ),
+            &[],
+            std::collections::HashMap::new(),
+            -1,
+        )
         .await?;
-    Ok((name, scope_object_path))
+    Ok(())
+}
+
+/// Watch the scope `name` of the app `app_name` for an immediate exit after launch.
+///
+/// If the scope fails within [`LAUNCH_FAILURE_WINDOW`] log an error and notify the user, unless
+/// `private_mode` is enabled.
+async fn supervise_launch(
+    connection: &zbus::Connection,
+    app_name: &str,
+    app_label: &str,
+    app_icon: &str,
+    name: &str,
+    call_timeout: Duration,
+    private_mode: &PrivateMode,
+) {
+    match watch_scope(connection, name, LAUNCH_FAILURE_WINDOW).await {
+        Err(error) => {
+            event!(Level::WARN, %error, "Failed to watch scope {name} of app {app_name}: {error}");
+        }
+        Ok(None) => {
+            event!(Level::DEBUG, "App {app_name} still running in scope {name}");
+        }
+        Ok(Some(end)) if end.is_failure() => {
+            event!(
+                Level::ERROR,
+                scope = name,
+                active_state = end.active_state,
+                result = end.result,
+                "App {app_name} exited within {}s after launch; scope {name} is {} with result {}",
+                LAUNCH_FAILURE_WINDOW.as_secs(),
+                end.active_state,
+                end.result
+            );
+            if private_mode.is_enabled() {
+                event!(
+                    Level::DEBUG,
+                    "Private mode enabled, not notifying about failed launch of {app_name}"
+                );
+                return;
+            }
+            let notify = notify_launch_failure(connection, app_icon, app_label, name, &end);
+            if let Err(error) = with_timeout(call_timeout, notify).await {
+                event!(Level::WARN, %error, "Failed to notify about failed launch of {app_name}: {error}");
+            }
+        }
+        Ok(Some(end)) => {
+            event!(
+                Level::INFO,
+                "App {app_name} exited within {}s after launch with result {}; handed over to a running instance?",
+                LAUNCH_FAILURE_WINDOW.as_secs(),
+                end.result
+            );
+        }
+    }
 }
 
 /**
  * Create a launch context.
  *
- * This context moves all launched applications to their own system scope.
+ * This context moves all launched applications to their own system scope, and records the
+ * given `provenance` in the scope, if any.
+ *
+ * The context moves every PID only once, and skips PIDs which no longer refer to a process
+ * launched by this service.  It gives up on moving a PID if systemd doesn't reply within
+ * `call_timeout`.
+ *
+ * After moving a PID the context watches the new scope for a short while, and notifies the user
+ * if the app fails right away, unless `private_mode` is enabled by then.
  */
-pub fn create_launch_context(connection: zbus::Connection) -> gio::AppLaunchContext {
+pub fn create_launch_context(
+    connection: zbus::Connection,
+    provenance: Option<LaunchProvenance>,
+    call_timeout: Duration,
+    private_mode: PrivateMode,
+) -> gio::AppLaunchContext {
     let context = gio::AppLaunchContext::new();
+    let seen_pids = RefCell::new(HashSet::new());
     context.connect_launched(move |_, app, platform_data| {
         let app_id = app.id().unwrap().to_string();
-        let _guard = span!(Level::INFO, "launched", %app_id, %platform_data).entered();
+        let _guard = span!(
+            Level::INFO,
+            "launched",
+            %app_id,
+            platform_data = %redact::platform_data(platform_data)
+        )
+        .entered();
         event!(
             Level::TRACE,
-            "App {} launched with platform_data: {:?}",
+            "App {} launched with platform_data: {}",
             app_id,
-            platform_data
+            redact::platform_data(platform_data)
         );
         if let Some(pid) = get_pid(platform_data) {
             event!(Level::INFO, "App {} launched with PID {pid}", app.id().unwrap());
+            if !seen_pids.borrow_mut().insert(pid) {
+                event!(Level::DEBUG, "Skipping PID {pid}, already moved to a scope");
+                return;
+            }
+            if !is_launched_process(pid) {
+                event!(Level::WARN, "Not moving PID {pid} of app {app_id} to a new scope");
+                return;
+            }
             let app_name = app.id().unwrap().to_string();
+            let app_label = app.display_name().to_string();
+            let app_icon = app
+                .icon()
+                .and_then(|icon| IconExt::to_string(&icon))
+                .map_or_else(String::new, |icon| icon.to_string());
             let connection_inner = connection.clone();
+            let provenance = provenance.clone();
+            let private_mode = private_mode.clone();
             glib::MainContext::ref_thread_default().spawn(
                 async move {
-                    match move_to_scope(&connection_inner, &app_name, pid as u32).await {
+                    let move_to_scope = move_to_scope(&connection_inner, &app_name, &app_label, pid as u32, provenance.as_ref());
+                    match with_timeout(call_timeout, move_to_scope).await {
                         Err(err) => {
                             event!(Level::ERROR, "Failed to move running process {pid} of app {app_name} into new systemd scope: {err}");
                         },
                         Ok((name, path)) => {
                             event!(Level::INFO, "Moved running process {pid} of app {app_name} into new systemd scope {name} at {}", path.into_inner());
+                            supervise_launch(&connection_inner, &app_name, &app_label, &app_icon, &name, call_timeout, &private_mode).await;
                         },
                     }
                 }.in_current_span(),
@@ -123,3 +497,219 @@ pub fn create_launch_context(connection: zbus::Connection) -> gio::AppLaunchCont
     });
     context
 }
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+    use zbus::zvariant::{Array, Value};
+
+    use crate::systemd::mock::*;
+
+    use super::*;
+
+    #[test]
+    fn scrub_environment_keeps_requested_variables() {
+        let context = gio::AppLaunchContext::new();
+        context.setenv("RUST_LOG", "debug");
+        context.setenv("RUST_BACKTRACE", "1");
+        context.setenv("FOO", "bar");
+        scrub_environment(&context, &["RUST_BACKTRACE"]);
+        let environment = context
+            .environment()
+            .into_iter()
+            .map(|v| v.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(!environment.iter().any(|v| v.starts_with("RUST_LOG=")));
+        assert!(environment.contains(&"RUST_BACKTRACE=1".to_string()));
+        assert!(environment.contains(&"FOO=bar".to_string()));
+    }
+
+    #[test]
+    fn scope_end_failure() {
+        let end = |active_state: &str, result: &str| ScopeEnd {
+            active_state: active_state.to_string(),
+            result: result.to_string(),
+        };
+        assert!(!end("inactive", "success").is_failure());
+        assert!(end("failed", "success").is_failure());
+        assert!(end("inactive", "oom-kill").is_failure());
+        assert!(end("failed", "timeout").is_failure());
+    }
+
+    #[test]
+    fn session_variables_of_environment() {
+        let environment = [
+            "PATH=/usr/bin",
+            "WAYLAND_DISPLAY=wayland-0",
+            "DISPLAY=:0",
+            "XDG_SESSION_TYPE=wayland",
+            "BROKEN",
+        ]
+        .map(ToOwned::to_owned);
+        assert_eq!(
+            session_variables(&environment),
+            vec![
+                ("WAYLAND_DISPLAY", "wayland-0"),
+                ("DISPLAY", ":0"),
+                ("XDG_SESSION_TYPE", "wayland")
+            ]
+        );
+    }
+
+    #[test]
+    fn refresh_session_environment_from_manager() {
+        let manager = MockSystemd1Manager {
+            environment: vec![
+                "WAYLAND_DISPLAY=wayland-1".to_string(),
+                "DISPLAY=:0".to_string(),
+                "FOO=bar".to_string(),
+            ],
+            ..MockSystemd1Manager::default()
+        };
+        let context = gio::AppLaunchContext::new();
+        context.setenv("DISPLAY", ":0");
+        context.unsetenv("WAYLAND_DISPLAY");
+        context.unsetenv("FOO");
+        // with_timeout needs a glib main context
+        glib::MainContext::new().block_on(async {
+            let (_server, connection) = connect(manager).await.unwrap();
+            refresh_session_environment(&connection, &context, Duration::from_secs(10)).await;
+        });
+        let environment = context
+            .environment()
+            .into_iter()
+            .map(|v| v.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(environment.contains(&"WAYLAND_DISPLAY=wayland-1".to_string()));
+        assert!(environment.contains(&"DISPLAY=:0".to_string()));
+        assert!(!environment.iter().any(|v| v.starts_with("FOO=")));
+    }
+
+    #[test]
+    fn parse_proc_stat_of_process() {
+        assert_eq!(
+            parse_proc_stat("4242 (idea.sh) S 1000 4242 4242 0 -1 4194560 1234"),
+            Some('S')
+        );
+        assert_eq!(
+            parse_proc_stat("4242 (weird) Z (name)) Z 1 4242"),
+            Some('Z')
+        );
+        assert_eq!(parse_proc_stat("4242 (truncated"), None);
+        assert_eq!(parse_proc_stat("4242 (idea)"), None);
+    }
+
+    #[test]
+    fn missing_process_is_not_launched() {
+        assert!(!is_launched_process(i32::MAX));
+    }
+
+    #[test]
+    fn process_launched_through_gio_is_launched_process() {
+        let key_file = glib::KeyFile::new();
+        key_file
+            .load_from_data(
+                "[Desktop Entry]\nType=Application\nName=Sleep\nExec=sleep 10\n",
+                glib::KeyFileFlags::NONE,
+            )
+            .unwrap();
+        let app = gio::DesktopAppInfo::from_keyfile(&key_file).unwrap();
+        let context = gio::AppLaunchContext::new();
+        let launched = std::rc::Rc::new(RefCell::new(None));
+        let launched_inner = launched.clone();
+        context.connect_launched(move |_, _, platform_data| {
+            *launched_inner.borrow_mut() = get_pid(platform_data);
+        });
+        app.launch_uris(&[], Some(&context)).unwrap();
+        let pid = launched.borrow().unwrap();
+        let is_launched = is_launched_process(pid);
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+        std::process::Command::new("kill")
+            .arg(pid.to_string())
+            .status()
+            .unwrap();
+        // GIO double-forks, so the launched process is not our child.
+        let ppid = stat[stat.rfind(')').unwrap() + 1..]
+            .split_whitespace()
+            .nth(1)
+            .unwrap()
+            .parse::<u32>()
+            .unwrap();
+        assert_ne!(ppid, std::process::id());
+        assert!(is_launched);
+    }
+
+    #[test]
+    fn move_to_scope_starts_transient_scope() {
+        let manager = MockSystemd1Manager::default();
+        let calls = manager.calls.clone();
+        let (name, path) = zbus::block_on(async {
+            let (_server, connection) = connect(manager).await.unwrap();
+            move_to_scope(
+                &connection,
+                "jetbrains-idea.desktop",
+                "IntelliJ IDEA",
+                4242,
+                None,
+            )
+            .await
+            .unwrap()
+        });
+
+        assert_eq!(
+            name,
+            "app-gnome-search-providers-jetbrains-jetbrains\\x2didea-4242.scope"
+        );
+        assert_eq!(path.as_str(), "/org/freedesktop/systemd1/job/42");
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        let call = &calls[0];
+        assert_eq!(call.name, name);
+        assert_eq!(call.mode, "fail");
+        let pids: &Array = call.properties["PIDs"].downcast_ref().unwrap();
+        assert_eq!(pids.get::<u32>(0).unwrap(), Some(4242));
+        assert_eq!(pids.len(), 1);
+        assert_eq!(
+            *call.properties["CollectMode"],
+            Value::from("inactive-or-failed")
+        );
+        assert!(!call.properties.contains_key("Description"));
+        assert!(!call.properties.contains_key("Documentation"));
+    }
+
+    #[test]
+    fn move_to_scope_records_provenance() {
+        let manager = MockSystemd1Manager::default();
+        let calls = manager.calls.clone();
+        let provenance = LaunchProvenance {
+            project_name: "mdcat".to_string(),
+            project_directory: "/home/foo/Code/mdcat".to_string(),
+        };
+        zbus::block_on(async {
+            let (_server, connection) = connect(manager).await.unwrap();
+            move_to_scope(
+                &connection,
+                "jetbrains-idea.desktop",
+                "IntelliJ IDEA",
+                4242,
+                Some(&provenance),
+            )
+            .await
+            .unwrap()
+        });
+
+        let calls = calls.lock().unwrap();
+        let call = &calls[0];
+        assert_eq!(
+            *call.properties["Description"],
+            Value::from("mdcat – IntelliJ IDEA")
+        );
+        let documentation: &Array = call.properties["Documentation"].downcast_ref().unwrap();
+        assert_eq!(documentation.len(), 1);
+        assert_eq!(
+            documentation.get::<&str>(0).unwrap(),
+            Some("file:///home/foo/Code/mdcat")
+        );
+    }
+}
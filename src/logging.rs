@@ -0,0 +1,111 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Setup logging for the service.
+//!
+//! With the default `log-control` feature we log directly to the systemd journal if connected to
+//! it, or nicely formatted to the TTY otherwise, and expose the log level and target over the
+//! `org.freedesktop.LogControl1` interface.  Without this feature we merely log plain text to
+//! stderr.
+//!
+//! We only build the layer stack once we actually start serving, so diagnostic commands never
+//! connect to the journal or pay for the subscriber.
+
+use anyhow::Result;
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{EnvFilter, Registry};
+
+/// Control over logging to expose on the bus.
+#[cfg(feature = "log-control")]
+pub struct LogControl(
+    logcontrol_tracing::TracingLogControl1<
+        logcontrol_tracing::PrettyLogControl1LayerFactory,
+        tracing_subscriber::layer::Layered<Option<EnvFilter>, Registry>,
+    >,
+);
+
+/// Control over logging to expose on the bus.
+///
+/// Without the `log-control` feature there's nothing to control.
+#[cfg(not(feature = "log-control"))]
+pub struct LogControl;
+
+/// Setup logging and install it as global default subscriber.
+///
+/// Return the control over logging to serve on the bus with
+/// [`ConnectionBuilderExt::serve_log_control`].
+pub fn setup() -> Result<LogControl> {
+    // Setup env filter for convenient log control on console
+    let env_filter = EnvFilter::try_from_default_env().ok();
+    // If an env filter is set with $RUST_LOG use the lowest level as default for the control part,
+    // to make sure the env filter takes precedence initially.
+    let default_level = if env_filter.is_some() {
+        Level::TRACE
+    } else {
+        Level::INFO
+    };
+
+    let control = install(env_filter, default_level)?;
+
+    // Direct glib to rust log, and…
+    #[cfg(feature = "log-bridge")]
+    glib::log_set_default_handler(glib::rust_log_handler);
+    // …rust log to tracing.
+    #[cfg(feature = "log-bridge")]
+    tracing_log::LogTracer::init()?;
+
+    Ok(control)
+}
+
+#[cfg(feature = "log-control")]
+fn install(env_filter: Option<EnvFilter>, default_level: Level) -> Result<LogControl> {
+    use anyhow::Context;
+    use logcontrol_tracing::{PrettyLogControl1LayerFactory, TracingLogControl1};
+
+    let (control, control_layer) =
+        TracingLogControl1::new_auto(PrettyLogControl1LayerFactory, default_level)
+            .with_context(|| "Failed to setup logging".to_string())?;
+    // If we're connected to systemd, directly log to the journal, otherwise log nicely to the TTY.
+    tracing::subscriber::set_global_default(
+        Registry::default().with(env_filter).with(control_layer),
+    )?;
+    Ok(LogControl(control))
+}
+
+#[cfg(not(feature = "log-control"))]
+fn install(env_filter: Option<EnvFilter>, default_level: Level) -> Result<LogControl> {
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(false)
+        .without_time();
+    let level = tracing_subscriber::filter::LevelFilter::from_level(default_level);
+    tracing::subscriber::set_global_default(
+        Registry::default()
+            .with(env_filter.unwrap_or_else(|| EnvFilter::default().add_directive(level.into())))
+            .with(layer),
+    )?;
+    Ok(LogControl)
+}
+
+/// Serve log control on a connection.
+pub trait ConnectionBuilderExt: Sized {
+    /// Serve `control` at the standard path if built with `log-control`.
+    fn serve_log_control(self, control: LogControl) -> zbus::Result<Self>;
+}
+
+impl<'a> ConnectionBuilderExt for zbus::ConnectionBuilder<'a> {
+    #[cfg(feature = "log-control")]
+    fn serve_log_control(self, control: LogControl) -> zbus::Result<Self> {
+        use logcontrol_zbus::ConnectionBuilderExt;
+        ConnectionBuilderExt::serve_log_control(self, logcontrol_zbus::LogControl1::new(control.0))
+    }
+
+    #[cfg(not(feature = "log-control"))]
+    fn serve_log_control(self, _control: LogControl) -> zbus::Result<Self> {
+        Ok(self)
+    }
+}
@@ -10,22 +10,63 @@
 //! Gnome search provider for Jetbrains products
 
 use anyhow::{Context, Result};
-use logcontrol_tracing::{PrettyLogControl1LayerFactory, TracingLogControl1};
-use logcontrol_zbus::{ConnectionBuilderExt, LogControl1};
-use tracing::{event, Level};
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::Registry;
+use tracing::{event, Instrument, Level};
 
+use hook::ActivationHook;
+use logging::ConnectionBuilderExt;
 use providers::*;
 use reload::*;
 use searchprovider::*;
 
+mod activate;
+mod api;
+mod appinfo;
+mod background;
+mod bundle;
+mod calltrace;
+mod capabilities;
 mod config;
+mod contents;
+mod detect;
+mod dump;
+mod history;
+mod hook;
+mod icons;
+mod ignore;
+mod instance;
+mod latency;
+#[cfg(feature = "launch")]
 mod launch;
+mod logging;
+mod manpage;
+mod metrics;
+#[cfg(feature = "launch")]
+mod notifications;
+mod plugins;
+mod power;
+mod private;
 mod providers;
+mod recents;
+mod redact;
 mod reload;
+mod routes;
+mod score;
+mod scoring;
 mod searchprovider;
+mod session;
+mod settings;
+mod shellsettings;
+mod shutdown;
+mod stats;
+mod sweep;
+#[cfg(feature = "launch")]
 mod systemd;
+mod timeout;
+mod tracebatch;
+mod trash;
+mod validate;
+mod watch;
+mod worktree;
 
 /// The name to request on the bus.
 const BUSNAME: &str = "de.swsnr.searchprovider.Jetbrains";
@@ -36,59 +77,594 @@ async fn tick(connection: zbus::Connection) {
     }
 }
 
-async fn reload(connection: zbus::Connection) {
-    let _ = reload_all_on_object_server(&connection.object_server()).await;
+/// Load recent projects of the providers at `paths` on `connection`, one after another.
+///
+/// Providers which got used meanwhile already loaded their recent projects, so this skips them.
+async fn preload(connection: zbus::Connection, paths: Vec<zbus::zvariant::OwnedObjectPath>) {
+    for path in paths {
+        // Let pending calls go first
+        glib::timeout_future_with_priority(glib::Priority::DEFAULT_IDLE, std::time::Duration::ZERO)
+            .await;
+        if let Ok(interface) = connection
+            .object_server()
+            .interface::<_, JetbrainsProductSearchProvider>(&path)
+            .await
+        {
+            interface.get_mut().await.ensure_loaded();
+        }
+    }
+}
+
+async fn reload(connection: zbus::Connection, reloadables: Reloadables) {
+    let _ = reloadables
+        .reload_all_on_object_server(&connection.object_server())
+        .await;
 }
 
 fn app() -> clap::Command {
     use clap::*;
-    command!()
+    let command = command!()
         .dont_collapse_args_in_usage(true)
         .term_width(80)
         .after_help(
             "\
 Set $RUST_LOG to control the log level",
         )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .value_parser(value_parser!(std::path::PathBuf))
+                .global(true)
+                .help("Read settings from FILE instead of the default configuration file"),
+        )
+        .arg(
+            Arg::new("print-manpage")
+                .long("print-manpage")
+                .value_name("SUBCOMMAND")
+                .num_args(0..=1)
+                .require_equals(true)
+                .exclusive(true)
+                .help("Print the man page of this program, or of SUBCOMMAND, in roff format"),
+        )
+        .arg(
+            Arg::new("print-config-schema")
+                .long("print-config-schema")
+                .action(ArgAction::SetTrue)
+                .exclusive(true)
+                .help("Print a JSON schema of the configuration file"),
+        )
         .arg(
             Arg::new("providers")
                 .long("providers")
                 .action(ArgAction::SetTrue)
                 .help("List all providers"),
         )
+        .arg(
+            Arg::new("detect")
+                .long("detect")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("providers")
+                .help("Detect installed Jetbrains IDEs without provider"),
+        )
+        .arg(
+            Arg::new("debug-bundle")
+                .long("debug-bundle")
+                .value_name("FILE")
+                .value_parser(value_parser!(std::path::PathBuf))
+                .conflicts_with_all(["providers", "detect"])
+                .help("Write information for bug reports to a tarball at FILE, after asking for consent"),
+        )
+        .arg(
+            Arg::new("replace")
+                .long("replace")
+                .action(ArgAction::SetTrue)
+                .help("Take over from a running instance of the service"),
+        )
+        .arg(
+            Arg::new("busname")
+                .long("busname")
+                .value_name("NAME")
+                .value_parser(|s: &str| {
+                    zbus::names::WellKnownName::try_from(s)
+                        .map(|name| name.to_string())
+                        .map_err(|error| error.to_string())
+                })
+                .default_value(BUSNAME)
+                .help("Acquire NAME on the session bus"),
+        )
+        .arg(
+            Arg::new("object-path-prefix")
+                .long("object-path-prefix")
+                .value_name("PATH")
+                .value_parser(|s: &str| {
+                    zbus::zvariant::ObjectPath::try_from(s)
+                        .map(|path| path.to_string())
+                        .map_err(|error| error.to_string())
+                })
+                .default_value(OBJECT_PATH_PREFIX)
+                .help("Serve search providers below PATH"),
+        )
+        .arg(
+            Arg::new("lazy")
+                .long("lazy")
+                .action(ArgAction::SetTrue)
+                .help("Load recent projects of each provider on first search only"),
+        )
+        .arg(
+            Arg::new("removed-grace-period")
+                .long("removed-grace-period")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Keep serving projects removed from recent projects for SECONDS"),
+        )
+        .arg(
+            Arg::new("keep-env")
+                .long("keep-env")
+                .value_name("VARIABLE")
+                .action(ArgAction::Append)
+                .help("Pass VARIABLE on to launched apps even if we'd remove it otherwise"),
+        )
+        .arg(
+            Arg::new("home-root")
+                .long("home-root")
+                .value_name("DIRECTORY")
+                .action(ArgAction::Append)
+                .help("Look for projects in the home directory below DIRECTORY as well"),
+        )
+        .arg(
+            Arg::new("config-root")
+                .long("config-root")
+                .value_name("DIRECTORY")
+                .value_parser(value_parser!(std::path::PathBuf))
+                .action(ArgAction::Append)
+                .help("Look for IDE configuration directories in DIRECTORY as well"),
+        )
+        .arg(
+            Arg::new("all-versions")
+                .long("all-versions")
+                .action(ArgAction::SetTrue)
+                .help("Read recent projects of all installed IDE versions, not just the latest"),
+        )
+        .arg(
+            Arg::new("worktree-branches")
+                .long("worktree-branches")
+                .action(ArgAction::SetTrue)
+                .help("Show the branch of projects which are worktrees of the same git repository"),
+        )
+        .arg(
+            Arg::new("show-opened-at")
+                .long("show-opened-at")
+                .action(ArgAction::SetTrue)
+                .help("Show when projects were last opened in their descriptions"),
+        )
+        .arg(
+            Arg::new("dedupe-symlinks")
+                .long("dedupe-symlinks")
+                .action(ArgAction::SetTrue)
+                .help("Show projects reached through symlinks only once, at their most recently opened path"),
+        )
+        .arg(
+            Arg::new("folder-results")
+                .long("folder-results")
+                .action(ArgAction::SetTrue)
+                .help("Also offer to open the directory of each project in the file manager"),
+        )
+        .arg(
+            Arg::new("open-residual-terms")
+                .long("open-residual-terms")
+                .action(ArgAction::SetTrue)
+                .help("Also open entries of the project directory which remaining search terms refer to"),
+        )
+        .arg(
+            Arg::new("restore-from-trash")
+                .long("restore-from-trash")
+                .action(ArgAction::SetTrue)
+                .help("Keep recent projects which are in the trash, and offer to restore them on activation"),
+        )
+        .arg(
+            Arg::new("index-contents")
+                .long("index-contents")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .default_value("0")
+                .help("Also search top-level file names of the N most recent projects of each provider"),
+        )
+        .arg(
+            Arg::new("max-recents-file-size")
+                .long("max-recents-file-size")
+                .value_name("BYTES")
+                .value_parser(value_parser!(u64))
+                .default_value("4194304")
+                .help("Skip recent projects files larger than BYTES"),
+        )
+        .arg(
+            Arg::new("max-recent-entry-size")
+                .long("max-recent-entry-size")
+                .value_name("BYTES")
+                .value_parser(value_parser!(usize))
+                .default_value("4096")
+                .help("Ignore meta info of recent projects with more than BYTES of data"),
+        )
+        .arg(
+            Arg::new("trace-each-item")
+                .long("trace-each-item")
+                .action(ArgAction::SetTrue)
+                .help("Log every project at trace level, instead of a summary"),
+        )
+        .arg(
+            Arg::new("redact-logs")
+                .long("redact-logs")
+                .action(ArgAction::SetTrue)
+                .help("Hash directories of project paths and omit platform data in logs"),
+        )
+        .arg(
+            Arg::new("max-results")
+                .long("max-results")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .default_value("5")
+                .help("Return at most N results per provider, or all results if 0"),
+        )
+        .arg(
+            Arg::new("min-results")
+                .long("min-results")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .default_value("0")
+                .help("Cut results at the largest score gap after N results, or never if 0"),
+        )
+        .arg(
+            Arg::new("call-timeout")
+                .long("call-timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("10")
+                .help("Give up on calls to other DBus services, e.g. systemd, after SECONDS"),
+        )
+        .arg(
+            Arg::new("plugin-timeout")
+                .long("plugin-timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("10")
+                .help("Kill plugins which do not finish within SECONDS"),
+        )
+        .arg(
+            Arg::new("latency-budget")
+                .long("latency-budget")
+                .value_name("MILLISECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("2000")
+                .help("Skip providers for a while if a search takes longer than MILLISECONDS, never if 0"),
+        )
+        .arg(
+            Arg::new("reload-budget")
+                .long("reload-budget")
+                .value_name("MILLISECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("10000")
+                .help("Skip providers for a while if reloading recent projects takes longer than MILLISECONDS, never if 0"),
+        )
+        .arg(
+            Arg::new("stale-results")
+                .long("stale-results")
+                .value_name("skip|placeholder")
+                .value_parser(|s: &str| s.parse::<StaleResults>().map_err(|error| error.to_string()))
+                .default_value("skip")
+                .help("Skip unknown results, or return a placeholder named \"unavailable\""),
+        )
+        .arg(
+            Arg::new("activation-hook")
+                .long("activation-hook")
+                .value_name("COMMAND")
+                .value_parser(|s: &str| s.parse::<ActivationHook>().map_err(|error| format!("{error:#}")))
+                .help("Run COMMAND after launching an app, e.g. to move its window to the current workspace"),
+        )
+        .arg(
+            Arg::new("allow-activations-caller")
+                .long("allow-activations-caller")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .default_value("org.gnome.Shell")
+                .help("Let the owner of bus NAME get recently activated projects"),
+        )
+        .arg(
+            Arg::new("private-mode-timeout")
+                .long("private-mode-timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .default_value("3600")
+                .help("End private mode automatically after SECONDS"),
+        )
+        .subcommand(
+            Command::new("validate-install")
+                .about("Validate the installation of this service, for packagers"),
+        )
+        .subcommand(
+            Command::new("dump-projects")
+                .long_flag("dump-projects")
+                .about("Dump recent projects of all providers, without connecting to DBus")
+                .arg(
+                    Arg::new("provider")
+                        .long("provider")
+                        .value_name("PROVIDER")
+                        .help(
+                            "Only dump projects of the provider with the given label or desktop ID",
+                        ),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["json", "table"])
+                        .default_value("table")
+                        .help("The output format"),
+                ),
+        );
+    #[cfg(feature = "prometheus")]
+    let command = command.arg(
+        Arg::new("metrics-textfile")
+            .long("metrics-textfile")
+            .value_name("FILE")
+            .value_parser(value_parser!(std::path::PathBuf))
+            .help("Periodically write Prometheus metrics to FILE"),
+    );
+    command
+}
+
+/// Get the value of the argument `id` from `matches`, or the value from `settings`.
+///
+/// Explicit command line arguments take precedence over settings, and settings take precedence
+/// over default values of arguments.
+fn setting<T: Clone + Send + Sync + 'static>(
+    matches: &clap::ArgMatches,
+    id: &str,
+    settings: Option<T>,
+) -> T {
+    match (matches.value_source(id), settings) {
+        (Some(clap::parser::ValueSource::CommandLine), _) | (_, None) => {
+            matches.get_one::<T>(id).unwrap().clone()
+        }
+        (_, Some(value)) => value,
+    }
+}
+
+/// Collate strings according to the locale of the user.
+///
+/// Leave all other locale categories alone, to not affect e.g. number formatting in logs.
+///
+/// `setlocale` is not thread-safe, so call this first thing in main, before we start any threads.
+fn setup_collation_locale() {
+    // The empty string selects the locale from the environment; if that locale doesn't exist we
+    // just keep collating by code points.
+    let _ = gettextrs::setlocale(gettextrs::LocaleCategory::LcCollate, "");
 }
 
 fn main() -> Result<()> {
+    setup_collation_locale();
     let matches = app().get_matches();
-    if matches.get_flag("providers") {
-        let mut labels: Vec<&'static str> = PROVIDERS.iter().map(|p| p.label).collect();
-        labels.sort_unstable();
+    if matches.contains_id("print-manpage") {
+        let subcommand = matches.get_one::<String>("print-manpage");
+        return manpage::render(
+            app(),
+            subcommand.map(String::as_str),
+            &mut std::io::stdout(),
+        );
+    }
+    if matches.get_flag("print-config-schema") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&settings::json_schema(PROVIDERS))?
+        );
+        return Ok(());
+    }
+    let settings = match matches.get_one::<std::path::PathBuf>("config") {
+        Some(path) => settings::Settings::load(path, true, PROVIDERS),
+        None => settings::Settings::load(settings::user_settings_file(), false, PROVIDERS),
+    };
+    // Diagnostics should work even with broken settings, so fall back to default settings, and
+    // only fail when serving.
+    let (settings, settings_error) = match settings {
+        Ok(settings) => (settings, None),
+        Err(error) => (settings::Settings::default(), Some(error)),
+    };
+    let warn_about_settings = || {
+        if let Some(error) = &settings_error {
+            eprintln!("warning: Invalid settings, using defaults: {error:#}");
+        }
+    };
+    // Known providers, followed by additional providers from settings
+    let providers = PROVIDERS
+        .iter()
+        .chain(
+            settings
+                .custom_providers
+                .iter()
+                .map(settings::CustomProvider::leak),
+        )
+        .collect::<Vec<_>>();
+    redact::set_redact(setting(&matches, "redact-logs", settings.redact_logs));
+    let recents_limits = recents::Limits {
+        max_file_size: setting(
+            &matches,
+            "max-recents-file-size",
+            settings.max_recents_file_size,
+        ),
+        max_entry_size: setting(
+            &matches,
+            "max-recent-entry-size",
+            settings.max_recent_entry_size,
+        ),
+    };
+    let home_roots = if matches.contains_id("home-root") {
+        matches
+            .get_many::<String>("home-root")
+            .unwrap_or_default()
+            .cloned()
+            .collect()
+    } else {
+        settings.home_roots.clone().unwrap_or_default()
+    };
+    let config_roots = config::extra_config_roots(&if matches.contains_id("config-root") {
+        matches
+            .get_many::<std::path::PathBuf>("config-root")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<_>>()
+    } else {
+        settings.config_roots.clone().unwrap_or_default()
+    });
+    let all_versions = setting(&matches, "all-versions", settings.all_versions);
+    if let Some(("validate-install", _)) = matches.subcommand() {
+        let mut report = validate::validate_install(
+            &validate::InstallLocations::standard(),
+            &providers,
+            BUSNAME,
+        );
+        validate::check_settings(&mut report, settings_error.as_ref());
+        validate::check_custom_providers(&mut report, &settings.custom_providers);
+        for finding in &report.findings {
+            println!("{finding}");
+        }
+        match report.errors() {
+            0 => Ok(()),
+            errors => anyhow::bail!("Installation invalid, found {errors} error(s)"),
+        }
+    } else if let Some(("dump-projects", matches)) = matches.subcommand() {
+        warn_about_settings();
+        let provider = matches.get_one::<String>("provider");
+        let mut providers = providers
+            .iter()
+            .filter(|p| provider.map_or(true, |s| s == p.label || s == p.desktop_id))
+            .map(|p| {
+                dump::dump_provider(p, &config_roots, all_versions, &recents_limits, &home_roots)
+            })
+            .collect::<Vec<_>>();
+        providers.sort_by_cached_key(|p| glib::CollationKey::from(&p.label));
+        if providers.is_empty() {
+            anyhow::bail!("No provider {}", provider.unwrap());
+        }
+        match matches.get_one::<String>("format").unwrap().as_str() {
+            "json" => println!("{}", dump::to_json(&providers)),
+            _ => print!("{}", dump::to_table(&providers)),
+        }
+        Ok(())
+    } else if let Some(target) = matches.get_one::<std::path::PathBuf>("debug-bundle") {
+        println!("A debug bundle contains:\n");
+        for (name, description) in bundle::CONTENTS {
+            println!("- {name}: {description}");
+        }
+        print!("\nWrite debug bundle to {}? [y/N] ", target.display());
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            anyhow::bail!("Not writing debug bundle without consent");
+        }
+        let mut report = validate::validate_install(
+            &validate::InstallLocations::standard(),
+            &providers,
+            BUSNAME,
+        );
+        validate::check_settings(&mut report, settings_error.as_ref());
+        validate::check_custom_providers(&mut report, &settings.custom_providers);
+        let providers = providers
+            .iter()
+            .map(|p| {
+                dump::dump_provider(p, &config_roots, all_versions, &recents_limits, &home_roots)
+            })
+            .collect();
+        bundle::write_bundle(target, &report, providers)?;
+        println!("Wrote debug bundle to {}", target.display());
+        Ok(())
+    } else if matches.get_flag("providers") {
+        warn_about_settings();
+        let mut labels: Vec<String> = providers.iter().map(|p| p.display_label()).collect();
+        labels.sort_by_cached_key(|label| glib::CollationKey::from(label));
         for label in labels {
             println!("{label}")
         }
         Ok(())
+    } else if matches.get_flag("detect") {
+        warn_about_settings();
+        let apps = detect::find_apps_without_provider(&providers);
+        let scripts = detect::find_scripts_without_desktop_entry(
+            &detect::toolbox_scripts_dir(),
+            |desktop_id| gio::DesktopAppInfo::new(desktop_id).is_some(),
+        );
+        if apps.is_empty() && scripts.is_empty() {
+            println!("All installed Jetbrains IDEs have a provider");
+        }
+        for (script, provider) in scripts {
+            println!(
+                "Found Toolbox script {script} for {}, but no desktop entry {}; Gnome Shell only shows results of providers whose app has a desktop entry\n",
+                provider.label, provider.desktop_id
+            );
+        }
+        let config_roots = config::ConfigLocation::config_roots(
+            &config::ConfigLocation {
+                vendor_dir: settings::DEFAULT_VENDOR_DIR,
+                config_prefix: "",
+                projects_filename: settings::DEFAULT_PROJECTS_FILENAME,
+                excluded_channels: &[],
+            },
+            &glib::user_config_dir(),
+            &config_roots,
+        );
+        let config_file = matches
+            .get_one::<std::path::PathBuf>("config")
+            .cloned()
+            .unwrap_or_else(settings::user_settings_file);
+        let search_providers_dir = glib::user_data_dir()
+            .join("gnome-shell")
+            .join("search-providers");
+        for app in &apps {
+            let suggestion = detect::suggest_provider(app, &config_roots);
+            match suggestion.renamed_from {
+                Some(known) => println!(
+                    "No provider for {} ({}), but it looks like {} ({}) with a different desktop ID; add this provider to {}:\n",
+                    suggestion.label,
+                    suggestion.desktop_id,
+                    known.label,
+                    known.desktop_id,
+                    config_file.display()
+                ),
+                None => println!(
+                    "No provider for {} ({}); add this provider to {}:\n",
+                    suggestion.label,
+                    suggestion.desktop_id,
+                    config_file.display()
+                ),
+            }
+            println!("{}", suggestion.to_config_group());
+            if suggestion.config_prefix.is_none() {
+                println!("Failed to find the configuration directories of {}; please fill in ConfigPrefix yourself\n", suggestion.label);
+            }
+            let obj_path = suggestion.config_obj_path();
+            println!(
+                "Gnome Shell only searches the provider with a search provider file, e.g. {}:\n",
+                search_providers_dir
+                    .join(format!(
+                        "de.swsnr.searchprovider.jetbrains.{}.ini",
+                        obj_path.replace('/', ".")
+                    ))
+                    .display()
+            );
+            println!("{}", suggestion.search_provider_file(BUSNAME, &obj_path));
+            println!(
+                "To build the provider into the service instead, add this definition to src/providers.rs, and serve it at {OBJECT_PATH_PREFIX}/{}:\n",
+                suggestion.relative_obj_path()
+            );
+            println!("{}\n", suggestion.to_definition());
+        }
+        Ok(())
     } else {
-        // Setup env filter for convenient log control on console
-        let env_filter = tracing_subscriber::EnvFilter::try_from_default_env().ok();
-        // If an env filter is set with $RUST_LOG use the lowest level as default for the control part,
-        // to make sure the env filter takes precedence initially.
-        let default_level = if env_filter.is_some() {
-            Level::TRACE
-        } else {
-            Level::INFO
-        };
-        let (control, control_layer) =
-            TracingLogControl1::new_auto(PrettyLogControl1LayerFactory, default_level)
-                .with_context(|| "Failed to setup logging".to_string())?;
-
-        // Setup tracing: If we're connected to systemd, directly log to the journal, otherwise log nicely to the TTY.
-        tracing::subscriber::set_global_default(
-            Registry::default().with(env_filter).with(control_layer),
-        )
-        .unwrap();
-        // Direct glib to rust log, and…
-        glib::log_set_default_handler(glib::rust_log_handler);
-        // …rust log to tracing.
-        tracing_log::LogTracer::init().unwrap();
+        if let Some(error) = settings_error {
+            return Err(error);
+        }
+        session::check_current_user_session()?;
+        let log_control = logging::setup()?;
 
         event!(
             Level::INFO,
@@ -96,33 +672,198 @@ fn main() -> Result<()> {
             env!("CARGO_BIN_NAME"),
             env!("CARGO_PKG_VERSION")
         );
+        for (provider, other) in settings::clashing_obj_paths(&settings.custom_providers) {
+            event!(
+                Level::WARN,
+                "Object path of [{}] clashes with [{}], serving it at {} instead",
+                provider.group(),
+                other.group(),
+                provider.relative_obj_path
+            );
+        }
 
+        let busname = matches.get_one::<String>("busname").unwrap().clone();
+        let replace = matches.get_flag("replace");
+        glib::MainContext::default().block_on(instance::check_not_running(&busname, replace))?;
+        let object_path_prefix = matches
+            .get_one::<String>("object-path-prefix")
+            .unwrap()
+            .clone();
         event!(
             Level::DEBUG,
             "Connecting to session bus, registering interfaces for search providers, and acquiring {}",
-            BUSNAME
+            busname
         );
 
         // Connect to DBus and register all our objects for search providers.
-        let connection = glib::MainContext::default().block_on(async {
-            PROVIDERS
+        tracebatch::set_trace_each_item(setting(
+            &matches,
+            "trace-each-item",
+            settings.trace_each_item,
+        ));
+        let is_lazy = setting(&matches, "lazy", settings.lazy);
+        let keep_environment = if matches.contains_id("keep-env") {
+            matches
+                .get_many::<String>("keep-env")
+                .unwrap_or_default()
+                .cloned()
+                .collect()
+        } else {
+            settings.keep_environment.clone().unwrap_or_default()
+        };
+        let ignore_file = settings
+            .ignore_file
+            .clone()
+            .unwrap_or_else(ignore::user_ignore_file);
+        let options = ProviderOptions {
+            removed_grace_period: std::time::Duration::from_secs(setting(
+                &matches,
+                "removed-grace-period",
+                settings.removed_grace_period,
+            )),
+            keep_environment,
+            worktree_branches: setting(&matches, "worktree-branches", settings.worktree_branches),
+            show_opened_at: setting(&matches, "show-opened-at", settings.show_opened_at),
+            // A broken ignore list must not take down search entirely
+            ignore: ignore::IgnoreList::load(ignore_file).unwrap_or_else(|error| {
+                event!(
+                    Level::WARN,
+                    "Ignoring no projects, failed to load ignore list: {error:#}"
+                );
+                ignore::IgnoreList::default()
+            }),
+            private_mode: private::PrivateMode::default(),
+            disabled_providers: shellsettings::DisabledProviders::default(),
+            index_contents: setting(&matches, "index-contents", settings.index_contents),
+            recents_limits,
+            home_roots,
+            config_roots,
+            all_versions,
+            power_saver: power::PowerSaver::default(),
+            activations: history::ActivationHistory::default(),
+            stale_results: setting(&matches, "stale-results", settings.stale_results),
+            dedupe_symlinks: setting(&matches, "dedupe-symlinks", settings.dedupe_symlinks),
+            max_results: setting(&matches, "max-results", settings.max_results),
+            min_results: setting(&matches, "min-results", settings.min_results),
+            routes: routes::Routes::new(
+                &glib::home_dir().to_string_lossy(),
+                settings
+                    .routes
+                    .iter()
+                    .map(|(glob, desktop_id)| (glob.as_str(), desktop_id.as_str())),
+            ),
+            icons: icons::Icons::new(
+                settings
+                    .icons
+                    .iter()
+                    .map(|(desktop_id, icon)| (desktop_id.as_str(), icon.as_str())),
+            ),
+            folder_results: setting(&matches, "folder-results", settings.folder_results),
+            open_residual_terms: setting(
+                &matches,
+                "open-residual-terms",
+                settings.open_residual_terms,
+            ),
+            restore_from_trash: setting(
+                &matches,
+                "restore-from-trash",
+                settings.restore_from_trash,
+            ),
+            activation_hook: matches
+                .get_one::<ActivationHook>("activation-hook")
+                .cloned()
+                .or_else(|| settings.activation_hook.clone()),
+            call_timeout: std::time::Duration::from_secs(setting(
+                &matches,
+                "call-timeout",
+                settings.call_timeout,
+            )),
+            latency_budget: std::time::Duration::from_millis(setting(
+                &matches,
+                "latency-budget",
+                settings.latency_budget,
+            )),
+            reload_budget: std::time::Duration::from_millis(setting(
+                &matches,
+                "reload-budget",
+                settings.reload_budget,
+            )),
+        };
+        let power_saver = options.power_saver.clone();
+        let _power_profile_monitor = power_saver.track_system();
+        let _shell_search_settings = options.disabled_providers.track_system();
+        let _icon_theme_settings = appinfo::track_icon_theme();
+        let private_mode_timeout = std::time::Duration::from_secs(setting(
+            &matches,
+            "private-mode-timeout",
+            settings.private_mode_timeout,
+        ));
+        let plugin_timeout = std::time::Duration::from_secs(setting(
+            &matches,
+            "plugin-timeout",
+            settings.plugin_timeout,
+        ));
+        let mainloop = glib::MainLoop::new(None, false);
+        let shutdown = shutdown::Shutdown::new(
+            mainloop.clone(),
+            busname.clone(),
+            shutdown::SHUTDOWN_TIMEOUT,
+        );
+        let mut reloadables = Reloadables::default();
+        let plugins_path = zbus::zvariant::OwnedObjectPath::try_from(format!(
+            "{}/{}",
+            object_path_prefix.trim_end_matches('/'),
+            plugins::RELATIVE_OBJ_PATH
+        ))?;
+        reloadables.add::<plugins::PluginsSearchProvider>(plugins_path.clone());
+        let plugins_desktop_ids = plugins::find_desktop_ids(
+            &validate::InstallLocations::standard().data_dirs,
+            &busname,
+            plugins_path.as_str(),
+        );
+        let mut registered_providers = 0;
+        let mut provider_paths = Vec::new();
+        let startup_started = std::time::Instant::now();
+        let startup_span = tracing::info_span!("startup", busname = %busname).entered();
+        let (connection, name_lost) = glib::MainContext::default().block_on(async {
+            let connection = providers
                 .iter()
+                .filter(|provider| {
+                    settings.enabled_providers.as_ref().map_or(true, |enabled| {
+                        enabled.iter().any(|id| id == provider.desktop_id)
+                    })
+                })
                 .filter_map(|provider| {
                     gio::DesktopAppInfo::new(provider.desktop_id).map(|gio_app| {
+                        let _span =
+                            tracing::debug_span!("create_provider", app_id = provider.desktop_id)
+                                .entered();
                         event!(Level::INFO, "Found app {}", provider.desktop_id);
-                        let mut search_provider = JetbrainsProductSearchProvider::new(
+                        // Only register the provider here, and load recent projects later, to acquire
+                        // our bus name as fast as possible
+                        let search_provider = JetbrainsProductSearchProvider::new(
                             App::from(gio_app),
                             &provider.config,
+                            options.clone(),
                         );
-                        let _ = search_provider.reload_recent_projects();
-                        (provider.objpath(), search_provider)
+                        (
+                            provider.objpath_with_prefix(&object_path_prefix),
+                            search_provider,
+                        )
                     })
                 })
                 .try_fold(
                     // We disable the internal executor because we'd like to run the connection
-                    // exclusively on the glib mainloop, and thus tick it manually (see below).
+                    // exclusively on the glib mainloop, and thus tick it manually after building.
                     zbus::ConnectionBuilder::session()?.internal_executor(false),
                     |builder, (path, provider)| {
+                        let _span = tracing::debug_span!(
+                            "register_provider",
+                            app_id = %provider.app().id(),
+                            path = %path
+                        )
+                        .entered();
+                        registered_providers += 1;
                         event!(
                             Level::DEBUG,
                             app_id = %provider.app().id(),
@@ -130,52 +871,167 @@ fn main() -> Result<()> {
                             provider.app().id(),
                             &path
                         );
-                        builder.serve_at(path, provider)
+                        let object_path: zbus::zvariant::OwnedObjectPath =
+                            zbus::zvariant::ObjectPath::try_from(path.as_str())?.into();
+                        reloadables.add::<JetbrainsProductSearchProvider>(object_path.clone());
+                        provider_paths.push(object_path);
+                        builder
+                            .serve_at(path.clone(), provider)?
+                            .serve_at(path.clone(), capabilities::ProviderCapabilities)?
+                            .serve_at(path.clone(), score::Score)?
+                            .serve_at(path.clone(), stats::Stats)?
+                            .serve_at(path, activate::ActivateResultEx)
                     },
                 )?
-                .serve_at("/", ReloadAll)?
-                .serve_log_control(LogControl1::new(control))?
-                .name(BUSNAME)?
+                .serve_at(
+                    "/",
+                    ReloadAll::new(
+                        reloadables.clone(),
+                        options.private_mode.clone(),
+                        private_mode_timeout,
+                    )
+                    .with_activations(
+                        options.activations.clone(),
+                        matches
+                            .get_many::<String>("allow-activations-caller")
+                            .unwrap_or_default()
+                            .cloned()
+                            .collect(),
+                    )
+                    .with_call_timeout(options.call_timeout),
+                )?
+                .serve_at(
+                    plugins_path,
+                    plugins::PluginsSearchProvider::new(
+                        settings
+                            .plugins_directory
+                            .clone()
+                            .unwrap_or_else(plugins::user_plugins_directory),
+                        plugin_timeout,
+                        options.private_mode.clone(),
+                    )
+                    .with_ignore(options.ignore.clone())
+                    .with_disabled_providers(
+                        options.disabled_providers.clone(),
+                        plugins_desktop_ids,
+                    ),
+                )?
+                .serve_at("/", api::Manager)?
+                .serve_log_control(log_control)?
+                .serve_at(
+                    background::actions_path(&busname),
+                    background::Actions::new(shutdown.clone()),
+                )?
                 .build()
+                .instrument(tracing::info_span!("connect"))
                 .await
-                .with_context(|| "Failed to connect to session bus")
+                .with_context(|| "Failed to connect to session bus")?;
+            // Manually tick the connection on the glib mainloop to make all code in zbus run on the mainloop.
+            glib::MainContext::default().spawn(tick(connection.clone()));
+            let name_lost = instance::acquire_name(&connection, &busname, replace)
+                .instrument(tracing::info_span!("acquire_name"))
+                .await?;
+            anyhow::Ok((connection, name_lost))
         })?;
+        event!(
+            Level::INFO,
+            providers = registered_providers,
+            elapsed_ms = startup_started.elapsed().as_millis(),
+            "Registered {} providers in {} ms",
+            registered_providers,
+            startup_started.elapsed().as_millis()
+        );
+        drop(startup_span);
 
-        // Manually tick the connection on the glib mainloop to make all code in zbus run on the mainloop.
-        glib::MainContext::default().spawn(tick(connection.clone()));
+        shutdown.attach(connection.clone());
+        glib::MainContext::default().spawn(instance::shutdown_on_name_lost(
+            name_lost,
+            busname.clone(),
+            shutdown.clone(),
+        ));
 
-        // Automatically reload all providers every five minutes, on grounds that
-        // if you create a new project you're probably going to work with it for
-        // at least a few minutes, so it doesn't matter if it only appears in
-        // search results after a few minutes.
-        glib::timeout_add_seconds(5 * 60, move || {
-            event!(Level::INFO, "Scheduling reload all providers on timeout");
-            glib::MainContext::default().spawn(reload(connection.clone()));
-            glib::ControlFlow::Continue
-        });
+        // Load recent projects of all providers in the background, unless lazy; lazy providers load
+        // recent projects on first use only.
+        if !is_lazy {
+            glib::MainContext::default().spawn(preload(connection.clone(), provider_paths.clone()));
+        }
+
+        // Revalidate a few projects at a time, when there's nothing else to do
+        glib::MainContext::default().spawn(sweep::sweep(
+            connection.clone(),
+            provider_paths,
+            shutdown.clone(),
+        ));
+
+        // Tell the user what we're doing in the background, where the desktop supports it
+        let status = background::status_message(registered_providers);
+        let call_timeout = options.call_timeout;
+        glib::MainContext::default().spawn(glib::clone!(@strong connection => async move {
+            if let Err(error) = background::set_status(&connection, &status, call_timeout).await {
+                event!(Level::DEBUG, %error, "Failed to set background status: {error}");
+            }
+        }));
+
+        // Automatically reload all providers periodically, except in power saver mode, where
+        // providers refresh stale projects on search instead.
+        glib::timeout_add_seconds(
+            RELOAD_INTERVAL.as_secs() as u32,
+            glib::clone!(@strong shutdown => move || {
+                if shutdown.is_started() {
+                    event!(Level::DEBUG, "Skipping reload on timeout while shutting down");
+                } else if power_saver.is_enabled() {
+                    event!(
+                        Level::DEBUG,
+                        "Skipping reload on timeout in power saver mode"
+                    );
+                } else {
+                    event!(Level::INFO, "Scheduling reload all providers on timeout");
+                    glib::MainContext::default().spawn(reload(connection.clone(), reloadables.clone()));
+                }
+                glib::ControlFlow::Continue
+            }),
+        );
+
+        // Write metrics for the node exporter every 30 seconds; that's the default scrape
+        // interval of Prometheus anyway.
+        #[cfg(feature = "prometheus")]
+        if let Some(path) = matches.get_one::<std::path::PathBuf>("metrics-textfile") {
+            let path = path.clone();
+            glib::timeout_add_seconds(30, move || match metrics::write_textfile(&path) {
+                Ok(()) => glib::ControlFlow::Continue,
+                // On read-only systems warn once and keep metrics in memory only
+                Err(error) if metrics::is_read_only(&error) => {
+                    event!(
+                        Level::WARN,
+                        "Cannot write metrics, no longer trying: {error:#}"
+                    );
+                    glib::ControlFlow::Break
+                }
+                Err(error) => {
+                    event!(Level::WARN, "Failed to write metrics: {error:#}");
+                    glib::ControlFlow::Continue
+                }
+            });
+        }
 
         event!(
             Level::INFO,
             "Acquired name {}, serving search providers",
-            BUSNAME
+            busname
         );
 
-        let mainloop = glib::MainLoop::new(None, false);
-
-        // Quit our mainloop on SIGTERM and SIGINT
+        // Shut down on SIGTERM and SIGINT
         glib::source::unix_signal_add(
             libc::SIGTERM,
-            glib::clone!(@strong mainloop =>  move || {
-                event!(Level::DEBUG, "Terminated, quitting mainloop");
-                mainloop.quit();
+            glib::clone!(@strong shutdown =>  move || {
+                shutdown.request("Terminated");
                 glib::ControlFlow::Break
             }),
         );
         glib::source::unix_signal_add(
             libc::SIGINT,
-            glib::clone!(@strong mainloop =>  move || {
-                event!(Level::DEBUG, "Interrupted, quitting mainloop");
-                mainloop.quit();
+            glib::clone!(@strong shutdown =>  move || {
+                shutdown.request("Interrupted");
                 glib::ControlFlow::Break
             }),
         );
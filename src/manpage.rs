@@ -0,0 +1,85 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Render man pages from the command line definition.
+//!
+//! We generate man pages from the clap command instead of maintaining them separately, so that
+//! they always document all options and subcommands of the binary they come with.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+/// Render the man page of `command`, or of its subcommand named `subcommand`, to `out`.
+///
+/// The page of a subcommand is named after the command and the subcommand, e.g. `foo-bar` for
+/// subcommand `bar` of `foo`, like the page of `command` refers to it.
+pub fn render(
+    mut command: clap::Command,
+    subcommand: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    // Build the command to get automatic arguments, e.g. --version, global arguments of
+    // subcommands, and the names of subcommand pages
+    command.build();
+    let page = match subcommand {
+        None => command,
+        Some(name) => command
+            .find_subcommand(name)
+            .cloned()
+            .with_context(|| format!("No subcommand {name}"))?,
+    };
+    clap_mangen::Man::new(page)
+        .render(out)
+        .with_context(|| "Failed to write man page")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+
+    fn command() -> Command {
+        Command::new("demo")
+            .version("1.0.0")
+            .about("A demo")
+            .arg(
+                Arg::new("max-results")
+                    .long("max-results")
+                    .value_name("N")
+                    .help("Return at most N results"),
+            )
+            .subcommand(
+                Command::new("dump-projects")
+                    .about("Dump projects")
+                    .arg(Arg::new("format").long("format").help("The output format")),
+            )
+    }
+
+    fn render_to_string(subcommand: Option<&str>) -> Result<String> {
+        let mut page = Vec::new();
+        render(command(), subcommand, &mut page)?;
+        Ok(String::from_utf8(page).unwrap())
+    }
+
+    #[test]
+    fn render_command() {
+        let page = render_to_string(None).unwrap();
+        assert!(
+            page.starts_with(".ie \\n(.g .ds Aq \\(aq\n.el .ds Aq '\n.TH demo 1  \"demo 1.0.0\"")
+        );
+        assert!(page.contains("\\-\\-max\\-results"));
+        assert!(page.contains("demo\\-dump\\-projects(1)"));
+    }
+
+    #[test]
+    fn render_subcommand() {
+        let page = render_to_string(Some("dump-projects")).unwrap();
+        assert!(page.contains(".TH demo-dump-projects 1 "));
+        assert!(page.contains("\\-\\-format"));
+        assert!(render_to_string(Some("frobnicate")).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Service metrics.
+//!
+//! With the `prometheus` feature enabled we count searches, reloads and launches per provider
+//! and periodically write these metrics to a file for the [textfile collector] of the Prometheus
+//! node exporter.  Without this feature all recording functions do nothing.
+//!
+//! [textfile collector]: https://github.com/prometheus/node_exporter#textfile-collector
+
+#[cfg(feature = "prometheus")]
+pub use enabled::*;
+
+#[cfg(not(feature = "prometheus"))]
+pub use disabled::*;
+
+#[cfg(feature = "prometheus")]
+mod enabled {
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+
+    use crate::searchprovider::{AppId, CacheSize};
+
+    /// Metrics of a single provider.
+    #[derive(Debug, Default)]
+    struct ProviderMetrics {
+        searches: u64,
+        search_seconds: f64,
+        reloads: u64,
+        reload_failures: u64,
+        reload_seconds: f64,
+        launches: u64,
+        launch_failures: u64,
+        cache_entries: usize,
+        cache_string_bytes: usize,
+        last_search_seconds: f64,
+        suspended: bool,
+        calls: BTreeMap<&'static str, CallMetrics>,
+    }
+
+    /// Metrics of calls to a single DBus method of a provider.
+    #[derive(Debug, Default)]
+    struct CallMetrics {
+        calls: u64,
+        request_bytes: u64,
+        reply_bytes: u64,
+        seconds: f64,
+    }
+
+    /// Metrics of all providers, by app ID.
+    static METRICS: Mutex<BTreeMap<String, ProviderMetrics>> = Mutex::new(BTreeMap::new());
+
+    fn update<F: FnOnce(&mut ProviderMetrics)>(app_id: &AppId, f: F) {
+        let mut metrics = METRICS.lock().unwrap();
+        f(metrics.entry(app_id.to_string()).or_default())
+    }
+
+    /// Record a search of the given `app_id` which took `duration`.
+    pub fn record_search(app_id: &AppId, duration: Duration) {
+        update(app_id, |m| {
+            m.searches += 1;
+            m.search_seconds += duration.as_secs_f64();
+        })
+    }
+
+    /// Record a reload of recent projects of the given `app_id` which took `duration`.
+    pub fn record_reload(app_id: &AppId, duration: Duration, is_success: bool) {
+        update(app_id, |m| {
+            m.reloads += 1;
+            m.reload_seconds += duration.as_secs_f64();
+            if !is_success {
+                m.reload_failures += 1;
+            }
+        })
+    }
+
+    /// Record a launch of the given `app_id`.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub fn record_launch(app_id: &AppId, is_success: bool) {
+        update(app_id, |m| {
+            m.launches += 1;
+            if !is_success {
+                m.launch_failures += 1;
+            }
+        })
+    }
+
+    /// Record the current `size` of the caches of the given `app_id`.
+    pub fn record_cache_size(app_id: &AppId, size: &CacheSize) {
+        update(app_id, |m| {
+            m.cache_entries = size.entries;
+            m.cache_string_bytes = size.string_bytes;
+        })
+    }
+
+    /// Record the `latency` of the last search of the given `app_id`, and whether the provider is
+    /// `suspended` for exceeding its latency budget.
+    pub fn record_latency(app_id: &AppId, latency: Duration, suspended: bool) {
+        update(app_id, |m| {
+            m.last_search_seconds = latency.as_secs_f64();
+            m.suspended = suspended;
+        })
+    }
+
+    /// Record whether the given `app_id` is `suspended`, e.g. for exceeding its reload budget.
+    pub fn record_suspended(app_id: &AppId, suspended: bool) {
+        update(app_id, |m| m.suspended = suspended)
+    }
+
+    /// Record a call to the DBus `method` of the given `app_id` which took `duration`.
+    pub fn record_call(
+        app_id: &AppId,
+        method: &'static str,
+        request_bytes: usize,
+        reply_bytes: usize,
+        duration: Duration,
+    ) {
+        update(app_id, |m| {
+            let call = m.calls.entry(method).or_default();
+            call.calls += 1;
+            call.request_bytes += request_bytes as u64;
+            call.reply_bytes += reply_bytes as u64;
+            call.seconds += duration.as_secs_f64();
+        })
+    }
+
+    /// A metric family, with name, type, help text, and a function to get its value.
+    type MetricFamily = (
+        &'static str,
+        &'static str,
+        &'static str,
+        fn(&ProviderMetrics) -> f64,
+    );
+
+    /// All metric families we export.
+    const FAMILIES: &[MetricFamily] = &[
+        (
+            "searches_total",
+            "counter",
+            "Total number of searches",
+            |m| m.searches as f64,
+        ),
+        (
+            "search_duration_seconds_total",
+            "counter",
+            "Total time spent searching",
+            |m| m.search_seconds,
+        ),
+        (
+            "reloads_total",
+            "counter",
+            "Total number of reloads of recent projects",
+            |m| m.reloads as f64,
+        ),
+        (
+            "reload_failures_total",
+            "counter",
+            "Total number of failed reloads of recent projects",
+            |m| m.reload_failures as f64,
+        ),
+        (
+            "reload_duration_seconds_total",
+            "counter",
+            "Total time spent reloading recent projects",
+            |m| m.reload_seconds,
+        ),
+        (
+            "launches_total",
+            "counter",
+            "Total number of app launches",
+            |m| m.launches as f64,
+        ),
+        (
+            "launch_failures_total",
+            "counter",
+            "Total number of failed app launches",
+            |m| m.launch_failures as f64,
+        ),
+        (
+            "cache_entries",
+            "gauge",
+            "Number of entries in caches after the last reload",
+            |m| m.cache_entries as f64,
+        ),
+        (
+            "cache_string_bytes",
+            "gauge",
+            "Approximate size of strings in caches after the last reload",
+            |m| m.cache_string_bytes as f64,
+        ),
+        (
+            "last_search_latency_seconds",
+            "gauge",
+            "Time taken by the last search, excluding loading recent projects",
+            |m| m.last_search_seconds,
+        ),
+        (
+            "provider_suspended",
+            "gauge",
+            "Whether the provider is skipped for exceeding its latency or reload budget",
+            |m| u8::from(m.suspended).into(),
+        ),
+    ];
+
+    /// A metric family of DBus calls, with name, type, help text, and a function to get its value.
+    type CallMetricFamily = (
+        &'static str,
+        &'static str,
+        &'static str,
+        fn(&CallMetrics) -> f64,
+    );
+
+    /// All metric families of DBus calls we export.
+    const CALL_FAMILIES: &[CallMetricFamily] = &[
+        (
+            "calls_total",
+            "counter",
+            "Total number of DBus calls",
+            |m| m.calls as f64,
+        ),
+        (
+            "call_request_bytes_total",
+            "counter",
+            "Total size of arguments of DBus calls",
+            |m| m.request_bytes as f64,
+        ),
+        (
+            "call_reply_bytes_total",
+            "counter",
+            "Total size of replies to DBus calls",
+            |m| m.reply_bytes as f64,
+        ),
+        (
+            "call_duration_seconds_total",
+            "counter",
+            "Total time spent handling DBus calls",
+            |m| m.seconds,
+        ),
+    ];
+
+    /// Escape `value` for use as a label value in the Prometheus text format.
+    fn escape_label_value(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '"' => escaped.push_str("\\\""),
+                '\n' => escaped.push_str("\\n"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    fn render() -> String {
+        let metrics = METRICS.lock().unwrap();
+        let mut output = String::new();
+        for (name, kind, help, value) in FAMILIES {
+            let name = format!("gnome_search_providers_jetbrains_{name}");
+            writeln!(output, "# HELP {name} {help}").unwrap();
+            writeln!(output, "# TYPE {name} {kind}").unwrap();
+            for (app_id, provider_metrics) in metrics.iter() {
+                writeln!(
+                    output,
+                    "{name}{{app_id=\"{}\"}} {}",
+                    escape_label_value(app_id),
+                    value(provider_metrics)
+                )
+                .unwrap();
+            }
+        }
+        for (name, kind, help, value) in CALL_FAMILIES {
+            let name = format!("gnome_search_providers_jetbrains_{name}");
+            writeln!(output, "# HELP {name} {help}").unwrap();
+            writeln!(output, "# TYPE {name} {kind}").unwrap();
+            for (app_id, provider_metrics) in metrics.iter() {
+                let app_id = escape_label_value(app_id);
+                for (method, call_metrics) in &provider_metrics.calls {
+                    writeln!(
+                        output,
+                        "{name}{{app_id=\"{app_id}\",method=\"{}\"}} {}",
+                        escape_label_value(method),
+                        value(call_metrics)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        output
+    }
+
+    /// Write all metrics to the given textfile `path`.
+    ///
+    /// Write to a temporary file first and then rename it to `path`, to make sure the node
+    /// exporter never sees a partially written file.
+    pub fn write_textfile(path: &Path) -> Result<()> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        std::fs::write(&temp_path, render())
+            .with_context(|| format!("Failed to write metrics to {}", path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to write metrics to {}", path.display()))
+    }
+
+    /// Whether `error` from [`write_textfile`] means that we can't write metrics at all.
+    ///
+    /// This is the case if the file is on a read-only filesystem, e.g. on kiosk or immutable
+    /// systems, or if we may not write to its directory; retrying won't help in either case.
+    pub fn is_read_only(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<std::io::Error>().is_some_and(|error| {
+            error.raw_os_error() == Some(libc::EROFS)
+                || error.kind() == std::io::ErrorKind::PermissionDenied
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn read_only_errors() {
+            let error = anyhow::Error::from(std::io::Error::from_raw_os_error(libc::EROFS))
+                .context("Failed to write metrics");
+            assert!(is_read_only(&error));
+            let error =
+                anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+            assert!(is_read_only(&error));
+            let error = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+            assert!(!is_read_only(&error));
+        }
+
+        #[test]
+        fn escape_label_values() {
+            assert_eq!(
+                escape_label_value("jetbrains-idea.desktop"),
+                "jetbrains-idea.desktop"
+            );
+            assert_eq!(escape_label_value("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+        }
+
+        #[test]
+        fn render_escaped_label_values() {
+            let app_id = AppId::try_from("metrics-escape-test.desktop").unwrap();
+            record_call(&app_id, "Get\"Result\\Metas\n", 1, 1, Duration::ZERO);
+            assert!(render().contains(
+                "gnome_search_providers_jetbrains_calls_total{app_id=\"metrics-escape-test.desktop\",method=\"Get\\\"Result\\\\Metas\\n\"} 1\n"
+            ));
+        }
+
+        #[test]
+        fn render_provider_metrics() {
+            let app_id = AppId::try_from("metrics-test.desktop").unwrap();
+            record_search(&app_id, Duration::from_millis(500));
+            record_launch(&app_id, false);
+            record_latency(&app_id, Duration::from_secs(3), true);
+            record_call(
+                &app_id,
+                "GetResultMetas",
+                20,
+                300,
+                Duration::from_millis(250),
+            );
+            record_cache_size(
+                &app_id,
+                &CacheSize {
+                    entries: 3,
+                    string_bytes: 120,
+                },
+            );
+            let output = render();
+            assert!(output.contains(
+                "gnome_search_providers_jetbrains_searches_total{app_id=\"metrics-test.desktop\"} 1\n"
+            ));
+            assert!(output.contains(
+                "gnome_search_providers_jetbrains_launch_failures_total{app_id=\"metrics-test.desktop\"} 1\n"
+            ));
+            assert!(
+                output.contains("# TYPE gnome_search_providers_jetbrains_reloads_total counter\n")
+            );
+            assert!(output.contains(
+                "gnome_search_providers_jetbrains_cache_string_bytes{app_id=\"metrics-test.desktop\"} 120\n"
+            ));
+            assert!(output.contains(
+                "gnome_search_providers_jetbrains_provider_suspended{app_id=\"metrics-test.desktop\"} 1\n"
+            ));
+            assert!(output.contains(
+                "gnome_search_providers_jetbrains_call_reply_bytes_total{app_id=\"metrics-test.desktop\",method=\"GetResultMetas\"} 300\n"
+            ));
+        }
+    }
+}
+
+#[cfg(not(feature = "prometheus"))]
+mod disabled {
+    use std::time::Duration;
+
+    use crate::searchprovider::{AppId, CacheSize};
+
+    /// Record a search; does nothing without the `prometheus` feature.
+    pub fn record_search(_app_id: &AppId, _duration: Duration) {}
+
+    /// Record a reload; does nothing without the `prometheus` feature.
+    pub fn record_reload(_app_id: &AppId, _duration: Duration, _is_success: bool) {}
+
+    /// Record a launch; does nothing without the `prometheus` feature.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub fn record_launch(_app_id: &AppId, _is_success: bool) {}
+
+    /// Record the size of caches; does nothing without the `prometheus` feature.
+    pub fn record_cache_size(_app_id: &AppId, _size: &CacheSize) {}
+
+    /// Record the latency of a search; does nothing without the `prometheus` feature.
+    pub fn record_latency(_app_id: &AppId, _latency: Duration, _suspended: bool) {}
+
+    /// Record whether a provider is suspended; does nothing without the `prometheus` feature.
+    pub fn record_suspended(_app_id: &AppId, _suspended: bool) {}
+
+    /// Record a DBus call; does nothing without the `prometheus` feature.
+    pub fn record_call(
+        _app_id: &AppId,
+        _method: &'static str,
+        _request_bytes: usize,
+        _reply_bytes: usize,
+        _duration: Duration,
+    ) {
+    }
+}
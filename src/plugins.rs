@@ -0,0 +1,734 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Search items of external plugin executables.
+//!
+//! Every executable file in the plugins directory is a plugin.  To get the items of a plugin we
+//! run it without arguments, write a single line with a JSON request to its standard input, and
+//! close standard input; the plugin then prints a JSON array of items to standard output, and
+//! exits with status 0.  The request is
+//!
+//! ```json
+//! {"version":1,"request":"items"}
+//! ```
+//!
+//! and each item is an object with a unique `id`, a human readable `name`, and the `uri` to open
+//! when the user activates the item:
+//!
+//! ```json
+//! [{"id":"gnome-shell","name":"gnome-shell","uri":"vscode://file/home/foo/gnome-shell"}]
+//! ```
+//!
+//! We serve the items of all plugins under a single generic search provider.
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use gio::prelude::*;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use tracing::{event, instrument, Level};
+use zbus::{interface, zvariant};
+
+use crate::ignore::IgnoreList;
+use crate::private::PrivateMode;
+#[cfg(feature = "launch")]
+use crate::redact;
+use crate::reload::Reloadable;
+use crate::scoring::{self, ScoredResult};
+use crate::searchprovider::AppId;
+use crate::shellsettings::DisabledProviders;
+
+/// The request we write to plugins.
+const REQUEST: &[u8] = b"{\"version\":1,\"request\":\"items\"}\n";
+
+/// The maximum size of the output of a plugin in bytes.
+const MAX_OUTPUT_SIZE: usize = 4 * 1024 * 1024;
+
+/// How many bytes to read from the output of a plugin at once.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The relative object path of the plugins provider.
+pub const RELATIVE_OBJ_PATH: &str = "plugins";
+
+/// The default plugins directory of the current user.
+pub fn user_plugins_directory() -> PathBuf {
+    glib::user_data_dir()
+        .join(env!("CARGO_PKG_NAME"))
+        .join("plugins")
+}
+
+/// Find the desktop IDs under which Gnome Shell shows plugin items.
+///
+/// Users choose the app to show plugin items under in their own search provider file; look for
+/// search provider files in `data_dirs` which refer to `busname` and `object_path`, and return
+/// their desktop IDs.
+pub fn find_desktop_ids(data_dirs: &[PathBuf], busname: &str, object_path: &str) -> Vec<AppId> {
+    const GROUP: &str = "Shell Search Provider";
+    let mut ids = Vec::new();
+    for dir in data_dirs {
+        let dir = dir.join("gnome-shell").join("search-providers");
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !path.extension().is_some_and(|e| e == "ini") {
+                continue;
+            }
+            let key_file = glib::KeyFile::new();
+            if key_file
+                .load_from_file(&path, glib::KeyFileFlags::NONE)
+                .is_err()
+            {
+                continue;
+            }
+            let refers_to_plugins = key_file
+                .string(GROUP, "BusName")
+                .is_ok_and(|b| b == busname)
+                && key_file
+                    .string(GROUP, "ObjectPath")
+                    .is_ok_and(|p| p == object_path);
+            if refers_to_plugins {
+                if let Some(id) = key_file
+                    .string(GROUP, "DesktopId")
+                    .ok()
+                    .and_then(|id| AppId::try_from(id.as_str()).ok())
+                {
+                    event!(
+                        Level::DEBUG,
+                        "Showing plugin items under {id} per {}",
+                        path.display()
+                    );
+                    ids.push(id);
+                }
+            }
+        }
+    }
+    ids
+}
+
+/// An item contributed by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PluginItem {
+    /// The ID of the item, unique within its plugin.
+    pub id: String,
+    /// The human readable name of the item.
+    pub name: String,
+    /// The URI to open when activating the item.
+    pub uri: String,
+}
+
+impl PluginItem {
+    /// The location of this item to match against the ignore list.
+    ///
+    /// This is the path of `file://` URIs, and the URI itself otherwise.
+    fn location(&self) -> String {
+        glib::filename_from_uri(&self.uri).map_or_else(
+            |_| self.uri.clone(),
+            |(path, _)| path.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+/// Parse the `output` of a plugin.
+fn parse_items(output: &[u8]) -> Result<Vec<PluginItem>> {
+    serde_json::from_slice(output).with_context(|| "Failed to parse items")
+}
+
+/// Find all plugins in `directory`, in order of their file names.
+///
+/// Return no plugins if `directory` does not exist.
+fn find_plugins(directory: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read {}", directory.display()));
+        }
+    };
+    let mut plugins = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_executable = std::fs::metadata(&path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+        if is_executable {
+            plugins.push(path);
+        }
+    }
+    plugins.sort();
+    Ok(plugins)
+}
+
+/// Write the request to the plugin `process` and read all of its output.
+///
+/// Kill the plugin and fail as soon as its output exceeds [`MAX_OUTPUT_SIZE`], instead of
+/// buffering output without bounds.
+async fn communicate(process: &gio::Subprocess, path: &Path) -> Result<Vec<u8>> {
+    if let Some(stdin) = process.stdin_pipe() {
+        let written = stdin
+            .write_all_future(REQUEST, glib::Priority::DEFAULT)
+            .await
+            .map_err(|(_, error)| error)
+            .and_then(|(_, _, error)| error.map_or(Ok(()), Err));
+        match written {
+            // Like g_subprocess_communicate, tolerate plugins which exit without reading the request
+            Err(error) if error.matches(gio::IOErrorEnum::BrokenPipe) => {}
+            result => result?,
+        }
+        stdin.close_future(glib::Priority::DEFAULT).await?;
+    }
+    let stdout = process
+        .stdout_pipe()
+        .with_context(|| format!("No standard output for plugin {}", path.display()))?;
+    let mut output = Vec::new();
+    loop {
+        let chunk = stdout
+            .read_bytes_future(READ_CHUNK_SIZE, glib::Priority::DEFAULT)
+            .await?;
+        if chunk.is_empty() {
+            return Ok(output);
+        }
+        output.extend_from_slice(&chunk);
+        if MAX_OUTPUT_SIZE < output.len() {
+            process.force_exit();
+            bail!(
+                "Output of plugin {} exceeds {} bytes",
+                path.display(),
+                MAX_OUTPUT_SIZE
+            );
+        }
+    }
+}
+
+/// Run the plugin at `path` and get its items.
+///
+/// Kill the plugin and fail if it does not exit within `timeout`, even if it closed its standard
+/// output already, or if its output grows too large.
+#[instrument]
+async fn run_plugin(path: &Path, timeout: Duration) -> Result<Vec<PluginItem>> {
+    let process = gio::Subprocess::newv(
+        &[path.as_os_str()],
+        gio::SubprocessFlags::STDIN_PIPE
+            | gio::SubprocessFlags::STDOUT_PIPE
+            | gio::SubprocessFlags::STDERR_SILENCE,
+    )
+    .with_context(|| format!("Failed to run plugin {}", path.display()))?;
+    let run = async {
+        let output = communicate(&process, path)
+            .await
+            .with_context(|| format!("Failed to communicate with plugin {}", path.display()))?;
+        process
+            .wait_check_future()
+            .await
+            .with_context(|| format!("Plugin {} failed", path.display()))?;
+        anyhow::Ok(output)
+    };
+    let output = match glib::future_with_timeout(timeout, run).await {
+        Ok(result) => result?,
+        Err(_) => {
+            process.force_exit();
+            bail!(
+                "Plugin {} did not finish within {}s",
+                path.display(),
+                timeout.as_secs()
+            );
+        }
+    };
+    parse_items(&output).with_context(|| format!("Invalid output of plugin {}", path.display()))
+}
+
+/// Run all plugins in `directory` and collect their items, except for `ignored` items.
+///
+/// Skip plugins which fail; fail only if we can't read the plugins directory.
+async fn load_items(
+    directory: &Path,
+    timeout: Duration,
+    ignore: &IgnoreList,
+) -> Result<IndexMap<String, PluginItem>> {
+    let mut items = IndexMap::new();
+    for plugin in find_plugins(directory)? {
+        let name = plugin_name(&plugin);
+        match run_plugin(&plugin, timeout).await {
+            Ok(plugin_items) => {
+                event!(
+                    Level::DEBUG,
+                    "Plugin {} returned {} items",
+                    name,
+                    plugin_items.len()
+                );
+                for item in plugin_items {
+                    if ignore.is_ignored(&item.name, &item.location()) {
+                        event!(Level::DEBUG, "Ignoring item {} of plugin {name}", item.id);
+                    } else {
+                        items.insert(format!("plugin-{name}-{}", item.id), item);
+                    }
+                }
+            }
+            Err(error) => {
+                event!(Level::WARN, %error, "Skipping plugin {name}: {error:#}");
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Get the name of the plugin at `path`, for use in result IDs.
+fn plugin_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().to_string())
+}
+
+/// The items of all plugins, shared with loads in progress.
+#[derive(Debug, Default)]
+struct LoadedItems {
+    items: IndexMap<String, PluginItem>,
+    loaded_at: Option<Instant>,
+    is_loading: bool,
+}
+
+/// A search provider for the items of all plugins.
+#[derive(Debug)]
+pub struct PluginsSearchProvider {
+    directory: PathBuf,
+    timeout: Duration,
+    private_mode: PrivateMode,
+    ignore: IgnoreList,
+    disabled_providers: DisabledProviders,
+    desktop_ids: Vec<AppId>,
+    loaded: Arc<Mutex<LoadedItems>>,
+}
+
+impl PluginsSearchProvider {
+    /// Create a new provider for plugins in `directory`.
+    ///
+    /// Give plugins `timeout` to return their items, and return no items while `private_mode` is
+    /// enabled.
+    pub fn new(directory: PathBuf, timeout: Duration, private_mode: PrivateMode) -> Self {
+        Self {
+            directory,
+            timeout,
+            private_mode,
+            ignore: IgnoreList::default(),
+            disabled_providers: DisabledProviders::default(),
+            desktop_ids: Vec::new(),
+            loaded: Arc::default(),
+        }
+    }
+
+    /// Skip plugin items matched by `ignore`.
+    pub fn with_ignore(mut self, ignore: IgnoreList) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// Return no items if Gnome Shell disabled all providers for `desktop_ids`.
+    ///
+    /// `desktop_ids` are the desktop IDs Gnome Shell shows plugin items under, see
+    /// [`find_desktop_ids`].
+    pub fn with_disabled_providers(
+        mut self,
+        disabled_providers: DisabledProviders,
+        desktop_ids: Vec<AppId>,
+    ) -> Self {
+        self.disabled_providers = disabled_providers;
+        self.desktop_ids = desktop_ids;
+        self
+    }
+
+    /// Whether Gnome Shell disabled this provider.
+    fn is_disabled(&self) -> bool {
+        !self.desktop_ids.is_empty()
+            && self
+                .desktop_ids
+                .iter()
+                .all(|id| self.disabled_providers.is_disabled(id))
+    }
+
+    /// Run all plugins and load their items in the background.
+    ///
+    /// Do nothing if a load is already in progress.  Keep serving the current items until the load
+    /// finished.
+    pub fn reload_items(&self) {
+        {
+            let mut loaded = self.loaded.lock().unwrap();
+            if loaded.is_loading {
+                return;
+            }
+            loaded.is_loading = true;
+        }
+        let directory = self.directory.clone();
+        let timeout = self.timeout;
+        let ignore = self.ignore.clone();
+        let loaded = self.loaded.clone();
+        glib::MainContext::ref_thread_default().spawn_from_within(move || async move {
+            let result = load_items(&directory, timeout, &ignore).await;
+            let mut loaded = loaded.lock().unwrap();
+            loaded.is_loading = false;
+            match result {
+                Ok(items) => {
+                    loaded.items = items;
+                    loaded.loaded_at = Some(Instant::now());
+                }
+                Err(error) => {
+                    event!(Level::ERROR, %error, "Failed to load plugin items: {error:#}");
+                }
+            }
+        });
+    }
+
+    /// Start loading items if this provider didn't load them yet.
+    fn ensure_loaded(&self) {
+        if self.loaded.lock().unwrap().loaded_at.is_none() {
+            self.reload_items();
+        }
+    }
+
+    /// Find all items matching `terms`, best match first.
+    ///
+    /// Return no items until the first load finished.
+    fn find_results(&self, terms: &[&str]) -> Vec<String> {
+        if self.private_mode.is_enabled() || self.is_disabled() {
+            return Vec::new();
+        }
+        self.ensure_loaded();
+        let loaded = self.loaded.lock().unwrap();
+        let results = loaded
+            .items
+            .iter()
+            .map(|(id, item)| ScoredResult {
+                id: id.as_str(),
+                name: &item.name,
+                score: scoring::score(&item.name, &item.uri, terms),
+                opened_at: None,
+            })
+            .filter(|result| 0.0 < result.score)
+            .collect();
+        scoring::rank(results, |name| glib::CollationKey::from(name))
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Get the item with the given `id`.
+    fn item(&self, id: &str) -> Option<PluginItem> {
+        self.loaded.lock().unwrap().items.get(id).cloned()
+    }
+}
+
+impl Reloadable for PluginsSearchProvider {
+    /// Reload plugins which ran already; others run on first search anyway.
+    fn wants_reload(&self) -> bool {
+        self.loaded.lock().unwrap().loaded_at.is_some()
+    }
+
+    fn reloaded_at(&self) -> Option<Instant> {
+        self.loaded.lock().unwrap().loaded_at
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.reload_items();
+        Ok(())
+    }
+}
+
+#[interface(name = "org.gnome.Shell.SearchProvider2")]
+impl PluginsSearchProvider {
+    /// Starts a search.
+    #[instrument(skip(self))]
+    fn get_initial_result_set(&mut self, terms: Vec<&str>) -> Vec<String> {
+        self.find_results(&terms)
+    }
+
+    /// Refine an ongoing search.
+    #[instrument(skip(self))]
+    fn get_subsearch_result_set(
+        &mut self,
+        previous_results: Vec<&str>,
+        terms: Vec<&str>,
+    ) -> Vec<String> {
+        self.find_results(&terms)
+            .into_iter()
+            .filter(|id| previous_results.contains(&id.as_str()))
+            .collect()
+    }
+
+    /// Get metadata for results.
+    #[instrument(skip(self, results))]
+    fn get_result_metas(&self, results: Vec<String>) -> Vec<HashMap<String, zvariant::Value<'_>>> {
+        if self.private_mode.is_enabled() {
+            return Vec::new();
+        }
+        results
+            .into_iter()
+            .filter_map(|id| {
+                let item = self.item(&id)?;
+                Some(HashMap::from([
+                    ("id".to_string(), id.into()),
+                    ("name".to_string(), item.name.into()),
+                    ("description".to_string(), item.uri.into()),
+                    ("gicon".to_string(), "application-x-executable".into()),
+                ]))
+            })
+            .collect()
+    }
+
+    /// Activate an individual result.
+    ///
+    /// Open the URI of the item with its default handler in the background, and return
+    /// immediately.
+    #[cfg(feature = "launch")]
+    #[instrument(skip(self, item_id), fields(item_id = %redact::path(item_id)))]
+    fn activate_result(
+        &mut self,
+        item_id: &str,
+        _terms: Vec<&str>,
+        _timestamp: u32,
+    ) -> zbus::fdo::Result<()> {
+        let item = self.item(item_id).ok_or_else(|| {
+            zbus::fdo::Error::Failed(format!("Result {} not found", redact::path(item_id)))
+        })?;
+        let uri = item.uri;
+        event!(Level::INFO, "Opening {}", redact::path(&uri));
+        glib::MainContext::default().spawn_local(async move {
+            let context = gio::AppLaunchContext::NONE;
+            if let Err(error) = gio::AppInfo::launch_default_for_uri_future(&uri, context).await {
+                event!(Level::ERROR, %error, "Failed to open {uri}: {error:#}");
+            }
+        });
+        Ok(())
+    }
+
+    /// Activate an individual result.
+    ///
+    /// Always fail, because this service was built without support for launching apps.
+    #[cfg(not(feature = "launch"))]
+    fn activate_result(
+        &mut self,
+        _item_id: &str,
+        _terms: Vec<&str>,
+        _timestamp: u32,
+    ) -> zbus::fdo::Result<()> {
+        Err(zbus::fdo::Error::NotSupported(
+            "Launching apps not supported".to_string(),
+        ))
+    }
+
+    /// Launch a search within the app.
+    ///
+    /// Plugins have no app to search in, so do nothing.
+    fn launch_search(&self, _terms: Vec<String>, _timestamp: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn parse_plugin_items() {
+        let items = parse_items(
+            br#"[{"id":"mdcat","name":"mdcat","uri":"vscode://file/home/foo/mdcat","extra":1}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![PluginItem {
+                id: "mdcat".to_string(),
+                name: "mdcat".to_string(),
+                uri: "vscode://file/home/foo/mdcat".to_string(),
+            }]
+        );
+        assert!(parse_items(br#"[{"id":"mdcat"}]"#).is_err());
+        assert!(parse_items(b"").is_err());
+    }
+
+    /// Create a temporary plugins directory with some plugins.
+    fn plugins_directory() -> PathBuf {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("plugins-XXXXXX")).unwrap();
+        let write_plugin = |name: &str, script: &str| {
+            let path = directory.join(name);
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        write_plugin(
+            "code",
+            "#!/bin/sh\nread request\necho '[{\"id\":\"mdcat\",\"name\":\"mdcat\",\"uri\":\"file:///srv/mdcat\"},{\"id\":\"tmp\",\"name\":\"mdcat-tmp\",\"uri\":\"file:///tmp/mdcat\"}]'\n",
+        );
+        write_plugin("broken", "#!/bin/sh\nexit 1\n");
+        write_plugin("hanging", "#!/bin/sh\nexec sleep 10\n");
+        write_plugin("chatty", "#!/bin/sh\nexec yes\n");
+        std::fs::write(directory.join("README"), "not a plugin").unwrap();
+        directory
+    }
+
+    /// Search `provider` for `terms` in `context`, and wait for items to load first.
+    fn search_loaded(
+        context: &glib::MainContext,
+        provider: &mut PluginsSearchProvider,
+        terms: Vec<&str>,
+    ) -> Vec<String> {
+        provider.ensure_loaded();
+        context.block_on(async {
+            while provider.loaded.lock().unwrap().is_loading {
+                glib::timeout_future(Duration::from_millis(10)).await;
+            }
+        });
+        provider.get_initial_result_set(terms)
+    }
+
+    #[test]
+    fn kill_plugins_with_too_much_output() {
+        let directory = plugins_directory();
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let started = Instant::now();
+                let error = context
+                    .block_on(run_plugin(&directory.join("chatty"), Duration::from_secs(10)))
+                    .unwrap_err();
+                assert!(started.elapsed() < Duration::from_secs(10));
+                assert_eq!(
+                    format!("{error:#}"),
+                    format!(
+                        "Failed to communicate with plugin {0}: Output of plugin {0} exceeds {1} bytes",
+                        directory.join("chatty").display(),
+                        MAX_OUTPUT_SIZE
+                    )
+                );
+            })
+            .unwrap();
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn kill_plugins_which_linger_after_closing_output() {
+        let directory = plugins_directory();
+        let plugin = directory.join("lingering");
+        std::fs::write(&plugin, "#!/bin/sh\nexec >&-\nexec sleep 10\n").unwrap();
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let started = Instant::now();
+                let error = context
+                    .block_on(run_plugin(&plugin, Duration::from_secs(1)))
+                    .unwrap_err();
+                assert!(started.elapsed() < Duration::from_secs(5));
+                assert_eq!(
+                    error.to_string(),
+                    format!("Plugin {} did not finish within 1s", plugin.display())
+                );
+            })
+            .unwrap();
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn run_plugins_in_directory() {
+        let directory = plugins_directory();
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let mut provider = PluginsSearchProvider::new(
+                    directory.clone(),
+                    Duration::from_secs(2),
+                    PrivateMode::default(),
+                )
+                .with_ignore(IgnoreList::parse("/tmp/**").unwrap());
+                assert!(provider.get_initial_result_set(vec!["mdcat"]).is_empty());
+                assert_eq!(
+                    search_loaded(&context, &mut provider, vec!["mdcat"]),
+                    vec!["plugin-code-mdcat"]
+                );
+                assert!(provider.get_initial_result_set(vec!["foo"]).is_empty());
+                let metas = provider.get_result_metas(vec!["plugin-code-mdcat".to_string()]);
+                assert_eq!(metas.len(), 1);
+                assert_eq!(metas[0]["name"], zvariant::Value::from("mdcat"));
+            })
+            .unwrap();
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn no_items_if_disabled() {
+        let directory = plugins_directory();
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let disabled = DisabledProviders::default();
+                let mut provider = PluginsSearchProvider::new(
+                    directory.clone(),
+                    Duration::from_secs(2),
+                    PrivateMode::default(),
+                )
+                .with_disabled_providers(
+                    disabled.clone(),
+                    vec![AppId::try_from("code.desktop").unwrap()],
+                );
+                assert_eq!(
+                    search_loaded(&context, &mut provider, vec!["mdcat"]).len(),
+                    2
+                );
+                disabled.set(["code.desktop".to_string()], [], false);
+                assert!(provider.get_initial_result_set(vec!["mdcat"]).is_empty());
+            })
+            .unwrap();
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn find_desktop_ids_of_plugins_provider() {
+        let data_dir = glib::mkdtemp(std::env::temp_dir().join("plugins-XXXXXX")).unwrap();
+        let providers = data_dir.join("gnome-shell").join("search-providers");
+        std::fs::create_dir_all(&providers).unwrap();
+        let write_provider = |name: &str, desktop_id: &str, object_path: &str| {
+            std::fs::write(
+                providers.join(name),
+                format!("[Shell Search Provider]\nDesktopId={desktop_id}\nBusName=de.swsnr.searchprovider.Jetbrains\nObjectPath={object_path}\nVersion=2\n"),
+            )
+            .unwrap();
+        };
+        write_provider(
+            "code.ini",
+            "code.desktop",
+            "/de/swsnr/searchprovider/jetbrains/plugins",
+        );
+        write_provider(
+            "idea.ini",
+            "jetbrains-idea.desktop",
+            "/de/swsnr/searchprovider/jetbrains/idea",
+        );
+        assert_eq!(
+            find_desktop_ids(
+                std::slice::from_ref(&data_dir),
+                "de.swsnr.searchprovider.Jetbrains",
+                "/de/swsnr/searchprovider/jetbrains/plugins"
+            ),
+            vec![AppId::try_from("code.desktop").unwrap()]
+        );
+        assert!(find_desktop_ids(
+            std::slice::from_ref(&data_dir),
+            "com.example.Other",
+            "/de/swsnr/searchprovider/jetbrains/plugins"
+        )
+        .is_empty());
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn no_plugins_without_directory() {
+        let plugins = find_plugins(Path::new("/this/does/not/exist")).unwrap();
+        assert!(plugins.is_empty());
+    }
+}
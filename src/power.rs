@@ -0,0 +1,47 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Track power saver mode, to refresh recent projects less eagerly on battery.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use gio::prelude::*;
+use tracing::{event, Level};
+
+/// Shared power saver state.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct PowerSaver(Arc<AtomicBool>);
+
+impl PowerSaver {
+    /// Whether power saver mode is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Set whether power saver mode is enabled.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Track the power saver mode of the system.
+    ///
+    /// Return the underlying monitor; the caller must keep it alive as long as it wants to track
+    /// power saver mode.
+    pub fn track_system(&self) -> gio::PowerProfileMonitor {
+        let monitor = gio::PowerProfileMonitor::get_default();
+        self.set_enabled(monitor.is_power_saver_enabled());
+        let power_saver = self.clone();
+        monitor.connect_power_saver_enabled_notify(move |monitor| {
+            let enabled = monitor.is_power_saver_enabled();
+            event!(Level::INFO, "Power saver mode enabled: {enabled}");
+            power_saver.set_enabled(enabled);
+        });
+        monitor
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Private mode, to temporarily hide all results.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Shared private mode state.
+///
+/// While private mode is enabled providers return no results.  Private mode ends automatically
+/// after the timeout given when enabling it.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct PrivateMode(Arc<Mutex<Option<Instant>>>);
+
+impl PrivateMode {
+    /// Enable private mode for `timeout`, starting `now`.
+    pub fn enable(&self, now: Instant, timeout: Duration) {
+        *self.0.lock().unwrap() = Some(now + timeout);
+    }
+
+    /// Disable private mode.
+    pub fn disable(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+
+    /// Whether private mode is enabled at `now`.
+    pub fn is_enabled_at(&self, now: Instant) -> bool {
+        self.0.lock().unwrap().is_some_and(|until| now < until)
+    }
+
+    /// Whether private mode is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_mode_expires() {
+        let private_mode = PrivateMode::default();
+        let now = Instant::now();
+        assert!(!private_mode.is_enabled_at(now));
+        let shared = private_mode.clone();
+        shared.enable(now, Duration::from_secs(60));
+        assert!(private_mode.is_enabled_at(now));
+        assert!(private_mode.is_enabled_at(now + Duration::from_secs(59)));
+        assert!(!private_mode.is_enabled_at(now + Duration::from_secs(60)));
+        shared.disable();
+        assert!(!private_mode.is_enabled_at(now));
+    }
+}
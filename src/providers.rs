@@ -6,8 +6,13 @@
 
 //! Provider definitions.
 
+use gio::prelude::*;
+
 use crate::config::ConfigLocation;
 
+/// The default prefix for object paths of search providers.
+pub const OBJECT_PATH_PREFIX: &str = "/de/swsnr/searchprovider/jetbrains";
+
 /// A search provider to expose from this service.
 pub struct ProviderDefinition<'a> {
     /// A human readable label for this provider.
@@ -21,10 +26,20 @@ pub struct ProviderDefinition<'a> {
 }
 
 impl ProviderDefinition<'_> {
-    /// Gets the full object path for this provider.
-    pub fn objpath(&self) -> String {
+    /// Get the label to show for this provider.
+    ///
+    /// Prefer the localized name of the app if it's installed, so that the label matches what
+    /// users see in Gnome Shell, and fall back to [`Self::label`] otherwise.
+    pub fn display_label(&self) -> String {
+        gio::DesktopAppInfo::new(self.desktop_id)
+            .map_or_else(|| self.label.to_string(), |app| app.display_name().into())
+    }
+
+    /// Gets the full object path for this provider under the given object path `prefix`.
+    pub fn objpath_with_prefix(&self, prefix: &str) -> String {
         format!(
-            "/de/swsnr/searchprovider/jetbrains/{}",
+            "{}/{}",
+            prefix.trim_end_matches('/'),
             self.relative_obj_path
         )
     }
@@ -45,6 +60,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "CLion",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -55,6 +71,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "GoLand",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -65,6 +82,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "IntelliJIdea",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -75,6 +93,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "IdeaIC",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -85,6 +104,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "IdeaIC",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -95,6 +115,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "PhpStorm",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -105,6 +126,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "PyCharm",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -115,6 +137,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "Rider",
             projects_filename: "recentSolutions.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -125,6 +148,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "RubyMine",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -135,6 +159,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "RustRover",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -145,6 +170,18 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "Google",
             config_prefix: "AndroidStudio",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &["Preview"],
+        },
+    },
+    ProviderDefinition {
+        label: "Android Studio Preview (toolbox)",
+        desktop_id: "jetbrains-studio-preview.desktop",
+        relative_obj_path: "toolbox/studiopreview",
+        config: ConfigLocation {
+            vendor_dir: "Google",
+            config_prefix: "AndroidStudioPreview",
+            projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
     ProviderDefinition {
@@ -155,6 +192,7 @@ pub const PROVIDERS: &[ProviderDefinition] = &[
             vendor_dir: "JetBrains",
             config_prefix: "WebStorm",
             projects_filename: "recentProjects.xml",
+            excluded_channels: &[],
         },
     },
 ];
@@ -169,7 +207,10 @@ mod tests {
 
     use anyhow::{anyhow, Context, Result};
 
-    use crate::{BUSNAME, PROVIDERS};
+    use crate::{BUSNAME, OBJECT_PATH_PREFIX, PROVIDERS};
+
+    use super::ProviderDefinition;
+    use crate::config::ConfigLocation;
 
     struct ProviderFile {
         desktop_id: String,
@@ -223,9 +264,15 @@ mod tests {
                 provider.desktop_id
             );
 
-            assert_eq!(provider_file.unwrap().object_path, provider.objpath());
+            assert_eq!(
+                provider_file.unwrap().object_path,
+                provider.objpath_with_prefix(OBJECT_PATH_PREFIX)
+            );
             assert_eq!(provider_file.unwrap().bus_name, BUSNAME);
-            assert_eq!(provider_file.unwrap().version, "2");
+            assert_eq!(
+                provider_file.unwrap().version,
+                crate::capabilities::PROVIDER_VERSION.to_string()
+            );
         }
     }
 
@@ -248,11 +295,27 @@ mod tests {
     fn dbus_paths_are_unique() {
         let mut paths = HashSet::new();
         for provider in PROVIDERS {
-            paths.insert(provider.objpath());
+            paths.insert(provider.objpath_with_prefix(OBJECT_PATH_PREFIX));
         }
         assert_eq!(PROVIDERS.len(), paths.len());
     }
 
+    #[test]
+    fn display_label_falls_back_to_label() {
+        let provider = ProviderDefinition {
+            label: "Nonexistent IDE (toolbox)",
+            desktop_id: "jetbrains-does-not-exist.desktop",
+            relative_obj_path: "toolbox/nonexistent",
+            config: ConfigLocation {
+                vendor_dir: "JetBrains",
+                config_prefix: "Nonexistent",
+                projects_filename: "recentProjects.xml",
+                excluded_channels: &[],
+            },
+        };
+        assert_eq!(provider.display_label(), "Nonexistent IDE (toolbox)");
+    }
+
     #[test]
     fn all_providers_are_in_readme() {
         let readme = Path::new(env!("CARGO_MANIFEST_DIR")).join("README.md");
@@ -0,0 +1,387 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Parse recent projects files of Jetbrains IDEs.
+//!
+//! This module only depends on external crates but not on any other module of this crate,
+//! because the fuzz targets in `fuzz/` include it directly.
+
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use elementtree::Element;
+use tracing::{event, Level};
+
+/// A recent project entry.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RecentProjectEntry {
+    /// The path of the project, with `$USER_HOME$` expanded.
+    pub path: String,
+    /// The rest of the path after `$USER_HOME$`, if the IDE recorded the path relative to the home
+    /// directory.
+    pub home_relative: Option<String>,
+    /// The kind of solution, e.g. `Unity` or `Unreal`, as recorded by Rider.
+    pub solution_kind: Option<String>,
+    /// The target framework of a solution, as recorded by Rider.
+    pub target_framework: Option<String>,
+    /// When the project was last opened, in milliseconds since the Unix epoch.
+    ///
+    /// This is the later of the time the IDE opened the project and the time the user last
+    /// switched to the window of the project.
+    pub opened_at: Option<i64>,
+    /// Further directories attached to the project in the same window, with `$USER_HOME$`
+    /// expanded.
+    pub attached_roots: Vec<String>,
+}
+
+/// Limits on the size of recent projects data.
+///
+/// Recent projects files are small in practice, but a corrupted file could contain megabytes of
+/// data, which we'd rather not read into memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of a recent projects file in bytes.
+    pub max_file_size: u64,
+    /// The maximum size of the data of a single recent project entry in bytes.
+    ///
+    /// We keep the path of larger entries, but ignore their meta info.
+    pub max_entry_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 4 * 1024 * 1024,
+            max_entry_size: 4096,
+        }
+    }
+}
+
+/// Get the size of all attribute values and text in `element` and its descendants.
+fn data_size(element: &Element) -> usize {
+    element.attrs().map(|(_, value)| value.len()).sum::<usize>()
+        + element.text().trim().len()
+        + element.children().map(data_size).sum::<usize>()
+}
+
+/// Get the value of the option `name` in the meta info of the given recent project `entry`.
+///
+/// The meta info element is `RecentProjectMetaInfo` for most IDEs, but has a different name in
+/// some IDEs, so we just take the first child of the `value` element.
+fn get_meta_info_option<'a>(entry: &'a Element, name: &str) -> Option<&'a str> {
+    entry
+        .find("value")?
+        .children()
+        .next()?
+        .find_all("option")
+        .find(|option| option.get_attr("name") == Some(name))?
+        .get_attr("value")
+        .filter(|value| !value.is_empty())
+}
+
+/// Get the values of the list option `name` in the meta info of the given recent project `entry`.
+///
+/// Return an empty list if the entry has no such option.
+fn get_meta_info_list<'a>(entry: &'a Element, name: &str) -> Vec<&'a str> {
+    entry
+        .find("value")
+        .and_then(|value| value.children().next())
+        .and_then(|meta_info| {
+            meta_info
+                .find_all("option")
+                .find(|option| option.get_attr("name") == Some(name))
+        })
+        .and_then(|option| option.find("list"))
+        .map(|list| {
+            list.find_all("option")
+                .filter_map(|option| option.get_attr("value"))
+                .filter(|value| !value.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Read all recent projects from the given `reader`.
+///
+/// Replace `$USER_HOME$` in all paths with `home`.
+///
+/// Read directories attached to a project from the `attachedRoots` list in its meta info, and
+/// return the project with all its roots as a single entry.
+///
+/// Fail if `reader` has more data than permitted by `limits`.  For entries with more data than
+/// permitted only return the path, and ignore all meta info, e.g. timestamps and attached roots;
+/// skip entries whose path alone exceeds the limit.
+pub fn parse_recent_jetbrains_projects<R: Read>(
+    home: &str,
+    reader: R,
+    limits: &Limits,
+) -> Result<Vec<RecentProjectEntry>> {
+    let mut data = Vec::new();
+    reader
+        .take(limits.max_file_size.saturating_add(1))
+        .read_to_end(&mut data)?;
+    if limits.max_file_size < data.len() as u64 {
+        bail!(
+            "Recent projects exceed the maximum size of {} bytes",
+            limits.max_file_size
+        );
+    }
+    let element = Element::from_reader(data.as_slice())?;
+
+    let projects: Vec<RecentProjectEntry> = element
+        .find_all("component")
+        .find(|e| {
+            e.get_attr("name") == Some("RecentProjectsManager")
+                || e.get_attr("name") == Some("RiderRecentProjectsManager")
+        })
+        .and_then(|comp| {
+            comp.find_all("option")
+                .find(|e| e.get_attr("name") == Some("additionalInfo"))
+        })
+        .and_then(|opt| opt.find("map"))
+        .map(|map| {
+            map.find_all("entry")
+                .filter_map(|entry| {
+                    let key = entry.get_attr("key")?;
+                    if limits.max_entry_size < key.len() {
+                        event!(
+                            Level::WARN,
+                            "Skipping recent project entry with a path of {} bytes, exceeds maximum of {} bytes",
+                            key.len(),
+                            limits.max_entry_size
+                        );
+                        return None;
+                    }
+                    let project = RecentProjectEntry {
+                        path: key.replace("$USER_HOME$", home),
+                        home_relative: key.strip_prefix("$USER_HOME$").map(ToOwned::to_owned),
+                        ..RecentProjectEntry::default()
+                    };
+                    let size = data_size(entry);
+                    if limits.max_entry_size < size {
+                        event!(
+                            Level::WARN,
+                            "Ignoring meta info of recent project entry with {} bytes of data, exceeds maximum of {} bytes",
+                            size,
+                            limits.max_entry_size
+                        );
+                        return Some(project);
+                    }
+                    Some(RecentProjectEntry {
+                        solution_kind: get_meta_info_option(entry, "solutionKind")
+                            .map(ToOwned::to_owned),
+                        target_framework: get_meta_info_option(entry, "targetFramework")
+                            .map(ToOwned::to_owned),
+                        opened_at: ["projectOpenTimestamp", "activationTimestamp"]
+                            .into_iter()
+                            .filter_map(|name| get_meta_info_option(entry, name)?.parse().ok())
+                            .max(),
+                        attached_roots: get_meta_info_list(entry, "attachedRoots")
+                            .into_iter()
+                            .map(|root| root.replace("$USER_HOME$", home))
+                            .collect(),
+                        ..project
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    event!(Level::TRACE, "Parsed {} projects", projects.len());
+
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn read_recent_projects() {
+        let data: &[u8] = include_bytes!("tests/recentProjects.xml");
+        let home = glib::home_dir();
+        let recent_projects =
+            parse_recent_jetbrains_projects(home.to_str().unwrap(), data, &Limits::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>();
+
+        assert_eq!(
+            recent_projects,
+            vec![
+                home.join("Code")
+                    .join("gh")
+                    .join("mdcat")
+                    .to_string_lossy()
+                    .to_string(),
+                home.join("Code")
+                    .join("gh")
+                    .join("gnome-search-providers-jetbrains")
+                    .to_string_lossy()
+                    .to_string()
+            ]
+        )
+    }
+
+    #[test]
+    fn read_recent_solutions() {
+        let data: &[u8] = include_bytes!("tests/recentSolutions.xml");
+        let home = glib::home_dir();
+        let recent_projects =
+            parse_recent_jetbrains_projects(home.to_str().unwrap(), data, &Limits::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>();
+
+        assert_eq!(
+            recent_projects,
+            vec![
+                home.join("Code")
+                    .join("gh")
+                    .join("mdcat")
+                    .to_string_lossy()
+                    .to_string(),
+                home.join("Code")
+                    .join("gh")
+                    .join("gnome-search-providers-jetbrains")
+                    .to_string_lossy()
+                    .to_string()
+            ]
+        )
+    }
+
+    #[test]
+    fn read_recent_solutions_with_kind_and_framework() {
+        let data: &[u8] = include_bytes!("tests/recentSolutionsGameEngines.xml");
+        let recent_projects =
+            parse_recent_jetbrains_projects("/home/foo", data, &Limits::default()).unwrap();
+
+        assert_eq!(
+            recent_projects,
+            vec![
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/MyGame/MyGame.sln".to_string(),
+                    home_relative: Some("/RiderProjects/MyGame/MyGame.sln".to_string()),
+                    solution_kind: Some("Unity".to_string()),
+                    target_framework: Some("netstandard2.1".to_string()),
+                    opened_at: Some(1705312234567),
+                    attached_roots: Vec::new(),
+                },
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/Shooter/Shooter.uproject".to_string(),
+                    home_relative: Some("/RiderProjects/Shooter/Shooter.uproject".to_string()),
+                    solution_kind: Some("Unreal".to_string()),
+                    target_framework: None,
+                    opened_at: Some(1705312298765),
+                    attached_roots: Vec::new(),
+                },
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/WebApi/WebApi.sln".to_string(),
+                    home_relative: Some("/RiderProjects/WebApi/WebApi.sln".to_string()),
+                    solution_kind: None,
+                    target_framework: Some("net8.0".to_string()),
+                    opened_at: None,
+                    attached_roots: Vec::new(),
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn read_attached_roots() {
+        let data: &[u8] = include_bytes!("tests/recentProjectsAttached.xml");
+        let recent_projects =
+            parse_recent_jetbrains_projects("/home/foo", data, &Limits::default()).unwrap();
+
+        assert_eq!(
+            recent_projects,
+            vec![
+                RecentProjectEntry {
+                    path: "/home/foo/Code/shop/frontend".to_string(),
+                    home_relative: Some("/Code/shop/frontend".to_string()),
+                    opened_at: Some(1618250731842),
+                    attached_roots: vec![
+                        "/home/foo/Code/shop/backend".to_string(),
+                        "/srv/shop/shared".to_string()
+                    ],
+                    ..RecentProjectEntry::default()
+                },
+                RecentProjectEntry {
+                    path: "/home/foo/Code/gh/mdcat".to_string(),
+                    home_relative: Some("/Code/gh/mdcat".to_string()),
+                    opened_at: Some(1618243465479),
+                    ..RecentProjectEntry::default()
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn ignore_meta_info_of_oversized_entries() {
+        let data: &[u8] = include_bytes!("tests/recentSolutionsGameEngines.xml");
+        let limits = Limits {
+            max_entry_size: 150,
+            ..Limits::default()
+        };
+        let recent_projects = parse_recent_jetbrains_projects("/home/foo", data, &limits).unwrap();
+        assert_eq!(
+            recent_projects,
+            vec![
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/MyGame/MyGame.sln".to_string(),
+                    home_relative: Some("/RiderProjects/MyGame/MyGame.sln".to_string()),
+                    ..RecentProjectEntry::default()
+                },
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/Shooter/Shooter.uproject".to_string(),
+                    home_relative: Some("/RiderProjects/Shooter/Shooter.uproject".to_string()),
+                    ..RecentProjectEntry::default()
+                },
+                RecentProjectEntry {
+                    path: "/home/foo/RiderProjects/WebApi/WebApi.sln".to_string(),
+                    home_relative: Some("/RiderProjects/WebApi/WebApi.sln".to_string()),
+                    target_framework: Some("net8.0".to_string()),
+                    ..RecentProjectEntry::default()
+                },
+            ]
+        );
+
+        // Skip entries whose path alone exceeds the limit
+        let limits = Limits {
+            max_entry_size: 45,
+            ..Limits::default()
+        };
+        let recent_projects = parse_recent_jetbrains_projects("/home/foo", data, &limits)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recent_projects,
+            vec![
+                "/home/foo/RiderProjects/MyGame/MyGame.sln".to_string(),
+                "/home/foo/RiderProjects/WebApi/WebApi.sln".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_oversized_file() {
+        let data: &[u8] = include_bytes!("tests/recentProjects.xml");
+        let limits = Limits {
+            max_file_size: 100,
+            ..Limits::default()
+        };
+        let error = parse_recent_jetbrains_projects("/home/foo", data, &limits).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Recent projects exceed the maximum size of 100 bytes"
+        );
+    }
+}
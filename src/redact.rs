@@ -0,0 +1,134 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Redact project paths and platform data in logs.
+//!
+//! Debug and trace logs contain project paths and the platform data of launched apps, which
+//! reveal more about the user's projects than they might like to share along with their logs.
+//! If enabled, we hash all components of a path except its basename, and log only the keys of
+//! platform data.  Hashes are stable across runs, so redacted logs still tell which events
+//! refer to the same directory.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether to redact paths and platform data.
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+/// Set whether to redact paths and platform data in logs.
+pub fn set_redact(enabled: bool) {
+    REDACT.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether to redact paths and platform data in logs.
+pub fn is_redacting() -> bool {
+    REDACT.load(Ordering::Relaxed)
+}
+
+/// Hash a single path `component`.
+fn hash_component(component: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    component.hash(&mut hasher);
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+/// A path to log, redacted if enabled with [`set_redact`].
+///
+/// Redacts in both, `Display` and `Debug`.
+#[derive(Clone, Copy)]
+pub struct RedactedPath<'a>(&'a str);
+
+impl<'a> Debug for RedactedPath<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl<'a> Display for RedactedPath<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !is_redacting() {
+            return f.write_str(self.0);
+        }
+        let mut components = self.0.split('/').peekable();
+        while let Some(component) = components.next() {
+            let last = components.peek().is_none();
+            if component.is_empty() || last {
+                f.write_str(component)?;
+            } else {
+                f.write_str(&hash_component(component))?;
+            }
+            if !last {
+                f.write_str("/")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Log `path`, redacted if enabled.
+pub fn path<P: AsRef<str> + ?Sized>(path: &P) -> RedactedPath<'_> {
+    RedactedPath(path.as_ref())
+}
+
+/// Platform data of a launched app to log, reduced to its keys if redaction is enabled.
+#[cfg(feature = "launch")]
+#[derive(Debug, Clone, Copy)]
+pub struct RedactedPlatformData<'a>(&'a glib::Variant);
+
+#[cfg(feature = "launch")]
+impl<'a> Display for RedactedPlatformData<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !is_redacting() {
+            return write!(f, "{:?}", self.0);
+        }
+        match self.0.get::<glib::VariantDict>() {
+            Some(dict) => {
+                let mut keys = Vec::new();
+                for entry in dict.end().iter() {
+                    if let Some(key) = entry.child_value(0).str() {
+                        keys.push(key.to_string());
+                    }
+                }
+                write!(f, "<redacted with keys {}>", keys.join(", "))
+            }
+            None => f.write_str("<redacted>"),
+        }
+    }
+}
+
+/// Log `platform_data`, reduced to its keys if redaction is enabled.
+#[cfg(feature = "launch")]
+pub fn platform_data(platform_data: &glib::Variant) -> RedactedPlatformData<'_> {
+    RedactedPlatformData(platform_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn redact_all_but_basename() {
+        set_redact(false);
+        assert_eq!(
+            path("/home/foo/Code/mdcat").to_string(),
+            "/home/foo/Code/mdcat"
+        );
+        set_redact(true);
+        let redacted = path("/home/foo/Code/mdcat").to_string();
+        set_redact(false);
+        let components = redacted.split('/').collect::<Vec<_>>();
+        assert_eq!(components.len(), 5);
+        assert_eq!(components[0], "");
+        assert_eq!(components[1], hash_component("home"));
+        assert_eq!(components[2], hash_component("foo"));
+        assert_eq!(components[3], hash_component("Code"));
+        assert_eq!(components[4], "mdcat");
+        assert_ne!(components[2], "foo");
+    }
+}
@@ -6,68 +6,231 @@
 
 //! Reload all recent projects across all exposed provider interfaces.
 
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
 use tracing::{event, instrument, Level};
-use zbus::{interface, ObjectServer};
-
-use crate::searchprovider::JetbrainsProductSearchProvider;
-use crate::{providers::PROVIDERS, ProviderDefinition};
-
-/// Reload recent projects of a single `provider` on the given object `server`.
-async fn reload_provider_on_object_server(
-    server: &ObjectServer,
-    provider: &ProviderDefinition<'_>,
-) -> anyhow::Result<()> {
-    let app_id = provider.desktop_id;
-    event!(
-        Level::DEBUG,
-        %app_id,
-        "Reloading recent projects of search provider registered at {}",
-        provider.objpath()
-    );
-    let maybe_interface = server
-        .interface::<_, JetbrainsProductSearchProvider>(provider.objpath())
-        .await
-        .map_err(|error| {
+use tracing_futures::Instrument;
+use zbus::message::Header;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{interface, ObjectServer, SignalContext};
+
+use crate::history::ActivationHistory;
+use crate::private::PrivateMode;
+use crate::timeout::{with_timeout, DEFAULT_CALL_TIMEOUT};
+
+/// How often to reload all providers.
+///
+/// If you create a new project you're probably going to work with it for at least a few minutes,
+/// so it doesn't matter if it only appears in search results after a few minutes.
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long to skip reloads after a provider reloaded.
+///
+/// Reload requests can come in bursts, e.g. from scripts which call `ReloadAll` for every changed
+/// file.  Requests within this window merge into the preceding reload: they wait for the preceding
+/// reload to finish, and then succeed without reloading again.
+pub const RELOAD_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// Whether a provider which last reloaded at `reloaded_at` should skip a reload at `now`.
+fn is_cooling_down(reloaded_at: Option<Instant>, now: Instant) -> bool {
+    reloaded_at
+        .is_some_and(|reloaded_at| now.saturating_duration_since(reloaded_at) < RELOAD_COOLDOWN)
+}
+
+/// A provider interface which can reload its contents.
+pub trait Reloadable: zbus::Interface {
+    /// Whether to reload this provider when reloading all providers.
+    fn wants_reload(&self) -> bool;
+
+    /// When this provider last reloaded successfully, if ever.
+    fn reloaded_at(&self) -> Option<Instant>;
+
+    /// Reload this provider.
+    fn reload(&mut self) -> anyhow::Result<()>;
+}
+
+/// A future reloading a provider.
+type ReloadFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+/// A function to reload the provider at an object path on an object server.
+type Reloader = for<'a> fn(&'a ObjectServer, &'a OwnedObjectPath) -> ReloadFuture<'a>;
+
+/// Reload the provider interface `I` at `path` on the given object `server`.
+fn reload_at<'a, I: Reloadable>(
+    server: &'a ObjectServer,
+    path: &'a OwnedObjectPath,
+) -> ReloadFuture<'a> {
+    Box::pin(async move {
+        event!(
+            Level::DEBUG,
+            "Reloading search provider registered at {}",
+            path.as_str()
+        );
+        let interface = match server.interface::<_, I>(path).await {
+            Ok(interface) => interface,
+            Err(error) => {
+                event!(Level::DEBUG, "Skipping {}: {error}", path.as_str());
+                return Ok(());
+            }
+        };
+        // Concurrent reloads of the same provider wait here for the preceding reload to finish
+        let mut provider = interface.get_mut().await;
+        if !provider.wants_reload() {
+            event!(Level::DEBUG, "Skipping {}: Not loaded yet", path.as_str());
+            Ok(())
+        } else if is_cooling_down(provider.reloaded_at(), Instant::now()) {
             event!(
                 Level::DEBUG,
-                %app_id,
-                "Skipping {}: {error}",
-                provider.label,
+                "Skipping {}: Reloaded just now",
+                path.as_str()
             );
-        })
-        .ok();
+            Ok(())
+        } else {
+            provider.reload()
+        }
+    })
+}
+
+/// All reloadable providers on an object server.
+#[derive(Debug, Clone, Default)]
+pub struct Reloadables(Vec<(OwnedObjectPath, Reloader)>);
 
-    match maybe_interface {
-        Some(interface) => interface.get_mut().await.reload_recent_projects(),
-        None => Ok(()),
+impl Reloadables {
+    /// Add the provider interface `I` at `path`.
+    pub fn add<I: Reloadable>(&mut self, path: OwnedObjectPath) {
+        self.0.push((path, reload_at::<I>));
+    }
+
+    /// Reload all providers on the given object `server`.
+    pub async fn reload_all_on_object_server(
+        &self,
+        server: &ObjectServer,
+    ) -> zbus::fdo::Result<()> {
+        event!(
+            Level::DEBUG,
+            "Reloading recent projects of all registered search providers"
+        );
+        let mut is_failed = false;
+        for (path, reload) in &self.0 {
+            if let Err(error) = reload(server, path).await {
+                is_failed = true;
+                event!(
+                    Level::ERROR,
+                    "Failed to reload search provider at {}: {error:#}",
+                    path.as_str()
+                );
+            }
+        }
+        if is_failed {
+            Err(zbus::fdo::Error::Failed(
+                "Failed to reload recent projects of some providers".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
     }
 }
 
-/// Reload all providers registered on the given object `server`.
-pub async fn reload_all_on_object_server(server: &ObjectServer) -> zbus::fdo::Result<()> {
-    event!(
-        Level::DEBUG,
-        "Reloading recent projects of all registered search providers"
-    );
-    let mut is_failed = false;
-    for provider in PROVIDERS {
-        if let Err(error) = reload_provider_on_object_server(server, provider).await {
-            is_failed = true;
-            let app_id = provider.desktop_id;
-            event!(Level::ERROR, %app_id, "Failed to reload recent projects of {}: {}", app_id, error);
+/// The global interface of this service.
+#[derive(Debug)]
+pub struct ReloadAll {
+    /// All providers to reload.
+    reloadables: Reloadables,
+    /// The private mode shared with all providers.
+    private_mode: PrivateMode,
+    /// How long private mode lasts before it ends automatically.
+    private_mode_timeout: Duration,
+    /// The history of activated results shared with all providers.
+    activations: ActivationHistory,
+    /// Well-known bus names whose owners may get the activation history.
+    activations_allowed_callers: Vec<String>,
+    /// How long to wait for replies of the bus.
+    call_timeout: Duration,
+}
+
+impl ReloadAll {
+    /// Create the global interface for the given `reloadables`, to control the given shared
+    /// `private_mode`.
+    ///
+    /// Enabling private mode over DBus lasts for `private_mode_timeout`.
+    pub fn new(
+        reloadables: Reloadables,
+        private_mode: PrivateMode,
+        private_mode_timeout: Duration,
+    ) -> Self {
+        Self {
+            reloadables,
+            private_mode,
+            private_mode_timeout,
+            activations: ActivationHistory::default(),
+            activations_allowed_callers: Vec::new(),
+            call_timeout: DEFAULT_CALL_TIMEOUT,
         }
     }
-    if is_failed {
-        Err(zbus::fdo::Error::Failed(
-            "Failed to reload recent projects of some providers".to_string(),
-        ))
-    } else {
-        Ok(())
+
+    /// Wait for replies of the bus at most for `call_timeout`.
+    pub fn with_call_timeout(mut self, call_timeout: Duration) -> Self {
+        self.call_timeout = call_timeout;
+        self
+    }
+
+    /// Expose the given history of `activations` to owners of the `allowed_callers` bus names.
+    pub fn with_activations(
+        mut self,
+        activations: ActivationHistory,
+        allowed_callers: Vec<String>,
+    ) -> Self {
+        self.activations = activations;
+        self.activations_allowed_callers = allowed_callers;
+        self
+    }
+
+    /// Whether the `sender` of a message owns any of the allowed caller names.
+    async fn is_allowed_caller(&self, connection: &zbus::Connection, sender: &str) -> bool {
+        let Ok(dbus) = zbus::fdo::DBusProxy::new(connection).await else {
+            return false;
+        };
+        for name in &self.activations_allowed_callers {
+            let Ok(name) = zbus::names::BusName::try_from(name.as_str()) else {
+                continue;
+            };
+            let owner = with_timeout(self.call_timeout, async {
+                dbus.get_name_owner(name).await.map_err(zbus::Error::from)
+            })
+            .await;
+            if owner.is_ok_and(|owner| owner.as_str() == sender) {
+                return true;
+            }
+        }
+        false
     }
 }
 
-#[derive(Debug)]
-pub struct ReloadAll;
+/// Emit a change of the private mode property at `ctxt` after `timeout`.
+///
+/// If private mode got enabled again in the meantime the signal still carries the current value,
+/// so clients never see a stale state.
+async fn notify_private_mode_expired(ctxt: SignalContext<'static>, timeout: Duration) {
+    glib::timeout_future(timeout).await;
+    let result = async {
+        let iface = ctxt
+            .connection()
+            .object_server()
+            .interface::<_, ReloadAll>(ctxt.path())
+            .await?;
+        let iface = iface.get().await;
+        iface.private_mode_changed(&ctxt).await
+    }
+    .await;
+    if let Err(error) = result {
+        event!(
+            Level::WARN,
+            "Failed to notify about end of private mode: {error}"
+        );
+    }
+}
 
 #[interface(name = "de.swsnr.searchprovider.ReloadAll")]
 impl ReloadAll {
@@ -77,6 +240,169 @@ impl ReloadAll {
         &self,
         #[zbus(object_server)] server: &ObjectServer,
     ) -> zbus::fdo::Result<()> {
-        reload_all_on_object_server(server).await
+        self.reloadables.reload_all_on_object_server(server).await
+    }
+
+    /// Enable or disable private mode.
+    ///
+    /// While private mode is enabled, no provider returns any results.  Private mode ends
+    /// automatically after a timeout.
+    #[instrument(skip(self, ctxt))]
+    pub async fn set_private_mode(
+        &self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        enabled: bool,
+    ) -> zbus::fdo::Result<()> {
+        if enabled {
+            event!(
+                Level::INFO,
+                "Enabling private mode for {}s",
+                self.private_mode_timeout.as_secs()
+            );
+            self.private_mode
+                .enable(Instant::now(), self.private_mode_timeout);
+            // Tell clients when private mode ends by itself
+            glib::MainContext::ref_thread_default().spawn(
+                notify_private_mode_expired(ctxt.to_owned(), self.private_mode_timeout)
+                    .in_current_span(),
+            );
+        } else {
+            event!(Level::INFO, "Disabling private mode");
+            self.private_mode.disable();
+        }
+        self.private_mode_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    /// Get at most `limit` most recently activated projects.
+    ///
+    /// Return the desktop ID of the app, the project name and directory, and the time of
+    /// activation in seconds since the Unix epoch for each activation, most recent first.
+    ///
+    /// Only callers which own an allowed bus name may call this method; in private mode, return
+    /// no activations at all.
+    #[instrument(skip(self, connection, header))]
+    pub async fn get_recent_activations(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: Header<'_>,
+        limit: u32,
+    ) -> zbus::fdo::Result<Vec<(String, String, String, u64)>> {
+        let sender = header.sender().map(|s| s.to_string()).unwrap_or_default();
+        if !self.is_allowed_caller(connection, &sender).await {
+            event!(Level::WARN, "Denying activation history to {sender}");
+            return Err(zbus::fdo::Error::AccessDenied(format!(
+                "{sender} may not access the activation history"
+            )));
+        }
+        if self.private_mode.is_enabled() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .activations
+            .recent(limit as usize)
+            .into_iter()
+            .map(|a| (a.app_id, a.name, a.directory, a.timestamp))
+            .collect())
+    }
+
+    /// Whether private mode is currently enabled.
+    #[zbus(property)]
+    pub fn private_mode(&self) -> bool {
+        self.private_mode.is_enabled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cool_down_after_reload() {
+        let now = Instant::now();
+        assert!(!is_cooling_down(None, now));
+        assert!(is_cooling_down(Some(now), now));
+        assert!(is_cooling_down(
+            Some(now),
+            now + RELOAD_COOLDOWN - Duration::from_millis(1)
+        ));
+        assert!(!is_cooling_down(Some(now), now + RELOAD_COOLDOWN));
+    }
+
+    #[test]
+    fn notify_about_private_mode_changes() {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                context.block_on(async {
+                    let (server_stream, client_stream) =
+                        std::os::unix::net::UnixStream::pair().unwrap();
+                    let reload_all = ReloadAll::new(
+                        Reloadables::default(),
+                        PrivateMode::default(),
+                        Duration::from_millis(100),
+                    );
+                    let server = zbus::ConnectionBuilder::unix_stream(server_stream)
+                        .server(zbus::Guid::generate())
+                        .unwrap()
+                        .p2p()
+                        .internal_executor(false)
+                        .serve_at("/", reload_all)
+                        .unwrap()
+                        .build();
+                    let client = zbus::ConnectionBuilder::unix_stream(client_stream)
+                        .p2p()
+                        .internal_executor(false)
+                        .build();
+                    let (server, client) =
+                        futures_lite::future::try_zip(server, client).await.unwrap();
+                    for connection in [&server, &client] {
+                        let executor = connection.executor().clone();
+                        glib::MainContext::ref_thread_default().spawn_local(async move {
+                            loop {
+                                executor.tick().await;
+                            }
+                        });
+                    }
+
+                    let mut signals = zbus::MessageStream::for_match_rule(
+                        zbus::MatchRule::builder()
+                            .msg_type(zbus::message::Type::Signal)
+                            .member("PropertiesChanged")
+                            .unwrap()
+                            .build(),
+                        &client,
+                        None,
+                    )
+                    .await
+                    .unwrap();
+                    client
+                        .call_method(
+                            None::<()>,
+                            "/",
+                            Some("de.swsnr.searchprovider.ReloadAll"),
+                            "SetPrivateMode",
+                            &(true),
+                        )
+                        .await
+                        .unwrap();
+
+                    let mut values = Vec::new();
+                    while values.len() < 2 {
+                        let message = futures_lite::StreamExt::next(&mut signals)
+                            .await
+                            .unwrap()
+                            .unwrap();
+                        let (_, changed, _): (
+                            String,
+                            std::collections::HashMap<String, zbus::zvariant::OwnedValue>,
+                            Vec<String>,
+                        ) = message.body().deserialize().unwrap();
+                        values.push(bool::try_from(&changed["PrivateMode"]).unwrap());
+                    }
+                    assert_eq!(values, vec![true, false]);
+                })
+            })
+            .unwrap();
     }
 }
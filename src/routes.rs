@@ -0,0 +1,106 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Route projects to specific apps by their directory or project file.
+//!
+//! Users may open the same project with different IDEs, e.g. an Android app with IDEA and with
+//! Android Studio, but prefer one IDE for activation, regardless of the provider which found the
+//! project.
+
+use regex::Regex;
+use tracing::{event, Level};
+
+use crate::ignore::glob_to_regex;
+use crate::searchprovider::AppId;
+
+/// Routes from project directories and files to apps.
+#[derive(Debug, Default, Clone)]
+pub struct Routes {
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    routes: Vec<(Regex, AppId)>,
+}
+
+impl Routes {
+    /// Create routes from pairs of directory globs and desktop IDs.
+    ///
+    /// Expand a leading `~/` in globs to `home`; see [`glob_to_regex`] for globs.  Skip routes
+    /// with invalid desktop IDs.
+    pub fn new<'a, I>(home: &str, routes: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let routes = routes
+            .into_iter()
+            .filter_map(|(glob, desktop_id)| {
+                let app_id = match AppId::try_from(desktop_id) {
+                    Ok(app_id) => app_id,
+                    Err(error) => {
+                        event!(Level::WARN, "Skipping route for {glob}: {error}");
+                        return None;
+                    }
+                };
+                let glob = match glob.strip_prefix("~/") {
+                    Some(rest) => format!("{}/{rest}", home.trim_end_matches('/')),
+                    None => glob.to_string(),
+                };
+                // Globs only contain escaped literals and valid patterns
+                let regex = Regex::new(&glob_to_regex(&glob)).unwrap();
+                Some((regex, app_id))
+            })
+            .collect();
+        Self { routes }
+    }
+
+    /// Get the app to activate the project at `directory` with.
+    ///
+    /// If the project has a project `file`, e.g. a Rider solution, routes match this file as well,
+    /// so that globs can select projects by file name.
+    ///
+    /// Return the app of the first route matching `directory` or `file`, or `None` if no route
+    /// matches.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub fn route(&self, directory: &str, file: Option<&str>) -> Option<&AppId> {
+        self.routes
+            .iter()
+            .find(|(regex, _)| {
+                regex.is_match(directory) || file.is_some_and(|file| regex.is_match(file))
+            })
+            .map(|(_, app_id)| app_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn route_by_first_matching_glob() {
+        let routes = Routes::new(
+            "/home/foo/",
+            [
+                ("~/android/legacy-*", "jetbrains-idea.desktop"),
+                ("~/android/**", "android-studio.desktop"),
+                ("/srv/**/*.sln", "jetbrains-rider.desktop"),
+            ],
+        );
+        assert_eq!(
+            routes.route("/home/foo/android/legacy-app", None),
+            Some(&AppId::try_from("jetbrains-idea.desktop").unwrap())
+        );
+        assert_eq!(
+            routes.route("/home/foo/android/app/lib", None),
+            Some(&AppId::try_from("android-studio.desktop").unwrap())
+        );
+        assert_eq!(
+            routes.route("/srv/games", Some("/srv/games/MyGame.sln")),
+            Some(&AppId::try_from("jetbrains-rider.desktop").unwrap())
+        );
+        assert_eq!(routes.route("/srv/games", None), None);
+        assert_eq!(routes.route("/home/foo/android", None), None);
+        assert_eq!(routes.route("/home/bar/android/app", None), None);
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Query the scores of recent projects.
+//!
+//! This exposes the scorer of the running service, e.g. for extensions which reorder results on
+//! their own, or for regression tests of rankings.
+
+use tracing::{event, instrument, Level};
+use zbus::{interface, message::Header, ObjectServer};
+
+use crate::searchprovider::provider_at;
+
+/// Score recent projects of the search provider at the same object path.
+#[derive(Debug, Default)]
+pub struct Score;
+
+#[interface(name = "de.swsnr.searchprovider.Score")]
+impl Score {
+    /// Score the recent project at `project_path` for `terms`.
+    ///
+    /// Return zero if the project doesn't match `terms`, and fail if there's no recent project
+    /// at `project_path`.
+    #[instrument(skip(self, server, header))]
+    async fn score(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(header)] header: Header<'_>,
+        terms: Vec<&str>,
+        project_path: &str,
+    ) -> zbus::fdo::Result<f64> {
+        let interface = provider_at(server, &header).await?;
+        let mut provider = interface.get_mut().await;
+        event!(
+            Level::DEBUG,
+            "Scoring project of {} for {terms:?}",
+            provider.app().id()
+        );
+        provider.score_project(&terms, project_path)
+    }
+}
@@ -0,0 +1,763 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Scoring of projects against search terms.
+//!
+//! This module is pure and deliberately has no dependencies beyond `std`, to make it easy to
+//! test and fuzz.
+
+/// Whether `c` separates words in project names and paths.
+fn is_word_separator(c: char) -> bool {
+    matches!(c, '-' | '_' | '/' | '.') || c.is_whitespace()
+}
+
+/// Split `s` into lowercase words.
+///
+/// Split at word separators (see [`is_word_separator`]) and at camelCase boundaries, e.g.
+/// `gnome-search_providersJetbrains` becomes `gnome`, `search`, `providers` and `jetbrains`.
+/// A run of uppercase letters is kept together as a single word, so `XMLParser` becomes `xml` and
+/// `parser`.
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut previous: Option<char> = None;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_word_separator(c) {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            previous = None;
+            continue;
+        }
+        if let Some(p) = previous {
+            let is_boundary = ((p.is_lowercase() || p.is_numeric()) && c.is_uppercase())
+                || (p.is_uppercase()
+                    && c.is_uppercase()
+                    && chars.peek().is_some_and(|n| n.is_lowercase()));
+            if is_boundary && !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        }
+        current.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Remove all word separators from `s` and convert it to lowercase.
+///
+/// This lets `searchproviders` match `search-providers` or `SearchProviders`.
+fn squash(s: &str) -> String {
+    tokenize(s).concat()
+}
+
+/// Find the last occurrence of `term` in `text`, relative to the length of `text`.
+///
+/// Try a literal match first, and fall back to matching the squashed `term` against the squashed
+/// `text` (see [`squash`]).  Both `text` and `term` must already be lowercase.
+///
+/// We add 1 to the index to avoid returning zero if the term matches right at the beginning.
+/// Nothing matches an empty `text`.
+fn rfind_relative(text: &str, squashed_text: &str, term: &str) -> Option<f64> {
+    if text.is_empty() {
+        return None;
+    }
+    text.rfind(term)
+        .map(|index| (index + 1) as f64 / text.len() as f64)
+        .or_else(|| {
+            let squashed_term = squash(term);
+            if squashed_term.is_empty() {
+                None
+            } else {
+                squashed_text
+                    .rfind(&squashed_term)
+                    .map(|index| (index + 1) as f64 / squashed_text.len() as f64)
+            }
+        })
+}
+
+/// Match a `term` containing `/` against the components of `directory`.
+///
+/// The components of `term` must match consecutive components of `directory`, where the first
+/// component of `term` may match the end of a directory component, and the last the start of a
+/// directory component, e.g. `gh/md` matches `/home/foo/gh/mdcat`.  A leading or trailing `/`
+/// in `term` anchors the first or last component to a whole directory component.
+///
+/// Return the position of the rightmost match relative to the number of components, or `None`
+/// if `term` doesn't match.  Both `directory` and `term` must already be lowercase.
+fn match_path_term(directory: &str, term: &str) -> Option<f64> {
+    let components = directory
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    let segments = term
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let anchored_start = term.starts_with('/');
+    let anchored_end = term.ends_with('/');
+    let n = segments.len();
+    if n == 0 || components.len() < n {
+        return None;
+    }
+    (0..=components.len() - n).rev().find_map(|start| {
+        let is_match = segments
+            .iter()
+            .zip(&components[start..start + n])
+            .enumerate()
+            .all(|(k, (segment, component))| {
+                let open_start = k == 0 && !anchored_start;
+                let open_end = k == n - 1 && !anchored_end;
+                match (open_start, open_end) {
+                    (true, true) => component.contains(segment),
+                    (true, false) => component.ends_with(segment),
+                    (false, true) => component.starts_with(segment),
+                    (false, false) => component == segment,
+                }
+            });
+        is_match.then(|| (start + n) as f64 / components.len() as f64)
+    })
+}
+
+/// Whether `term` consists of path separators only, like `/`.
+fn is_separator_only(term: &str) -> bool {
+    !term.is_empty() && term.chars().all(|c| c == '/')
+}
+
+/// The base score for projects whose name matches all terms.
+const NAME_SCORE: f64 = 10.0;
+
+/// The components of the score of a project.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Score {
+    /// The base score for matching all terms against the name, see [`NAME_SCORE`].
+    pub name_score: f64,
+    /// The score for matching terms against the directory.
+    pub directory_score: f64,
+    /// The score for matching terms containing `/` against components of the directory.
+    pub path_score: f64,
+    /// The bonus for terms which match whole words of the name or the directory.
+    pub word_bonus: f64,
+    /// The bonus for terms which match entries of the directory.
+    pub entry_bonus: f64,
+}
+
+impl Score {
+    /// The total score.
+    pub fn total(&self) -> f64 {
+        self.directory_score
+            + self.name_score
+            + self.path_score
+            + self.word_bonus
+            + self.entry_bonus
+    }
+}
+
+/// Calculate how well a project with the given `name` and `directory` matches all `terms`.
+///
+/// If all terms match the `name` of the project, the project receives a base score of 10, plus
+/// the highest score a project could possibly get from matching terms against its directory
+/// alone, so that name matches always rank above directory matches, regardless of the number of
+/// terms.
+/// If all terms match the `directory` of the project, the project gets scored for each
+/// term according to how far right the term appears in the directory, under the assumption that
+/// the right most part of a directory path is the most specific.
+///
+/// Terms also match across word separators and camelCase boundaries, i.e. `searchproviders`
+/// matches `gnome-search-providers-jetbrains`.  If the project matches at all, every term which
+/// matches a whole word of the name or the directory adds 1 to the score, to rank whole-word
+/// matches above mere substring matches.
+///
+/// Terms containing `/` only filter by directory (see [`match_path_term`]): The project must
+/// match all of these, and scores for each according to how far right the term appears in the
+/// directory, but these terms never match the name.  This lets users disambiguate projects of
+/// the same name with terms like `gh/mdcat`.  We ignore terms of separators only, like `/`,
+/// which users type while starting a path term.
+///
+/// All matches are done on the lowercase text, i.e. case insensitve.
+pub fn score(name: &str, directory: &str, terms: &[&str]) -> f64 {
+    explain(name, directory, terms).total()
+}
+
+/// Calculate the components of the [`score`] of a project with `name` and `directory`.
+///
+/// Return all-zero components if the project doesn't match `terms`.
+pub fn explain(name: &str, directory: &str, terms: &[&str]) -> Score {
+    let (path_terms, terms): (Vec<_>, Vec<_>) = terms
+        .iter()
+        // Terms of separators only, e.g. while typing a path term, don't filter anything
+        .filter(|term| !is_separator_only(term))
+        .map(|term| term.to_lowercase())
+        .partition(|term| term.contains('/'));
+    let name_tokens = tokenize(name);
+    let name = name.to_lowercase();
+    let squashed_name = name_tokens.concat();
+    let directory_tokens = tokenize(directory);
+    let directory = directory.to_lowercase();
+    let squashed_directory = directory_tokens.concat();
+
+    let mut path_score = 0.0;
+    for term in &path_terms {
+        match match_path_term(&directory, term) {
+            Some(score) => path_score += score,
+            None => return Score::default(),
+        }
+    }
+
+    let directory_score = terms
+        .iter()
+        .try_fold(0.0, |score, term| {
+            rfind_relative(&directory, &squashed_directory, term).map(|s| score + s)
+        })
+        .unwrap_or(0.0);
+    let name_score = if !terms.is_empty()
+        && terms
+            .iter()
+            .all(|term| rfind_relative(&name, &squashed_name, term).is_some())
+    {
+        // Every term adds at most 1 for its position in the directory and 1 for a whole word
+        // match; every path term adds at most 1 for its position in the directory.
+        NAME_SCORE + 2.0 * terms.len() as f64 + path_terms.len() as f64
+    } else {
+        0.0
+    };
+    if 0.0 < directory_score + name_score || (terms.is_empty() && 0.0 < path_score) {
+        let whole_words = terms
+            .iter()
+            .filter(|term| name_tokens.contains(term) || directory_tokens.contains(term))
+            .count();
+        Score {
+            name_score,
+            directory_score,
+            path_score,
+            word_bonus: whole_words as f64,
+            entry_bonus: 0.0,
+        }
+    } else {
+        Score::default()
+    }
+}
+
+/// Calculate how well a project matches all `terms`, also considering the `entries` of its
+/// directory.
+///
+/// If the project matches all terms by name and directory, return the [`score`] for all terms.
+/// Otherwise, let terms which start the name of any entry match the project, if all remaining
+/// terms match the project, and add a little bonus for each of these terms.  Entries must already
+/// be lowercase.
+///
+/// This lets `mdcat docs` find a project named `mdcat` with a `docs` directory.  At least one term
+/// must match the name or directory of the project.
+///
+/// Return the components of the score, which are all zero if the project doesn't match `terms`.
+pub fn explain_with_entries(
+    name: &str,
+    directory: &str,
+    entries: &[String],
+    terms: &[&str],
+) -> Score {
+    let score = explain(name, directory, terms);
+    if 0.0 < score.total() || entries.is_empty() {
+        return score;
+    }
+    let (entry_terms, other_terms): (Vec<&str>, Vec<&str>) = terms.iter().partition(|term| {
+        let term = term.to_lowercase();
+        !term.is_empty() && entries.iter().any(|entry| entry.starts_with(&term))
+    });
+    let score = if entry_terms.is_empty() || other_terms.is_empty() {
+        Score::default()
+    } else {
+        explain(name, directory, &other_terms)
+    };
+    if 0.0 < score.total() {
+        Score {
+            entry_bonus: 0.5 * entry_terms.len() as f64,
+            ..score
+        }
+    } else {
+        Score::default()
+    }
+}
+
+/// Get all `terms` which do not match the project with `name` and `directory` by themselves.
+///
+/// These terms matched the project by its entries, if at all (see [`explain_with_entries`]), or
+/// the user typed them for another purpose, e.g. to refer to a file within the project.
+#[cfg_attr(not(feature = "launch"), allow(dead_code))]
+pub fn residual_terms<'a>(name: &str, directory: &str, terms: &[&'a str]) -> Vec<&'a str> {
+    terms
+        .iter()
+        .filter(|term| score(name, directory, &[term]) <= 0.0)
+        .copied()
+        .collect()
+}
+
+/// A search result with its score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoredResult<'a> {
+    /// The ID of the result.
+    pub id: &'a str,
+    /// The name of the result.
+    pub name: &'a str,
+    /// The score of the result, see [`score`].
+    pub score: f64,
+    /// When the project of the result was last opened, in milliseconds since the Unix epoch.
+    pub opened_at: Option<i64>,
+}
+
+/// Rank scored `results`, and return their IDs with their scores in order.
+///
+/// Rank by descending score first, then more recently opened projects first, then by the
+/// `collation_key` of their names, and finally by ID, so that the ranking is total and doesn't
+/// depend on the order of `results`.
+///
+/// Callers pass a key which collates names according to the rules of the current locale; this
+/// module has no business with locales itself.
+///
+/// Compare scores exactly; scores which differ only by tiny fractions, e.g. from the relative
+/// position of a match in long paths, are not ties.
+pub fn rank_with_scores<'a, K: Ord>(
+    results: Vec<ScoredResult<'a>>,
+    collation_key: impl Fn(&str) -> K,
+) -> Vec<(&'a str, f64)> {
+    let mut results = results
+        .into_iter()
+        .map(|result| (collation_key(result.name), result))
+        .collect::<Vec<_>>();
+    results.sort_unstable_by(|(key_a, a), (key_b, b)| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| b.opened_at.cmp(&a.opened_at))
+            .then_with(|| key_a.cmp(key_b))
+            .then_with(|| a.id.cmp(b.id))
+    });
+    results
+        .into_iter()
+        .map(|(_, result)| (result.id, result.score))
+        .collect()
+}
+
+/// Rank scored `results`, and return their IDs in order.
+///
+/// See [`rank_with_scores`].
+pub fn rank<'a, K: Ord>(
+    results: Vec<ScoredResult<'a>>,
+    collation_key: impl Fn(&str) -> K,
+) -> Vec<&'a str> {
+    rank_with_scores(results, collation_key)
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// The minimum ratio of the scores around a gap to cut results at.
+///
+/// See [`cut_at_score_gap`].
+const SCORE_GAP_RATIO: f64 = 2.0;
+
+/// Find where to cut ranked results at the largest gap in their `scores`.
+///
+/// `scores` must be positive, and sorted in descending order, as returned by
+/// [`rank_with_scores`].  Look for the largest relative drop between consecutive scores after
+/// the first `min_results` scores, where the next score is at most half of the previous score,
+/// e.g. between projects whose name matches and projects which match only by their directory.
+///
+/// Return the number of results before this drop, or the number of all results if there's no
+/// such drop.  Among equal drops take the last one, to keep more results.
+pub fn cut_at_score_gap(scores: &[f64], min_results: usize) -> usize {
+    (min_results.max(1)..scores.len())
+        .map(|index| (index, scores[index - 1] / scores[index]))
+        .filter(|(_, ratio)| SCORE_GAP_RATIO <= *ratio)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(scores.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn tokenize_separators_and_camel_case() {
+        assert_eq!(
+            tokenize("gnome-search_providers/Jetbrains"),
+            vec!["gnome", "search", "providers", "jetbrains"]
+        );
+        assert_eq!(
+            tokenize("searchProviderJetbrains"),
+            vec!["search", "provider", "jetbrains"]
+        );
+        assert_eq!(tokenize("XMLParser2Go"), vec!["xml", "parser2", "go"]);
+        assert_eq!(tokenize("--foo  bar--"), vec!["foo", "bar"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn score_matches_across_word_separators() {
+        let name = "gnome-search-providers-jetbrains";
+        let directory = "/home/foo/Code/gnome-search-providers-jetbrains";
+        assert!(10.0 < score(name, directory, &["searchproviders"]));
+        assert!(10.0 < score(name, directory, &["SearchProviders"]));
+        assert_eq!(score(name, directory, &["mdcat"]), 0.0);
+    }
+
+    #[test]
+    fn score_ranks_whole_words_above_substrings() {
+        assert!(
+            score("researcher", "/home/foo/researcher", &["search"])
+                < score(
+                    "gnome-search-providers",
+                    "/home/foo/gnome-search-providers",
+                    &["search"]
+                )
+        );
+    }
+
+    #[test]
+    fn score_path_terms_against_directory_components() {
+        let directory = "/home/foo/Code/gh/mdcat";
+        assert!(0.0 < score("mdcat", directory, &["gh/mdcat"]));
+        assert!(0.0 < score("mdcat", directory, &["gh/md"]));
+        assert!(0.0 < score("mdcat", directory, &["code/"]));
+        assert!(0.0 < score("mdcat", directory, &["/gh/", "mdcat"]));
+        assert_eq!(score("mdcat", directory, &["gl/mdcat"]), 0.0);
+        assert_eq!(score("mdcat", directory, &["/g/"]), 0.0);
+        assert_eq!(score("mdcat", directory, &["gh/", "bar"]), 0.0);
+        assert_eq!(
+            score("mdcat", directory, &["mdcat", "/"]),
+            score("mdcat", directory, &["mdcat"])
+        );
+        assert_eq!(
+            score("mdcat", directory, &["//", "gh/"]),
+            score("mdcat", directory, &["gh/"])
+        );
+        assert_eq!(score("mdcat", directory, &["/"]), 0.0);
+        assert!(
+            score("mdcat", "/home/foo/Code/gl/mdcat", &["gh/", "mdcat"])
+                < score("mdcat", directory, &["gh/", "mdcat"])
+        );
+    }
+
+    #[test]
+    fn score_without_terms() {
+        assert_eq!(score("mdcat", "/home/foo/mdcat", &[]), 0.0);
+        assert_eq!(score("", "", &[]), 0.0);
+        assert_eq!(score("", "", &[""]), 0.0);
+    }
+
+    #[test]
+    fn score_terms_longer_than_directory() {
+        assert_eq!(score("mdcat", "/md", &["mdcat-is-long"]), 0.0);
+        assert!(10.0 <= score("gnome-search-providers", "/g", &["gnomesearchproviders"]));
+        assert_eq!(score("mdcat", "/md", &["/md/cat/"]), 0.0);
+    }
+
+    #[test]
+    fn score_requires_all_terms_to_match() {
+        let directory = "/home/foo/Code/mdcat";
+        assert_eq!(score("mdcat", directory, &["md", "xyz"]), 0.0);
+        // "code" matches only the directory, so the name doesn't match all terms
+        let split = score("mdcat", directory, &["code", "md"]);
+        assert!(0.0 < split && split < 10.0);
+        assert!(split < score("mdcat", directory, &["cat", "md"]));
+    }
+
+    #[test]
+    fn score_name_match_above_directory_match_on_short_paths() {
+        // Many terms which all match whole words in a short directory used to add up to more
+        // than a name match.
+        let terms = ["a", "b", "c", "d", "e", "f", "g"];
+        let directory_only = score("xyz", "/a-b-c-d-e-f-g", &terms);
+        let name_only = score("gfedcba", "/xyz", &terms);
+        assert!(0.0 < directory_only);
+        assert!(directory_only < name_only);
+    }
+
+    #[test]
+    fn score_with_entries_of_directory() {
+        let directory = "/home/foo/Code/gh/mdcat";
+        let entries = ["docs".to_string(), "src".to_string()];
+        assert_eq!(score("mdcat", directory, &["mdcat", "docs"]), 0.0);
+        assert!(
+            10.0 < explain_with_entries("mdcat", directory, &entries, &["mdcat", "Doc"]).total()
+        );
+        assert_eq!(
+            explain_with_entries("mdcat", directory, &entries, &["mdcat"]).total(),
+            score("mdcat", directory, &["mdcat"])
+        );
+        assert_eq!(
+            explain_with_entries("mdcat", directory, &entries, &["docs"]).total(),
+            0.0
+        );
+        assert_eq!(
+            explain_with_entries("mdcat", directory, &entries, &["mdcat", "docs", "xyz"]).total(),
+            0.0
+        );
+        assert_eq!(
+            explain_with_entries("mdcat", directory, &[], &["mdcat", "docs"]).total(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn explain_score_components() {
+        let directory = "/home/foo/Code/gh/mdcat";
+        let entries = ["docs".to_string()];
+        let score = explain_with_entries("mdcat", directory, &entries, &["gh/", "mdcat", "docs"]);
+        assert_eq!(score.name_score, NAME_SCORE + 3.0);
+        assert!(0.0 < score.directory_score);
+        assert!(0.0 < score.path_score);
+        assert_eq!(score.word_bonus, 1.0);
+        assert_eq!(score.entry_bonus, 0.5);
+        assert_eq!(explain("mdcat", directory, &["xyz"]), Score::default());
+    }
+
+    #[test]
+    fn residual_terms_of_project() {
+        assert_eq!(
+            residual_terms("mdcat", "/home/foo/gh/mdcat", &["md", "render.rs", "gh/"]),
+            vec!["render.rs"]
+        );
+        assert!(residual_terms("mdcat", "/home/foo/gh/mdcat", &["mdcat"]).is_empty());
+    }
+
+    #[test]
+    fn rank_ties_by_name_before_id() {
+        let result = |id, name| ScoredResult {
+            id,
+            name,
+            score: 1.0,
+            opened_at: Some(42),
+        };
+        assert_eq!(
+            rank(
+                vec![
+                    result("a", "Zeta"),
+                    result("b", "Alpha"),
+                    result("c", "Alpha")
+                ],
+                str::to_owned
+            ),
+            vec!["b", "c", "a"]
+        );
+        // Collate names with the given key
+        let results = vec![result("a", "alpha"), result("b", "Beta")];
+        assert_eq!(rank(results.clone(), str::to_owned), vec!["b", "a"]);
+        assert_eq!(rank(results, str::to_lowercase), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn rank_near_ties_by_exact_score() {
+        let result = |id, score, opened_at| ScoredResult {
+            id,
+            name: "mdcat",
+            score,
+            opened_at: Some(opened_at),
+        };
+        // These scores collapse to the same value when scaled to three decimal places, and the
+        // lower scores belong to more recently opened projects.
+        assert_eq!(
+            rank(
+                vec![
+                    result("a", 10.0004, 1),
+                    result("b", 10.0001, 2),
+                    result("c", 10.0001 + 1e-12, 3),
+                    result("d", 10.0, 4),
+                    result("e", 10.0001, 5),
+                ],
+                str::to_owned
+            ),
+            vec!["a", "c", "e", "b", "d"]
+        );
+        // Adjacent floating point values still rank apart
+        let score = 10.5_f64;
+        let next = f64::from_bits(score.to_bits() + 1);
+        assert_eq!(
+            rank(
+                vec![result("a", score, 2), result("b", next, 1)],
+                str::to_owned
+            ),
+            vec!["b", "a"]
+        );
+    }
+
+    #[test]
+    fn cut_results_at_largest_score_gap() {
+        assert_eq!(cut_at_score_gap(&[], 1), 0);
+        assert_eq!(cut_at_score_gap(&[10.0], 1), 1);
+        // No gap large enough
+        assert_eq!(cut_at_score_gap(&[10.5, 10.4, 6.0, 3.5], 1), 4);
+        // Cut at the largest of two gaps
+        assert_eq!(cut_at_score_gap(&[12.0, 10.5, 1.0, 0.9, 0.2], 1), 2);
+        assert_eq!(cut_at_score_gap(&[12.0, 5.0, 4.0, 0.1], 1), 3);
+        // Keep at least the given number of results
+        assert_eq!(cut_at_score_gap(&[12.0, 1.0, 0.9, 0.2], 2), 3);
+        assert_eq!(cut_at_score_gap(&[12.0, 1.0, 0.9, 0.8], 2), 4);
+        assert_eq!(cut_at_score_gap(&[12.0, 1.0], 5), 2);
+        // Take the last of equal gaps
+        assert_eq!(cut_at_score_gap(&[8.0, 2.0, 0.5], 1), 2);
+    }
+
+    #[test]
+    fn rank_returns_scores() {
+        let result = |id, score| ScoredResult {
+            id,
+            name: id,
+            score,
+            opened_at: None,
+        };
+        assert_eq!(
+            rank_with_scores(
+                vec![result("a", 0.5), result("b", 2.0), result("c", 1.0)],
+                str::to_owned
+            ),
+            vec![("b", 2.0), ("c", 1.0), ("a", 0.5)]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn score_is_finite_and_not_negative(
+            name in "\\PC*",
+            directory in "\\PC*",
+            terms in prop::collection::vec("\\PC*", 0..4),
+        ) {
+            let terms = terms.iter().map(String::as_str).collect::<Vec<_>>();
+            let score = score(&name, &directory, &terms);
+            prop_assert!(score.is_finite());
+            prop_assert!(0.0 <= score);
+        }
+
+        #[test]
+        fn score_ignores_case_of_terms(
+            name in "[a-zA-Z_-]{1,20}",
+            directory in "(/[a-zA-Z_-]{1,10}){1,4}",
+            terms in prop::collection::vec("[a-z]{1,5}", 1..4),
+        ) {
+            let upper = terms.iter().map(|t| t.to_uppercase()).collect::<Vec<_>>();
+            let terms = terms.iter().map(String::as_str).collect::<Vec<_>>();
+            let upper = upper.iter().map(String::as_str).collect::<Vec<_>>();
+            prop_assert_eq!(score(&name, &directory, &terms), score(&name, &directory, &upper));
+        }
+
+        #[test]
+        fn adding_a_matching_term_never_decreases_score(
+            name in "[a-z]{1,10}",
+            parent in "(/[a-z-]{1,10}){0,3}",
+            terms in prop::collection::vec("[a-z]{1,5}", 0..4),
+            start in 0usize..10,
+            len in 1usize..10,
+        ) {
+            // Pick a term from the name, which also matches the directory.
+            let start = start.min(name.len() - 1);
+            let end = (start + len).min(name.len());
+            let term = &name[start..end];
+            let directory = format!("{parent}/{name}");
+            let terms = terms.iter().map(String::as_str).collect::<Vec<_>>();
+            let mut more_terms = terms.clone();
+            more_terms.push(term);
+            prop_assert!(score(&name, &directory, &terms) <= score(&name, &directory, &more_terms));
+        }
+
+        #[test]
+        fn rank_is_independent_of_order(
+            (results, shuffled) in prop::collection::btree_map(
+                "[a-z]{1,8}",
+                (0u8..4, prop::option::of(0i64..3)),
+                0..20,
+            )
+            .prop_map(|results| results.into_iter().collect::<Vec<_>>())
+            .prop_flat_map(|results| (Just(results.clone()), Just(results).prop_shuffle())),
+        ) {
+            // Draw scores from a small set of values to get plenty of ties.
+            fn scored(results: &[(String, (u8, Option<i64>))]) -> Vec<ScoredResult<'_>> {
+                results
+                    .iter()
+                    .map(|(id, (score, opened_at))| ScoredResult {
+                        id: id.as_str(),
+                        name: &id[..1],
+                        score: f64::from(*score) / 2.0,
+                        opened_at: *opened_at,
+                    })
+                    .collect::<Vec<_>>()
+            }
+            let ranked = rank(scored(&results), str::to_owned);
+            prop_assert_eq!(&ranked, &rank(scored(&shuffled), str::to_owned));
+            prop_assert_eq!(ranked.len(), results.len());
+            let by_id = scored(&results)
+                .into_iter()
+                .map(|result| (result.id, result))
+                .collect::<std::collections::HashMap<_, _>>();
+            for pair in ranked.windows(2) {
+                let (a, b) = (by_id[pair[0]], by_id[pair[1]]);
+                prop_assert!(b.score <= a.score);
+                if a.score == b.score {
+                    prop_assert!(b.opened_at <= a.opened_at);
+                    if a.opened_at == b.opened_at {
+                        prop_assert!(a.id < b.id);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn rank_orders_by_descending_score(
+            scores in prop::collection::vec(0.0f64..20.0, 0..20),
+        ) {
+            let ids = (0..scores.len()).map(|i| i.to_string()).collect::<Vec<_>>();
+            let results = ids
+                .iter()
+                .zip(&scores)
+                .map(|(id, score)| ScoredResult {
+                    id,
+                    name: "project",
+                    score: *score,
+                    // Older projects get higher scores, to make sure recency never overrides
+                    // the score.
+                    opened_at: Some(-(*score * 1e9) as i64),
+                })
+                .collect::<Vec<_>>();
+            let ranked = rank_with_scores(results, str::to_owned);
+            prop_assert_eq!(ranked.len(), scores.len());
+            for pair in ranked.windows(2) {
+                prop_assert!(pair[1].1 <= pair[0].1);
+            }
+        }
+
+        #[test]
+        fn name_matches_rank_above_directory_matches(
+            other_name in "[0-9]{1,10}",
+            parent in "(/[a-z-]{1,10}){0,3}",
+            terms in prop::collection::vec("[a-z]{1,3}", 1..8),
+        ) {
+            // A project whose name matches all terms, but not its directory, and a project whose
+            // directory matches all terms as whole words, but not its name.
+            let name = terms.concat();
+            let directory = format!("{parent}/{}", terms.join("-"));
+            let terms = terms.iter().map(String::as_str).collect::<Vec<_>>();
+            let directory_score = score(&other_name, &directory, &terms);
+            prop_assert!(0.0 < directory_score);
+            prop_assert!(directory_score < score(&name, "/", &terms));
+        }
+
+        #[test]
+        fn name_matches_score_at_least_10(
+            name in "[a-z]{1,10}",
+            directory in "(/[a-z]{1,10}){1,4}",
+            start in 0usize..10,
+            len in 1usize..10,
+        ) {
+            let start = start.min(name.len() - 1);
+            let end = (start + len).min(name.len());
+            prop_assert!(10.0 <= score(&name, &directory, &[&name[start..end]]));
+        }
+    }
+}
@@ -6,22 +6,55 @@
 
 //! The search provider service for recent projects in Jetbrains products.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use elementtree::Element;
 use gio::prelude::*;
 use indexmap::IndexMap;
-use tracing::{event, instrument, Level, Span};
+#[cfg(feature = "launch")]
+use tracing::Span;
+use tracing::{event, instrument, Level};
 use tracing_futures::Instrument;
-use zbus::{interface, zvariant};
+use zbus::object_server::InterfaceRef;
+use zbus::{interface, message::Header, zvariant, ObjectServer};
 
-use crate::config::ConfigLocation;
-use crate::launch::create_launch_context;
+use crate::activate::ActivationOptions;
+use crate::appinfo;
+use crate::calltrace::CallTrace;
+use crate::config::{find_data_directory_name, ConfigLocation};
+#[cfg(feature = "launch")]
+use crate::contents;
+use crate::contents::{index_directories, ContentIndex, IndexedDirectory};
+use crate::history::ActivationHistory;
+use crate::hook::ActivationHook;
+use crate::icons::Icons;
+use crate::ignore::IgnoreList;
+use crate::latency::{LatencyTracker, REPROBE_INTERVAL};
+#[cfg(feature = "launch")]
+use crate::launch::{
+    create_launch_context, refresh_session_environment, scrub_environment, LaunchProvenance,
+};
+use crate::metrics;
+use crate::power::PowerSaver;
+use crate::private::PrivateMode;
+use crate::recents::{parse_recent_jetbrains_projects, Limits};
+use crate::redact;
+use crate::reload::{Reloadable, RELOAD_INTERVAL};
+use crate::routes::Routes;
+use crate::scoring;
+use crate::shellsettings::DisabledProviders;
+use crate::tracebatch::TraceBatch;
+#[cfg(feature = "launch")]
+use crate::trash;
+use crate::trash::{trashed_items, TrashedProject};
+use crate::watch::ProjectsWatcher;
+use crate::worktree;
 
 /// The desktop ID of an app.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -46,15 +79,59 @@ impl TryFrom<&AppId> for gio::DesktopAppInfo {
     }
 }
 
-impl From<String> for AppId {
-    fn from(v: String) -> Self {
-        Self(v)
+/// Why a string is not a valid desktop ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppIdError {
+    /// The desktop ID is empty, or has nothing before `.desktop`.
+    Empty,
+    /// The desktop ID contains a path separator; desktop IDs use `-` instead.
+    PathSeparator(String),
+    /// The desktop ID contains whitespace or a control character.
+    InvalidCharacter(String, char),
+}
+
+impl Display for AppIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppIdError::Empty => write!(f, "Empty desktop ID"),
+            AppIdError::PathSeparator(id) => {
+                write!(f, "Desktop ID {id:?} contains a path separator")
+            }
+            AppIdError::InvalidCharacter(id, c) => {
+                write!(f, "Desktop ID {id:?} contains invalid character {c:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AppIdError {}
+
+impl FromStr for AppId {
+    type Err = AppIdError;
+
+    /// Parse and normalize a desktop ID.
+    ///
+    /// Append `.desktop` if `s` lacks it, e.g. `jetbrains-idea` becomes `jetbrains-idea.desktop`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_end_matches(".desktop").is_empty() {
+            Err(AppIdError::Empty)
+        } else if s.contains('/') {
+            Err(AppIdError::PathSeparator(s.to_string()))
+        } else if let Some(c) = s.chars().find(|c| c.is_whitespace() || c.is_control()) {
+            Err(AppIdError::InvalidCharacter(s.to_string(), c))
+        } else if s.ends_with(".desktop") {
+            Ok(Self(s.to_string()))
+        } else {
+            Ok(Self(format!("{s}.desktop")))
+        }
     }
 }
 
-impl From<&str> for AppId {
-    fn from(v: &str) -> Self {
-        v.to_string().into()
+impl TryFrom<&str> for AppId {
+    type Error = AppIdError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
 
@@ -69,6 +146,8 @@ impl From<&gio::DesktopAppInfo> for AppId {
 pub struct App {
     /// The ID of this app
     id: AppId,
+    /// The human readable name of this app
+    name: String,
     /// The icon to use for this app
     icon: String,
 }
@@ -79,6 +158,11 @@ impl App {
         &self.id
     }
 
+    /// The human readable name of this app.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// The icon of this app.
     pub fn icon(&self) -> &str {
         &self.icon
@@ -89,6 +173,7 @@ impl From<gio::DesktopAppInfo> for App {
     fn from(app: gio::DesktopAppInfo) -> Self {
         Self {
             id: (&app).into(),
+            name: app.name().to_string(),
             icon: IconExt::to_string(&app.icon().unwrap())
                 .unwrap()
                 .to_string(),
@@ -96,40 +181,6 @@ impl From<gio::DesktopAppInfo> for App {
     }
 }
 
-/// Read paths of all recent projects from the given `reader`.
-fn parse_recent_jetbrains_projects<R: Read>(home: &str, reader: R) -> Result<Vec<String>> {
-    let element = Element::from_reader(reader)?;
-    event!(Level::TRACE, "Finding projects in {:?}", element);
-
-    let projects = element
-        .find_all("component")
-        .find(|e| {
-            e.get_attr("name") == Some("RecentProjectsManager")
-                || e.get_attr("name") == Some("RiderRecentProjectsManager")
-        })
-        .and_then(|comp| {
-            comp.find_all("option")
-                .find(|e| e.get_attr("name") == Some("additionalInfo"))
-        })
-        .and_then(|opt| opt.find("map"))
-        .map(|map| {
-            map.find_all("entry")
-                .filter_map(|entry| entry.get_attr("key"))
-                .map(|key| key.replace("$USER_HOME$", home))
-                .collect()
-        })
-        .unwrap_or_default();
-
-    event!(
-        Level::TRACE,
-        "Parsed projects {:?} from {:?}",
-        projects,
-        element
-    );
-
-    Ok(projects)
-}
-
 /// Try to read the name of a Jetbrains project from the `name` file of the given project directory.
 ///
 /// Look for a `name` file in the `.idea` sub-directory and return the contents of this file.
@@ -138,35 +189,178 @@ fn read_name_from_file<P: AsRef<Path>>(path: P) -> Result<String> {
     event!(
         Level::TRACE,
         "Trying to read name from {}",
-        name_file.display()
+        redact::path(&name_file.to_string_lossy())
     );
     let contents = std::fs::read_to_string(&name_file)
         .with_context(|| format!("Failed to read project name from {}", name_file.display()))?;
     Ok(contents.trim().to_string())
 }
 
+/// Names of projects from `.idea/.name` files, by project directory.
+///
+/// Every entry remembers the modification time of the `.idea` directory when we read the name;
+/// we only read the name again after the `.idea` directory changed, i.e. after the IDE created,
+/// replaced or removed any file in it.
+#[derive(Debug, Default)]
+pub struct ProjectNameCache(HashMap<PathBuf, (SystemTime, Option<String>)>);
+
+impl ProjectNameCache {
+    /// Get the name of the project in `directory` from its `.idea/.name` file.
+    ///
+    /// Return `None` if the project has no such file, or if we fail to read it.
+    fn read_name(&mut self, directory: &Path) -> Option<String> {
+        let modified = std::fs::metadata(directory.join(".idea")).and_then(|m| m.modified());
+        let Ok(modified) = modified else {
+            // Not an IDEA project, so there's nothing to cache
+            self.0.remove(directory);
+            return None;
+        };
+        if let Some((cached_modified, name)) = self.0.get(directory) {
+            if *cached_modified == modified {
+                event!(
+                    Level::TRACE,
+                    "Using cached name of {}",
+                    redact::path(&directory.to_string_lossy())
+                );
+                return name.clone();
+            }
+        }
+        let name = read_name_from_file(directory)
+            .map_err(|error| {
+                event!(
+                    Level::DEBUG,
+                    "Failed to read project name from file {error:#}"
+                );
+            })
+            .ok();
+        self.0
+            .insert(directory.to_path_buf(), (modified, name.clone()));
+        name
+    }
+
+    /// Forget names of all projects whose directory is not in `directories`.
+    fn retain<'a, I: IntoIterator<Item = &'a str>>(&mut self, directories: I) {
+        let directories = directories
+            .into_iter()
+            .map(Path::new)
+            .collect::<HashSet<_>>();
+        self.0
+            .retain(|directory, _| directories.contains(directory.as_path()));
+    }
+
+    /// Add the size of this cache to `size`.
+    fn add_size(&self, size: &mut CacheSize) {
+        for (directory, (_, name)) in &self.0 {
+            size.add_entry([
+                directory.to_str().unwrap_or_default(),
+                name.as_deref().unwrap_or_default(),
+            ]);
+        }
+    }
+}
+
 /// Get the name of the Jetbrains product at the given path.
 ///
-/// Look for a `name` file in the `.idea` sub-directory; if that file does not exist
-/// or cannot be read take the file name of `path`, and ultimately return `None` if
-/// the name cannot be determined.
-fn get_project_name<P: AsRef<Path>>(path: P) -> Option<String> {
-    match read_name_from_file(path.as_ref()) {
-        Ok(name) => Some(name),
-        Err(error) => {
-            event!(
-                Level::DEBUG,
-                "Failed to read project name from file {:#}; falling back to file name of {}",
-                error,
-                path.as_ref().display()
-            );
-            path.as_ref()
-                .file_name()
-                .map(|name| name.to_string_lossy().to_string())
+/// Look for a `name` file in the `.idea` sub-directory, through the `names` cache; if that file
+/// does not exist or cannot be read take the file name of `path`, and ultimately return `None`
+/// if the name cannot be determined.
+fn get_project_name<P: AsRef<Path>>(path: P, names: &mut ProjectNameCache) -> Option<String> {
+    names.read_name(path.as_ref()).or_else(|| {
+        event!(
+            Level::DEBUG,
+            "No project name in file; falling back to file name of {}",
+            redact::path(&path.as_ref().to_string_lossy())
+        );
+        path.as_ref()
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    })
+}
+
+/// Where a recent project lives.
+#[derive(Debug, PartialEq, Eq)]
+struct ProjectLocation {
+    /// The human readable project name.
+    name: String,
+    /// The project directory.
+    directory: String,
+    /// The project file, if the recent project refers to a file instead of a directory.
+    file: Option<String>,
+}
+
+impl ProjectLocation {
+    /// Move this location from below `from` to below `to`.
+    ///
+    /// Leave paths which are not below `from` as they are.
+    fn relocate(self, from: &str, to: &str) -> Self {
+        let relocate = |path: String| match path.strip_prefix(from) {
+            Some(rest) => format!("{to}{rest}"),
+            None => path,
+        };
+        Self {
+            name: self.name,
+            directory: relocate(self.directory),
+            file: self.file.map(relocate),
         }
     }
 }
 
+/// Locate the recent project at `path`.
+///
+/// Some IDEs, notably Rider and MPS, refer to project files (e.g. `.sln` files) instead of
+/// project directories; for these take the parent directory as project directory, and the name
+/// of the file without extension as project name.  For directories, see [`get_project_name`].
+///
+/// Return `None` if `path` does not exist anymore, or if we fail to determine a name.
+fn locate_project(path: &str, names: &mut ProjectNameCache) -> Option<ProjectLocation> {
+    let path = Path::new(path);
+    if path.is_file() {
+        Some(ProjectLocation {
+            name: path.file_stem()?.to_string_lossy().to_string(),
+            directory: path.parent()?.to_str()?.to_string(),
+            file: Some(path.to_str()?.to_string()),
+        })
+    } else if path.is_dir() {
+        Some(ProjectLocation {
+            name: get_project_name(path, names)?,
+            directory: path.to_str()?.to_string(),
+            file: None,
+        })
+    } else {
+        event!(
+            Level::DEBUG,
+            "{} does not exist",
+            redact::path(&path.to_string_lossy())
+        );
+        None
+    }
+}
+
+/// Extensions of project files which IDEs refer to instead of project directories.
+const PROJECT_FILE_EXTENSIONS: &[&str] = &["sln", "slnf", "uproject", "ipr", "mpr"];
+
+/// Locate the recent project at a `path` which does not exist, or which we can't access.
+///
+/// Like the IDEs themselves we keep recent project directories which don't exist, e.g. on
+/// unmounted drives, with the name from [`get_project_name`].  We can't tell project files apart
+/// from directories without looking at them, though, so return `None` if `path` has the extension
+/// of a project file.
+fn locate_missing_project(path: &str, names: &mut ProjectNameCache) -> Option<ProjectLocation> {
+    let path = Path::new(path);
+    let is_project_file = path
+        .extension()
+        .is_some_and(|extension| PROJECT_FILE_EXTENSIONS.iter().any(|e| extension == *e));
+    if is_project_file {
+        None
+    } else {
+        Some(ProjectLocation {
+            name: get_project_name(path, names)?,
+            directory: path.to_str()?.to_string(),
+            file: None,
+        })
+    }
+}
+
 /// A recent project from a Jetbrains IDE.
 ///
 /// Note that rider calls these solutions per dotnet lingo.
@@ -183,67 +377,580 @@ pub struct JetbrainsRecentProject {
     /// We deliberately use String here instead of `PathBuf`, since we never really operate on this
     /// as a path, but a `PathBuf` would loose us easy access to the string API for matching.
     directory: String,
+
+    /// The project file, if the IDE opened a file instead of the project directory.
+    ///
+    /// We open this file instead of the project directory on activation.
+    file: Option<String>,
+
+    /// When this project disappeared from the recent projects of the IDE, if at all.
+    ///
+    /// We keep removed projects around for a grace period, see [`ProviderOptions`].
+    removed_at: Option<Instant>,
+
+    /// The kind of solution, e.g. `Unity`, for Rider solutions.
+    solution_kind: Option<String>,
+
+    /// The target framework, for Rider solutions.
+    target_framework: Option<String>,
+
+    /// When the project was last opened, in milliseconds since the Unix epoch.
+    opened_at: Option<i64>,
+
+    /// The branch checked out in the project directory.
+    ///
+    /// Only set if the project directory is one of several worktrees of the same git repository
+    /// among the recent projects, see [`annotate_worktree_branches`].
+    branch: Option<String>,
+
+    /// A suffix of the parent directory to tell this project apart from others of the same name.
+    ///
+    /// See [`disambiguate_names`].
+    qualifier: Option<String>,
+
+    /// Where this project is in the trash, if the user trashed it.
+    ///
+    /// See [`ProviderOptions::restore_from_trash`].
+    trashed: Option<TrashedProject>,
+
+    /// Further directories attached to this project in the same window.
+    ///
+    /// We open these along with the project directory on activation.
+    attached_roots: Vec<String>,
+
+    /// Whether the project directory was missing when we last looked, e.g. on an unmounted drive.
+    missing: bool,
+}
+
+impl JetbrainsRecentProject {
+    /// The human readable name of this project.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The directory of this project.
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    /// When this project was last opened, in milliseconds since the Unix epoch.
+    pub fn opened_at(&self) -> Option<i64> {
+        self.opened_at
+    }
+
+    /// The name to display for this project.
+    ///
+    /// This is the name of the project, followed by its qualifier if any, e.g. `backend (acme)`.
+    fn display_name(&self) -> String {
+        match &self.qualifier {
+            Some(qualifier) => format!("{} ({qualifier})", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Describe this project for the search result.
+    ///
+    /// Describe the kind of solution and the target framework if known, followed by the project
+    /// directory and all attached roots, e.g. `Unity solution (netstandard2.1) – /home/foo/MyGame`
+    /// or `/home/foo/frontend + /home/foo/backend`, and by the branch name of worktrees, e.g.
+    /// `/home/foo/mdcat-feature [feature]`.
+    fn description(&self) -> String {
+        let details = match (&self.solution_kind, &self.target_framework) {
+            (Some(kind), Some(framework)) => Some(format!("{kind} solution ({framework})")),
+            (Some(kind), None) => Some(format!("{kind} solution")),
+            (None, Some(framework)) => Some(framework.clone()),
+            (None, None) => None,
+        };
+        let mut description = match details {
+            Some(details) => format!("{details} – {}", self.directory),
+            None => self.directory.clone(),
+        };
+        for root in &self.attached_roots {
+            description.push_str(&format!(" + {root}"));
+        }
+        if let Some(branch) = &self.branch {
+            description.push_str(&format!(" [{branch}]"));
+        }
+        if self.removed_at.is_some() {
+            description.push_str(" (removed from recents)");
+        }
+        if self.trashed.is_some() {
+            description.push_str(" (in trash)");
+        }
+        if self.missing {
+            description.push_str(" (missing)");
+        }
+        description
+    }
+}
+
+/// Describe when a project was last `opened_at`, relative to `now`.
+///
+/// Both times are in milliseconds since the Unix epoch.  Describe times within the last week
+/// relative to `now`, e.g. `opened 3 days ago`, and older times by their date in the preferred
+/// format of the current locale.
+fn describe_opened_at(opened_at: i64, now: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let ago = |count: i64, unit: &str| {
+        if count == 1 {
+            format!("opened 1 {unit} ago")
+        } else {
+            format!("opened {count} {unit}s ago")
+        }
+    };
+    let seconds = (now - opened_at).max(0) / 1000;
+    if seconds < MINUTE {
+        "opened just now".to_string()
+    } else if seconds < HOUR {
+        ago(seconds / MINUTE, "minute")
+    } else if seconds < DAY {
+        ago(seconds / HOUR, "hour")
+    } else if seconds < 2 * DAY {
+        "opened yesterday".to_string()
+    } else if seconds < 7 * DAY {
+        ago(seconds / DAY, "day")
+    } else {
+        glib::DateTime::from_unix_local(opened_at / 1000)
+            .and_then(|t| t.format("%x"))
+            .map_or_else(
+                |_| ago(seconds / DAY, "day"),
+                |date| format!("opened {date}"),
+            )
+    }
+}
+
+/// A recent projects file we read recent projects from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectsSource {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// When the file was last modified, if known.
+    pub modified: Option<SystemTime>,
 }
 
-#[instrument(fields(app_id = %app_id))]
-fn read_recent_projects(
+impl Display for ProjectsSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let modified = self
+            .modified
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .and_then(|d| glib::DateTime::from_unix_utc(d.as_secs() as i64).ok())
+            .and_then(|t| t.format_iso8601().ok());
+        match modified {
+            Some(modified) => write!(f, "{} (modified {modified})", self.path.display()),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+/// The approximate memory retained by caches of a provider.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheSize {
+    /// The number of cache entries.
+    pub entries: usize,
+    /// The total size of all strings in cache entries, in bytes.
+    pub string_bytes: usize,
+}
+
+impl CacheSize {
+    /// Add a cache entry with the given `strings`.
+    pub fn add_entry<'a, I: IntoIterator<Item = &'a str>>(&mut self, strings: I) {
+        self.entries += 1;
+        self.string_bytes += strings.into_iter().map(str::len).sum::<usize>();
+    }
+}
+
+impl Display for CacheSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} entries with {} bytes of strings",
+            self.entries, self.string_bytes
+        )
+    }
+}
+
+/// Describe all `sources` for logging.
+fn describe_sources(sources: &[ProjectsSource]) -> String {
+    if sources.is_empty() {
+        "no files".to_string()
+    } else {
+        sources
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Options for reading recent projects.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOptions<'a> {
+    /// Additional roots to look for configuration directories in, see
+    /// [`ConfigLocation::config_roots`].
+    pub config_roots: &'a [PathBuf],
+    /// Whether to read recent projects of all versions, newest first; projects of older versions
+    /// only add to those of newer versions.
+    pub all_versions: bool,
+    /// Limits on the size of recent projects files and entries.
+    pub limits: &'a Limits,
+    /// Alternative home directories to look for projects recorded relative to the home directory.
+    pub home_roots: &'a [String],
+    /// Whether to look up projects which do not exist in the trash.
+    pub find_trashed: bool,
+}
+
+/// Read recent projects of the app with `app_id` from the given `config` location.
+///
+/// Look for the latest configuration directory in `$XDG_CONFIG_HOME` and in the config roots of
+/// `options`, or for configuration directories of all versions if `options` says so.
+///
+/// Add a human readable message to `warnings` for every recent projects file and every project
+/// we skip, and add every file we read projects from to `sources`.
+///
+/// If `options` asks to find trashed projects, look up projects which do not exist in the trash,
+/// and keep them if they are in the trash.
+///
+/// Read names of projects through the `names` cache.
+///
+/// If a project recorded relative to the home directory does not exist in the home directory,
+/// look for it below each of the home roots of `options` in turn, but keep pointing the project
+/// at the home directory, because that's where the IDE opened it.
+#[instrument(skip(options, warnings, sources, names), fields(app_id = %app_id))]
+pub fn read_recent_projects(
     config: &ConfigLocation<'_>,
     app_id: &AppId,
+    options: &ReadOptions<'_>,
+    warnings: &mut Vec<String>,
+    sources: &mut Vec<ProjectsSource>,
+    names: &mut ProjectNameCache,
 ) -> Result<IndexMap<String, JetbrainsRecentProject>> {
     event!(Level::INFO, %app_id, "Reading recents projects of {}", app_id);
-    match config
-        .find_latest_recent_projects_file(&glib::user_config_dir())
-        .and_then(|projects_file| {
-            File::open(&projects_file).with_context(|| {
-                format!(
-                    "Failed to open recent projects file at {}",
+    let data_directory_name = gio::DesktopAppInfo::try_from(app_id)
+        .ok()
+        .and_then(|info| find_data_directory_name(&info.executable()));
+    let preferred_channel = data_directory_name
+        .as_deref()
+        .and_then(|name| config.channel(name));
+    event!(Level::DEBUG, %app_id, "Preferring config channel {:?} for {}", preferred_channel, app_id);
+    let ReadOptions {
+        config_roots,
+        all_versions,
+        limits,
+        home_roots,
+        find_trashed,
+    } = *options;
+    let roots = config.config_roots(&glib::user_config_dir(), config_roots);
+    let projects_files = match if all_versions {
+        config.find_all_recent_projects_files(&roots, preferred_channel)
+    } else {
+        config.find_latest_recent_projects_files(&roots, preferred_channel)
+    } {
+        Ok(projects_files) => projects_files,
+        Err(error) => {
+            event!(Level::DEBUG, %error, "No recent project available: {:#}", error);
+            warnings.push(format!("No recent project available: {error:#}"));
+            return Ok(IndexMap::new());
+        }
+    };
+    let home = glib::home_dir();
+    let home_s = home
+        .to_str()
+        .with_context(|| "Failed to convert home directory path to UTF-8 string")?;
+    let mut recent_projects = IndexMap::new();
+    let mut found = TraceBatch::new("Found projects", |message| {
+        event!(Level::TRACE, "{message}")
+    });
+    let mut duplicates = TraceBatch::new(
+        "Skipped projects already found in another file",
+        |message| event!(Level::TRACE, "{message}"),
+    );
+    let mut missing = TraceBatch::new(
+        "Skipped projects which do not exist or whose name we failed to determine",
+        |message| event!(Level::TRACE, "{message}"),
+    );
+    let mut trash = None;
+    for projects_file in projects_files {
+        let mut source = match File::open(&projects_file) {
+            Ok(source) => source,
+            Err(error) => {
+                event!(
+                    Level::DEBUG,
+                    %error,
+                    "Failed to open recent projects file at {}: {:#}",
+                    projects_file.display(),
+                    error
+                );
+                warnings.push(format!(
+                    "Failed to open recent projects file at {}: {error:#}",
                     projects_file.display()
-                )
-            })
-        }) {
-        Ok(mut source) => {
-            let home = glib::home_dir();
-            let home_s = home
-                .to_str()
-                .with_context(|| "Failed to convert home directory path to UTF-8 string")?;
-            let mut recent_projects = IndexMap::new();
-            for path in parse_recent_jetbrains_projects(home_s, &mut source)? {
-                if let Some(name) = get_project_name(&path) {
-                    event!(Level::TRACE, %app_id, "Found project {} at {}", name, path);
-                    let id = format!("jetbrains-recent-project-{app_id}-{path}");
-                    recent_projects.insert(
-                        id,
-                        JetbrainsRecentProject {
-                            name,
-                            directory: path.to_string(),
-                        },
+                ));
+                continue;
+            }
+        };
+        let metadata = source.metadata().ok();
+        let size = metadata.as_ref().map_or(0, |metadata| metadata.len());
+        if limits.max_file_size < size {
+            event!(
+                Level::WARN,
+                "Skipping recent projects file at {}, {} bytes exceed maximum of {} bytes",
+                projects_file.display(),
+                size,
+                limits.max_file_size
+            );
+            warnings.push(format!(
+                "Skipping recent projects file at {}, {size} bytes exceed maximum of {} bytes",
+                projects_file.display(),
+                limits.max_file_size
+            ));
+            continue;
+        }
+        let entries = match parse_recent_jetbrains_projects(home_s, &mut source, limits) {
+            Ok(entries) => entries,
+            Err(error) => {
+                event!(
+                    Level::WARN,
+                    %error,
+                    "Skipping recent projects file at {}, failed to parse: {:#}",
+                    projects_file.display(),
+                    error
+                );
+                warnings.push(format!(
+                    "Skipping recent projects file at {}, failed to parse: {error:#}",
+                    projects_file.display()
+                ));
+                continue;
+            }
+        };
+        sources.push(ProjectsSource {
+            path: projects_file.clone(),
+            modified: metadata.and_then(|metadata| metadata.modified().ok()),
+        });
+        for entry in entries {
+            let path = entry.path;
+            let attached_roots = entry
+                .attached_roots
+                .into_iter()
+                .filter(|root| {
+                    let exists = Path::new(root).is_dir();
+                    if !exists {
+                        event!(
+                            Level::DEBUG,
+                            "Skipping attached root {} of {}, not a directory",
+                            redact::path(root),
+                            redact::path(&path)
+                        );
+                    }
+                    exists
+                })
+                .collect::<Vec<_>>();
+            let id = ProjectId::new(app_id, &path).to_string();
+            if recent_projects.contains_key(&id) {
+                duplicates.add(redact::path(&path));
+            } else if let Some(location) = locate_project(&path, names).or_else(|| {
+                let relative = entry.home_relative.as_deref()?;
+                home_roots.iter().find_map(|root| {
+                    let candidate = format!("{root}{relative}");
+                    event!(
+                        Level::TRACE,
+                        "Looking for {} at {}",
+                        redact::path(&path),
+                        redact::path(&candidate)
                     );
-                } else {
-                    event!(Level::TRACE, %app_id, "Skipping {}, failed to determine project name", path);
+                    locate_project(&candidate, names)
+                        .map(|location| location.relocate(root, home_s))
+                })
+            }) {
+                found.add(format_args!("{} at {}", location.name, redact::path(&path)));
+                recent_projects.insert(
+                    id,
+                    JetbrainsRecentProject {
+                        name: location.name,
+                        directory: location.directory,
+                        file: location.file,
+                        removed_at: None,
+                        solution_kind: entry.solution_kind,
+                        target_framework: entry.target_framework,
+                        opened_at: entry.opened_at,
+                        branch: None,
+                        qualifier: None,
+                        trashed: None,
+                        attached_roots,
+                        missing: false,
+                    },
+                );
+            } else if let Some(trashed) = find_trashed
+                .then(|| trash.get_or_insert_with(trashed_items).get(&path).cloned())
+                .flatten()
+            {
+                found.add(format_args!("{} in trash", redact::path(&path)));
+                let name = Path::new(&path)
+                    .file_name()
+                    .map_or_else(|| path.clone(), |name| name.to_string_lossy().to_string());
+                recent_projects.insert(
+                    id,
+                    JetbrainsRecentProject {
+                        name,
+                        directory: path,
+                        file: None,
+                        removed_at: None,
+                        solution_kind: entry.solution_kind,
+                        target_framework: entry.target_framework,
+                        opened_at: entry.opened_at,
+                        branch: None,
+                        qualifier: None,
+                        trashed: Some(trashed),
+                        attached_roots,
+                        missing: false,
+                    },
+                );
+            } else if let Some(location) = locate_missing_project(&path, names) {
+                found.add(format_args!(
+                    "{} at {} (missing)",
+                    location.name,
+                    redact::path(&path)
+                ));
+                recent_projects.insert(
+                    id,
+                    JetbrainsRecentProject {
+                        name: location.name,
+                        directory: location.directory,
+                        file: None,
+                        removed_at: None,
+                        solution_kind: entry.solution_kind,
+                        target_framework: entry.target_framework,
+                        opened_at: entry.opened_at,
+                        branch: None,
+                        qualifier: None,
+                        trashed: None,
+                        attached_roots,
+                        missing: true,
+                    },
+                );
+            } else {
+                missing.add(redact::path(&path));
+                warnings.push(format!(
+                    "Skipping {path} from {}, does not exist or failed to determine project name",
+                    projects_file.display()
+                ));
+            }
+        }
+    }
+    event!(Level::INFO, %app_id, "Found {} recent project(s) for app {}", recent_projects.len(), app_id);
+    Ok(recent_projects)
+}
+
+/// Qualify projects which have the same name with the shortest distinct suffix of their parent
+/// directories.
+///
+/// For instance, qualify projects named `backend` at `/home/foo/acme/backend` and
+/// `/home/foo/globex/backend` with `acme` and `globex` respectively.  Leave projects whose
+/// parent directories we can't tell apart unqualified.
+fn disambiguate_names(projects: &mut IndexMap<String, JetbrainsRecentProject>) {
+    let mut projects_per_name: HashMap<String, Vec<&mut JetbrainsRecentProject>> = HashMap::new();
+    for project in projects.values_mut() {
+        project.qualifier = None;
+        projects_per_name
+            .entry(project.name.clone())
+            .or_default()
+            .push(project);
+    }
+    for (name, mut projects) in projects_per_name {
+        if projects.len() < 2 {
+            continue;
+        }
+        let parents = projects
+            .iter()
+            .map(|project| {
+                Path::new(&project.directory)
+                    .parent()
+                    .map(|parent| {
+                        parent
+                            .components()
+                            .rev()
+                            .filter_map(|c| c.as_os_str().to_str())
+                            .filter(|c| *c != "/")
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let max_depth = parents.iter().map(Vec::len).max().unwrap_or(0);
+        let qualifiers = (1..=max_depth).find_map(|depth| {
+            let qualifiers = parents
+                .iter()
+                .map(|components| {
+                    let mut suffix = components.iter().take(depth).copied().collect::<Vec<_>>();
+                    suffix.reverse();
+                    suffix.join("/")
+                })
+                .collect::<Vec<_>>();
+            let unique = qualifiers.iter().collect::<std::collections::HashSet<_>>();
+            (unique.len() == qualifiers.len()).then_some(qualifiers)
+        });
+        match qualifiers {
+            Some(qualifiers) => {
+                for (project, qualifier) in projects.iter_mut().zip(qualifiers) {
+                    project.qualifier = Some(qualifier);
                 }
             }
-            event!(Level::INFO, %app_id, "Found {} recent project(s) for app {}", recent_projects.len(), app_id);
-            Ok(recent_projects)
+            None => {
+                event!(Level::DEBUG, "Failed to disambiguate projects named {name}");
+            }
         }
-        Err(error) => {
-            event!(Level::DEBUG, %error, "No recent project available: {:#}", error);
-            Ok(IndexMap::new())
+    }
+}
+
+/// Annotate projects which are worktrees of the same git repository with their branch.
+///
+/// Projects which are the only checkout of their repository among `projects` remain as they are.
+fn annotate_worktree_branches(projects: &mut IndexMap<String, JetbrainsRecentProject>) {
+    let checkouts = projects
+        .values()
+        .map(|project| worktree::inspect(&project.directory))
+        .collect::<Vec<_>>();
+    let mut worktrees_per_repo: HashMap<&Path, usize> = HashMap::new();
+    for checkout in checkouts.iter().flatten() {
+        *worktrees_per_repo.entry(&checkout.common_dir).or_default() += 1;
+    }
+    for (project, checkout) in projects.values_mut().zip(&checkouts) {
+        if let Some(checkout) = checkout {
+            if 1 < worktrees_per_repo[checkout.common_dir.as_path()] {
+                event!(
+                    Level::TRACE,
+                    "Project at {} is worktree on branch {:?}",
+                    redact::path(&project.directory),
+                    checkout.branch
+                );
+                project.branch = checkout.branch.clone();
+            }
         }
     }
 }
 
-/// Launch the given app, optionally passing a given URI.
+/// Launch the given app, optionally passing the given URIs.
 ///
 /// Move the launched app to a dedicated systemd scope for resource control, and return the result
 /// of launching the app.
-#[instrument(skip(connection))]
+#[cfg(feature = "launch")]
+#[instrument(
+    skip(connection, uris, provenance),
+    fields(uris = ?uris.iter().map(redact::path).collect::<Vec<_>>())
+)]
 async fn launch_app_in_new_scope(
     connection: zbus::Connection,
     app_id: AppId,
-    uri: Option<String>,
+    uris: Vec<String>,
+    provenance: Option<LaunchProvenance>,
+    keep_environment: Vec<String>,
+    call_timeout: Duration,
+    private_mode: PrivateMode,
 ) -> zbus::fdo::Result<()> {
-    let context = create_launch_context(connection);
-    let app = gio::DesktopAppInfo::try_from(&app_id).map_err(|error| {
+    let context = create_launch_context(connection.clone(), provenance, call_timeout, private_mode);
+    scrub_environment(&context, &keep_environment);
+    refresh_session_environment(&connection, &context, call_timeout).await;
+    let app = appinfo::lookup(&app_id).map_err(|error| {
         event!(
             Level::ERROR,
             %error,
@@ -251,303 +958,2944 @@ async fn launch_app_in_new_scope(
         );
         zbus::fdo::Error::Failed(format!("Failed to find app {app_id}: {error}"))
     })?;
-    match uri {
-        None => app.launch_uris_future(&[], Some(&context)),
-        Some(ref uri) => app.launch_uris_future(&[uri], Some(&context)),
-    }
-    .await
-    .map_err(|error| {
-        event!(
-            Level::ERROR,
-            %error,
-            "Failed to launch app {app_id} with {uri:?}: {error:#}",
-        );
-        zbus::fdo::Error::Failed(format!(
-            "Failed to launch app {app_id} with {uri:?}: {error}"
-        ))
-    })
-}
-
-/// A search provider for recent Jetbrains products.
-#[derive(Debug)]
-pub struct JetbrainsProductSearchProvider {
-    app: App,
-    recent_projects: IndexMap<String, JetbrainsRecentProject>,
-    config: &'static ConfigLocation<'static>,
+    let uri_refs = uris.iter().map(String::as_str).collect::<Vec<_>>();
+    app.launch_uris_future(&uri_refs, Some(&context))
+        .await
+        .map_err(|error| {
+            event!(
+                Level::ERROR,
+                %error,
+                "Failed to launch app {app_id} with {uris:?}: {error:#}",
+            );
+            zbus::fdo::Error::Failed(format!(
+                "Failed to launch app {app_id} with {uris:?}: {error}"
+            ))
+        })
 }
 
-impl JetbrainsProductSearchProvider {
-    /// Create a new search provider for a jetbrains product.
-    ///
-    /// `app` describes the underlying app to launch projects with, and `config` describes
-    /// where this Jetbrains product has its configuration.
-    pub fn new(app: App, config: &'static ConfigLocation<'static>) -> Self {
-        Self {
-            app,
-            config,
-            recent_projects: IndexMap::new(),
+/// Remove projects whose path is a symlink alias of another project's path.
+///
+/// Of all projects with the same canonical path keep the most recently opened one, with the path
+/// the user opened it at.  Look up canonical paths in `cache`, and forget canonical paths of
+/// projects which are gone.
+///
+/// Resolving canonical paths can block on slow file systems, so we do not resolve them here;
+/// instead return the paths of all projects not in `cache`.  Resolve these paths in the
+/// background, and then dedupe again.
+fn dedupe_symlink_aliases(
+    projects: &mut IndexMap<String, JetbrainsRecentProject>,
+    cache: &mut HashMap<String, Option<PathBuf>>,
+) -> Vec<String> {
+    let mut unresolved = Vec::new();
+    let mut canonical_paths: HashMap<PathBuf, (&str, Option<i64>)> = HashMap::new();
+    let mut aliases = Vec::new();
+    for (id, project) in projects.iter() {
+        let path = project.file.as_ref().unwrap_or(&project.directory);
+        let Some(canonical_path) = cache.get(path) else {
+            unresolved.push(path.clone());
+            continue;
+        };
+        let Some(canonical_path) = canonical_path.clone() else {
+            continue;
+        };
+        match canonical_paths.get_mut(&canonical_path) {
+            Some(kept) if kept.1 < project.opened_at => {
+                event!(
+                    Level::DEBUG,
+                    "Dropping {}, alias of {}",
+                    redact::path(&kept.0),
+                    redact::path(&id)
+                );
+                aliases.push(kept.0.to_string());
+                *kept = (id, project.opened_at);
+            }
+            Some(kept) => {
+                event!(
+                    Level::DEBUG,
+                    "Dropping {}, alias of {}",
+                    redact::path(&id),
+                    redact::path(&kept.0)
+                );
+                aliases.push(id.clone());
+            }
+            None => {
+                canonical_paths.insert(canonical_path, (id, project.opened_at));
+            }
         }
     }
+    projects.retain(|id, _| !aliases.contains(id));
+    cache.retain(|path, _| {
+        projects
+            .values()
+            .any(|project| project.file.as_ref().unwrap_or(&project.directory) == path)
+    });
+    unresolved
+}
 
-    /// Get the underyling app for this Jetbrains product.
-    pub fn app(&self) -> &App {
-        &self.app
-    }
+/// Canonical paths resolved in the background, by path.
+type ResolvedPaths = Arc<Mutex<Vec<(String, Option<PathBuf>)>>>;
 
-    /// Reload all recent projects provided by this search provider.
-    pub fn reload_recent_projects(&mut self) -> Result<()> {
-        self.recent_projects = read_recent_projects(self.config, self.app.id())?;
-        Ok(())
-    }
+/// Contents of project directories indexed in the background.
+type IndexedContents = Arc<Mutex<Vec<IndexedDirectory>>>;
 
-    #[instrument(skip(self, connection), fields(app_id = %self.app.id()))]
-    async fn launch_app_on_default_main_context(
-        &self,
-        connection: zbus::Connection,
-        uri: Option<String>,
-    ) -> zbus::fdo::Result<()> {
-        let app_id = self.app.id().clone();
-        let span = Span::current();
-        glib::MainContext::default()
-            .spawn_from_within(move || {
-                launch_app_in_new_scope(connection, app_id, uri.clone()).instrument(span)
-            })
-            .await
-            .map_err(|error| {
-                event!(
-                    Level::ERROR,
-                    %error,
-                    "Join from main loop failed: {error:#}",
-                );
-                zbus::fdo::Error::Failed(format!("Join from main loop failed: {error:#}",))
-            })?
+/// Cap `ids` to at most `max_results` results.
+///
+/// `ids` must be sorted already, best results first, to keep the best results.  If
+/// `max_results` is zero, do not cap results.
+///
+/// The session result always comes last, so we'd always drop it when capping; instead reserve the
+/// last slot for it.
+fn cap_results(ids: &mut Vec<&str>, max_results: usize) {
+    if 0 < max_results && max_results < ids.len() {
+        let session_result = ids
+            .last()
+            .copied()
+            .filter(|id| id.starts_with(SESSION_RESULT_PREFIX));
+        ids.truncate(max_results);
+        if let Some(session_result) = session_result {
+            ids[max_results - 1] = session_result;
+        }
     }
 }
 
-/// Calculate how well `recent_projects` matches all of the given `terms`.
+/// The prefix of IDs of recent projects.
 ///
-/// If all terms match the name of the `recent_projects`, the project receives a base score of 10.
-/// If all terms match the directory of the `recent_projects`, the project gets scored for each
-/// term according to how far right the term appears in the directory, under the assumption that
-/// the right most part of a directory path is the most specific.
+/// The app ID, a dash, and the absolute path of the project follow this prefix.
+const PROJECT_RESULT_PREFIX: &str = "jetbrains-recent-project-";
+
+/// The prefix of IDs of results to open project directories in the file manager.
 ///
-/// All matches are done on the lowercase text, i.e. case insensitve.
-fn score_recent_project(recent_project: &JetbrainsRecentProject, terms: &[&str]) -> f64 {
-    let name = recent_project.name.to_lowercase();
-    let directory = recent_project.directory.to_lowercase();
-    terms
-        .iter()
-        .try_fold(0.0, |score, term| {
-            directory
-                .rfind(&term.to_lowercase())
-                // We add 1 to avoid returning zero if the term matches right at the beginning.
-                .map(|index| score + ((index + 1) as f64 / recent_project.directory.len() as f64))
-        })
-        .unwrap_or(0.0)
-        + if terms.iter().all(|term| name.contains(&term.to_lowercase())) {
-            10.0
-        } else {
-            0.0
+/// The ID of the project follows this prefix.
+const FOLDER_RESULT_PREFIX: &str = "jetbrains-open-folder-";
+
+/// The prefix of IDs of results to reopen the last session of an app.
+///
+/// The app ID follows this prefix.
+const SESSION_RESULT_PREFIX: &str = "jetbrains-last-session-";
+
+/// Why a string is not a valid project ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectIdError {
+    /// The ID lacks the prefix of project IDs.
+    Prefix(String),
+    /// The ID lacks the app ID, or an absolute path after the app ID.
+    Malformed(String),
+}
+
+impl Display for ProjectIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectIdError::Prefix(id) => {
+                write!(f, "Project ID {id:?} lacks prefix {PROJECT_RESULT_PREFIX}")
+            }
+            ProjectIdError::Malformed(id) => {
+                write!(f, "Project ID {id:?} lacks app ID or absolute path")
+            }
         }
+    }
 }
 
-/// The DBus interface of the search provider.
+impl std::error::Error for ProjectIdError {}
+
+/// The ID of a recent project of an app.
 ///
-/// See <https://developer.gnome.org/SearchProvider/> for information.
-#[interface(name = "org.gnome.Shell.SearchProvider2")]
-impl JetbrainsProductSearchProvider {
-    /// Starts a search.
+/// The ID consists of [`PROJECT_RESULT_PREFIX`], the app ID, a dash, and the absolute path of
+/// the project.  App IDs never contain a slash, so the first `-/` separates the app ID from the
+/// path, even if both contain dashes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProjectId {
+    app_id: String,
+    path: String,
+}
+
+impl ProjectId {
+    /// Create the ID of the project at the absolute `path` for the app with `app_id`.
+    pub fn new(app_id: &AppId, path: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    /// The ID of the app of this project.
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+}
+
+impl Display for ProjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{PROJECT_RESULT_PREFIX}{}-{}", self.app_id, self.path)
+    }
+}
+
+impl FromStr for ProjectId {
+    type Err = ProjectIdError;
+
+    /// Parse a project ID.
     ///
-    /// This function is called when a new search is started. It gets an array of search terms as arguments,
-    /// and should return an array of result IDs. gnome-shell will call GetResultMetas for (some) of these result
-    /// IDs to get details about the result that can be be displayed in the result list.
-    #[instrument(skip(self), fields(app_id = %self.app.id()))]
-    fn get_initial_result_set(&self, terms: Vec<&str>) -> Vec<&str> {
-        event!(Level::DEBUG, "Searching for {:?}", terms);
-        let mut scored_ids = self
-            .recent_projects
-            .iter()
-            .filter_map(|(id, item)| {
-                let score = score_recent_project(item, &terms);
-                if 0.0 < score {
-                    Some((id.as_ref(), score))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        scored_ids.sort_by_key(|(_, score)| -((score * 1000.0) as i64));
-        let ids = scored_ids.into_iter().map(|(id, _)| id).collect();
-        event!(Level::DEBUG, "Found ids {:?}", ids);
-        ids
+    /// Take the app ID verbatim; it need not be a valid desktop ID.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix(PROJECT_RESULT_PREFIX)
+            .ok_or_else(|| ProjectIdError::Prefix(s.to_string()))?;
+        match rest.split_once("-/") {
+            Some((app_id, _)) if !app_id.is_empty() => Ok(Self {
+                app_id: app_id.to_string(),
+                path: rest[app_id.len() + 1..].to_string(),
+            }),
+            _ => Err(ProjectIdError::Malformed(s.to_string())),
+        }
     }
+}
 
-    /// Refine an ongoing search.
+/// A result ID, parsed into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ResultId {
+    /// A recent project.
+    Project(ProjectId),
+    /// The directory of a recent project.
+    Folder(ProjectId),
+    /// The last session of `app_id`.
+    Session { app_id: String },
+}
+
+impl ResultId {
+    /// Parse a result `id`.
     ///
-    /// This function is called to refine the initial search results when the user types more characters in the search entry.
-    /// It gets the previous search results and the current search terms as arguments, and should return an array of result IDs,
-    /// just like GetInitialResultSet.
-    #[instrument(skip(self), fields(app_id = %self.app.id()))]
-    fn get_subsearch_result_set(&self, previous_results: Vec<&str>, terms: Vec<&str>) -> Vec<&str> {
-        event!(
-            Level::DEBUG,
-            "Searching for {:?} in {:?}",
-            terms,
-            previous_results
-        );
-        // For simplicity just run the overall search again, and filter out everything not already matched.
-        let ids = self
-            .get_initial_result_set(terms)
-            .into_iter()
-            .filter(|id| previous_results.contains(id))
-            .collect();
-        event!(Level::DEBUG, "Found ids {:?}", ids);
-        ids
+    /// Return `None` if `id` is malformed.
+    fn parse(id: &str) -> Option<Self> {
+        if let Some(project_id) = id.strip_prefix(FOLDER_RESULT_PREFIX) {
+            project_id.parse().ok().map(Self::Folder)
+        } else if let Some(app_id) = id.strip_prefix(SESSION_RESULT_PREFIX) {
+            (!app_id.is_empty()).then(|| Self::Session {
+                app_id: app_id.to_string(),
+            })
+        } else {
+            id.parse().ok().map(Self::Project)
+        }
     }
 
-    /// Get metadata for results.
+    /// The ID of the app this result belongs to.
+    fn app_id(&self) -> &str {
+        match self {
+            Self::Project(id) | Self::Folder(id) => id.app_id(),
+            Self::Session { app_id } => app_id,
+        }
+    }
+}
+
+/// Check that `item_id` is a well-formed ID of a result of the app with `app_id`.
+///
+/// Fail with invalid arguments otherwise, to refuse IDs from the namespace of other providers.
+fn check_result_id(app_id: &AppId, item_id: &str) -> zbus::fdo::Result<()> {
+    match ResultId::parse(item_id) {
+        None => Err(zbus::fdo::Error::InvalidArgs(format!(
+            "Malformed result ID {item_id}"
+        ))),
+        Some(id) if id.app_id() != app_id.to_string() => Err(zbus::fdo::Error::InvalidArgs(
+            format!("Result {item_id} belongs to another provider"),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Options for a search provider.
+#[derive(Debug, Default, Clone)]
+pub struct ProviderOptions {
+    /// How long to keep projects which disappeared from the recent projects of the IDE.
     ///
-    /// This function is called to obtain detailed information for results.
-    /// It gets an array of result IDs as arguments, and should return a matching array of dictionaries
-    /// (ie one a{sv} for each passed-in result ID).
+    /// We keep serving such projects for at least this duration, but mark them as removed, to
+    /// avoid that projects suddenly disappear from the search in the midst of a session.
+    /// Zero disables this grace period.
+    pub removed_grace_period: Duration,
+
+    /// Environment variables to pass on to launched apps.
     ///
-    /// The following pieces of information should be provided for each result:
-    //
-    //  - "id": the result ID
-    //  - "name": the display name for the result
-    //  - "icon": a serialized GIcon (see g_icon_serialize()), or alternatively,
-    //  - "gicon": a textual representation of a GIcon (see g_icon_to_string()), or alternatively,
-    //  - "icon-data": a tuple of type (iiibiiay) describing a pixbuf with width, height, rowstride, has-alpha, bits-per-sample, and image data
-    //  - "description": an optional short description (1-2 lines)
-    #[instrument(skip(self), fields(app_id = %self.app.id()))]
-    fn get_result_metas(
-        &self,
-        results: Vec<String>,
-    ) -> zbus::fdo::Result<Vec<HashMap<String, zvariant::Value<'_>>>> {
-        event!(Level::DEBUG, "Getting meta info for {:?}", results);
-        let mut metas = Vec::with_capacity(results.len());
-        for item_id in results {
-            if let Some(item) = self.recent_projects.get(&item_id) {
-                event!(Level::DEBUG, %item_id, "Compiling meta info for {}", item_id);
-                let mut meta: HashMap<String, zvariant::Value> = HashMap::new();
-                meta.insert("id".to_string(), item_id.clone().into());
-                meta.insert("name".to_string(), item.name.clone().into());
-                event!(Level::DEBUG, %item_id, "Using icon {}", self.app.icon());
-                meta.insert("gicon".to_string(), self.app.icon().to_string().into());
-                meta.insert("description".to_string(), item.directory.clone().into());
-                metas.push(meta);
+    /// We remove some variables from the environment of launched apps, see
+    /// `SCRUBBED_ENVIRONMENT` in the `launch` module; this list overrides this.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub keep_environment: Vec<String>,
+
+    /// Whether to show the branch of projects which are worktrees of the same git repository.
+    pub worktree_branches: bool,
+
+    /// Whether to append when a project was last opened to its description.
+    pub show_opened_at: bool,
+
+    /// Projects to never show.
+    pub ignore: IgnoreList,
+
+    /// Private mode shared by all providers; while enabled providers return no results.
+    pub private_mode: PrivateMode,
+
+    /// Providers disabled in Gnome settings; disabled providers return no results.
+    pub disabled_providers: DisabledProviders,
+
+    /// The number of most recent projects whose directory contents to index for search.
+    ///
+    /// If zero, do not index directory contents.
+    pub index_contents: usize,
+
+    /// Limits on the size of recent projects files and entries.
+    pub recents_limits: Limits,
+
+    /// Alternative home directories to look for projects which the IDE recorded relative to the
+    /// home directory, in order.
+    pub home_roots: Vec<String>,
+
+    /// Additional directories to look for configuration directories of IDEs in.
+    pub config_roots: Vec<PathBuf>,
+
+    /// Whether to read recent projects of all installed versions of the IDE.
+    ///
+    /// Right after an upgrade the new version may not know all recent projects of the previous
+    /// version yet.
+    pub all_versions: bool,
+
+    /// Power saver mode shared by all providers.
+    ///
+    /// In power saver mode we skip periodic reloads, so providers refresh stale recent projects
+    /// on search instead.
+    pub power_saver: PowerSaver,
+
+    /// The history of activated results shared by all providers.
+    pub activations: ActivationHistory,
+
+    /// What to return for unknown result IDs in `GetResultMetas`.
+    pub stale_results: StaleResults,
+
+    /// Whether to remove projects whose path is a symlink alias of another project.
+    pub dedupe_symlinks: bool,
+
+    /// The maximum number of results to return for a search.
+    ///
+    /// Gnome Shell only shows the first five results of a provider anyway.
+    /// Zero disables this limit.
+    pub max_results: usize,
+
+    /// The minimum number of results to keep when cutting results at a gap in their scores.
+    ///
+    /// If non-zero, drop all results after the largest drop in scores after this many results,
+    /// see [`scoring::cut_at_score_gap`], before capping results at `max_results`.  Zero
+    /// disables cutting results at score gaps.
+    pub min_results: usize,
+
+    /// Apps to activate projects with by their directory, instead of the app of the provider.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub routes: Routes,
+
+    /// Icons to use for results instead of the icons of apps.
+    pub icons: Icons,
+
+    /// Whether to open entries of the project directory which remaining search terms refer to.
+    ///
+    /// If enabled, activating a project with terms which do not match the project itself, e.g.
+    /// `render` in `mdcat render`, also opens the first top-level entry of the project directory
+    /// whose name starts with each such term.  Off by default, because IDEs differ in how they
+    /// handle files passed along with a project.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub open_residual_terms: bool,
+
+    /// Whether to offer a result to open the directory of each project in the file manager.
+    pub folder_results: bool,
+
+    /// Whether to keep recent projects which are in the trash, and offer to restore them.
+    pub restore_from_trash: bool,
+
+    /// A command to run after launching an app, e.g. to move its window to the current workspace.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub activation_hook: Option<ActivationHook>,
+
+    /// How long to wait for replies of other DBus services, e.g. systemd.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    pub call_timeout: Duration,
+
+    /// How long a search may take before we skip the provider for a while.
+    ///
+    /// See [`crate::latency`].  Zero disables this budget.
+    pub latency_budget: Duration,
+
+    /// How long reloading recent projects may take before we skip the provider for a while.
+    ///
+    /// See [`crate::latency`].  Zero disables this budget.
+    pub reload_budget: Duration,
+}
+
+impl ProviderOptions {
+    /// Options to read recent projects with.
+    pub fn read_options(&self) -> ReadOptions<'_> {
+        ReadOptions {
+            config_roots: &self.config_roots,
+            all_versions: self.all_versions,
+            limits: &self.recents_limits,
+            home_roots: &self.home_roots,
+            find_trashed: self.restore_from_trash,
+        }
+    }
+}
+
+/// What to return for unknown, i.e. stale, result IDs in `GetResultMetas`.
+///
+/// The shell may ask for meta information of results which disappeared from recent projects
+/// since it obtained their IDs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StaleResults {
+    /// Skip stale results, and return meta information only for known results.
+    #[default]
+    Skip,
+    /// Return placeholder meta information named "unavailable" for stale results.
+    Placeholder,
+}
+
+impl std::str::FromStr for StaleResults {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "placeholder" => Ok(Self::Placeholder),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value {s} for stale results, expected skip or placeholder"
+            )),
+        }
+    }
+}
+
+/// Keep projects in `old_projects` which are missing in `new_projects` for `grace_period`.
+///
+/// Mark missing projects as removed at `now`, and add them to `new_projects`, unless they were
+/// removed more than `grace_period` before `now`.
+fn keep_removed_projects(
+    old_projects: IndexMap<String, JetbrainsRecentProject>,
+    new_projects: &mut IndexMap<String, JetbrainsRecentProject>,
+    grace_period: Duration,
+    now: Instant,
+) {
+    for (id, mut project) in old_projects {
+        if !new_projects.contains_key(&id) {
+            let removed_at = *project.removed_at.get_or_insert(now);
+            if now.duration_since(removed_at) < grace_period {
+                event!(
+                    Level::DEBUG,
+                    "Keeping removed project {}",
+                    redact::path(&project.directory)
+                );
+                new_projects.insert(id, project);
             }
         }
-        event!(Level::DEBUG, "Return meta info {:?}", &metas);
-        Ok(metas)
     }
+}
 
-    /// Activate an individual result.
-    ///
-    /// This function is called when the user clicks on an individual result to open it in the application.
-    /// The arguments are the result ID, the current search terms and a timestamp.
+/// The maximum delay before retrying to load recent projects after failures.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The delay before retrying to load recent projects after `failed_reloads` consecutive failures.
+///
+/// Start with one second and double the delay for every subsequent failure, up to
+/// [`MAX_RETRY_DELAY`].
+fn retry_delay(failed_reloads: u32) -> Duration {
+    Duration::from_secs(1)
+        .checked_mul(2u32.saturating_pow(failed_reloads.saturating_sub(1)))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Whether recent projects loaded at `loaded_at` are stale at `now`.
+///
+/// Recent projects are stale if they are older than the [`RELOAD_INTERVAL`].
+fn is_stale(loaded_at: Option<Instant>, now: Instant) -> bool {
+    loaded_at.map_or(true, |loaded_at| {
+        RELOAD_INTERVAL <= now.saturating_duration_since(loaded_at)
+    })
+}
+
+/// Whether the paths of a recent project still exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectState {
+    /// The project directory and the project file, if any, exist.
+    Present,
+    /// The project directory is missing, e.g. on an unmounted drive.
+    MissingDirectory,
+    /// The project file vanished.
+    MissingFile,
+}
+
+/// The paths of a recent project to revalidate.
+///
+/// See [`JetbrainsProductSearchProvider::projects_to_revalidate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectPaths {
+    id: String,
+    directory: String,
+    file: Option<String>,
+}
+
+impl ProjectPaths {
+    /// Check whether the paths of this project still exist.
     ///
-    /// Launches the underlying app with the path to the selected item.
-    #[instrument(skip(self, connection), fields(app_id = %self.app.id()))]
-    async fn activate_result(
-        &mut self,
-        #[zbus(connection)] connection: &zbus::Connection,
-        item_id: &str,
-        terms: Vec<&str>,
-        timestamp: u32,
-    ) -> zbus::fdo::Result<()> {
-        event!(
-            Level::DEBUG,
-            item_id,
-            "Activating result {} for {:?} at {}",
-            item_id,
-            terms,
-            timestamp
-        );
-        if let Some(item) = self.recent_projects.get(item_id) {
-            event!(Level::INFO, item_id, "Launching recent item {:?}", item);
-            self.launch_app_on_default_main_context(
-                connection.clone(),
-                Some(item.directory.clone()),
-            )
-            .await
+    /// Return the ID of the project and its state.  This does blocking IO, so call it off the
+    /// main loop, e.g. with [`gio::spawn_blocking`].
+    pub fn check(self) -> (String, ProjectState) {
+        let state = if self
+            .file
+            .as_deref()
+            .is_some_and(|file| !Path::new(file).is_file())
+        {
+            ProjectState::MissingFile
+        } else if Path::new(&self.directory).is_dir() {
+            ProjectState::Present
         } else {
-            event!(Level::ERROR, item_id, "Item not found");
-            Err(zbus::fdo::Error::Failed(format!(
-                "Result {item_id} not found"
-            )))
+            ProjectState::MissingDirectory
+        };
+        (self.id, state)
+    }
+}
+
+/// A search provider for recent Jetbrains products.
+#[derive(Debug)]
+pub struct JetbrainsProductSearchProvider {
+    app: App,
+    recent_projects: IndexMap<String, JetbrainsRecentProject>,
+    /// Whether recent projects were successfully loaded at least once.
+    is_loaded: bool,
+    /// When recent projects were last loaded successfully.
+    loaded_at: Option<Instant>,
+    /// The number of consecutive failures to reload recent projects.
+    failed_reloads: u32,
+    /// When to retry loading recent projects on use after failures, see [`retry_delay`].
+    retry_at: Option<Instant>,
+    /// The contents of the most recent project directories, see [`ProviderOptions::index_contents`].
+    content_index: ContentIndex,
+    /// Contents indexed in the background, not yet added to `content_index`.
+    indexed_contents: IndexedContents,
+    /// The number of successful reloads so far.
+    generation: u64,
+    /// The files we read recent projects from in the last successful reload.
+    sources: Vec<ProjectsSource>,
+    /// The ID of the result to reopen the last session of the app.
+    session_result_id: String,
+    /// IDs of results to open project directories in the file manager, by project ID.
+    ///
+    /// See [`ProviderOptions::folder_results`].
+    folder_result_ids: HashMap<String, String>,
+    /// Canonical paths of projects, see [`ProviderOptions::dedupe_symlinks`].
+    canonical_paths: HashMap<String, Option<PathBuf>>,
+    /// Canonical paths resolved in the background, not yet added to `canonical_paths`.
+    resolved_paths: ResolvedPaths,
+    /// Names of projects read from their `.idea` directory.
+    project_names: ProjectNameCache,
+    /// Search and reload latency, see [`ProviderOptions::latency_budget`] and
+    /// [`ProviderOptions::reload_budget`].
+    latency: LatencyTracker,
+    /// The index of the next recent project to revalidate, see [`Self::revalidate_projects`].
+    revalidate_at: usize,
+    /// Watch the files we read recent projects from, to reload them after changes.
+    watcher: ProjectsWatcher,
+    config: &'static ConfigLocation<'static>,
+    options: ProviderOptions,
+}
+
+impl JetbrainsProductSearchProvider {
+    /// Create a new search provider for a jetbrains product.
+    ///
+    /// `app` describes the underlying app to launch projects with, `config` describes
+    /// where this Jetbrains product has its configuration, and `options` configures the
+    /// behaviour of this provider.
+    ///
+    /// The new provider has no recent projects yet; it loads recent projects on the first call
+    /// to any DBus method, unless [`Self::reload_recent_projects`] was called before.
+    pub fn new(
+        app: App,
+        config: &'static ConfigLocation<'static>,
+        options: ProviderOptions,
+    ) -> Self {
+        Self {
+            generation: 0,
+            sources: Vec::new(),
+            session_result_id: format!("{SESSION_RESULT_PREFIX}{}", app.id()),
+            app,
+            config,
+            options,
+            recent_projects: IndexMap::new(),
+            is_loaded: false,
+            loaded_at: None,
+            failed_reloads: 0,
+            retry_at: None,
+            content_index: ContentIndex::default(),
+            indexed_contents: IndexedContents::default(),
+            canonical_paths: HashMap::new(),
+            resolved_paths: ResolvedPaths::default(),
+            project_names: ProjectNameCache::default(),
+            folder_result_ids: HashMap::new(),
+            latency: LatencyTracker::default(),
+            revalidate_at: 0,
+            watcher: ProjectsWatcher::default(),
         }
     }
 
-    /// Launch a search within the App.
+    /// The icon to show for the app with `app_id`, as serialized string.
     ///
-    /// This function is called when the user clicks on the provider icon to display more search results in the application.
-    /// The arguments are the current search terms and a timestamp.
+    /// Prefer the icon configured for the app, then the current icon of the app over the icon we
+    /// found at startup, in case the app was updated meanwhile.
+    fn icon_of(&self, app_id: &AppId) -> String {
+        match self.options.icons.get(app_id) {
+            Some(icon) => icon.to_string(),
+            None => appinfo::icon(app_id).unwrap_or_else(|| self.app.icon().to_string()),
+        }
+    }
+
+    /// Whether to offer reopening the last session of the app for `terms`.
     ///
-    /// Currently it simply launches the app without any arguments.
-    #[instrument(skip(self, connection), fields(app_id = %self.app.id()))]
-    async fn launch_search(
-        &self,
-        #[zbus(connection)] connection: &zbus::Connection,
-        _terms: Vec<String>,
-        _timestamp: u32,
-    ) -> zbus::fdo::Result<()> {
-        event!(Level::DEBUG, "Launching app directly");
-        self.launch_app_on_default_main_context(connection.clone(), None)
-            .await
+    /// We only offer this result if the terms match the name of the app.
+    fn matches_session_result(&self, terms: &[&str]) -> bool {
+        !terms.is_empty() && 0.0 < scoring::score(self.app.name(), "", terms)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use similar_asserts::assert_eq;
+    /// Get the underyling app for this Jetbrains product.
+    pub fn app(&self) -> &App {
+        &self.app
+    }
 
-    #[test]
-    fn read_recent_projects() {
-        let data: &[u8] = include_bytes!("tests/recentProjects.xml");
-        let home = glib::home_dir();
-        let recent_projects =
-            parse_recent_jetbrains_projects(home.to_str().unwrap(), data).unwrap();
+    /// The generation of recent projects, i.e. the number of successful reloads.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
 
-        assert_eq!(
-            recent_projects,
-            vec![
-                home.join("Code")
-                    .join("gh")
-                    .join("mdcat")
-                    .to_string_lossy()
-                    .to_string(),
-                home.join("Code")
-                    .join("gh")
-                    .join("gnome-search-providers-jetbrains")
-                    .to_string_lossy()
-                    .to_string()
-            ]
-        )
+    /// The files we read recent projects from in the last successful reload.
+    pub fn sources(&self) -> &[ProjectsSource] {
+        &self.sources
     }
 
-    #[test]
-    fn read_recent_solutions() {
-        let data: &[u8] = include_bytes!("tests/recentSolutions.xml");
-        let home = glib::home_dir();
-        let recent_projects =
-            parse_recent_jetbrains_projects(home.to_str().unwrap(), data).unwrap();
+    /// Whether this provider loaded its recent projects at least once.
+    pub fn is_loaded(&self) -> bool {
+        self.is_loaded
+    }
 
-        assert_eq!(
-            recent_projects,
-            vec![
-                home.join("Code")
-                    .join("gh")
-                    .join("mdcat")
-                    .to_string_lossy()
-                    .to_string(),
-                home.join("Code")
-                    .join("gh")
-                    .join("gnome-search-providers-jetbrains")
-                    .to_string_lossy()
-                    .to_string()
-            ]
-        )
+    /// Whether the last attempt to reload recent projects failed.
+    pub fn has_failed(&self) -> bool {
+        0 < self.failed_reloads
+    }
+
+    /// How much longer this provider is suspended for exceeding its latency or reload budget.
+    pub fn suspended_for(&self) -> Option<Duration> {
+        self.latency.suspended_for(Instant::now())
+    }
+
+    /// Reload all recent projects provided by this search provider.
+    ///
+    /// Always try to reload, even if previous reloads failed; if reloading fails, back off before
+    /// trying to load recent projects again on use, see [`Self::ensure_loaded`].
+    ///
+    /// If reloading, successful or not, exceeds the reload budget suspend this provider, see
+    /// [`crate::latency`].
+    pub fn reload_recent_projects(&mut self) -> Result<()> {
+        let started = Instant::now();
+        let result = self.load_recent_projects();
+        let latency = started.elapsed();
+        let budget = self.options.reload_budget;
+        if !budget.is_zero() && budget < latency {
+            event!(
+                Level::WARN,
+                app_id = %self.app.id(),
+                "Reloading recent projects took {}ms, exceeding the reload budget of {}ms; skipping provider for {}s",
+                latency.as_millis(),
+                budget.as_millis(),
+                REPROBE_INTERVAL.as_secs()
+            );
+            self.latency.suspend(Instant::now());
+            metrics::record_suspended(self.app.id(), true);
+        }
+        result
+    }
+
+    /// Load recent projects, and update all caches.
+    fn load_recent_projects(&mut self) -> Result<()> {
+        let start = Instant::now();
+        let mut sources = Vec::new();
+        let result = read_recent_projects(
+            self.config,
+            self.app.id(),
+            &self.options.read_options(),
+            &mut Vec::new(),
+            &mut sources,
+            &mut self.project_names,
+        );
+        metrics::record_reload(self.app.id(), start.elapsed(), result.is_ok());
+        let mut recent_projects = match result {
+            Ok(recent_projects) => recent_projects,
+            Err(error) => {
+                self.failed_reloads = self.failed_reloads.saturating_add(1);
+                let delay = retry_delay(self.failed_reloads);
+                event!(
+                    Level::DEBUG,
+                    app_id = %self.app.id(),
+                    "Reload failed {} time(s) in a row, retrying in {}s at the earliest",
+                    self.failed_reloads,
+                    delay.as_secs()
+                );
+                self.retry_at = Some(Instant::now() + delay);
+                return Err(error);
+            }
+        };
+        self.failed_reloads = 0;
+        self.retry_at = None;
+        self.generation += 1;
+        event!(
+            Level::INFO,
+            app_id = %self.app.id(),
+            generation = self.generation,
+            "Loaded generation {} of recent projects from {}",
+            self.generation,
+            describe_sources(&sources)
+        );
+        self.sources = sources;
+        self.watcher
+            .watch(self.sources.iter().map(|source| source.path.as_path()));
+        recent_projects.retain(|_, project| {
+            let is_ignored = self
+                .options
+                .ignore
+                .is_ignored(&project.name, &project.directory);
+            if is_ignored {
+                event!(
+                    Level::DEBUG,
+                    "Ignoring project at {}",
+                    redact::path(&project.directory)
+                );
+            }
+            !is_ignored
+        });
+        if self.options.dedupe_symlinks {
+            self.canonical_paths.extend(self.take_resolved_paths());
+            let unresolved =
+                dedupe_symlink_aliases(&mut recent_projects, &mut self.canonical_paths);
+            self.resolve_canonical_paths(unresolved);
+        }
+        if self.options.worktree_branches {
+            annotate_worktree_branches(&mut recent_projects);
+        }
+        if !self.options.removed_grace_period.is_zero() {
+            keep_removed_projects(
+                std::mem::take(&mut self.recent_projects),
+                &mut recent_projects,
+                self.options.removed_grace_period,
+                Instant::now(),
+            );
+        }
+        disambiguate_names(&mut recent_projects);
+        self.project_names
+            .retain(recent_projects.values().map(|p| p.directory.as_str()));
+        self.recent_projects = recent_projects;
+        self.update_folder_result_ids();
+        self.is_loaded = true;
+        self.loaded_at = Some(Instant::now());
+        if 0 < self.options.index_contents {
+            self.update_content_index();
+        }
+        let cache_size = self.cache_size();
+        event!(
+            Level::INFO,
+            app_id = %self.app.id(),
+            projects = self.recent_projects.len(),
+            cache_entries = cache_size.entries,
+            cache_string_bytes = cache_size.string_bytes,
+            "Retaining {} recent projects in caches of {}",
+            self.recent_projects.len(),
+            cache_size
+        );
+        metrics::record_cache_size(self.app.id(), &cache_size);
+        Ok(())
+    }
+
+    /// Take all canonical paths resolved in the background so far.
+    fn take_resolved_paths(&self) -> Vec<(String, Option<PathBuf>)> {
+        std::mem::take(&mut *self.resolved_paths.lock().unwrap())
+    }
+
+    /// Resolve canonical paths of `paths` in the background.
+    ///
+    /// Dedupe recent projects again with these paths on the next search, see
+    /// [`Self::dedupe_resolved_paths`].
+    fn resolve_canonical_paths(&self, paths: Vec<String>) {
+        if paths.is_empty() {
+            return;
+        }
+        event!(
+            Level::DEBUG,
+            app_id = %self.app.id(),
+            "Resolving canonical paths of {} projects",
+            paths.len()
+        );
+        let resolved_paths = self.resolved_paths.clone();
+        glib::MainContext::ref_thread_default().spawn(
+            async move {
+                let resolved = gio::spawn_blocking(move || {
+                    paths
+                        .into_iter()
+                        .map(|path| {
+                            let canonical_path = std::fs::canonicalize(&path).ok();
+                            (path, canonical_path)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await;
+                match resolved {
+                    Ok(resolved) => resolved_paths.lock().unwrap().extend(resolved),
+                    Err(_) => event!(Level::ERROR, "Failed to resolve canonical paths"),
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Dedupe recent projects with canonical paths resolved in the background meanwhile.
+    fn dedupe_resolved_paths(&mut self) {
+        let resolved = self.take_resolved_paths();
+        if resolved.is_empty() || !self.options.dedupe_symlinks {
+            return;
+        }
+        self.canonical_paths.extend(resolved);
+        let count = self.recent_projects.len();
+        // Paths still unresolved belong to projects of a later reload, which resolves them itself
+        dedupe_symlink_aliases(&mut self.recent_projects, &mut self.canonical_paths);
+        if self.recent_projects.len() < count {
+            disambiguate_names(&mut self.recent_projects);
+            self.update_folder_result_ids();
+        }
+    }
+
+    /// Update IDs of results to open project directories for the current recent projects.
+    fn update_folder_result_ids(&mut self) {
+        self.folder_result_ids = if self.options.folder_results {
+            self.recent_projects
+                .keys()
+                .map(|id| (id.clone(), format!("{FOLDER_RESULT_PREFIX}{id}")))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+    }
+
+    /// Get the approximate memory retained by the caches of this provider.
+    ///
+    /// Count recent projects, canonical paths, folder result IDs and indexed directory contents.
+    pub fn cache_size(&self) -> CacheSize {
+        let mut size = CacheSize::default();
+        for (id, project) in &self.recent_projects {
+            size.add_entry(
+                [
+                    Some(id),
+                    Some(&project.name),
+                    Some(&project.directory),
+                    project.file.as_ref(),
+                    project.solution_kind.as_ref(),
+                    project.target_framework.as_ref(),
+                    project.branch.as_ref(),
+                    project.qualifier.as_ref(),
+                ]
+                .into_iter()
+                .flatten()
+                .map(String::as_str),
+            );
+        }
+        for (path, canonical_path) in &self.canonical_paths {
+            size.add_entry([
+                path.as_str(),
+                canonical_path
+                    .as_ref()
+                    .and_then(|p| p.to_str())
+                    .unwrap_or_default(),
+            ]);
+        }
+        for (project_id, folder_id) in &self.folder_result_ids {
+            size.add_entry([project_id.as_str(), folder_id.as_str()]);
+        }
+        self.project_names.add_size(&mut size);
+        self.content_index.add_size(&mut size);
+        size
+    }
+
+    /// Index the contents of the most recently opened projects.
+    ///
+    /// Index at most [`ProviderOptions::index_contents`] projects; the index caches contents for
+    /// a while, so this only reads directories which were not indexed recently.  Read directories
+    /// in the background, and keep serving the previous index meanwhile; searches pick up the new
+    /// contents, see [`Self::take_indexed_contents`].
+    fn update_content_index(&mut self) {
+        self.take_indexed_contents();
+        let mut projects = self.recent_projects.values().collect::<Vec<_>>();
+        projects.sort_by_key(|project| std::cmp::Reverse(project.opened_at));
+        let directories = self.content_index.update(
+            projects
+                .into_iter()
+                .take(self.options.index_contents)
+                .map(|project| project.directory.as_str()),
+            Instant::now(),
+        );
+        if directories.is_empty() {
+            return;
+        }
+        event!(
+            Level::DEBUG,
+            app_id = %self.app.id(),
+            "Indexing contents of {} project directories",
+            directories.len()
+        );
+        let indexed_contents = self.indexed_contents.clone();
+        glib::MainContext::ref_thread_default().spawn(
+            async move {
+                let indexed =
+                    gio::spawn_blocking(move || index_directories(directories, Instant::now()))
+                        .await;
+                match indexed {
+                    Ok(indexed) => indexed_contents.lock().unwrap().extend(indexed),
+                    Err(_) => event!(Level::ERROR, "Failed to index contents of projects"),
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Add contents indexed in the background meanwhile to the content index.
+    fn take_indexed_contents(&mut self) {
+        let indexed = std::mem::take(&mut *self.indexed_contents.lock().unwrap());
+        self.content_index.insert(indexed);
+    }
+
+    /// Get the app to activate the project `item` with the given `item_id` with.
+    ///
+    /// Use the app of the first matching route, or the app of this provider.
+    #[cfg_attr(not(feature = "launch"), allow(dead_code))]
+    fn route(&self, item_id: &str, item: &JetbrainsRecentProject) -> AppId {
+        match self
+            .options
+            .routes
+            .route(&item.directory, item.file.as_deref())
+        {
+            Some(app_id) => {
+                event!(
+                    Level::INFO,
+                    item_id = %redact::path(item_id),
+                    %app_id,
+                    "Routing {} to {app_id}",
+                    redact::path(item.file.as_ref().unwrap_or(&item.directory))
+                );
+                app_id.clone()
+            }
+            None => self.app.id().clone(),
+        }
+    }
+
+    /// Activate the result with the given `item_id` with additional `options`.
+    ///
+    /// Launch the underlying app with the project directory and all its attached roots, or with
+    /// the target file of `options`, in the background, and return immediately.  For the session result launch the
+    /// app without any project, to let it restore its last session.
+    ///
+    /// Fail if the result does not exist, or if the target file is not within the project.
+    #[cfg(feature = "launch")]
+    pub fn activate_result_with_options(
+        &mut self,
+        connection: &zbus::Connection,
+        item_id: &str,
+        options: &ActivationOptions,
+    ) -> zbus::fdo::Result<()> {
+        check_result_id(self.app.id(), item_id)?;
+        if item_id == self.session_result_id {
+            // Launch the app without a project, to let it restore its last session
+            event!(Level::INFO, item_id = %redact::path(item_id), "Reopening last session");
+            self.spawn_launch_on_default_main_context(
+                connection.clone(),
+                self.app.id().clone(),
+                Vec::new(),
+                None,
+                None,
+                options.timestamp,
+            );
+            return Ok(());
+        }
+        self.ensure_loaded();
+        let folder_of = item_id
+            .strip_prefix(FOLDER_RESULT_PREFIX)
+            .and_then(|project_id| self.recent_projects.get(project_id));
+        if let Some(item) = folder_of {
+            let uri = glib::filename_to_uri(&item.directory, None).map_err(|error| {
+                zbus::fdo::Error::Failed(format!(
+                    "Failed to convert {} to URI: {error}",
+                    item.directory
+                ))
+            })?;
+            event!(
+                Level::INFO,
+                item_id = %redact::path(item_id),
+                "Opening {} in file manager",
+                redact::path(&item.directory)
+            );
+            glib::MainContext::default().spawn_local(async move {
+                let context = gio::AppLaunchContext::NONE;
+                if let Err(error) = gio::AppInfo::launch_default_for_uri_future(&uri, context).await
+                {
+                    event!(Level::ERROR, %error, "Failed to open {uri}: {error:#}");
+                }
+            });
+            return Ok(());
+        }
+        let Some(item) = self.recent_projects.get(item_id) else {
+            event!(Level::ERROR, item_id = %redact::path(item_id), "Item not found");
+            return Err(zbus::fdo::Error::Failed(format!(
+                "Result {item_id} not found"
+            )));
+        };
+        event!(
+            Level::INFO,
+            item_id = %redact::path(item_id),
+            "Launching recent item at {}",
+            redact::path(item.file.as_ref().unwrap_or(&item.directory))
+        );
+        if options.new_window {
+            // Jetbrains IDEs decide about windows themselves when opening a project
+            event!(
+                Level::DEBUG,
+                item_id = %redact::path(item_id),
+                "Opening new windows not supported yet"
+            );
+        }
+        let uris = match &options.target_line {
+            Some((file, line)) => {
+                let path = Path::new(file);
+                let is_in_project = path.starts_with(&item.directory)
+                    && !path
+                        .components()
+                        .any(|c| c == std::path::Component::ParentDir);
+                if !is_in_project {
+                    return Err(zbus::fdo::Error::InvalidArgs(format!(
+                        "File {file} not in project {}",
+                        item.directory
+                    )));
+                }
+                // We can't pass a line through the desktop file, so just open the file for now
+                event!(
+                    Level::DEBUG,
+                    item_id = %redact::path(item_id),
+                    "Opening {} without line {line}",
+                    redact::path(file)
+                );
+                vec![file.clone()]
+            }
+            None => {
+                // Open all attached roots along with the project, to restore the whole window
+                let mut uris = std::iter::once(item.file.as_ref().unwrap_or(&item.directory))
+                    .chain(&item.attached_roots)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if self.options.open_residual_terms {
+                    let terms = options.terms.iter().map(String::as_str).collect::<Vec<_>>();
+                    for term in scoring::residual_terms(&item.name, &item.directory, &terms) {
+                        match contents::find_entry(Path::new(&item.directory), term) {
+                            Some(entry) => {
+                                event!(
+                                    Level::DEBUG,
+                                    item_id = %redact::path(item_id),
+                                    "Opening {} for term {term}",
+                                    redact::path(&entry.to_string_lossy())
+                                );
+                                uris.push(entry.to_string_lossy().to_string());
+                            }
+                            None => event!(
+                                Level::DEBUG,
+                                item_id = %redact::path(item_id),
+                                "No entry for term {term}"
+                            ),
+                        }
+                    }
+                }
+                uris
+            }
+        };
+        let app_id = self.route(item_id, item);
+        self.options
+            .activations
+            .record(&app_id.to_string(), &item.name, &item.directory);
+        let provenance = LaunchProvenance {
+            project_name: item.name.clone(),
+            project_directory: item.directory.clone(),
+        };
+        self.spawn_launch_on_default_main_context(
+            connection.clone(),
+            app_id,
+            uris,
+            Some(provenance),
+            item.trashed.clone(),
+            options.timestamp,
+        );
+        Ok(())
+    }
+
+    /// Activate the result with the given `item_id` with additional `options`.
+    ///
+    /// Always fail, because this service was built without support for launching apps.
+    #[cfg(not(feature = "launch"))]
+    pub fn activate_result_with_options(
+        &mut self,
+        _connection: &zbus::Connection,
+        item_id: &str,
+        _options: &ActivationOptions,
+    ) -> zbus::fdo::Result<()> {
+        event!(
+            Level::WARN,
+            item_id = %redact::path(item_id),
+            "Not activating {item_id}, launching apps not supported"
+        );
+        Err(zbus::fdo::Error::NotSupported(
+            "Launching apps not supported".to_string(),
+        ))
+    }
+
+    /// Get the paths of the next `count` recent projects to revalidate.
+    ///
+    /// Continue with the following projects on the next call, starting over after the last
+    /// project, and skip trashed projects, which we know are missing.  Return nothing for
+    /// providers which didn't load projects yet, which are suspended for their latency, e.g.
+    /// because of a hung network mount, and for all providers in power saver mode.
+    ///
+    /// Check these paths off the main loop, see [`ProjectPaths::check`], and then update
+    /// projects with [`Self::update_project_states`].
+    pub fn projects_to_revalidate(&mut self, count: usize) -> Vec<ProjectPaths> {
+        if !self.is_loaded
+            || self.options.power_saver.is_enabled()
+            || self.latency.is_suspended(Instant::now())
+        {
+            return Vec::new();
+        }
+        let mut paths = Vec::new();
+        for _ in 0..count.min(self.recent_projects.len()) {
+            if self.recent_projects.len() <= self.revalidate_at {
+                self.revalidate_at = 0;
+            }
+            let Some((id, project)) = self.recent_projects.get_index(self.revalidate_at) else {
+                break;
+            };
+            self.revalidate_at += 1;
+            if project.trashed.is_none() {
+                paths.push(ProjectPaths {
+                    id: id.clone(),
+                    directory: project.directory.clone(),
+                    file: project.file.clone(),
+                });
+            }
+        }
+        paths
+    }
+
+    /// Update recent projects with the `states` of revalidated projects.
+    ///
+    /// Like [`read_recent_projects`] drop projects whose project file vanished, and keep projects
+    /// whose directory is missing, e.g. on unmounted drives, but mark them as missing in their
+    /// description.  Ignore projects which we dropped meanwhile, e.g. in a reload.
+    ///
+    /// Return the number of dropped projects.
+    pub fn update_project_states(&mut self, states: Vec<(String, ProjectState)>) -> usize {
+        let mut dropped = 0;
+        for (id, state) in states {
+            let Some(index) = self.recent_projects.get_index_of(&id) else {
+                continue;
+            };
+            if state == ProjectState::MissingFile {
+                event!(
+                    Level::DEBUG,
+                    app_id = %self.app.id(),
+                    "Dropping {}, project file no longer exists",
+                    redact::path(&id)
+                );
+                self.recent_projects.shift_remove_index(index);
+                if index < self.revalidate_at {
+                    self.revalidate_at -= 1;
+                }
+                dropped += 1;
+            } else {
+                let missing = state == ProjectState::MissingDirectory;
+                let project = &mut self.recent_projects[index];
+                if project.missing != missing {
+                    event!(
+                        Level::DEBUG,
+                        app_id = %self.app.id(),
+                        "Project directory of {} {}",
+                        redact::path(&id),
+                        if missing { "went missing" } else { "is back" }
+                    );
+                    project.missing = missing;
+                }
+            }
+        }
+        if 0 < dropped {
+            self.update_folder_result_ids();
+        }
+        dropped
+    }
+
+    /// Load recent projects if this provider didn't load them yet.
+    ///
+    /// If loading failed before, wait for the retry delay before trying again, to avoid repeating
+    /// the same failing IO on every search.
+    pub fn ensure_loaded(&mut self) {
+        let now = Instant::now();
+        let is_stale_in_power_saver = self.is_loaded
+            && self.options.power_saver.is_enabled()
+            && is_stale(self.loaded_at, now);
+        let has_changed = self.is_loaded && self.watcher.take_changed();
+        if self.is_loaded && !is_stale_in_power_saver && !has_changed {
+            return;
+        }
+        if let Some(retry_at) = self.retry_at.filter(|retry_at| now < *retry_at) {
+            event!(
+                Level::DEBUG,
+                app_id = %self.app.id(),
+                "Not loading recent projects before retry in {}s",
+                retry_at.duration_since(now).as_secs()
+            );
+        } else {
+            if has_changed {
+                event!(Level::DEBUG, app_id = %self.app.id(), "Reloading changed recent projects");
+            } else if self.is_loaded {
+                event!(Level::DEBUG, app_id = %self.app.id(), "Refreshing stale recent projects in power saver mode");
+            } else {
+                event!(Level::DEBUG, app_id = %self.app.id(), "Loading recent projects on first use");
+            }
+            if let Err(error) = self.reload_recent_projects() {
+                event!(Level::ERROR, app_id = %self.app.id(), "Failed to load recent projects: {error:#}");
+            }
+        }
+    }
+
+    /// Score the recent project in `directory` for `terms`.
+    ///
+    /// Use the same scorer as searches, but return the score even if it's zero, i.e. if the
+    /// project doesn't match `terms`.  Fail if there's no recent project in `directory`, or if
+    /// private mode is enabled.
+    pub fn score_project(&mut self, terms: &[&str], directory: &str) -> zbus::fdo::Result<f64> {
+        if self.options.private_mode.is_enabled() {
+            return Err(zbus::fdo::Error::AccessDenied(
+                "Private mode enabled".to_string(),
+            ));
+        }
+        self.ensure_loaded();
+        self.take_indexed_contents();
+        let directory = directory.trim_end_matches('/');
+        let item = self
+            .recent_projects
+            .values()
+            .find(|item| item.directory == directory)
+            .ok_or_else(|| {
+                zbus::fdo::Error::InvalidArgs(format!("No recent project at {directory}"))
+            })?;
+        let components = scoring::explain_with_entries(
+            &item.name,
+            &item.directory,
+            self.content_index.entries(&item.directory),
+            terms,
+        );
+        event!(
+            Level::DEBUG,
+            app_id = %self.app.id(),
+            "Scored {} with {components:?}",
+            redact::path(&item.directory)
+        );
+        Ok(components.total())
+    }
+
+    /// Find all results for `terms`, best results first.
+    fn find_results(&mut self, terms: &[&str]) -> Vec<&str> {
+        if self.options.private_mode.is_enabled() {
+            event!(
+                Level::DEBUG,
+                "Private mode enabled, not returning any results"
+            );
+            return Vec::new();
+        }
+        if self.options.disabled_providers.is_disabled(self.app.id()) {
+            event!(
+                Level::DEBUG,
+                "Provider disabled in Gnome settings, not returning any results"
+            );
+            return Vec::new();
+        }
+        if self.latency.is_suspended(Instant::now()) {
+            event!(
+                Level::DEBUG,
+                "Provider suspended for exceeding its latency budget, not returning any results"
+            );
+            return Vec::new();
+        }
+        self.ensure_loaded();
+        if self.latency.is_suspended(Instant::now()) {
+            event!(
+                Level::DEBUG,
+                "Provider suspended for exceeding its reload budget, not returning any results"
+            );
+            return Vec::new();
+        }
+        self.dedupe_resolved_paths();
+        self.take_indexed_contents();
+        event!(
+            Level::DEBUG,
+            generation = self.generation,
+            "Searching generation {} of recent projects from {}, loaded {}s ago",
+            self.generation,
+            describe_sources(&self.sources),
+            self.loaded_at.map_or(0, |t| t.elapsed().as_secs())
+        );
+        // Loading recent projects counts towards the more generous reload budget instead, see
+        // reload_recent_projects
+        let started = Instant::now();
+        let scored = self
+            .recent_projects
+            .iter()
+            .filter_map(|(id, item)| {
+                let components = scoring::explain_with_entries(
+                    &item.name,
+                    &item.directory,
+                    self.content_index.entries(&item.directory),
+                    terms,
+                );
+                let score = components.total();
+                if 0.0 < score {
+                    // Structured fields, to explain rankings from the journal after the fact
+                    event!(
+                        Level::TRACE,
+                        item_id = %redact::path(id),
+                        score,
+                        name_score = components.name_score,
+                        directory_score = components.directory_score,
+                        path_score = components.path_score,
+                        word_bonus = components.word_bonus,
+                        entry_bonus = components.entry_bonus,
+                        opened_at = item.opened_at,
+                        "Scored {} with {score}",
+                        redact::path(id)
+                    );
+                }
+                (0.0 < score).then_some(scoring::ScoredResult {
+                    id,
+                    name: &item.name,
+                    score,
+                    opened_at: item.opened_at,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut ranked = scoring::rank_with_scores(scored, |name| glib::CollationKey::from(name));
+        if 0 < self.options.min_results {
+            let scores = ranked.iter().map(|(_, score)| *score).collect::<Vec<_>>();
+            let keep = scoring::cut_at_score_gap(&scores, self.options.min_results);
+            if keep < ranked.len() {
+                event!(
+                    Level::DEBUG,
+                    "Cutting {} result(s) with score {} or less",
+                    ranked.len() - keep,
+                    scores[keep]
+                );
+                ranked.truncate(keep);
+            }
+        }
+        if let Some((id, score)) = ranked.first() {
+            event!(
+                Level::DEBUG,
+                best_score = score,
+                "Ranked {} results, best {} with score {score}",
+                ranked.len(),
+                redact::path(id)
+            );
+        }
+        let mut ids = ranked
+            .into_iter()
+            .flat_map(|(id, _)| [Some(id), self.folder_result_ids.get(id).map(String::as_str)])
+            .flatten()
+            .collect::<Vec<_>>();
+        if self.matches_session_result(terms) {
+            ids.push(self.session_result_id.as_str());
+        }
+        metrics::record_search(self.app.id(), started.elapsed());
+        let latency = started.elapsed();
+        let suspended = self
+            .latency
+            .record(latency, self.options.latency_budget, Instant::now());
+        if suspended {
+            event!(
+                Level::WARN,
+                app_id = %self.app.id(),
+                "Search took {}ms, exceeding the latency budget of {}ms; skipping provider for {}s",
+                latency.as_millis(),
+                self.options.latency_budget.as_millis(),
+                REPROBE_INTERVAL.as_secs()
+            );
+        }
+        metrics::record_latency(self.app.id(), latency, suspended);
+        ids
+    }
+
+    /// Launch the app with `app_id` on the default main context, in the background.
+    ///
+    /// Launching an IDE can take a few seconds, so we do not wait for the launch to complete,
+    /// to avoid blocking other calls to this provider meanwhile.  Instead we spawn the launch on
+    /// the main context, and just log its outcome.
+    ///
+    /// If the project is in the trash, ask the user to `restore` it first, and only launch the app
+    /// if the user confirmed and restoring succeeded.
+    ///
+    /// After a successful launch run the activation hook if any, with the `timestamp` of the
+    /// activation.
+    #[cfg(feature = "launch")]
+    #[instrument(
+        skip(self, connection, app_id, uris, provenance),
+        fields(app_id = %app_id, uris = ?uris.iter().map(redact::path).collect::<Vec<_>>())
+    )]
+    fn spawn_launch_on_default_main_context(
+        &self,
+        connection: zbus::Connection,
+        app_id: AppId,
+        uris: Vec<String>,
+        provenance: Option<LaunchProvenance>,
+        restore: Option<TrashedProject>,
+        timestamp: Option<u32>,
+    ) {
+        let keep_environment = self.options.keep_environment.clone();
+        let call_timeout = self.options.call_timeout;
+        let activation_hook = self.options.activation_hook.clone();
+        let private_mode = self.options.private_mode.clone();
+        let icon = self.icon_of(&app_id);
+        let span = Span::current();
+        glib::MainContext::default().spawn_from_within(move || {
+            async move {
+                if let Some(trashed) = restore {
+                    let name = provenance
+                        .as_ref()
+                        .map_or("project", |provenance| provenance.project_name.as_str());
+                    let confirmed = trash::confirm_restore(
+                        &connection,
+                        &icon,
+                        name,
+                        &trashed.original_path,
+                        call_timeout,
+                        &private_mode,
+                    )
+                    .await;
+                    match confirmed {
+                        Ok(true) => {
+                            if let Err(error) = trash::restore(&trashed) {
+                                event!(Level::ERROR, %app_id, %error, "Failed to restore {}: {error:#}", trashed.original_path);
+                                return;
+                            }
+                        }
+                        Ok(false) => {
+                            event!(Level::INFO, %app_id, "Not restoring {}", trashed.original_path);
+                            return;
+                        }
+                        Err(error) => {
+                            event!(Level::ERROR, %app_id, %error, "Failed to ask for restoring {}: {error:#}", trashed.original_path);
+                            return;
+                        }
+                    }
+                }
+                let result = launch_app_in_new_scope(
+                    connection,
+                    app_id.clone(),
+                    uris,
+                    provenance,
+                    keep_environment,
+                    call_timeout,
+                    private_mode,
+                )
+                .await;
+                metrics::record_launch(&app_id, result.is_ok());
+                if result.is_ok() {
+                    event!(Level::DEBUG, %app_id, "Launched {app_id}");
+                    if let Some(hook) = activation_hook {
+                        if let Err(error) = hook.run(&app_id, timestamp).await {
+                            event!(Level::WARN, %app_id, "{:#}", error);
+                        }
+                    }
+                }
+            }
+            .instrument(span)
+        });
+    }
+}
+
+impl Reloadable for JetbrainsProductSearchProvider {
+    /// Reload providers which loaded recent projects, or failed to load them.
+    ///
+    /// Don't load lazy providers which were never used; they load fresh projects on first use
+    /// anyway.  Retry failed providers immediately, regardless of any backoff.  Skip providers
+    /// suspended for exceeding their latency budget; they reload on the next probe if needed.
+    fn wants_reload(&self) -> bool {
+        (self.is_loaded() || self.has_failed()) && !self.latency.is_suspended(Instant::now())
+    }
+
+    fn reloaded_at(&self) -> Option<Instant> {
+        self.loaded_at
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.reload_recent_projects()
+    }
+}
+
+/// Get the search provider at the object path of the call with the given `header`.
+///
+/// For interfaces which extend search providers, at the same object path.
+pub async fn provider_at(
+    server: &ObjectServer,
+    header: &Header<'_>,
+) -> zbus::fdo::Result<InterfaceRef<JetbrainsProductSearchProvider>> {
+    let path = header
+        .path()
+        .ok_or_else(|| zbus::fdo::Error::Failed("Missing object path".to_string()))?;
+    Ok(server
+        .interface::<_, JetbrainsProductSearchProvider>(path)
+        .await?)
+}
+
+/// The DBus interface of the search provider.
+///
+/// See <https://developer.gnome.org/SearchProvider/> for information.
+#[interface(name = "org.gnome.Shell.SearchProvider2")]
+impl JetbrainsProductSearchProvider {
+    /// Starts a search.
+    ///
+    /// This function is called when a new search is started. It gets an array of search terms as arguments,
+    /// and should return an array of result IDs. gnome-shell will call GetResultMetas for (some) of these result
+    /// IDs to get details about the result that can be be displayed in the result list.
+    #[instrument(skip(self), fields(app_id = %self.app.id()))]
+    fn get_initial_result_set(&mut self, terms: Vec<&str>) -> Vec<&str> {
+        let trace = CallTrace::start(self.app.id(), "GetInitialResultSet", &(&terms,));
+        event!(Level::DEBUG, "Searching for {:?}", terms);
+        let max_results = self.options.max_results;
+        let mut ids = self.find_results(&terms);
+        cap_results(&mut ids, max_results);
+        event!(
+            Level::DEBUG,
+            "Found ids {:?}",
+            ids.iter().map(redact::path).collect::<Vec<_>>()
+        );
+        trace.finish(ids)
+    }
+
+    /// Refine an ongoing search.
+    ///
+    /// This function is called to refine the initial search results when the user types more characters in the search entry.
+    /// It gets the previous search results and the current search terms as arguments, and should return an array of result IDs,
+    /// just like GetInitialResultSet.
+    #[instrument(skip(self), fields(app_id = %self.app.id()))]
+    fn get_subsearch_result_set(
+        &mut self,
+        previous_results: Vec<&str>,
+        terms: Vec<&str>,
+    ) -> Vec<&str> {
+        let trace = CallTrace::start(
+            self.app.id(),
+            "GetSubsearchResultSet",
+            &(&previous_results, &terms),
+        );
+        event!(
+            Level::DEBUG,
+            "Searching for {:?} in {:?}",
+            terms,
+            previous_results
+        );
+        // For simplicity just run the overall search again, and filter out everything not already
+        // matched.  If we capped the previous results, or cut them at a score gap, they lack
+        // matches, so don't filter then.
+        let max_results = self.options.max_results;
+        let was_capped = (0 < max_results && max_results <= previous_results.len())
+            || 0 < self.options.min_results;
+        let mut ids = self
+            .find_results(&terms)
+            .into_iter()
+            .filter(|id| was_capped || previous_results.contains(id))
+            .collect();
+        cap_results(&mut ids, max_results);
+        event!(
+            Level::DEBUG,
+            "Found ids {:?}",
+            ids.iter().map(redact::path).collect::<Vec<_>>()
+        );
+        trace.finish(ids)
+    }
+
+    /// Get metadata for results.
+    ///
+    /// This function is called to obtain detailed information for results.
+    /// It gets an array of result IDs as arguments, and should return a matching array of dictionaries
+    /// (ie one a{sv} for each passed-in result ID).
+    ///
+    /// The following pieces of information should be provided for each result:
+    //
+    //  - "id": the result ID
+    //  - "name": the display name for the result
+    //  - "icon": a serialized GIcon (see g_icon_serialize()), or alternatively,
+    //  - "gicon": a textual representation of a GIcon (see g_icon_to_string()), or alternatively,
+    //  - "icon-data": a tuple of type (iiibiiay) describing a pixbuf with width, height, rowstride, has-alpha, bits-per-sample, and image data
+    //  - "description": an optional short description (1-2 lines)
+    #[instrument(skip(self, results), fields(app_id = %self.app.id()))]
+    fn get_result_metas(
+        &mut self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        results: Vec<String>,
+    ) -> zbus::fdo::Result<Vec<HashMap<String, zvariant::Value<'_>>>> {
+        let trace = CallTrace::start(self.app.id(), "GetResultMetas", &(&results,));
+        event!(
+            Level::DEBUG,
+            "Getting meta info for {:?}",
+            results.iter().map(redact::path).collect::<Vec<_>>()
+        );
+        if self.options.private_mode.is_enabled() {
+            event!(
+                Level::DEBUG,
+                "Private mode enabled, not returning any meta info"
+            );
+            return Ok(trace.finish(Vec::new()));
+        }
+        if self.options.disabled_providers.is_disabled(self.app.id()) {
+            event!(
+                Level::DEBUG,
+                "Provider disabled in Gnome settings, not returning any meta info"
+            );
+            return Ok(trace.finish(Vec::new()));
+        }
+        self.ensure_loaded();
+        let mut metas = Vec::with_capacity(results.len());
+        let icon = self.icon_of(self.app.id());
+        for item_id in results {
+            // IDs from other providers or malformed IDs never match any of our results, so treat
+            // them like stale results, instead of failing the whole call.
+            let rejected = check_result_id(self.app.id(), &item_id).err();
+            let mut meta: HashMap<String, zvariant::Value> = HashMap::new();
+            let mut item_icon = icon.clone();
+            let folder_of = item_id
+                .strip_prefix(FOLDER_RESULT_PREFIX)
+                .and_then(|project_id| self.recent_projects.get(project_id));
+            if item_id == self.session_result_id {
+                meta.insert(
+                    "name".to_string(),
+                    format!("Reopen last {} session", self.app.name()).into(),
+                );
+                meta.insert(
+                    "description".to_string(),
+                    "Restore the windows of the last session".into(),
+                );
+            } else if let Some(item) = folder_of {
+                meta.insert(
+                    "name".to_string(),
+                    format!("Open {} in Files", item.display_name()).into(),
+                );
+                meta.insert("description".to_string(), item.directory.clone().into());
+                meta.insert("clipboardText".to_string(), item.directory.clone().into());
+                item_icon = "folder".to_string();
+            } else if let Some(item) = self.recent_projects.get(&item_id) {
+                event!(
+                    Level::DEBUG,
+                    item_id = %redact::path(&item_id),
+                    "Compiling meta info for {}",
+                    redact::path(&item_id)
+                );
+                meta.insert("name".to_string(), item.display_name().into());
+                let mut description = item.description();
+                if let Some(opened_at) = item.opened_at.filter(|_| self.options.show_opened_at) {
+                    let now = glib::real_time() / 1000;
+                    description.push_str(" · ");
+                    description.push_str(&describe_opened_at(opened_at, now));
+                }
+                meta.insert("description".to_string(), description.into());
+                // Gnome Shell 45 and newer let users copy this text from the result; older
+                // versions ignore it.
+                let path = item.file.as_ref().unwrap_or(&item.directory);
+                meta.insert("clipboardText".to_string(), path.clone().into());
+            } else {
+                let sender = header.sender().map(|s| s.to_string()).unwrap_or_default();
+                match rejected {
+                    Some(error) => event!(
+                        Level::WARN,
+                        item_id = %redact::path(&item_id),
+                        %sender,
+                        stale_results = ?self.options.stale_results,
+                        "{} requested meta info for invalid result {}: {}",
+                        sender,
+                        redact::path(&item_id),
+                        error
+                    ),
+                    None => event!(
+                        Level::WARN,
+                        item_id = %redact::path(&item_id),
+                        %sender,
+                        stale_results = ?self.options.stale_results,
+                        "{} requested meta info for unknown result {}",
+                        sender,
+                        redact::path(&item_id)
+                    ),
+                }
+                match self.options.stale_results {
+                    StaleResults::Skip => continue,
+                    StaleResults::Placeholder => {
+                        meta.insert("name".to_string(), "unavailable".into());
+                        meta.insert(
+                            "description".to_string(),
+                            "This project is no longer available".into(),
+                        );
+                    }
+                }
+            }
+            event!(
+                Level::DEBUG,
+                item_id = %redact::path(&item_id),
+                "Using icon {}",
+                item_icon
+            );
+            meta.insert("id".to_string(), item_id.into());
+            meta.insert("gicon".to_string(), item_icon.into());
+            metas.push(meta);
+        }
+        if redact::is_redacting() {
+            event!(Level::DEBUG, "Return meta info for {} results", metas.len());
+        } else {
+            event!(Level::DEBUG, "Return meta info {:?}", &metas);
+        }
+        Ok(trace.finish(metas))
+    }
+
+    /// Activate an individual result.
+    ///
+    /// This function is called when the user clicks on an individual result to open it in the application.
+    /// The arguments are the result ID, the current search terms and a timestamp.
+    ///
+    /// Launches the underlying app with the path to the selected item in the background, and
+    /// returns immediately.
+    #[instrument(
+        skip(self, connection, item_id),
+        fields(app_id = %self.app.id(), item_id = %redact::path(item_id))
+    )]
+    fn activate_result(
+        &mut self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        item_id: &str,
+        terms: Vec<&str>,
+        timestamp: u32,
+    ) -> zbus::fdo::Result<()> {
+        let trace = CallTrace::start(
+            self.app.id(),
+            "ActivateResult",
+            &(item_id, &terms, timestamp),
+        );
+        event!(
+            Level::DEBUG,
+            item_id = %redact::path(item_id),
+            "Activating result {} for {:?} at {}",
+            redact::path(item_id),
+            terms,
+            timestamp
+        );
+        let options = ActivationOptions {
+            timestamp: Some(timestamp),
+            terms: terms.iter().map(ToString::to_string).collect(),
+            ..ActivationOptions::default()
+        };
+        self.activate_result_with_options(connection, item_id, &options)
+            .map(|reply| trace.finish(reply))
+    }
+
+    /// Launch a search within the App.
+    ///
+    /// This function is called when the user clicks on the provider icon to display more search results in the application.
+    /// The arguments are the current search terms and a timestamp.
+    ///
+    /// Currently it simply launches the app without any arguments.
+    #[instrument(skip(self, connection), fields(app_id = %self.app.id()))]
+    fn launch_search(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        _terms: Vec<String>,
+        timestamp: u32,
+    ) -> zbus::fdo::Result<()> {
+        event!(Level::DEBUG, "Launching app directly");
+        #[cfg(feature = "launch")]
+        {
+            self.spawn_launch_on_default_main_context(
+                connection.clone(),
+                self.app.id().clone(),
+                Vec::new(),
+                None,
+                None,
+                Some(timestamp),
+            );
+            Ok(())
+        }
+        #[cfg(not(feature = "launch"))]
+        {
+            let _ = (connection, timestamp);
+            Err(zbus::fdo::Error::NotSupported(
+                "Launching apps not supported".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    fn project(name: &str) -> (String, JetbrainsRecentProject) {
+        (
+            format!("jetbrains-recent-project-test-/{name}"),
+            JetbrainsRecentProject {
+                name: name.to_string(),
+                directory: format!("/{name}"),
+                file: None,
+                removed_at: None,
+                solution_kind: None,
+                target_framework: None,
+                opened_at: None,
+                branch: None,
+                qualifier: None,
+                trashed: None,
+                attached_roots: Vec::new(),
+                missing: false,
+            },
+        )
+    }
+
+    fn idea() -> App {
+        App {
+            id: AppId::try_from("jetbrains-idea.desktop").unwrap(),
+            name: "IntelliJ IDEA Ultimate".to_string(),
+            icon: "jetbrains-idea".to_string(),
+        }
+    }
+
+    /// Create a provider for IntelliJ IDEA which already loaded `projects`.
+    fn loaded_provider(
+        options: ProviderOptions,
+        projects: impl IntoIterator<Item = (String, JetbrainsRecentProject)>,
+    ) -> JetbrainsProductSearchProvider {
+        let mut provider = JetbrainsProductSearchProvider::new(
+            idea(),
+            &crate::providers::PROVIDERS[0].config,
+            options,
+        );
+        provider.recent_projects = projects.into_iter().collect();
+        provider.is_loaded = true;
+        provider.loaded_at = Some(Instant::now());
+        provider
+    }
+
+    /// A `recentProjects.xml` file which lists the project at `directory`.
+    fn recent_projects_xml(directory: &Path) -> String {
+        format!(
+            r#"<application>
+  <component name="RecentProjectsManager">
+    <option name="additionalInfo">
+      <map>
+        <entry key="{}">
+          <value>
+            <RecentProjectMetaInfo />
+          </value>
+        </entry>
+      </map>
+    </option>
+  </component>
+</application>
+"#,
+            directory.display()
+        )
+    }
+
+    #[test]
+    fn parse_and_normalize_app_ids() {
+        assert_eq!(
+            "jetbrains-idea.desktop"
+                .parse::<AppId>()
+                .unwrap()
+                .to_string(),
+            "jetbrains-idea.desktop"
+        );
+        assert_eq!(
+            "jetbrains-idea".parse::<AppId>().unwrap().to_string(),
+            "jetbrains-idea.desktop"
+        );
+        assert_eq!("".parse::<AppId>(), Err(AppIdError::Empty));
+        assert_eq!(".desktop".parse::<AppId>(), Err(AppIdError::Empty));
+        assert_eq!(
+            "/usr/share/applications/idea.desktop".parse::<AppId>(),
+            Err(AppIdError::PathSeparator(
+                "/usr/share/applications/idea.desktop".to_string()
+            ))
+        );
+        assert_eq!(
+            "jetbrains idea".parse::<AppId>(),
+            Err(AppIdError::InvalidCharacter(
+                "jetbrains idea".to_string(),
+                ' '
+            ))
+        );
+        assert_eq!(
+            "idea.desktop\n".parse::<AppId>(),
+            Err(AppIdError::InvalidCharacter(
+                "idea.desktop\n".to_string(),
+                '\n'
+            ))
+        );
+    }
+
+    #[test]
+    fn locate_project_directories_and_files() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("locate-XXXXXX")).unwrap();
+        let directory_s = directory.to_str().unwrap().to_string();
+        let solution = directory.join("MyGame.sln");
+        std::fs::write(&solution, "").unwrap();
+        let mut names = ProjectNameCache::default();
+
+        assert_eq!(
+            locate_project(solution.to_str().unwrap(), &mut names),
+            Some(ProjectLocation {
+                name: "MyGame".to_string(),
+                directory: directory_s.clone(),
+                file: Some(solution.to_str().unwrap().to_string()),
+            })
+        );
+        assert_eq!(
+            locate_project(&directory_s, &mut names),
+            Some(ProjectLocation {
+                name: directory.file_name().unwrap().to_str().unwrap().to_string(),
+                directory: directory_s.clone(),
+                file: None,
+            })
+        );
+        assert_eq!(
+            locate_project(directory.join("Gone.sln").to_str().unwrap(), &mut names),
+            None
+        );
+        assert_eq!(
+            locate_missing_project(directory.join("Gone.sln").to_str().unwrap(), &mut names),
+            None
+        );
+        assert_eq!(
+            locate_missing_project(directory.join("gone").to_str().unwrap(), &mut names),
+            Some(ProjectLocation {
+                name: "gone".to_string(),
+                directory: directory.join("gone").to_str().unwrap().to_string(),
+                file: None,
+            })
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn relocate_project_location() {
+        let location = ProjectLocation {
+            name: "MyGame".to_string(),
+            directory: "/work/foo/MyGame".to_string(),
+            file: Some("/work/foo/MyGame/MyGame.sln".to_string()),
+        };
+        assert_eq!(
+            location.relocate("/work/foo", "/home/foo"),
+            ProjectLocation {
+                name: "MyGame".to_string(),
+                directory: "/home/foo/MyGame".to_string(),
+                file: Some("/home/foo/MyGame/MyGame.sln".to_string()),
+            }
+        );
+        let location = ProjectLocation {
+            name: "mdcat".to_string(),
+            directory: "/srv/mdcat".to_string(),
+            file: None,
+        };
+        assert_eq!(
+            location.relocate("/work/foo", "/home/foo"),
+            ProjectLocation {
+                name: "mdcat".to_string(),
+                directory: "/srv/mdcat".to_string(),
+                file: None,
+            }
+        );
+    }
+
+    #[test]
+    fn cache_project_names_by_modification_time() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("names-XXXXXX")).unwrap();
+        let idea = directory.join(".idea");
+        std::fs::create_dir(&idea).unwrap();
+        std::fs::write(idea.join(".name"), "Foo\n").unwrap();
+        let set_modified = |seconds: u64| {
+            File::open(&idea)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+        set_modified(1000);
+
+        let mut names = ProjectNameCache::default();
+        assert_eq!(names.read_name(&directory), Some("Foo".to_string()));
+        // Unchanged .idea directory, so we don't read the name again
+        std::fs::write(idea.join(".name"), "Bar\n").unwrap();
+        set_modified(1000);
+        assert_eq!(names.read_name(&directory), Some("Foo".to_string()));
+        set_modified(2000);
+        assert_eq!(names.read_name(&directory), Some("Bar".to_string()));
+
+        names.retain(["/foo"]);
+        assert!(names.0.is_empty());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn dedupe_symlink_aliases_keeps_most_recent_path() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("dedupe-XXXXXX")).unwrap();
+        let data = directory.join("data");
+        std::fs::create_dir(&data).unwrap();
+        let work = directory.join("work");
+        std::os::unix::fs::symlink(&data, &work).unwrap();
+        let project_at = |path: &Path, opened_at: i64| {
+            let (id, mut project) = project(path.to_str().unwrap());
+            project.directory = path.to_str().unwrap().to_string();
+            project.opened_at = Some(opened_at);
+            (id, project)
+        };
+        let mut cache = HashMap::from([("/gone".to_string(), None)]);
+        let mut projects = IndexMap::from([
+            project_at(&data, 1),
+            project_at(&work, 2),
+            project_at(&directory, 3),
+        ]);
+        let unresolved = dedupe_symlink_aliases(&mut projects, &mut cache);
+        assert_eq!(projects.len(), 3);
+        assert_eq!(cache.len(), 0);
+        cache.extend(
+            unresolved
+                .into_iter()
+                .map(|path| (path.clone(), std::fs::canonicalize(path).ok())),
+        );
+        assert!(dedupe_symlink_aliases(&mut projects, &mut cache).is_empty());
+        assert_eq!(
+            projects.values().map(|p| p.directory()).collect::<Vec<_>>(),
+            vec![work.to_str().unwrap(), directory.to_str().unwrap()]
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache[work.to_str().unwrap()],
+            Some(data.canonicalize().unwrap())
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn dedupe_symlink_aliases_in_background() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("dedupe-XXXXXX")).unwrap();
+        let data = directory.join("data");
+        std::fs::create_dir(&data).unwrap();
+        let work = directory.join("work");
+        std::os::unix::fs::symlink(&data, &work).unwrap();
+        let mut provider = loaded_provider(
+            ProviderOptions {
+                dedupe_symlinks: true,
+                ..ProviderOptions::default()
+            },
+            [(&data, 1), (&work, 2)].map(|(path, opened_at)| {
+                let (id, mut project) = project(path.to_str().unwrap());
+                project.directory = path.to_str().unwrap().to_string();
+                project.opened_at = Some(opened_at);
+                (id, project)
+            }),
+        );
+
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                context.block_on(async {
+                    let paths = provider
+                        .recent_projects
+                        .values()
+                        .map(|project| project.directory.clone())
+                        .collect();
+                    provider.resolve_canonical_paths(paths);
+                    while provider.resolved_paths.lock().unwrap().len() < 2 {
+                        glib::timeout_future(Duration::from_millis(10)).await;
+                    }
+                })
+            })
+            .unwrap();
+        assert_eq!(provider.recent_projects.len(), 2);
+        provider.dedupe_resolved_paths();
+        assert_eq!(
+            provider
+                .recent_projects
+                .values()
+                .map(|p| p.directory())
+                .collect::<Vec<_>>(),
+            vec![work.to_str().unwrap()]
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn index_contents_in_background() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("index-XXXXXX")).unwrap();
+        std::fs::create_dir(directory.join("Docs")).unwrap();
+        let mut provider = loaded_provider(
+            ProviderOptions {
+                index_contents: 1,
+                ..ProviderOptions::default()
+            },
+            [{
+                let (id, mut project) = project("index");
+                project.directory = directory.to_str().unwrap().to_string();
+                (id, project)
+            }],
+        );
+
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                context.block_on(async {
+                    provider.update_content_index();
+                    assert!(provider.find_results(&["index", "docs"]).is_empty());
+                    while provider.indexed_contents.lock().unwrap().is_empty() {
+                        glib::timeout_future(Duration::from_millis(10)).await;
+                    }
+                })
+            })
+            .unwrap();
+        assert_eq!(
+            provider.find_results(&["index", "docs"]),
+            vec!["jetbrains-recent-project-test-/index"]
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn offer_session_result_if_terms_match_app_name() {
+        let provider = JetbrainsProductSearchProvider::new(
+            idea(),
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions::default(),
+        );
+        assert_eq!(
+            provider.session_result_id,
+            "jetbrains-last-session-jetbrains-idea.desktop"
+        );
+        assert!(provider.matches_session_result(&["idea"]));
+        assert!(provider.matches_session_result(&["intellij", "ult"]));
+        assert!(!provider.matches_session_result(&["mdcat"]));
+        assert!(!provider.matches_session_result(&["idea", "mdcat"]));
+        assert!(!provider.matches_session_result(&[]));
+    }
+
+    #[test]
+    fn cap_results_after_ranking() {
+        let mut provider = loaded_provider(
+            ProviderOptions {
+                max_results: 2,
+                ..ProviderOptions::default()
+            },
+            ["mdcat-fork", "old-mdcat", "mdcat"].map(project),
+        );
+
+        provider.options.max_results = 0;
+        let all = provider
+            .get_initial_result_set(vec!["mdcat"])
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        assert_eq!(all.len(), 3);
+        provider.options.max_results = 2;
+        let capped = provider.get_initial_result_set(vec!["mdcat"]);
+        assert_eq!(capped, &all[..2]);
+
+        // Refining capped results must not drop matches beyond the cap.
+        let fork = "jetbrains-recent-project-test-/mdcat-fork";
+        assert!(!capped.contains(&fork));
+        let previous = capped
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        let ids = provider.get_subsearch_result_set(
+            previous.iter().map(String::as_str).collect(),
+            vec!["mdcat", "fork"],
+        );
+        assert_eq!(ids, vec![fork]);
+    }
+
+    #[test]
+    fn keep_session_result_when_capping() {
+        let mut provider = loaded_provider(
+            ProviderOptions {
+                max_results: 2,
+                ..ProviderOptions::default()
+            },
+            ["idea", "idea-plugin", "idea-tools"].map(project),
+        );
+
+        let ids = provider.get_initial_result_set(vec!["idea"]);
+        assert_eq!(
+            ids,
+            vec![
+                "jetbrains-recent-project-test-/idea",
+                "jetbrains-last-session-jetbrains-idea.desktop"
+            ]
+        );
+    }
+
+    #[test]
+    fn route_projects_by_project_file() {
+        let app = App {
+            id: AppId::try_from("jetbrains-clion.desktop").unwrap(),
+            name: "CLion".to_string(),
+            icon: "jetbrains-clion".to_string(),
+        };
+        let provider = JetbrainsProductSearchProvider::new(
+            app,
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions {
+                routes: Routes::new("/home/foo", [("/srv/**/*.sln", "jetbrains-rider.desktop")]),
+                ..ProviderOptions::default()
+            },
+        );
+        let (id, mut solution) = project("MyGame");
+        solution.directory = "/srv/games".to_string();
+        solution.file = Some("/srv/games/MyGame.sln".to_string());
+        assert_eq!(
+            provider.route(&id, &solution),
+            AppId::try_from("jetbrains-rider.desktop").unwrap()
+        );
+        solution.file = None;
+        assert_eq!(
+            provider.route(&id, &solution),
+            AppId::try_from("jetbrains-clion.desktop").unwrap()
+        );
+    }
+
+    #[test]
+    fn cut_results_at_score_gap() {
+        let (id, mut nested) = project("mdcat/docs");
+        nested.name = "docs".to_string();
+        let mut provider = loaded_provider(
+            ProviderOptions::default(),
+            [project("mdcat"), project("mdcat-fork"), (id, nested)],
+        );
+
+        let all = provider
+            .get_initial_result_set(vec!["mdcat"])
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2], "jetbrains-recent-project-test-/mdcat/docs");
+
+        provider.options.min_results = 1;
+        let cut = provider.get_initial_result_set(vec!["mdcat"]);
+        assert_eq!(cut, &all[..2]);
+        provider.options.min_results = 3;
+        assert_eq!(provider.get_initial_result_set(vec!["mdcat"]), all);
+
+        // Refining cut results must not drop matches beyond the cut.
+        provider.options.min_results = 1;
+        let ids = provider.get_subsearch_result_set(
+            vec![
+                "jetbrains-recent-project-test-/mdcat",
+                "jetbrains-recent-project-test-/mdcat-fork",
+            ],
+            vec!["mdcat", "docs"],
+        );
+        assert_eq!(ids, vec!["jetbrains-recent-project-test-/mdcat/docs"]);
+    }
+
+    #[test]
+    fn rank_recently_opened_projects_first_on_equal_scores() {
+        let opened_at = |name: &str, opened_at: Option<i64>| {
+            let (id, mut project) = project(name);
+            project.opened_at = opened_at;
+            (id, project)
+        };
+        let mut provider = loaded_provider(
+            ProviderOptions::default(),
+            [
+                opened_at("mdcat-old", Some(1)),
+                opened_at("mdcat-unk", None),
+                opened_at("mdcat-new", Some(2)),
+            ],
+        );
+
+        assert_eq!(
+            provider.get_initial_result_set(vec!["mdcat"]),
+            vec![
+                "jetbrains-recent-project-test-/mdcat-new",
+                "jetbrains-recent-project-test-/mdcat-old",
+                "jetbrains-recent-project-test-/mdcat-unk",
+            ]
+        );
+    }
+
+    #[test]
+    fn exclude_load_from_latency_budget() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("latency-XXXXXX")).unwrap();
+        let project = directory.join("mdcat");
+        std::fs::create_dir_all(project.join(".idea")).unwrap();
+        // Reading the name of the project blocks until we write to the FIFO, to make loading slow
+        let name_file = project.join(".idea").join(".name");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&name_file)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let options = directory.join("config").join("CLion2024.2").join("options");
+        std::fs::create_dir_all(&options).unwrap();
+        std::fs::write(
+            options.join("recentProjects.xml"),
+            recent_projects_xml(&project),
+        )
+        .unwrap();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            std::fs::write(name_file, "mdcat").unwrap();
+        });
+
+        let app = App {
+            id: AppId::try_from("jetbrains-clion.desktop").unwrap(),
+            name: "CLion".to_string(),
+            icon: "clion".to_string(),
+        };
+        let mut provider = JetbrainsProductSearchProvider::new(
+            app,
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions {
+                config_roots: vec![directory.join("config")],
+                latency_budget: Duration::from_millis(100),
+                ..ProviderOptions::default()
+            },
+        );
+        let started = Instant::now();
+        let results = provider
+            .get_initial_result_set(vec!["mdcat"])
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        assert!(Duration::from_millis(200) <= started.elapsed());
+        writer.join().unwrap();
+        assert_eq!(
+            results,
+            vec![ProjectId::new(provider.app.id(), &project.display().to_string()).to_string()]
+        );
+        assert!(!provider.latency.is_suspended(Instant::now()));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn suspend_provider_exceeding_reload_budget() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("reload-XXXXXX")).unwrap();
+        let project = directory.join("mdcat");
+        std::fs::create_dir_all(&project).unwrap();
+        let options = directory.join("config").join("CLion2024.2").join("options");
+        std::fs::create_dir_all(&options).unwrap();
+        std::fs::write(
+            options.join("recentProjects.xml"),
+            recent_projects_xml(&project),
+        )
+        .unwrap();
+
+        let mut provider = JetbrainsProductSearchProvider::new(
+            idea(),
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions {
+                config_roots: vec![directory.join("config")],
+                reload_budget: Duration::from_nanos(1),
+                ..ProviderOptions::default()
+            },
+        );
+        assert!(provider.get_initial_result_set(vec!["mdcat"]).is_empty());
+        assert!(provider.is_loaded());
+        assert!(provider
+            .suspended_for()
+            .is_some_and(|suspended_for| suspended_for <= REPROBE_INTERVAL));
+        assert!(!provider.wants_reload());
+
+        provider.options.reload_budget = Duration::ZERO;
+        provider.latency = LatencyTracker::default();
+        assert_eq!(provider.get_initial_result_set(vec!["mdcat"]).len(), 1);
+        assert_eq!(provider.suspended_for(), None);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn skip_corrupt_recent_projects_files() {
+        let directory = glib::mkdtemp(std::env::temp_dir().join("corrupt-XXXXXX")).unwrap();
+        let project = directory.join("mdcat");
+        std::fs::create_dir_all(&project).unwrap();
+        let config = directory.join("config");
+        let old = config.join("CLion2024.1").join("options");
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::write(
+            old.join("recentProjects.xml"),
+            recent_projects_xml(&project),
+        )
+        .unwrap();
+        let new = config.join("CLion2024.2").join("options");
+        std::fs::create_dir_all(&new).unwrap();
+        std::fs::write(
+            new.join("recentProjects.xml"),
+            "<application>\n  <component name=\"RecentProj",
+        )
+        .unwrap();
+
+        let options = ReadOptions {
+            config_roots: std::slice::from_ref(&config),
+            all_versions: true,
+            limits: &Limits::default(),
+            home_roots: &[],
+            find_trashed: false,
+        };
+        let mut warnings = Vec::new();
+        let mut sources = Vec::new();
+        let projects = read_recent_projects(
+            &crate::providers::PROVIDERS[0].config,
+            &AppId::try_from("jetbrains-clion.desktop").unwrap(),
+            &options,
+            &mut warnings,
+            &mut sources,
+            &mut ProjectNameCache::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            projects.values().map(|p| p.directory()).collect::<Vec<_>>(),
+            vec![project.to_str().unwrap()]
+        );
+        assert_eq!(
+            sources.iter().map(|s| s.path.as_path()).collect::<Vec<_>>(),
+            vec![old.join("recentProjects.xml")]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(&format!(
+            "Skipping recent projects file at {}, failed to parse",
+            new.join("recentProjects.xml").display()
+        )));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn disabled_provider_returns_nothing() {
+        let options = ProviderOptions::default();
+        let disabled = options.disabled_providers.clone();
+        let mut provider = loaded_provider(options, ["mdcat"].map(project));
+        assert_eq!(provider.get_initial_result_set(vec!["mdcat"]).len(), 1);
+
+        disabled.set(["jetbrains-idea.desktop".to_string()], [], false);
+        assert!(provider.get_initial_result_set(vec!["mdcat"]).is_empty());
+        assert!(provider
+            .get_subsearch_result_set(vec![], vec!["mdcat"])
+            .is_empty());
+    }
+
+    #[test]
+    fn score_project_by_directory() {
+        let options = ProviderOptions::default();
+        let private_mode = options.private_mode.clone();
+        let mut provider = loaded_provider(options, ["mdcat", "gnome-shell"].map(project));
+        assert_eq!(
+            provider.score_project(&["mdcat"], "/mdcat/").unwrap(),
+            scoring::score("mdcat", "/mdcat", &["mdcat"])
+        );
+        assert_eq!(provider.score_project(&["mdcat"], "/gnome-shell"), Ok(0.0));
+        assert!(matches!(
+            provider.score_project(&["mdcat"], "/missing"),
+            Err(zbus::fdo::Error::InvalidArgs(_))
+        ));
+        private_mode.enable(Instant::now(), Duration::from_secs(60));
+        assert!(matches!(
+            provider.score_project(&["mdcat"], "/mdcat"),
+            Err(zbus::fdo::Error::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn project_ids_round_trip() {
+        let idea = AppId::try_from("jetbrains-idea.desktop").unwrap();
+        for path in [
+            "/home/foo/my-app",
+            "/home/foo/-/my-app-",
+            "/home/foo/with space/ümlaut",
+            "/home/foo/jetbrains-recent-project-idea-/nested",
+            "/",
+        ] {
+            let id = ProjectId::new(&idea, path);
+            let s = id.to_string();
+            assert_eq!(
+                s,
+                format!("jetbrains-recent-project-jetbrains-idea.desktop-{path}")
+            );
+            assert_eq!(s.parse::<ProjectId>(), Ok(id.clone()), "{s:?}");
+            assert_eq!(id.app_id(), "jetbrains-idea.desktop");
+            assert_eq!(id.path, path);
+        }
+    }
+
+    #[test]
+    fn parse_project_ids() {
+        assert_eq!(
+            "jetbrains-recent-project-test-/mdcat".parse::<ProjectId>(),
+            Ok(ProjectId {
+                app_id: "test".to_string(),
+                path: "/mdcat".to_string()
+            })
+        );
+        for missing_prefix in [
+            "",
+            "jetbrains-open-folder-jetbrains-recent-project-test-/mdcat",
+            "jetbrains-recent-projec-test-/mdcat",
+        ] {
+            assert_eq!(
+                missing_prefix.parse::<ProjectId>(),
+                Err(ProjectIdError::Prefix(missing_prefix.to_string()))
+            );
+        }
+        for malformed in [
+            "jetbrains-recent-project-",
+            "jetbrains-recent-project--/home/foo",
+            "jetbrains-recent-project-jetbrains-idea.desktop",
+            "jetbrains-recent-project-jetbrains-idea.desktop-home/foo",
+            "jetbrains-recent-project-/home/foo",
+        ] {
+            assert_eq!(
+                malformed.parse::<ProjectId>(),
+                Err(ProjectIdError::Malformed(malformed.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_result_ids() {
+        let project = ProjectId {
+            app_id: "jetbrains-idea.desktop".to_string(),
+            path: "/home/foo/my-app".to_string(),
+        };
+        assert_eq!(
+            ResultId::parse("jetbrains-recent-project-jetbrains-idea.desktop-/home/foo/my-app"),
+            Some(ResultId::Project(project.clone()))
+        );
+        assert_eq!(
+            ResultId::parse(
+                "jetbrains-open-folder-jetbrains-recent-project-jetbrains-idea.desktop-/home/foo/my-app"
+            ),
+            Some(ResultId::Folder(project))
+        );
+        assert_eq!(
+            ResultId::parse("jetbrains-last-session-jetbrains-idea.desktop"),
+            Some(ResultId::Session {
+                app_id: "jetbrains-idea.desktop".to_string()
+            })
+        );
+        for malformed in [
+            "",
+            "jetbrains-recent-project-",
+            "jetbrains-recent-project--/home/foo",
+            "jetbrains-recent-project-jetbrains-idea.desktop",
+            "jetbrains-last-session-",
+            "jetbrains-open-folder-/home/foo",
+            "plugin-code-mdcat",
+        ] {
+            assert_eq!(ResultId::parse(malformed), None, "{malformed:?}");
+        }
+    }
+
+    #[test]
+    fn reject_result_ids_of_other_providers() {
+        let idea = AppId::try_from("jetbrains-idea.desktop").unwrap();
+        assert!(check_result_id(
+            &idea,
+            "jetbrains-recent-project-jetbrains-idea.desktop-/mdcat"
+        )
+        .is_ok());
+        assert!(check_result_id(&idea, "jetbrains-last-session-jetbrains-idea.desktop").is_ok());
+        for foreign in [
+            "jetbrains-recent-project-jetbrains-clion.desktop-/mdcat",
+            "jetbrains-open-folder-jetbrains-recent-project-jetbrains-clion.desktop-/mdcat",
+            "jetbrains-last-session-jetbrains-clion.desktop",
+            "jetbrains-recent-project-jetbrains-idea.desktop",
+        ] {
+            assert!(
+                matches!(
+                    check_result_id(&idea, foreign),
+                    Err(zbus::fdo::Error::InvalidArgs(_))
+                ),
+                "{foreign:?}"
+            );
+        }
+
+        let message = zbus::Message::method("/", "GetResultMetas")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        let own = "jetbrains-recent-project-jetbrains-idea.desktop-/mdcat".to_string();
+        let (_, project) = project("mdcat");
+        let mut provider = loaded_provider(ProviderOptions::default(), [(own.clone(), project)]);
+        let results = vec![
+            "jetbrains-recent-project-jetbrains-clion.desktop-/mdcat".to_string(),
+            own.clone(),
+            "malformed".to_string(),
+        ];
+        // Skip foreign and malformed IDs, but still return meta info for our own results
+        let metas = provider
+            .get_result_metas(message.header(), results.clone())
+            .unwrap();
+        assert_eq!(
+            metas
+                .iter()
+                .map(|m| m["id"].to_string())
+                .collect::<Vec<_>>(),
+            vec![format!("{own:?}")]
+        );
+        provider.options.stale_results = StaleResults::Placeholder;
+        let metas = provider
+            .get_result_metas(message.header(), results)
+            .unwrap();
+        assert_eq!(
+            metas
+                .iter()
+                .map(|m| m["name"].to_string())
+                .collect::<Vec<_>>(),
+            vec!["\"unavailable\"", "\"mdcat\"", "\"unavailable\""]
+        );
+    }
+
+    #[test]
+    fn revalidate_projects_incrementally() {
+        let root = glib::mkdtemp(std::env::temp_dir().join("projects-XXXXXX")).unwrap();
+        let mut provider = JetbrainsProductSearchProvider::new(
+            idea(),
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions::default(),
+        );
+        provider.recent_projects = ["mdcat", "unmounted", "Fork.sln", "Gone.sln", "trashed"]
+            .into_iter()
+            .map(|name| {
+                let (id, mut project) = project(name);
+                if name.ends_with(".sln") {
+                    project.directory = root.to_string_lossy().to_string();
+                    project.file = Some(root.join(name).to_string_lossy().to_string());
+                } else {
+                    project.directory = root.join(name).to_string_lossy().to_string();
+                }
+                match name {
+                    "mdcat" => std::fs::create_dir(&project.directory).unwrap(),
+                    "Fork.sln" => std::fs::write(project.file.as_ref().unwrap(), "").unwrap(),
+                    "trashed" => {
+                        project.trashed = Some(TrashedProject {
+                            uri: "trash:///trashed".to_string(),
+                            original_path: project.directory.clone(),
+                        });
+                    }
+                    _ => {}
+                }
+                (id, project)
+            })
+            .collect();
+        let revalidate = |provider: &mut JetbrainsProductSearchProvider| {
+            let states = provider
+                .projects_to_revalidate(2)
+                .into_iter()
+                .map(ProjectPaths::check)
+                .collect::<Vec<_>>();
+            let checked = states.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+            (checked, provider.update_project_states(states))
+        };
+        let ids = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| format!("jetbrains-recent-project-test-/{name}"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(revalidate(&mut provider), (Vec::new(), 0));
+        provider.is_loaded = true;
+        // Keep the missing directory of unmounted, like the loader does, but mark it as missing
+        assert_eq!(revalidate(&mut provider), (ids(&["mdcat", "unmounted"]), 0));
+        assert!(!provider.recent_projects[0].missing);
+        assert!(provider.recent_projects[1].missing);
+        assert!(provider.recent_projects[1]
+            .description()
+            .ends_with(" (missing)"));
+        assert_eq!(
+            revalidate(&mut provider),
+            (ids(&["Fork.sln", "Gone.sln"]), 1)
+        );
+        assert_eq!(provider.recent_projects.len(), 4);
+        // Skip the trashed project, and wrap around to mdcat
+        assert_eq!(revalidate(&mut provider), (ids(&["mdcat"]), 0));
+        std::fs::remove_file(root.join("Fork.sln")).unwrap();
+        std::fs::create_dir(root.join("unmounted")).unwrap();
+        assert_eq!(
+            revalidate(&mut provider),
+            (ids(&["unmounted", "Fork.sln"]), 1)
+        );
+        assert!(!provider.recent_projects[1].missing);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            provider
+                .recent_projects
+                .values()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["mdcat", "unmounted", "trashed"]
+        );
+    }
+
+    #[test]
+    fn offer_folder_results_after_projects() {
+        let mut provider = loaded_provider(
+            ProviderOptions {
+                folder_results: true,
+                ..ProviderOptions::default()
+            },
+            ["mdcat", "gnome-shell"].map(project),
+        );
+        provider.update_folder_result_ids();
+        assert_eq!(
+            provider.get_initial_result_set(vec!["mdcat"]),
+            vec![
+                "jetbrains-recent-project-test-/mdcat",
+                "jetbrains-open-folder-jetbrains-recent-project-test-/mdcat"
+            ]
+        );
+
+        provider.options.folder_results = false;
+        provider.update_folder_result_ids();
+        assert_eq!(
+            provider.get_initial_result_set(vec!["mdcat"]),
+            vec!["jetbrains-recent-project-test-/mdcat"]
+        );
+    }
+
+    #[test]
+    fn count_cache_size() {
+        let mut provider = JetbrainsProductSearchProvider::new(
+            idea(),
+            &crate::providers::PROVIDERS[0].config,
+            ProviderOptions::default(),
+        );
+        assert_eq!(provider.cache_size(), CacheSize::default());
+        let (id, mut project) = project("mdcat");
+        project.branch = Some("main".to_string());
+        provider.recent_projects.insert(id, project);
+        provider
+            .canonical_paths
+            .insert("/mdcat".to_string(), Some(PathBuf::from("/data/mdcat")));
+        assert_eq!(
+            provider.cache_size(),
+            CacheSize {
+                entries: 2,
+                // ID, name, directory, and branch, then path and canonical path
+                string_bytes: 36 + 5 + 6 + 4 + 6 + 11,
+            }
+        );
+        assert_eq!(
+            provider.cache_size().to_string(),
+            "2 entries with 68 bytes of strings"
+        );
+    }
+
+    #[test]
+    fn describe_projects_sources() {
+        let sources = vec![
+            ProjectsSource {
+                path: PathBuf::from(
+                    "/home/foo/.config/JetBrains/IntelliJIdea2024.1/options/recentProjects.xml",
+                ),
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1705312234)),
+            },
+            ProjectsSource {
+                path: PathBuf::from(
+                    "/home/foo/.config/JetBrains/settingsSync/options/recentProjects.xml",
+                ),
+                modified: None,
+            },
+        ];
+        assert_eq!(describe_sources(&[]), "no files");
+        assert_eq!(
+            describe_sources(&sources),
+            "/home/foo/.config/JetBrains/IntelliJIdea2024.1/options/recentProjects.xml (modified 2024-01-15T09:50:34Z), /home/foo/.config/JetBrains/settingsSync/options/recentProjects.xml"
+        );
+    }
+
+    #[test]
+    fn disambiguate_projects_with_same_name() {
+        let project_at = |name: &str, directory: &str| {
+            let (_, mut project) = project(name);
+            project.directory = directory.to_string();
+            (format!("{name}-{directory}"), project)
+        };
+        let mut projects = IndexMap::from([
+            project_at("backend", "/home/foo/clients/acme/backend"),
+            project_at("backend", "/home/foo/clients/globex/backend"),
+            project_at("frontend", "/home/foo/acme/frontend"),
+            project_at("frontend", "/home/foo/clients/acme/frontend"),
+            project_at("mdcat", "/home/foo/mdcat"),
+            project_at("game", "/home/foo/Game"),
+            (
+                "game-sln".to_string(),
+                project_at("game", "/home/foo/Game").1,
+            ),
+        ]);
+        disambiguate_names(&mut projects);
+        assert_eq!(
+            projects
+                .values()
+                .map(JetbrainsRecentProject::display_name)
+                .collect::<Vec<_>>(),
+            vec![
+                "backend (acme)",
+                "backend (globex)",
+                "frontend (foo/acme)",
+                "frontend (clients/acme)",
+                "mdcat",
+                "game",
+                "game",
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_removed_projects_for_grace_period() {
+        let start = Instant::now();
+        let grace_period = Duration::from_secs(60);
+        let old_projects = IndexMap::from([project("foo"), project("bar")]);
+        let mut new_projects = IndexMap::from([project("foo")]);
+        keep_removed_projects(old_projects, &mut new_projects, grace_period, start);
+        assert_eq!(new_projects.len(), 2);
+        assert_eq!(new_projects[0].removed_at, None);
+        assert_eq!(new_projects[1].name, "bar");
+        assert_eq!(new_projects[1].removed_at, Some(start));
+
+        // Still within the grace period, so keep the original removal time
+        let old_projects = new_projects;
+        let mut new_projects = IndexMap::from([project("foo")]);
+        let now = start + Duration::from_secs(30);
+        keep_removed_projects(old_projects, &mut new_projects, grace_period, now);
+        assert_eq!(new_projects.len(), 2);
+        assert_eq!(new_projects[1].removed_at, Some(start));
+
+        // Past the grace period
+        let old_projects = new_projects;
+        let mut new_projects = IndexMap::from([project("foo")]);
+        let now = start + Duration::from_secs(61);
+        keep_removed_projects(old_projects, &mut new_projects, grace_period, now);
+        assert_eq!(new_projects.len(), 1);
+    }
+
+    #[test]
+    fn describe_solutions() {
+        let (_, mut project) = project("MyGame");
+        assert_eq!(project.description(), "/MyGame");
+        project.solution_kind = Some("Unity".to_string());
+        assert_eq!(project.description(), "Unity solution – /MyGame");
+        project.target_framework = Some("netstandard2.1".to_string());
+        assert_eq!(
+            project.description(),
+            "Unity solution (netstandard2.1) – /MyGame"
+        );
+        project.solution_kind = None;
+        assert_eq!(project.description(), "netstandard2.1 – /MyGame");
+        project.removed_at = Some(Instant::now());
+        assert_eq!(
+            project.description(),
+            "netstandard2.1 – /MyGame (removed from recents)"
+        );
+    }
+
+    #[test]
+    fn describe_attached_roots() {
+        let (_, mut project) = project("frontend");
+        project.attached_roots = vec!["/backend".to_string(), "/shared".to_string()];
+        assert_eq!(project.description(), "/frontend + /backend + /shared");
+        project.branch = Some("main".to_string());
+        assert_eq!(
+            project.description(),
+            "/frontend + /backend + /shared [main]"
+        );
+    }
+
+    #[test]
+    fn describe_trashed_projects() {
+        let (_, mut project) = project("mdcat");
+        project.trashed = Some(TrashedProject {
+            uri: "trash:///mdcat".to_string(),
+            original_path: "/mdcat".to_string(),
+        });
+        assert_eq!(project.description(), "/mdcat (in trash)");
+    }
+
+    #[test]
+    fn parse_stale_results() {
+        assert_eq!("skip".parse::<StaleResults>().unwrap(), StaleResults::Skip);
+        assert_eq!(
+            "placeholder".parse::<StaleResults>().unwrap(),
+            StaleResults::Placeholder
+        );
+        assert!("drop".parse::<StaleResults>().is_err());
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(5), Duration::from_secs(16));
+        assert_eq!(retry_delay(9), Duration::from_secs(256));
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn recent_projects_become_stale_after_reload_interval() {
+        let now = Instant::now();
+        assert!(is_stale(None, now));
+        assert!(!is_stale(Some(now), now));
+        assert!(!is_stale(Some(now), now + RELOAD_INTERVAL / 2));
+        assert!(is_stale(Some(now), now + RELOAD_INTERVAL));
+    }
+
+    #[test]
+    fn describe_worktree_branch() {
+        let (_, mut project) = project("mdcat-feature");
+        project.branch = Some("feature".to_string());
+        assert_eq!(project.description(), "/mdcat-feature [feature]");
+    }
+
+    #[test]
+    fn describe_when_projects_were_opened() {
+        let now = 1_700_000_000_000;
+        let minute = 60 * 1000;
+        let day = 24 * 60 * minute;
+        assert_eq!(describe_opened_at(now - 5000, now), "opened just now");
+        assert_eq!(describe_opened_at(now + 5000, now), "opened just now");
+        assert_eq!(describe_opened_at(now - minute, now), "opened 1 minute ago");
+        assert_eq!(
+            describe_opened_at(now - 42 * minute, now),
+            "opened 42 minutes ago"
+        );
+        assert_eq!(
+            describe_opened_at(now - 3 * 60 * minute, now),
+            "opened 3 hours ago"
+        );
+        assert_eq!(describe_opened_at(now - day, now), "opened yesterday");
+        assert_eq!(describe_opened_at(now - 3 * day, now), "opened 3 days ago");
+        let date = glib::DateTime::from_unix_local((now - 30 * day) / 1000)
+            .unwrap()
+            .format("%x")
+            .unwrap();
+        assert_eq!(
+            describe_opened_at(now - 30 * day, now),
+            format!("opened {date}")
+        );
     }
 }
@@ -0,0 +1,97 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Make sure the service runs in the session of a user.
+//!
+//! Gnome Shell only asks search providers on the session bus of the logged in user, and the
+//! service reads recent projects from the configuration directory of the user it runs as, so a
+//! single instance can't serve multiple users.  Instead the session bus of each user activates
+//! its own instance through the systemd user unit.  If the service starts elsewhere, e.g. as a
+//! system service or as root, it would serve the wrong projects or none at all, so we refuse to
+//! start with a clear error instead.
+
+use std::ffi::OsStr;
+
+use anyhow::{bail, Result};
+
+use crate::validate::SYSTEMD_UNIT;
+
+/// The environment the service starts in.
+#[derive(Debug, Clone, Copy)]
+pub struct Environment<'a> {
+    /// The effective user ID of the service.
+    pub uid: u32,
+    /// The value of `$DBUS_SESSION_BUS_ADDRESS`.
+    pub session_bus_address: Option<&'a OsStr>,
+    /// The value of `$XDG_RUNTIME_DIR`.
+    pub runtime_dir: Option<&'a OsStr>,
+}
+
+/// Check that `environment` is the session of a regular user.
+///
+/// Fail if running as root, or if there's no session bus.  zbus falls back to the bus socket in
+/// `$XDG_RUNTIME_DIR` if `$DBUS_SESSION_BUS_ADDRESS` is unset, so we only need either of both.
+pub fn check_user_session(environment: &Environment<'_>) -> Result<()> {
+    if environment.uid == 0 {
+        bail!(
+            "Refusing to run as root: this service searches recent projects of a single user; \
+             let the session bus of each user start it through the systemd user unit {SYSTEMD_UNIT}"
+        );
+    }
+    let is_set = |value: Option<&OsStr>| value.is_some_and(|value| !value.is_empty());
+    if !is_set(environment.session_bus_address) && !is_set(environment.runtime_dir) {
+        bail!(
+            "No session bus, neither $DBUS_SESSION_BUS_ADDRESS nor $XDG_RUNTIME_DIR set: this \
+             service must run in the session of a user, not as a system service; enable the \
+             systemd user unit {SYSTEMD_UNIT} instead"
+        );
+    }
+    Ok(())
+}
+
+/// Check that the current process runs in the session of a regular user.
+///
+/// See [`check_user_session`].
+pub fn check_current_user_session() -> Result<()> {
+    let session_bus_address = std::env::var_os("DBUS_SESSION_BUS_ADDRESS");
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR");
+    check_user_session(&Environment {
+        uid: rustix::process::geteuid().as_raw(),
+        session_bus_address: session_bus_address.as_deref(),
+        runtime_dir: runtime_dir.as_deref(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_root_and_missing_session_bus() {
+        let user = Environment {
+            uid: 1000,
+            session_bus_address: Some(OsStr::new("unix:path=/run/user/1000/bus")),
+            runtime_dir: None,
+        };
+        assert!(check_user_session(&user).is_ok());
+        assert!(check_user_session(&Environment {
+            session_bus_address: None,
+            runtime_dir: Some(OsStr::new("/run/user/1000")),
+            ..user
+        })
+        .is_ok());
+
+        let error = check_user_session(&Environment { uid: 0, ..user }).unwrap_err();
+        assert!(error.to_string().starts_with("Refusing to run as root"));
+        let error = check_user_session(&Environment {
+            session_bus_address: Some(OsStr::new("")),
+            runtime_dir: None,
+            ..user
+        })
+        .unwrap_err();
+        assert!(error.to_string().starts_with("No session bus"));
+    }
+}
@@ -0,0 +1,1151 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Settings of the service from a configuration file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+use tracing::{event, Level};
+
+use crate::config::ConfigLocation;
+use crate::hook::ActivationHook;
+use crate::providers::ProviderDefinition;
+use crate::searchprovider::{AppId, StaleResults};
+
+/// The group for service options.
+const OPTIONS: &str = "Options";
+
+/// The group for providers.
+const PROVIDERS: &str = "Providers";
+
+/// The group for routes from project directories and files to providers.
+const ROUTES: &str = "Routes";
+
+/// The group for icons of providers.
+const ICONS: &str = "Icons";
+
+/// The prefix of groups which define additional providers, followed by the desktop ID.
+pub const PROVIDER_GROUP_PREFIX: &str = "Provider ";
+
+/// The default vendor directory of additional providers.
+pub const DEFAULT_VENDOR_DIR: &str = "JetBrains";
+
+/// The default name of the recent projects file of additional providers.
+pub const DEFAULT_PROJECTS_FILENAME: &str = "recentProjects.xml";
+
+/// The type of the value of a key in the configuration file.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// `true` or `false`.
+    Boolean,
+    /// A non-negative integer.
+    Integer,
+    /// An arbitrary string.
+    String,
+    /// A list of strings, each followed by `;`.
+    StringList,
+    /// One of the given strings.
+    OneOf(&'static [&'static str]),
+}
+
+impl Kind {
+    /// Get the JSON schema of a value of this kind, with the given `description`.
+    ///
+    /// The schema describes values as parsed from the configuration file; lists are arrays of
+    /// strings, and all other values are strings in the syntax of the key file.
+    fn schema(self, description: &str) -> serde_json::Value {
+        match self {
+            Kind::Boolean => json!({
+                "description": description,
+                "type": "string",
+                "enum": ["true", "false"],
+            }),
+            Kind::Integer => json!({
+                "description": description,
+                "type": "string",
+                "pattern": "^[0-9]+$",
+            }),
+            Kind::String => json!({"description": description, "type": "string"}),
+            Kind::StringList => json!({
+                "description": description,
+                "type": "array",
+                "items": {"type": "string"},
+            }),
+            Kind::OneOf(values) => json!({
+                "description": description,
+                "type": "string",
+                "enum": values,
+            }),
+        }
+    }
+}
+
+/// A regular expression for valid desktop IDs, with or without `.desktop`.
+///
+/// See [`AppId`].
+const DESKTOP_ID_PATTERN: &str = r"^[^/\s]+$";
+
+/// All keys in the options group, with the type of their value and a description.
+const OPTION_KEYS: &[(&str, Kind, &str)] = &[
+    (
+        "Lazy",
+        Kind::Boolean,
+        "Load recent projects on first search only",
+    ),
+    (
+        "RemovedGracePeriod",
+        Kind::Integer,
+        "Keep serving removed projects for this many seconds",
+    ),
+    (
+        "KeepEnvironment",
+        Kind::StringList,
+        "Environment variables to pass on to launched apps",
+    ),
+    (
+        "HomeRoots",
+        Kind::StringList,
+        "Alternative home directories to look for projects in",
+    ),
+    (
+        "ConfigRoots",
+        Kind::StringList,
+        "Additional directories to look for IDE configuration directories in",
+    ),
+    (
+        "AllVersions",
+        Kind::Boolean,
+        "Read recent projects of all installed IDE versions",
+    ),
+    (
+        "WorktreeBranches",
+        Kind::Boolean,
+        "Show branches of worktrees",
+    ),
+    (
+        "ShowOpenedAt",
+        Kind::Boolean,
+        "Show when projects were last opened",
+    ),
+    (
+        "IndexContents",
+        Kind::Integer,
+        "Index contents of this many recent projects",
+    ),
+    (
+        "PrivateModeTimeout",
+        Kind::Integer,
+        "End private mode after this many seconds",
+    ),
+    (
+        "MaxRecentsFileSize",
+        Kind::Integer,
+        "The maximum size of recent projects files in bytes",
+    ),
+    (
+        "MaxRecentEntrySize",
+        Kind::Integer,
+        "Ignore meta info of recent projects with more data than this many bytes",
+    ),
+    (
+        "DedupeSymlinks",
+        Kind::Boolean,
+        "Remove projects whose path is a symlink alias of another project",
+    ),
+    (
+        "FolderResults",
+        Kind::Boolean,
+        "Offer results to open project directories in the file manager",
+    ),
+    (
+        "OpenResidualTerms",
+        Kind::Boolean,
+        "Open entries of the project directory which remaining search terms refer to",
+    ),
+    (
+        "RestoreFromTrash",
+        Kind::Boolean,
+        "Keep recent projects in the trash, and offer to restore them",
+    ),
+    (
+        "TraceEachItem",
+        Kind::Boolean,
+        "Log every item instead of summaries at trace level",
+    ),
+    (
+        "RedactLogs",
+        Kind::Boolean,
+        "Redact project paths and platform data in logs",
+    ),
+    (
+        "MaxResults",
+        Kind::Integer,
+        "Return at most this many results per search, or all results if 0",
+    ),
+    (
+        "MinResults",
+        Kind::Integer,
+        "Cut results at the largest score gap after this many results, or never if 0",
+    ),
+    (
+        "CallTimeout",
+        Kind::Integer,
+        "Give up on calls to other DBus services after this many seconds",
+    ),
+    (
+        "LatencyBudget",
+        Kind::Integer,
+        "Skip providers for a while if a search takes longer than this many milliseconds",
+    ),
+    (
+        "ReloadBudget",
+        Kind::Integer,
+        "Skip providers for a while if reloading recent projects takes longer than this many milliseconds",
+    ),
+    (
+        "StaleResults",
+        Kind::OneOf(&["skip", "placeholder"]),
+        "What to return for stale result IDs",
+    ),
+    (
+        "ActivationHook",
+        Kind::String,
+        "A command to run after launching an app",
+    ),
+    ("IgnoreFile", Kind::String, "An alternative ignore list"),
+    (
+        "PluginsDirectory",
+        Kind::String,
+        "An alternative plugins directory",
+    ),
+    (
+        "PluginTimeout",
+        Kind::Integer,
+        "Kill plugins which do not finish within this many seconds",
+    ),
+];
+
+/// Get a JSON schema of the configuration file, with the given known `providers`.
+///
+/// The schema describes the configuration file as an object of groups, each of which is an
+/// object of keys, e.g. `{"Options": {"Lazy": "true"}}`, so that editors and other tools can
+/// validate configuration files converted to JSON.
+pub fn json_schema(providers: &[ProviderDefinition]) -> serde_json::Value {
+    let options = OPTION_KEYS
+        .iter()
+        .map(|(key, kind, description)| (key.to_string(), kind.schema(description)))
+        .collect::<serde_json::Map<_, _>>();
+    // Desktop IDs can also refer to additional providers from [Provider …] groups, which a
+    // schema can't enumerate, so only check the syntax of desktop IDs, and list known providers
+    // as examples.
+    let desktop_id = json!({
+        "type": "string",
+        "pattern": DESKTOP_ID_PATTERN,
+        "examples": providers.iter().map(|p| p.desktop_id).collect::<Vec<_>>(),
+    });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("Settings of {}", env!("CARGO_PKG_NAME")),
+        "type": "object",
+        "properties": {
+            OPTIONS: {
+                "description": "Options of the service",
+                "type": "object",
+                "properties": options,
+                "additionalProperties": false,
+            },
+            PROVIDERS: {
+                "description": "Providers to serve",
+                "type": "object",
+                "properties": {
+                    "Enabled": {
+                        "description": "The desktop IDs of providers to serve",
+                        "type": "array",
+                        "items": desktop_id,
+                    },
+                },
+                "additionalProperties": false,
+            },
+            ROUTES: {
+                "description": "Globs of project directories, and desktop IDs of providers to activate matching projects with",
+                "type": "object",
+                "additionalProperties": desktop_id,
+            },
+            ICONS: {
+                "description": "Desktop IDs of providers, and icon names or absolute paths of icon files to use for their results",
+                "type": "object",
+                "propertyNames": desktop_id,
+                "additionalProperties": {"type": "string", "pattern": "^(/.*|[^/]+)$"},
+            },
+        },
+        "patternProperties": {
+            format!("^{PROVIDER_GROUP_PREFIX}[^/]+$"): {
+                "description": "An additional provider for the app with the desktop ID in the group name",
+                "type": "object",
+                "properties": {
+                    "Label": {"description": "A human readable label for the provider", "type": "string"},
+                    "ConfigPrefix": {
+                        "description": "The name of configuration directories of the app without version, e.g. DataSpell for DataSpell2024.1",
+                        "type": "string",
+                        "minLength": 1,
+                    },
+                    "VendorDir": {
+                        "description": "The directory with configuration directories in $XDG_CONFIG_HOME",
+                        "type": "string",
+                        "default": DEFAULT_VENDOR_DIR,
+                    },
+                    "ProjectsFilename": {
+                        "description": "The name of the recent projects file",
+                        "type": "string",
+                        "default": DEFAULT_PROJECTS_FILENAME,
+                    },
+                },
+                "required": ["ConfigPrefix"],
+                "additionalProperties": false,
+            },
+        },
+        "additionalProperties": false,
+    })
+}
+
+/// The default location of the configuration file of the current user.
+pub fn user_settings_file() -> PathBuf {
+    glib::user_config_dir()
+        .join(env!("CARGO_PKG_NAME"))
+        .join("config.ini")
+}
+
+/// Settings from a configuration file.
+///
+/// All settings are optional; command line arguments take precedence over these settings.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// Load recent projects on first search only.
+    pub lazy: Option<bool>,
+    /// Keep serving removed projects for this many seconds.
+    pub removed_grace_period: Option<u64>,
+    /// Environment variables to pass on to launched apps.
+    pub keep_environment: Option<Vec<String>>,
+    /// Alternative home directories to look for projects in.
+    pub home_roots: Option<Vec<String>>,
+    /// Additional directories to look for IDE configuration directories in.
+    pub config_roots: Option<Vec<PathBuf>>,
+    /// Read recent projects of all installed IDE versions.
+    pub all_versions: Option<bool>,
+    /// Show branches of worktrees.
+    pub worktree_branches: Option<bool>,
+    /// Show when projects were last opened.
+    pub show_opened_at: Option<bool>,
+    /// Index contents of this many recent projects.
+    pub index_contents: Option<usize>,
+    /// End private mode after this many seconds.
+    pub private_mode_timeout: Option<u64>,
+    /// The maximum size of recent projects files in bytes.
+    pub max_recents_file_size: Option<u64>,
+    /// The maximum size of the data of a recent project in bytes.
+    pub max_recent_entry_size: Option<usize>,
+    /// Remove projects whose path is a symlink alias of another project.
+    pub dedupe_symlinks: Option<bool>,
+    /// Offer results to open project directories in the file manager.
+    pub folder_results: Option<bool>,
+    /// Open entries of the project directory which remaining search terms refer to.
+    pub open_residual_terms: Option<bool>,
+    /// Keep recent projects in the trash, and offer to restore them.
+    pub restore_from_trash: Option<bool>,
+    /// Log every item instead of summaries at trace level.
+    pub trace_each_item: Option<bool>,
+    /// Redact project paths and platform data in logs.
+    pub redact_logs: Option<bool>,
+    /// Return at most this many results per search.
+    pub max_results: Option<usize>,
+    /// Cut results at the largest score gap after this many results.
+    pub min_results: Option<usize>,
+    /// Give up on calls to other DBus services after this many seconds.
+    pub call_timeout: Option<u64>,
+    /// Skip providers for a while if a search takes longer than this many milliseconds.
+    pub latency_budget: Option<u64>,
+    /// Skip providers for a while if reloading takes longer than this many milliseconds.
+    pub reload_budget: Option<u64>,
+    /// What to return for stale result IDs.
+    pub stale_results: Option<StaleResults>,
+    /// A command to run after launching an app.
+    pub activation_hook: Option<ActivationHook>,
+    /// An alternative ignore list.
+    pub ignore_file: Option<PathBuf>,
+    /// An alternative plugins directory.
+    pub plugins_directory: Option<PathBuf>,
+    /// Kill plugins which do not finish within this many seconds.
+    pub plugin_timeout: Option<u64>,
+    /// The desktop IDs of providers to serve.
+    pub enabled_providers: Option<Vec<String>>,
+    /// Pairs of directory globs and desktop IDs of providers to activate matching projects with.
+    pub routes: Vec<(String, String)>,
+    /// Pairs of desktop IDs of providers and icon names or absolute paths of icon files.
+    pub icons: Vec<(String, String)>,
+    /// Additional providers, for apps which no known provider covers.
+    pub custom_providers: Vec<CustomProvider>,
+}
+
+/// An additional provider from the configuration file.
+///
+/// Like a [`ProviderDefinition`], but for apps which no known provider covers, e.g. IDEs which
+/// we don't know yet, or known IDEs whose desktop file has a different ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomProvider {
+    /// A human readable label for this provider.
+    pub label: String,
+    /// The ID of the desktop file of the app.
+    pub desktop_id: String,
+    /// The object path of this provider, relative to the object path prefix.
+    ///
+    /// Unique among all additional providers; see [`custom_obj_path`].
+    pub relative_obj_path: String,
+    /// The vendor configuration directory.
+    pub vendor_dir: String,
+    /// The prefix of configuration directories inside the vendor directory.
+    pub config_prefix: String,
+    /// The file name of recent projects.
+    pub projects_filename: String,
+}
+
+impl CustomProvider {
+    /// The group of this provider in the configuration file.
+    pub fn group(&self) -> String {
+        format!("{PROVIDER_GROUP_PREFIX}{}", self.desktop_id)
+    }
+
+    /// Turn this provider into a provider definition which lives for the rest of the process.
+    ///
+    /// Search providers need definitions with static lifetime; we only create definitions for
+    /// additional providers once at startup, so leaking them is fine.
+    pub fn leak(&self) -> &'static ProviderDefinition<'static> {
+        let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+        Box::leak(Box::new(ProviderDefinition {
+            label: leak(self.label.clone()),
+            desktop_id: leak(self.desktop_id.clone()),
+            relative_obj_path: leak(self.relative_obj_path.clone()),
+            config: ConfigLocation {
+                vendor_dir: leak(self.vendor_dir.clone()),
+                config_prefix: leak(self.config_prefix.clone()),
+                projects_filename: leak(self.projects_filename.clone()),
+                excluded_channels: &[],
+            },
+        }))
+    }
+}
+
+/// The object path of an additional provider for `desktop_id`, relative to the object path
+/// prefix.
+///
+/// Additional providers live below `custom/`, so that they never clash with known providers.
+/// Object paths only allow ASCII letters, digits and `_`, so replace all other characters with
+/// `_`.  This maps different desktop IDs to the same path, e.g. `foo-bar.desktop` and
+/// `foo_bar.desktop`, so the caller needs to make paths unique with [`uniquify_obj_paths`].
+pub fn custom_obj_path(desktop_id: &str) -> String {
+    let name = desktop_id
+        .trim_end_matches(".desktop")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("custom/{name}")
+}
+
+/// Make the object paths of all `providers` unique.
+///
+/// Keep the path of the first provider, and add a numeric suffix to the paths of all later
+/// providers with the same path, e.g. `custom/foo_bar_2`.
+fn uniquify_obj_paths(providers: &mut [CustomProvider]) {
+    let mut taken = std::collections::HashSet::new();
+    for provider in providers {
+        let path = custom_obj_path(&provider.desktop_id);
+        let mut unique = path.clone();
+        let mut n = 1;
+        while !taken.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{path}_{n}");
+        }
+        provider.relative_obj_path = unique;
+    }
+}
+
+/// Find additional providers whose object path clashed with the object path of another provider.
+///
+/// Return pairs of the provider whose object path got a suffix, and the provider which got the
+/// object path first.
+pub fn clashing_obj_paths(providers: &[CustomProvider]) -> Vec<(&CustomProvider, &CustomProvider)> {
+    providers
+        .iter()
+        .filter_map(|provider| {
+            let path = custom_obj_path(&provider.desktop_id);
+            if provider.relative_obj_path == path {
+                None
+            } else {
+                let other = providers.iter().find(|p| p.relative_obj_path == path)?;
+                Some((provider, other))
+            }
+        })
+        .collect()
+}
+
+/// Parse all additional providers in `key_file`.
+fn custom_providers(key_file: &glib::KeyFile) -> Result<Vec<CustomProvider>> {
+    let groups = key_file.groups();
+    let providers = groups
+        .iter()
+        .filter_map(|group| {
+            let desktop_id = group.as_str().strip_prefix(PROVIDER_GROUP_PREFIX)?;
+            Some((group.as_str(), desktop_id))
+        })
+        .map(|(group, desktop_id)| {
+            let desktop_id = AppId::try_from(desktop_id)
+                .with_context(|| format!("Invalid desktop ID in [{group}]"))?
+                .to_string();
+            let string = |key: &str| get_optional(key_file, group, key, |f, g, k| f.string(g, k));
+            let config_prefix = string("ConfigPrefix")?
+                .filter(|prefix| !prefix.is_empty())
+                .ok_or_else(|| anyhow!("Missing ConfigPrefix in [{group}]"))?;
+            let provider = CustomProvider {
+                label: string("Label")?.map_or_else(|| desktop_id.clone(), |s| s.to_string()),
+                relative_obj_path: custom_obj_path(&desktop_id),
+                vendor_dir: string("VendorDir")?
+                    .map_or_else(|| DEFAULT_VENDOR_DIR.to_string(), |s| s.to_string()),
+                config_prefix: config_prefix.to_string(),
+                projects_filename: string("ProjectsFilename")?
+                    .map_or_else(|| DEFAULT_PROJECTS_FILENAME.to_string(), |s| s.to_string()),
+                desktop_id,
+            };
+            Ok((group, provider))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (i, (group, provider)) in providers.iter().enumerate() {
+        if let Some((other, _)) = providers[..i]
+            .iter()
+            .find(|(_, p)| p.desktop_id == provider.desktop_id)
+        {
+            return Err(anyhow!(
+                "Provider {} defined twice, in [{other}] and [{group}]",
+                provider.desktop_id,
+            ));
+        }
+    }
+    let mut providers = providers
+        .into_iter()
+        .map(|(_, provider)| provider)
+        .collect::<Vec<_>>();
+    uniquify_obj_paths(&mut providers);
+    Ok(providers)
+}
+
+/// Get all pairs of keys and string values in `group` of `key_file`, in order.
+///
+/// Return an empty list if `group` doesn't exist.
+fn string_pairs(key_file: &glib::KeyFile, group: &str) -> Result<Vec<(String, String)>> {
+    if !key_file.has_group(group) {
+        return Ok(Vec::new());
+    }
+    key_file
+        .keys(group)?
+        .iter()
+        .map(|key| {
+            let value = key_file
+                .string(group, key)
+                .with_context(|| format!("Invalid value for {key} in [{group}]"))?;
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Get an optional value of `key` in `group` of `key_file` with `get`.
+///
+/// Return `None` if the key doesn't exist, and fail if the value is invalid.
+fn get_optional<T>(
+    key_file: &glib::KeyFile,
+    group: &str,
+    key: &str,
+    get: impl FnOnce(&glib::KeyFile, &str, &str) -> Result<T, glib::Error>,
+) -> Result<Option<T>> {
+    if key_file.has_key(group, key).unwrap_or(false) {
+        get(key_file, group, key)
+            .map(Some)
+            .with_context(|| format!("Invalid value for {key} in [{group}]"))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get a list of strings from `key` in `group` of `key_file`.
+fn string_list(
+    key_file: &glib::KeyFile,
+    group: &str,
+    key: &str,
+) -> Result<Vec<String>, glib::Error> {
+    key_file
+        .string_list(group, key)
+        .map(|list| list.iter().map(|s| s.to_string()).collect())
+}
+
+impl Settings {
+    /// Parse settings from the key file `data`, with the given known `providers`.
+    ///
+    /// Fail if `data` is not a valid key file, if any value is invalid, or if the settings enable
+    /// or route to an unknown provider.
+    pub fn parse(data: &str, providers: &[ProviderDefinition]) -> Result<Self> {
+        let key_file = glib::KeyFile::new();
+        key_file.load_from_data(data, glib::KeyFileFlags::NONE)?;
+        if key_file.has_group(OPTIONS) {
+            for key in key_file.keys(OPTIONS)?.iter() {
+                if !OPTION_KEYS
+                    .iter()
+                    .any(|(known, _, _)| *known == key.as_str())
+                {
+                    event!(Level::WARN, "Ignoring unknown key {key} in [{OPTIONS}]");
+                }
+            }
+        }
+        let mut settings = Self {
+            lazy: get_optional(&key_file, OPTIONS, "Lazy", glib::KeyFile::boolean)?,
+            removed_grace_period: get_optional(
+                &key_file,
+                OPTIONS,
+                "RemovedGracePeriod",
+                glib::KeyFile::uint64,
+            )?,
+            keep_environment: get_optional(&key_file, OPTIONS, "KeepEnvironment", string_list)?,
+            home_roots: get_optional(&key_file, OPTIONS, "HomeRoots", string_list)?,
+            config_roots: get_optional(&key_file, OPTIONS, "ConfigRoots", |f, g, k| {
+                string_list(f, g, k).map(|list| list.into_iter().map(PathBuf::from).collect())
+            })?,
+            all_versions: get_optional(&key_file, OPTIONS, "AllVersions", glib::KeyFile::boolean)?,
+            worktree_branches: get_optional(
+                &key_file,
+                OPTIONS,
+                "WorktreeBranches",
+                glib::KeyFile::boolean,
+            )?,
+            show_opened_at: get_optional(
+                &key_file,
+                OPTIONS,
+                "ShowOpenedAt",
+                glib::KeyFile::boolean,
+            )?,
+            index_contents: get_optional(
+                &key_file,
+                OPTIONS,
+                "IndexContents",
+                glib::KeyFile::uint64,
+            )?
+            .map(|n| n as usize),
+            private_mode_timeout: get_optional(
+                &key_file,
+                OPTIONS,
+                "PrivateModeTimeout",
+                glib::KeyFile::uint64,
+            )?,
+            max_recents_file_size: get_optional(
+                &key_file,
+                OPTIONS,
+                "MaxRecentsFileSize",
+                glib::KeyFile::uint64,
+            )?,
+            max_recent_entry_size: get_optional(
+                &key_file,
+                OPTIONS,
+                "MaxRecentEntrySize",
+                glib::KeyFile::uint64,
+            )?
+            .map(|n| n as usize),
+            folder_results: get_optional(
+                &key_file,
+                OPTIONS,
+                "FolderResults",
+                glib::KeyFile::boolean,
+            )?,
+            open_residual_terms: get_optional(
+                &key_file,
+                OPTIONS,
+                "OpenResidualTerms",
+                glib::KeyFile::boolean,
+            )?,
+            restore_from_trash: get_optional(
+                &key_file,
+                OPTIONS,
+                "RestoreFromTrash",
+                glib::KeyFile::boolean,
+            )?,
+            dedupe_symlinks: get_optional(
+                &key_file,
+                OPTIONS,
+                "DedupeSymlinks",
+                glib::KeyFile::boolean,
+            )?,
+            trace_each_item: get_optional(
+                &key_file,
+                OPTIONS,
+                "TraceEachItem",
+                glib::KeyFile::boolean,
+            )?,
+            redact_logs: get_optional(&key_file, OPTIONS, "RedactLogs", glib::KeyFile::boolean)?,
+            max_results: get_optional(&key_file, OPTIONS, "MaxResults", glib::KeyFile::uint64)?
+                .map(|n| n as usize),
+            min_results: get_optional(&key_file, OPTIONS, "MinResults", glib::KeyFile::uint64)?
+                .map(|n| n as usize),
+            call_timeout: get_optional(&key_file, OPTIONS, "CallTimeout", glib::KeyFile::uint64)?,
+            latency_budget: get_optional(
+                &key_file,
+                OPTIONS,
+                "LatencyBudget",
+                glib::KeyFile::uint64,
+            )?,
+            reload_budget: get_optional(&key_file, OPTIONS, "ReloadBudget", glib::KeyFile::uint64)?,
+            stale_results: get_optional(&key_file, OPTIONS, "StaleResults", |f, g, k| {
+                f.string(g, k)
+            })?
+            .map(|s| s.parse())
+            .transpose()?,
+            activation_hook: get_optional(&key_file, OPTIONS, "ActivationHook", |f, g, k| {
+                f.string(g, k)
+            })?
+            .map(|s| s.parse())
+            .transpose()?,
+            ignore_file: get_optional(&key_file, OPTIONS, "IgnoreFile", |f, g, k| {
+                f.string(g, k).map(|s| PathBuf::from(s.as_str()))
+            })?,
+            plugins_directory: get_optional(&key_file, OPTIONS, "PluginsDirectory", |f, g, k| {
+                f.string(g, k).map(|s| PathBuf::from(s.as_str()))
+            })?,
+            plugin_timeout: get_optional(
+                &key_file,
+                OPTIONS,
+                "PluginTimeout",
+                glib::KeyFile::uint64,
+            )?,
+            enabled_providers: get_optional(&key_file, PROVIDERS, "Enabled", string_list)?,
+            routes: string_pairs(&key_file, ROUTES)?,
+            icons: string_pairs(&key_file, ICONS)?,
+            custom_providers: custom_providers(&key_file)?,
+        };
+        let normalize = |desktop_id: &mut String, group: &str| -> Result<()> {
+            let app_id = AppId::try_from(desktop_id.as_str())
+                .with_context(|| format!("Invalid desktop ID in [{group}]"))?;
+            *desktop_id = app_id.to_string();
+            Ok(())
+        };
+        for desktop_id in settings.enabled_providers.iter_mut().flatten() {
+            normalize(desktop_id, PROVIDERS)?;
+        }
+        for (_, desktop_id) in &mut settings.routes {
+            normalize(desktop_id, ROUTES)?;
+        }
+        for (desktop_id, icon) in &mut settings.icons {
+            normalize(desktop_id, ICONS)?;
+            if icon.is_empty() || (icon.contains('/') && !icon.starts_with('/')) {
+                return Err(anyhow!(
+                    "Invalid icon {icon} for {desktop_id} in [{ICONS}], expected an icon name or an absolute path"
+                ));
+            }
+        }
+        for custom in &settings.custom_providers {
+            if providers.iter().any(|p| p.desktop_id == custom.desktop_id) {
+                return Err(anyhow!(
+                    "Provider {} in [{}] already exists",
+                    custom.desktop_id,
+                    custom.group()
+                ));
+            }
+        }
+        let is_known = |desktop_id: &str| {
+            providers.iter().any(|p| p.desktop_id == desktop_id)
+                || settings
+                    .custom_providers
+                    .iter()
+                    .any(|p| p.desktop_id == desktop_id)
+        };
+        for desktop_id in settings.enabled_providers.iter().flatten() {
+            if !is_known(desktop_id) {
+                return Err(anyhow!("Unknown provider {desktop_id} in [{PROVIDERS}]"));
+            }
+        }
+        for (_, desktop_id) in &settings.routes {
+            if !is_known(desktop_id) {
+                return Err(anyhow!("Unknown provider {desktop_id} in [{ROUTES}]"));
+            }
+        }
+        for (desktop_id, _) in &settings.icons {
+            if !is_known(desktop_id) {
+                return Err(anyhow!("Unknown provider {desktop_id} in [{ICONS}]"));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Load settings from the file at `path`.
+    ///
+    /// If `path` does not exist, return default settings if `must_exist` is false, and fail
+    /// otherwise.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        must_exist: bool,
+        providers: &[ProviderDefinition],
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(data) => {
+                event!(Level::INFO, "Loading settings from {}", path.display());
+                Self::parse(&data, providers)
+                    .with_context(|| format!("Failed to parse settings from {}", path.display()))
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !must_exist => {
+                event!(Level::DEBUG, "No settings at {}", path.display());
+                Ok(Self::default())
+            }
+            Err(error) => Err(error)
+                .with_context(|| format!("Failed to read settings from {}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::PROVIDERS;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn parse_settings() {
+        let settings = Settings::parse(
+            "\
+[Options]
+Lazy=true
+RemovedGracePeriod=60
+KeepEnvironment=RUST_LOG;JAVA_HOME;
+HomeRoots=/work/foo;
+ConfigRoots=/opt/idea/config;
+AllVersions=true
+IndexContents=5
+ShowOpenedAt=true
+MaxResults=8
+MinResults=2
+LatencyBudget=500
+ReloadBudget=3000
+RedactLogs=true
+RestoreFromTrash=true
+OpenResidualTerms=true
+MaxRecentEntrySize=8192
+StaleResults=placeholder
+ActivationHook=move-window --class {wm_class}
+IgnoreFile=/etc/demo/ignore
+PluginsDirectory=/etc/demo/plugins
+PluginTimeout=30
+
+[Providers]
+Enabled=jetbrains-idea.desktop;jetbrains-clion.desktop;
+
+[Routes]
+~/android/**=jetbrains-studio.desktop
+/srv/**=jetbrains-idea.desktop
+
+[Icons]
+jetbrains-idea=idea-symbolic
+jetbrains-clion.desktop=/usr/share/pixmaps/clion.svg
+dataspell=dataspell-symbolic
+
+[Provider dataspell]
+Label=DataSpell
+ConfigPrefix=DataSpell
+",
+            PROVIDERS,
+        )
+        .unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                lazy: Some(true),
+                removed_grace_period: Some(60),
+                keep_environment: Some(vec!["RUST_LOG".to_string(), "JAVA_HOME".to_string()]),
+                home_roots: Some(vec!["/work/foo".to_string()]),
+                config_roots: Some(vec![PathBuf::from("/opt/idea/config")]),
+                all_versions: Some(true),
+                worktree_branches: None,
+                show_opened_at: Some(true),
+                index_contents: Some(5),
+                private_mode_timeout: None,
+                max_recents_file_size: None,
+                max_recent_entry_size: Some(8192),
+                dedupe_symlinks: None,
+                folder_results: None,
+                open_residual_terms: Some(true),
+                restore_from_trash: Some(true),
+                trace_each_item: None,
+                redact_logs: Some(true),
+                max_results: Some(8),
+                min_results: Some(2),
+                call_timeout: None,
+                latency_budget: Some(500),
+                reload_budget: Some(3000),
+                stale_results: Some(StaleResults::Placeholder),
+                activation_hook: Some("move-window --class {wm_class}".parse().unwrap()),
+                ignore_file: Some(PathBuf::from("/etc/demo/ignore")),
+                plugins_directory: Some(PathBuf::from("/etc/demo/plugins")),
+                plugin_timeout: Some(30),
+                enabled_providers: Some(vec![
+                    "jetbrains-idea.desktop".to_string(),
+                    "jetbrains-clion.desktop".to_string()
+                ]),
+                routes: vec![
+                    (
+                        "~/android/**".to_string(),
+                        "jetbrains-studio.desktop".to_string()
+                    ),
+                    ("/srv/**".to_string(), "jetbrains-idea.desktop".to_string()),
+                ],
+                icons: vec![
+                    (
+                        "jetbrains-idea.desktop".to_string(),
+                        "idea-symbolic".to_string()
+                    ),
+                    (
+                        "jetbrains-clion.desktop".to_string(),
+                        "/usr/share/pixmaps/clion.svg".to_string()
+                    ),
+                    (
+                        "dataspell.desktop".to_string(),
+                        "dataspell-symbolic".to_string()
+                    ),
+                ],
+                custom_providers: vec![CustomProvider {
+                    label: "DataSpell".to_string(),
+                    desktop_id: "dataspell.desktop".to_string(),
+                    relative_obj_path: "custom/dataspell".to_string(),
+                    vendor_dir: "JetBrains".to_string(),
+                    config_prefix: "DataSpell".to_string(),
+                    projects_filename: "recentProjects.xml".to_string(),
+                }],
+            }
+        );
+        assert_eq!(Settings::parse("", PROVIDERS).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn json_schema_of_settings() {
+        let schema = json_schema(PROVIDERS);
+        let options = &schema["properties"]["Options"]["properties"];
+        assert_eq!(options.as_object().unwrap().len(), OPTION_KEYS.len());
+        assert_eq!(options["Lazy"]["enum"], json!(["true", "false"]));
+        assert_eq!(options["HomeRoots"]["type"], "array");
+        assert_eq!(
+            options["StaleResults"]["enum"],
+            json!(["skip", "placeholder"])
+        );
+        let enabled = &schema["properties"]["Providers"]["properties"]["Enabled"]["items"];
+        assert_eq!(enabled["pattern"], DESKTOP_ID_PATTERN);
+        assert!(enabled["examples"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("jetbrains-idea.desktop")));
+        // All keys in the example from the README are in the schema
+        let readme = include_str!("../README.md");
+        let example = readme
+            .split_once("```ini\n")
+            .and_then(|(_, rest)| rest.split_once("```"))
+            .unwrap()
+            .0;
+        let key_file = glib::KeyFile::new();
+        key_file
+            .load_from_data(example, glib::KeyFileFlags::NONE)
+            .unwrap();
+        for key in key_file.keys(OPTIONS).unwrap().iter() {
+            assert!(options.get(key.as_str()).is_some(), "{key} not in schema");
+        }
+    }
+
+    #[test]
+    fn json_schema_accepts_custom_providers() {
+        let config = "\
+[Providers]
+Enabled=jetbrains-idea.desktop;dataspell.desktop;dataspell;
+
+[Routes]
+/srv/**=dataspell.desktop
+
+[Icons]
+dataspell=dataspell-symbolic
+
+[Provider dataspell.desktop]
+Label=DataSpell
+ConfigPrefix=DataSpell
+";
+        let settings = Settings::parse(config, PROVIDERS).unwrap();
+        assert_eq!(settings.custom_providers.len(), 1);
+
+        let schema = json_schema(PROVIDERS);
+        let matches = |schema: &serde_json::Value, value: &str| {
+            regex::Regex::new(schema["pattern"].as_str().unwrap())
+                .unwrap()
+                .is_match(value)
+        };
+        let properties = &schema["properties"];
+        let desktop_id = &properties["Providers"]["properties"]["Enabled"]["items"];
+        for id in settings.enabled_providers.iter().flatten() {
+            assert!(matches(desktop_id, id), "{id}");
+        }
+        for id in ["dataspell", "jetbrains-idea"] {
+            assert!(matches(desktop_id, id), "{id}");
+        }
+        for (_, id) in &settings.routes {
+            assert!(matches(&properties["Routes"]["additionalProperties"], id));
+        }
+        for (id, _) in &settings.icons {
+            assert!(matches(&properties["Icons"]["propertyNames"], id));
+        }
+        for id in ["", "apps/idea.desktop", "jetbrains idea"] {
+            assert!(!matches(desktop_id, id), "{id:?}");
+        }
+        let (pattern, group) = schema["patternProperties"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        assert!(regex::Regex::new(pattern)
+            .unwrap()
+            .is_match("Provider dataspell.desktop"));
+        for key in ["Label", "ConfigPrefix"] {
+            assert!(group["properties"].get(key).is_some(), "{key}");
+        }
+    }
+
+    #[test]
+    fn reject_invalid_settings() {
+        let error = Settings::parse("[Options]\nLazy=maybe\n", PROVIDERS).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value for Lazy in [Options]");
+        let error =
+            Settings::parse("[Providers]\nEnabled=jetbrains-foo.desktop\n", PROVIDERS).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown provider jetbrains-foo.desktop in [Providers]"
+        );
+        let error =
+            Settings::parse("[Routes]\n/srv/**=jetbrains-foo.desktop\n", PROVIDERS).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown provider jetbrains-foo.desktop in [Routes]"
+        );
+        let error = Settings::parse(
+            "[Icons]
+jetbrains-idea=icons/idea.svg
+",
+            PROVIDERS,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid icon icons/idea.svg for jetbrains-idea.desktop in [Icons], expected an icon name or an absolute path"
+        );
+        let error =
+            Settings::parse("[Providers]\nEnabled=apps/jetbrains-idea\n", PROVIDERS).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Invalid desktop ID in [Providers]: Desktop ID \"apps/jetbrains-idea\" contains a path separator"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_custom_providers() {
+        let error =
+            Settings::parse("[Provider dataspell]\nLabel=DataSpell\n", PROVIDERS).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Missing ConfigPrefix in [Provider dataspell]"
+        );
+        let error = Settings::parse(
+            "[Provider jetbrains-idea]\nConfigPrefix=IntelliJIdea\n",
+            PROVIDERS,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Provider jetbrains-idea.desktop in [Provider jetbrains-idea.desktop] already exists"
+        );
+        let error = Settings::parse(
+            "[Provider dataspell]\nConfigPrefix=DataSpell\n\n[Provider dataspell.desktop]\nConfigPrefix=DataSpell\n",
+            PROVIDERS,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Provider dataspell.desktop defined twice, in [Provider dataspell] and [Provider dataspell.desktop]"
+        );
+    }
+
+    #[test]
+    fn unique_object_paths_of_custom_providers() {
+        let settings = Settings::parse(
+            "\
+[Provider foo-bar]
+ConfigPrefix=Foo
+
+[Provider foo_bar]
+ConfigPrefix=Foo
+
+[Provider foo.bar]
+ConfigPrefix=Foo
+
+[Provider foo-bar-2]
+ConfigPrefix=Foo
+",
+            PROVIDERS,
+        )
+        .unwrap();
+        assert_eq!(
+            settings
+                .custom_providers
+                .iter()
+                .map(|p| p.relative_obj_path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "custom/foo_bar",
+                "custom/foo_bar_2",
+                "custom/foo_bar_3",
+                "custom/foo_bar_2_2"
+            ]
+        );
+        assert_eq!(
+            clashing_obj_paths(&settings.custom_providers)
+                .iter()
+                .map(|(p, other)| (p.desktop_id.as_str(), other.desktop_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("foo_bar.desktop", "foo-bar.desktop"),
+                ("foo.bar.desktop", "foo-bar.desktop"),
+                ("foo-bar-2.desktop", "foo_bar.desktop"),
+            ]
+        );
+        let definition = settings.custom_providers[1].leak();
+        assert_eq!(definition.desktop_id, "foo_bar.desktop");
+        assert_eq!(definition.relative_obj_path, "custom/foo_bar_2");
+        assert_eq!(definition.config.config_prefix, "Foo");
+        assert_eq!(definition.config.channel("Foo2024.1"), Some(""));
+    }
+
+    #[test]
+    fn normalize_desktop_ids() {
+        let settings = Settings::parse(
+            "[Providers]\nEnabled=jetbrains-idea;\n\n[Routes]\n/srv/**=jetbrains-clion\n",
+            PROVIDERS,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.enabled_providers,
+            Some(vec!["jetbrains-idea.desktop".to_string()])
+        );
+        assert_eq!(
+            settings.routes,
+            vec![("/srv/**".to_string(), "jetbrains-clion.desktop".to_string())]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Track which search providers the user disabled in Gnome settings.
+//!
+//! Gnome Shell stops calling disabled providers, but calls already underway when the user
+//! disables a provider may still arrive; we answer these with empty results instead of loading
+//! and searching recent projects for nothing.
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use gio::prelude::*;
+use tracing::{event, Level};
+
+use crate::searchprovider::AppId;
+
+/// The settings schema of Gnome Shell for search providers.
+const SCHEMA: &str = "org.gnome.desktop.search-providers";
+
+/// The disabled state of search providers.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct State {
+    /// Desktop IDs of disabled providers.
+    disabled: HashSet<String>,
+    /// Desktop IDs of enabled providers, if `disable_external` is set.
+    enabled: HashSet<String>,
+    /// Whether providers are disabled by default.
+    ///
+    /// If set, Gnome Shell only calls providers in `enabled`, and ignores `disabled`.
+    disable_external: bool,
+}
+
+/// Shared state of disabled search providers.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct DisabledProviders(Arc<RwLock<State>>);
+
+impl DisabledProviders {
+    /// Set the desktop IDs of `disabled` and `enabled` providers, and whether providers are
+    /// disabled by default.
+    pub fn set<I, J>(&self, disabled: I, enabled: J, disable_external: bool)
+    where
+        I: IntoIterator<Item = String>,
+        J: IntoIterator<Item = String>,
+    {
+        *self.0.write().unwrap() = State {
+            disabled: disabled.into_iter().collect(),
+            enabled: enabled.into_iter().collect(),
+            disable_external,
+        };
+    }
+
+    /// Whether the provider for `app_id` is disabled.
+    pub fn is_disabled(&self, app_id: &AppId) -> bool {
+        let state = self.0.read().unwrap();
+        let app_id = app_id.to_string();
+        if state.disable_external {
+            !state.enabled.contains(&app_id)
+        } else {
+            state.disabled.contains(&app_id)
+        }
+    }
+
+    /// Update the state from `settings`.
+    fn update_from(&self, settings: &gio::Settings) {
+        let disabled = settings.strv("disabled");
+        let enabled = settings.strv("enabled");
+        let disable_external = settings.boolean("disable-external");
+        event!(
+            Level::DEBUG,
+            "Disabled search providers: {disabled:?}, enabled search providers: {enabled:?}, disabled by default: {disable_external}"
+        );
+        self.set(
+            disabled.iter().map(|id| id.to_string()),
+            enabled.iter().map(|id| id.to_string()),
+            disable_external,
+        );
+    }
+
+    /// Track disabled providers in the settings of Gnome Shell.
+    ///
+    /// Return the underlying settings; the caller must keep them alive as long as it wants to
+    /// track disabled providers.  Return `None` if the settings schema of Gnome Shell is not
+    /// installed.
+    pub fn track_system(&self) -> Option<gio::Settings> {
+        let Some(schema) =
+            gio::SettingsSchemaSource::default().and_then(|source| source.lookup(SCHEMA, true))
+        else {
+            event!(
+                Level::DEBUG,
+                "Settings schema {SCHEMA} not found, not tracking disabled providers"
+            );
+            return None;
+        };
+        let settings = gio::Settings::new_full(&schema, None::<&gio::SettingsBackend>, None);
+        self.update_from(&settings);
+        let disabled = self.clone();
+        settings.connect_changed(None, move |settings, key| {
+            if key == "disabled" || key == "enabled" || key == "disable-external" {
+                disabled.update_from(settings);
+            }
+        });
+        Some(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_providers() {
+        let disabled = DisabledProviders::default();
+        let idea = AppId::try_from("jetbrains-idea.desktop").unwrap();
+        let clion = AppId::try_from("jetbrains-clion.desktop").unwrap();
+        assert!(!disabled.is_disabled(&idea));
+        let shared = disabled.clone();
+        shared.set(["jetbrains-idea.desktop".to_string()], [], false);
+        assert!(disabled.is_disabled(&idea));
+        assert!(!disabled.is_disabled(&clion));
+        shared.set([], [], true);
+        assert!(disabled.is_disabled(&idea));
+        assert!(disabled.is_disabled(&clion));
+    }
+
+    #[test]
+    fn enabled_providers_with_disable_external() {
+        let disabled = DisabledProviders::default();
+        let idea = AppId::try_from("jetbrains-idea.desktop").unwrap();
+        let clion = AppId::try_from("jetbrains-clion.desktop").unwrap();
+        // Gnome Shell ignores the disabled list if providers are disabled by default
+        disabled.set(
+            ["jetbrains-idea.desktop".to_string()],
+            ["jetbrains-idea.desktop".to_string()],
+            true,
+        );
+        assert!(!disabled.is_disabled(&idea));
+        assert!(disabled.is_disabled(&clion));
+        // And ignores the enabled list otherwise
+        disabled.set([], ["jetbrains-clion.desktop".to_string()], false);
+        assert!(!disabled.is_disabled(&idea));
+        assert!(!disabled.is_disabled(&clion));
+    }
+}
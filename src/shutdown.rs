@@ -0,0 +1,134 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Shut down the service in an orderly fashion.
+//!
+//! Quitting the main loop right away drops replies to calls still in progress, and makes all our
+//! interfaces vanish in the middle of a conversation.  Instead we first release our bus name, so
+//! that no new calls arrive, then wait for the replies to calls in progress, close the
+//! connection, and only then quit the main loop.  The whole sequence is bounded by a timeout, and
+//! all requests to shut down, e.g. `SIGTERM` followed by `SIGINT`, coalesce into a single
+//! sequence.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tracing::{event, Level};
+
+use crate::calltrace;
+use crate::timeout::with_timeout;
+
+/// How long to wait for an orderly shutdown before quitting anyway.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check whether calls in progress finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Release `busname`, wait for calls in progress, and close `connection`.
+async fn shutdown_connection(connection: zbus::Connection, busname: &str) -> zbus::Result<()> {
+    event!(Level::DEBUG, "Releasing bus name {busname}");
+    connection.release_name(busname).await?;
+    while 0 < calltrace::in_flight() {
+        event!(
+            Level::DEBUG,
+            "Waiting for {} call(s) in progress",
+            calltrace::in_flight()
+        );
+        glib::timeout_future(POLL_INTERVAL).await;
+    }
+    event!(Level::DEBUG, "Closing connection");
+    connection.close().await
+}
+
+#[derive(Debug)]
+struct Inner {
+    mainloop: glib::MainLoop,
+    busname: String,
+    timeout: Duration,
+    connection: OnceLock<zbus::Connection>,
+    started: AtomicBool,
+}
+
+/// Shut down the service.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct Shutdown(Arc<Inner>);
+
+impl Shutdown {
+    /// Shut down the connection which owns `busname`, and then quit `mainloop`.
+    ///
+    /// Give up on an orderly shutdown after `timeout`.
+    pub fn new(mainloop: glib::MainLoop, busname: String, timeout: Duration) -> Self {
+        Self(Arc::new(Inner {
+            mainloop,
+            busname,
+            timeout,
+            connection: OnceLock::new(),
+            started: AtomicBool::new(false),
+        }))
+    }
+
+    /// Shut down `connection` on shutdown.
+    ///
+    /// Until we have a connection, shutdown just quits the main loop.
+    pub fn attach(&self, connection: zbus::Connection) {
+        if self.0.connection.set(connection).is_err() {
+            event!(Level::WARN, "Connection for shutdown already attached");
+        }
+    }
+
+    /// Whether shutdown started already.
+    pub fn is_started(&self) -> bool {
+        self.0.started.load(Ordering::SeqCst)
+    }
+
+    /// Start to shut down, because of `reason`.
+    ///
+    /// Shut down the connection, if any, on the context of the main loop and quit the main loop
+    /// afterwards, or after the shutdown timeout.  Ignore further requests while shutting down.
+    pub fn request(&self, reason: &str) {
+        if self.0.started.swap(true, Ordering::SeqCst) {
+            event!(Level::DEBUG, "{reason}, but already shutting down");
+            return;
+        }
+        event!(Level::INFO, "{reason}, shutting down");
+        let inner = self.0.clone();
+        self.0.mainloop.context().spawn(async move {
+            if let Some(connection) = inner.connection.get().cloned() {
+                let shutdown = shutdown_connection(connection, &inner.busname);
+                if let Err(error) = with_timeout(inner.timeout, shutdown).await {
+                    event!(Level::WARN, %error, "Failed to shut down connection: {error}");
+                }
+            }
+            event!(Level::DEBUG, "Quitting mainloop");
+            inner.mainloop.quit();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quit_mainloop_once_without_connection() {
+        let context = glib::MainContext::new();
+        let mainloop = glib::MainLoop::new(Some(&context), false);
+        let shutdown = Shutdown::new(
+            mainloop.clone(),
+            "de.swsnr.searchprovider.Test".to_string(),
+            SHUTDOWN_TIMEOUT,
+        );
+        assert!(!shutdown.is_started());
+        shutdown.request("Terminated");
+        shutdown.clone().request("Interrupted");
+        assert!(shutdown.is_started());
+        // Returns only if shutdown quit the mainloop
+        mainloop.run();
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Expose the state of search providers.
+//!
+//! When users report stale or missing results this tells which files a provider read, and how
+//! old its data is, without digging through the journal; for huge recent projects files it tells
+//! how much memory the provider retains, to tune size limits; and for slow searches it tells
+//! which calls take how long, and whether the service skips the provider for being too slow.
+
+use std::time::UNIX_EPOCH;
+
+use tracing::instrument;
+use zbus::{interface, message::Header, ObjectServer};
+
+use crate::calltrace;
+use crate::searchprovider::provider_at;
+
+/// Totals of calls to a method: name, calls, argument bytes, reply bytes, and microseconds.
+type MethodCalls = (&'static str, u64, u64, u64, u64);
+
+/// Statistics about the search provider at the same object path.
+#[derive(Debug, Default)]
+pub struct Stats;
+
+#[interface(name = "de.swsnr.searchprovider.Stats")]
+impl Stats {
+    /// Get the files the provider read its recent projects from.
+    ///
+    /// Return the generation of recent projects, which counts successful reloads, and the path
+    /// and the modification time in seconds since the Unix epoch of each file, or zero if the
+    /// modification time is unknown.
+    #[instrument(skip(self, server, header))]
+    async fn get_sources(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<(u64, Vec<(String, u64)>)> {
+        let interface = provider_at(server, &header).await?;
+        let provider = interface.get().await;
+        let sources = provider
+            .sources()
+            .iter()
+            .map(|source| {
+                let modified = source
+                    .modified
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |modified| modified.as_secs());
+                (source.path.display().to_string(), modified)
+            })
+            .collect();
+        Ok((provider.generation(), sources))
+    }
+
+    /// Get the approximate memory the provider retains in its caches.
+    ///
+    /// Return the number of cache entries, and the total size of all strings in these entries in
+    /// bytes, across recent projects, canonical paths, folder results, project names and indexed
+    /// directory contents.
+    #[instrument(skip(self, server, header))]
+    async fn get_cache_size(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<(u64, u64)> {
+        let interface = provider_at(server, &header).await?;
+        let size = interface.get().await.cache_size();
+        Ok((size.entries as u64, size.string_bytes as u64))
+    }
+
+    /// Get whether the provider is skipped for exceeding its latency or reload budget.
+    ///
+    /// Return whether the provider is suspended, and the seconds until the next search probes it
+    /// again, or zero if it is not suspended.
+    #[instrument(skip(self, server, header))]
+    async fn get_suspended(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<(bool, u64)> {
+        let interface = provider_at(server, &header).await?;
+        let suspended_for = interface.get().await.suspended_for();
+        Ok((
+            suspended_for.is_some(),
+            suspended_for.map_or(0, |duration| duration.as_secs_f64().ceil() as u64),
+        ))
+    }
+
+    /// Get totals of all finished calls to the search provider, by method.
+    ///
+    /// Return the name of the method, the number of calls, the total size of arguments and of
+    /// replies in bytes, and the total duration of all calls in microseconds.
+    #[instrument(skip(self, server, header))]
+    async fn get_calls(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<Vec<MethodCalls>> {
+        let interface = provider_at(server, &header).await?;
+        let app_id = interface.get().await.app().id().clone();
+        Ok(calltrace::totals(&app_id)
+            .into_iter()
+            .map(|(method, totals)| {
+                (
+                    method,
+                    totals.calls,
+                    totals.request_bytes,
+                    totals.reply_bytes,
+                    totals.duration.as_micros() as u64,
+                )
+            })
+            .collect())
+    }
+}
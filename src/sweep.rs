@@ -0,0 +1,83 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Revalidate recent projects in the background.
+//!
+//! Projects change while we serve them: project files vanish, and drives with project
+//! directories get unmounted and mounted again.  Checking paths during searches would add
+//! latency, so instead we check a few projects of each provider at a time, with idle priority,
+//! and off the main loop.  Like on reload we drop projects whose project file vanished, and mark
+//! projects whose directory is missing.
+
+use std::time::Duration;
+
+use tracing::{event, Level};
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::searchprovider::{JetbrainsProductSearchProvider, ProjectPaths};
+use crate::shutdown::Shutdown;
+
+/// How long to wait between batches.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How many projects of each provider to check per batch.
+pub const SWEEP_BATCH_SIZE: usize = 5;
+
+/// Revalidate recent projects of the providers at `paths` on `connection`, until shutdown.
+pub async fn sweep(connection: zbus::Connection, paths: Vec<OwnedObjectPath>, shutdown: Shutdown) {
+    loop {
+        glib::timeout_future_with_priority(glib::Priority::DEFAULT_IDLE, SWEEP_INTERVAL).await;
+        if shutdown.is_started() {
+            event!(Level::DEBUG, "Stopping revalidation sweep on shutdown");
+            return;
+        }
+        for path in &paths {
+            let interface = match connection
+                .object_server()
+                .interface::<_, JetbrainsProductSearchProvider>(path)
+                .await
+            {
+                Ok(interface) => interface,
+                Err(error) => {
+                    event!(Level::DEBUG, "Skipping {}: {error}", path.as_str());
+                    continue;
+                }
+            };
+            let projects = interface
+                .get_mut()
+                .await
+                .projects_to_revalidate(SWEEP_BATCH_SIZE);
+            if projects.is_empty() {
+                continue;
+            }
+            // Don't block the main loop on a hung mount; the sweep waits for the result though, so
+            // that a hung mount blocks at most one thread.
+            let states = gio::spawn_blocking(move || {
+                projects
+                    .into_iter()
+                    .map(ProjectPaths::check)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+            let Ok(states) = states else {
+                event!(
+                    Level::ERROR,
+                    "Failed to revalidate projects of search provider at {}",
+                    path.as_str()
+                );
+                continue;
+            };
+            let dropped = interface.get_mut().await.update_project_states(states);
+            if 0 < dropped {
+                event!(
+                    Level::INFO,
+                    "Dropped {dropped} vanished project(s) of search provider at {}",
+                    path.as_str()
+                );
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@
 
 //! Systemd utilities.
 
+use tracing::{event, instrument, Level};
 use zbus::proxy;
 use zbus::zvariant::{OwnedObjectPath, Value};
 
@@ -31,6 +32,49 @@ pub trait Systemd1Manager {
         properties: &[(&str, Value<'_>)],
         aux: &[(&str, Vec<(&str, Value<'_>)>)],
     ) -> zbus::Result<OwnedObjectPath>;
+
+    /// Get the object path of the loaded unit `name`.
+    ///
+    /// Fail if the unit is not loaded, e.g. because it stopped and systemd garbage-collected it.
+    fn get_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Subscribe to signals of the manager and its units.
+    ///
+    /// Without a subscription, systemd doesn't emit any signals for units to this client.
+    fn subscribe(&self) -> zbus::Result<()>;
+
+    /// The environment passed to all processes the manager spawns, as `NAME=VALUE` pairs.
+    ///
+    /// `dbus-update-activation-environment --systemd` and `systemctl --user import-environment`
+    /// update this environment, e.g. when the graphical session starts.
+    #[zbus(property)]
+    fn environment(&self) -> zbus::Result<Vec<String>>;
+}
+
+/// A systemd unit.
+///
+/// See `org.freedesktop.systemd1(5)`.
+#[proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+pub trait Systemd1Unit {
+    /// The state of the unit, e.g. `active`, `inactive` or `failed`.
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+}
+
+/// A systemd scope unit.
+///
+/// See `org.freedesktop.systemd1(5)`.
+#[proxy(
+    interface = "org.freedesktop.systemd1.Scope",
+    default_service = "org.freedesktop.systemd1"
+)]
+pub trait Systemd1Scope {
+    /// Why the scope stopped, e.g. `success`, `timeout` or `oom-kill`.
+    #[zbus(property)]
+    fn result(&self) -> zbus::Result<String>;
 }
 
 /// Properties for a new systemd scope.
@@ -80,3 +124,163 @@ pub fn escape_name(name: &str) -> String {
             .join("")
     }
 }
+
+/// Start a new systemd scope for the given `pid`.
+///
+/// The scope is named `<prefix>-<name>-<pid>.scope`, where `name` gets escaped for systemd.
+///
+/// Return the name of the new scope and the object path of the corresponding systemd job.
+#[instrument(skip(manager))]
+pub async fn start_app_scope(
+    manager: &Systemd1ManagerProxy<'_>,
+    properties: ScopeProperties<'_>,
+    pid: u32,
+) -> zbus::Result<(String, OwnedObjectPath)> {
+    // See https://gitlab.gnome.org/jf/start-transient-unit/-/blob/117c6f32c8dc0d1f28686408f698632aa71880bc/rust/src/main.rs#L94
+    // for inspiration.
+    // See https://www.freedesktop.org/wiki/Software/systemd/ControlGroupInterface/ for background.
+    let mut props = vec![
+        // I haven't found any documentation for the type of the PIDs property directly, but elsewhere
+        // in its DBus interface system always used u32 for PIDs.
+        ("PIDs", Value::Array(vec![pid].into())),
+        // libgnome passes this property too, see
+        // https://gitlab.gnome.org/GNOME/gnome-desktop/-/blob/106a729c3f98b8ee56823a0a49fa8504f78dd355/libgnome-desktop/gnome-systemd.c#L100
+        //
+        // I'm not entirely sure how it's relevant but it seems a good idea to do what Gnome does.
+        ("CollectMode", Value::Str("inactive-or-failed".into())),
+    ];
+    if let Some(description) = properties.description {
+        props.push(("Description", Value::Str(description.into())));
+    }
+    if !properties.documentation.is_empty() {
+        props.push((
+            "Documentation",
+            Value::Array(properties.documentation.into()),
+        ));
+    }
+    let name = format!(
+        "{}-{}-{}.scope",
+        properties.prefix,
+        escape_name(properties.name),
+        pid
+    );
+    event!(
+        Level::DEBUG,
+        "Creating new scope {name} for PID {pid} with {props:?}"
+    );
+    let scope_object_path = manager
+        .start_transient_unit(&name, "fail", &props, &[])
+        .await?;
+    Ok((name, scope_object_path))
+}
+
+/// A mock systemd manager for tests.
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use zbus::interface;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+    /// A recorded call to `StartTransientUnit`.
+    #[derive(Debug)]
+    pub struct StartTransientUnitCall {
+        /// The name of the unit.
+        pub name: String,
+        /// The job mode.
+        pub mode: String,
+        /// The properties of the new unit.
+        pub properties: HashMap<String, OwnedValue>,
+    }
+
+    /// An in-process implementation of `org.freedesktop.systemd1.Manager`.
+    ///
+    /// Records all calls to `StartTransientUnit`.
+    #[derive(Debug, Default, Clone)]
+    pub struct MockSystemd1Manager {
+        /// All calls to `StartTransientUnit` so far.
+        pub calls: Arc<Mutex<Vec<StartTransientUnitCall>>>,
+        /// The environment of the manager.
+        pub environment: Vec<String>,
+    }
+
+    #[interface(name = "org.freedesktop.systemd1.Manager")]
+    impl MockSystemd1Manager {
+        fn start_transient_unit(
+            &self,
+            name: String,
+            mode: String,
+            properties: Vec<(String, OwnedValue)>,
+            _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
+        ) -> OwnedObjectPath {
+            self.calls.lock().unwrap().push(StartTransientUnitCall {
+                name,
+                mode,
+                properties: properties.into_iter().collect(),
+            });
+            OwnedObjectPath::try_from("/org/freedesktop/systemd1/job/42").unwrap()
+        }
+
+        #[zbus(property)]
+        fn environment(&self) -> Vec<String> {
+            self.environment.clone()
+        }
+    }
+
+    /// Serve `manager` on a private peer-to-peer connection.
+    ///
+    /// Return the server and the client side of the connection; talk to the mock manager through
+    /// the client side, and keep the server side alive for as long as needed.
+    pub async fn connect(
+        manager: MockSystemd1Manager,
+    ) -> zbus::Result<(zbus::Connection, zbus::Connection)> {
+        let (server_stream, client_stream) = std::os::unix::net::UnixStream::pair()?;
+        let guid = zbus::Guid::generate();
+        let server = zbus::ConnectionBuilder::unix_stream(server_stream)
+            .server(guid)?
+            .p2p()
+            .serve_at("/org/freedesktop/systemd1", manager)?
+            .build();
+        let client = zbus::ConnectionBuilder::unix_stream(client_stream)
+            .p2p()
+            .build();
+        futures_lite::future::try_zip(server, client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn escape_name_like_systemd_escape() {
+        // Reference outputs from `systemd-escape -- <name>`
+        let cases = [
+            ("", ""),
+            ("IntelliJ IDEA", r"IntelliJ\x20IDEA"),
+            ("jetbrains-idea.desktop", r"jetbrains\x2didea.desktop"),
+            (".hidden", r"\x2ehidden"),
+            ("a/b/c", "a-b-c"),
+            ("Zürich ☃", r"Z\xc3\xbcrich\x20\xe2\x98\x83"),
+            ("foo_bar:baz", "foo_bar:baz"),
+            ("-lead", r"\x2dlead"),
+        ];
+        for (name, escaped) in cases {
+            assert_eq!(escape_name(name), escaped, "escaping {name:?}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn escaped_name_is_valid_in_unit_names(name in "\\PC*") {
+            let escaped = escape_name(&name);
+            prop_assert!(escaped
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ":_.\\-".contains(c)));
+            prop_assert!(!escaped.starts_with('.'));
+        }
+    }
+}
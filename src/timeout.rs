@@ -0,0 +1,53 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Timeouts for calls to other DBus services.
+//!
+//! A misbehaving peer may never reply to a call, so we give up on calls after a timeout, instead
+//! of waiting forever.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// The default timeout for calls to other DBus services.
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait for `call` at most for `timeout`.
+///
+/// Fail if `call` fails, or if it doesn't complete within `timeout`.
+///
+/// The timeout runs on the thread default main context.
+pub async fn with_timeout<T, F>(timeout: Duration, call: F) -> zbus::Result<T>
+where
+    F: Future<Output = zbus::Result<T>>,
+{
+    glib::future_with_timeout(timeout, call)
+        .await
+        .unwrap_or_else(|_| {
+            Err(zbus::Error::Failure(format!(
+                "Timed out after {}ms",
+                timeout.as_millis()
+            )))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn fail_calls_after_timeout() {
+        let context = glib::MainContext::new();
+        let result = context.block_on(with_timeout(
+            Duration::from_millis(10),
+            std::future::pending::<zbus::Result<()>>(),
+        ));
+        assert_eq!(result.unwrap_err().to_string(), "Timed out after 10ms");
+        let result = context.block_on(with_timeout(Duration::from_millis(10), async { Ok(42) }));
+        assert_eq!(result.unwrap(), 42);
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Batch trace events about many items into a single summary.
+//!
+//! Logging one event per project floods the journal for long lists of recent projects, so by
+//! default we only log a summary with the number of items and a few samples.
+
+use std::fmt::{Arguments, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The number of sample items to include in a summary.
+const SAMPLE_SIZE: usize = 5;
+
+/// Whether to log every item instead of a summary.
+static TRACE_EACH_ITEM: AtomicBool = AtomicBool::new(false);
+
+/// Set whether to log a trace event for every item instead of a summary.
+pub fn set_trace_each_item(enabled: bool) {
+    TRACE_EACH_ITEM.store(enabled, Ordering::Relaxed);
+}
+
+/// A batch of trace events about items.
+#[derive(Debug)]
+pub struct TraceBatch {
+    what: &'static str,
+    emit: fn(Arguments<'_>),
+    count: usize,
+    sample: Vec<String>,
+}
+
+impl TraceBatch {
+    /// Create a new empty batch, describing its items with `what`.
+    ///
+    /// Log items and the summary with `emit`, which should log a trace event, e.g.
+    /// `|message| event!(Level::TRACE, "{message}")`.  This way events keep the target and module
+    /// path of the caller, and log filters for the caller apply to them.
+    pub fn new(what: &'static str, emit: fn(Arguments<'_>)) -> Self {
+        Self {
+            what,
+            emit,
+            count: 0,
+            sample: Vec::new(),
+        }
+    }
+
+    /// Add an `item` to this batch.
+    ///
+    /// If enabled with [`set_trace_each_item`] log the item right away.
+    pub fn add<T: Display>(&mut self, item: T) {
+        if TRACE_EACH_ITEM.load(Ordering::Relaxed) {
+            (self.emit)(format_args!("{}: {}", self.what, item));
+        } else if self.sample.len() < SAMPLE_SIZE {
+            self.sample.push(item.to_string());
+        }
+        self.count += 1;
+    }
+
+    /// Summarize this batch.
+    ///
+    /// Return `None` if the batch is empty.
+    fn summary(&self) -> Option<String> {
+        if self.count == 0 {
+            None
+        } else if self.count <= self.sample.len() {
+            Some(format!(
+                "{} ({}): {}",
+                self.what,
+                self.count,
+                self.sample.join(", ")
+            ))
+        } else {
+            Some(format!(
+                "{} ({}): {}, and {} more",
+                self.what,
+                self.count,
+                self.sample.join(", "),
+                self.count - self.sample.len()
+            ))
+        }
+    }
+}
+
+impl Drop for TraceBatch {
+    /// Log a summary of this batch, unless we logged every item already.
+    fn drop(&mut self) {
+        if !TRACE_EACH_ITEM.load(Ordering::Relaxed) {
+            if let Some(summary) = self.summary() {
+                (self.emit)(format_args!("{}", summary));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    fn batch(count: usize) -> TraceBatch {
+        TraceBatch {
+            what: "Found projects",
+            emit: |_| {},
+            count,
+            sample: (0..count.min(SAMPLE_SIZE))
+                .map(|i| format!("p{i}"))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn summarize_batches() {
+        assert_eq!(batch(0).summary(), None);
+        assert_eq!(batch(2).summary().unwrap(), "Found projects (2): p0, p1");
+        assert_eq!(
+            batch(7).summary().unwrap(),
+            "Found projects (7): p0, p1, p2, p3, p4, and 2 more"
+        );
+    }
+
+    #[test]
+    fn emit_summary_through_caller() {
+        thread_local! {
+            static MESSAGES: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+        }
+        let mut batch = TraceBatch::new("Found projects", |message| {
+            MESSAGES.with_borrow_mut(|messages| messages.push(message.to_string()));
+        });
+        batch.add("mdcat");
+        batch.add("foo");
+        drop(batch);
+        assert_eq!(
+            MESSAGES.take(),
+            vec!["Found projects (2): mdcat, foo".to_string()]
+        );
+    }
+}
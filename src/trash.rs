@@ -0,0 +1,149 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Find recent projects in the trash, and restore them.
+//!
+//! Users sometimes trash a project by accident; instead of silently dropping the project from
+//! search results we can offer to restore it from the trash on activation.
+
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use tracing::{event, Level};
+
+/// A recent project which is in the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedProject {
+    /// The URI of the project in the trash.
+    pub uri: String,
+    /// The path to restore the project to.
+    pub original_path: String,
+}
+
+/// Get all items in the trash of the current user, by their original path.
+///
+/// Return an empty map if the trash is not available, e.g. because gvfs is not running.
+pub fn trashed_items() -> HashMap<String, TrashedProject> {
+    let trash = gio::File::for_uri("trash:///");
+    let enumerator = match trash.enumerate_children(
+        "standard::name,trash::orig-path",
+        gio::FileQueryInfoFlags::NONE,
+        gio::Cancellable::NONE,
+    ) {
+        Ok(enumerator) => enumerator,
+        Err(error) => {
+            event!(Level::DEBUG, %error, "Failed to read trash: {error}");
+            return HashMap::new();
+        }
+    };
+    enumerator
+        .filter_map(|info| info.ok())
+        .filter_map(|info| {
+            let original_path = info.attribute_byte_string("trash::orig-path")?.to_string();
+            let uri = trash.child(info.name()).uri().to_string();
+            Some((original_path.clone(), TrashedProject { uri, original_path }))
+        })
+        .collect()
+}
+
+/// Restore the `project` from the trash.
+///
+/// Fail if the project could not be moved back to its original path, e.g. because there's a new
+/// directory at this path.
+#[cfg(feature = "launch")]
+pub fn restore(project: &TrashedProject) -> Result<(), glib::Error> {
+    event!(
+        Level::INFO,
+        "Restoring {} from {}",
+        project.original_path,
+        project.uri
+    );
+    gio::File::for_uri(&project.uri).move_(
+        &gio::File::for_path(&project.original_path),
+        gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+        gio::Cancellable::NONE,
+        None,
+    )
+}
+
+#[cfg(feature = "launch")]
+pub use confirm::confirm_restore;
+
+#[cfg(feature = "launch")]
+mod confirm {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use tracing::{event, instrument, Level};
+    use zbus::export::futures_util::{stream, StreamExt};
+
+    use crate::notifications::NotificationsProxy;
+    use crate::private::PrivateMode;
+    use crate::timeout::with_timeout;
+
+    /// The action to restore a project.
+    const RESTORE_ACTION: &str = "restore";
+
+    /// Ask the user to confirm restoring the project `name` at `original_path` from the trash.
+    ///
+    /// Show a notification with an action to restore the project, and wait until the user
+    /// invokes the action or closes the notification.  Return whether the user invoked the
+    /// restore action.
+    ///
+    /// While `private_mode` is enabled do not show any notification, and do not restore.
+    #[instrument(skip(connection, private_mode))]
+    pub async fn confirm_restore(
+        connection: &zbus::Connection,
+        app_icon: &str,
+        name: &str,
+        original_path: &str,
+        call_timeout: Duration,
+        private_mode: &PrivateMode,
+    ) -> zbus::Result<bool> {
+        if private_mode.is_enabled() {
+            event!(
+                Level::DEBUG,
+                "Private mode enabled, not asking to restore {original_path}"
+            );
+            return Ok(false);
+        }
+        let notifications = NotificationsProxy::new(connection).await?;
+        // Subscribe to signals before showing the notification, to not miss any signal
+        let actions = notifications
+            .receive_action_invoked()
+            .await?
+            .filter_map(|signal| {
+                let action = signal.args().ok().map(|a| (a.id, Some(a.action_key)));
+                std::future::ready(action)
+            });
+        let closed = notifications
+            .receive_notification_closed()
+            .await?
+            .filter_map(|signal| std::future::ready(signal.args().ok().map(|a| (a.id, None))));
+        let id = with_timeout(
+            call_timeout,
+            notifications.notify(
+                env!("CARGO_PKG_NAME"),
+                0,
+                app_icon,
+                &format!("Restore {name} from trash?"),
+                &format!("{original_path} is in the trash."),
+                &[RESTORE_ACTION, "Restore and open"],
+                HashMap::new(),
+                0,
+            ),
+        )
+        .await?;
+        event!(Level::DEBUG, "Waiting for action on notification {id}");
+        let action = stream::select(actions, closed)
+            .filter(|(signal_id, _)| std::future::ready(*signal_id == id))
+            .next()
+            .await
+            .and_then(|(_, action)| action);
+        event!(Level::DEBUG, "Notification {id} got action {action:?}");
+        Ok(action.as_deref() == Some(RESTORE_ACTION))
+    }
+}
@@ -0,0 +1,483 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Validate an installation of this service, for packagers.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::capabilities::PROVIDER_VERSION;
+use crate::providers::{ProviderDefinition, OBJECT_PATH_PREFIX};
+use crate::settings::{clashing_obj_paths, CustomProvider};
+
+/// The name of the systemd user unit of this service.
+pub const SYSTEMD_UNIT: &str = concat!(env!("CARGO_PKG_NAME"), ".service");
+
+/// The severity of a finding.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// Everything's fine.
+    Ok,
+    /// Something looks odd, but doesn't break the service.
+    Warning,
+    /// Something's broken.
+    Error,
+}
+
+/// A single finding of the validation.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// How severe this finding is.
+    pub severity: Severity,
+    /// A human readable message.
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Ok => "ok",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}: {}", self.message)
+    }
+}
+
+/// Collects findings.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// All findings, in order.
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, message: String) {
+        self.findings.push(Finding { severity, message });
+    }
+
+    fn ok(&mut self, message: String) {
+        self.push(Severity::Ok, message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.push(Severity::Warning, message);
+    }
+
+    fn error(&mut self, message: String) {
+        self.push(Severity::Error, message);
+    }
+
+    /// The number of errors in this report.
+    pub fn errors(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count()
+    }
+}
+
+/// Where to look for installed files.
+#[derive(Debug)]
+pub struct InstallLocations {
+    /// XDG data directories, for search providers and DBus services.
+    pub data_dirs: Vec<PathBuf>,
+    /// Directories for systemd user units.
+    pub unit_dirs: Vec<PathBuf>,
+}
+
+impl InstallLocations {
+    /// The standard locations of the current user.
+    pub fn standard() -> Self {
+        let mut data_dirs = vec![glib::user_data_dir()];
+        data_dirs.extend(glib::system_data_dirs());
+        let mut unit_dirs = vec![glib::user_config_dir().join("systemd").join("user")];
+        unit_dirs.extend(data_dirs.iter().map(|d| d.join("systemd").join("user")));
+        unit_dirs.extend(
+            [
+                "/etc/systemd/user",
+                "/usr/local/lib/systemd/user",
+                "/usr/lib/systemd/user",
+            ]
+            .map(PathBuf::from),
+        );
+        Self {
+            data_dirs,
+            unit_dirs,
+        }
+    }
+}
+
+/// Find the first existing file `name` in any of `dirs`.
+fn find_file(dirs: &[PathBuf], name: &str) -> Option<PathBuf> {
+    dirs.iter().map(|d| d.join(name)).find(|p| p.is_file())
+}
+
+/// Load a key file from `path`, or add an error to `report`.
+fn load_key_file(report: &mut Report, path: &Path) -> Option<glib::KeyFile> {
+    let key_file = glib::KeyFile::new();
+    match key_file.load_from_file(path, glib::KeyFileFlags::NONE) {
+        Ok(()) => Some(key_file),
+        Err(error) => {
+            report.error(format!("Failed to parse {}: {error}", path.display()));
+            None
+        }
+    }
+}
+
+/// Check that `key` in `group` of `key_file` at `path` has the `expected` value.
+fn check_key(
+    report: &mut Report,
+    path: &Path,
+    key_file: &glib::KeyFile,
+    (group, key): (&str, &str),
+    expected: &str,
+) -> bool {
+    match key_file.string(group, key) {
+        Ok(value) if value == expected => true,
+        Ok(value) => {
+            report.error(format!(
+                "{}: {key} is {value}, expected {expected}",
+                path.display()
+            ));
+            false
+        }
+        Err(_) => {
+            report.error(format!("{}: {key} missing", path.display()));
+            false
+        }
+    }
+}
+
+/// Check the search provider files for `providers` in `data_dirs`.
+///
+/// Consider all provider files which refer to `busname`; expect one file for each provider, and
+/// no file for any other desktop ID.
+fn check_search_providers(
+    report: &mut Report,
+    data_dirs: &[PathBuf],
+    providers: &[&ProviderDefinition],
+    busname: &str,
+) {
+    const GROUP: &str = "Shell Search Provider";
+    let mut files = Vec::new();
+    for dir in data_dirs {
+        let dir = dir.join("gnome-shell").join("search-providers");
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().is_some_and(|e| e == "ini") {
+                if let Some(key_file) = load_key_file(report, &path) {
+                    if key_file
+                        .string(GROUP, "BusName")
+                        .is_ok_and(|b| b == busname)
+                    {
+                        files.push((path, key_file));
+                    }
+                }
+            }
+        }
+    }
+
+    for provider in providers {
+        let matching = files
+            .iter()
+            .filter(|(_, f)| {
+                f.string(GROUP, "DesktopId")
+                    .is_ok_and(|id| id == provider.desktop_id)
+            })
+            .collect::<Vec<_>>();
+        match matching.as_slice() {
+            [] => report.error(format!(
+                "No search provider file for {} ({})",
+                provider.label, provider.desktop_id
+            )),
+            [(path, key_file)] => {
+                let object_path = provider.objpath_with_prefix(OBJECT_PATH_PREFIX);
+                let is_valid =
+                    check_key(report, path, key_file, (GROUP, "ObjectPath"), &object_path)
+                        & check_key(
+                            report,
+                            path,
+                            key_file,
+                            (GROUP, "Version"),
+                            &PROVIDER_VERSION.to_string(),
+                        );
+                if is_valid {
+                    report.ok(format!(
+                        "Search provider file for {} at {}",
+                        provider.label,
+                        path.display()
+                    ));
+                }
+            }
+            _ => report.warning(format!(
+                "Multiple search provider files for {}: {}",
+                provider.label,
+                matching
+                    .iter()
+                    .map(|(p, _)| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+        if gio::DesktopAppInfo::new(provider.desktop_id).is_some() {
+            report.ok(format!("Desktop entry {} found", provider.desktop_id));
+        } else {
+            report.warning(format!(
+                "Desktop entry {} not found; is {} installed?",
+                provider.desktop_id, provider.label
+            ));
+        }
+    }
+
+    for (path, key_file) in &files {
+        let desktop_id = key_file.string(GROUP, "DesktopId").unwrap_or_default();
+        if !providers.iter().any(|p| p.desktop_id == desktop_id) {
+            report.error(format!(
+                "{} refers to unknown desktop ID {desktop_id}",
+                path.display()
+            ));
+        }
+    }
+}
+
+/// Check the DBus service file for `busname` in `data_dirs`.
+fn check_dbus_service(report: &mut Report, data_dirs: &[PathBuf], busname: &str) {
+    const GROUP: &str = "D-BUS Service";
+    let name = format!("dbus-1/services/{busname}.service");
+    match find_file(data_dirs, &name) {
+        None => report.error(format!("DBus service file {name} not found")),
+        Some(path) => {
+            if let Some(key_file) = load_key_file(report, &path) {
+                let is_valid = check_key(report, &path, &key_file, (GROUP, "Name"), busname)
+                    & check_key(
+                        report,
+                        &path,
+                        &key_file,
+                        (GROUP, "SystemdService"),
+                        SYSTEMD_UNIT,
+                    );
+                if is_valid {
+                    report.ok(format!("DBus service file at {}", path.display()));
+                }
+            }
+        }
+    }
+}
+
+/// Check the systemd user unit in `unit_dirs`.
+fn check_systemd_unit(report: &mut Report, unit_dirs: &[PathBuf], busname: &str) {
+    match find_file(unit_dirs, SYSTEMD_UNIT) {
+        None => report.error(format!("Systemd user unit {SYSTEMD_UNIT} not found")),
+        Some(path) => {
+            if let Some(key_file) = load_key_file(report, &path) {
+                let is_valid = check_key(report, &path, &key_file, ("Service", "BusName"), busname);
+                let exec_start = key_file.string("Service", "ExecStart").unwrap_or_default();
+                let program = exec_start.split_whitespace().next().unwrap_or_default();
+                let binary = if program.starts_with('/') {
+                    Some(PathBuf::from(program)).filter(|p| p.is_file())
+                } else if program.is_empty() {
+                    None
+                } else {
+                    glib::find_program_in_path(program)
+                };
+                let exec_valid = match binary {
+                    Some(binary) if binary.file_name() == Some(env!("CARGO_BIN_NAME").as_ref()) => {
+                        true
+                    }
+                    Some(binary) => {
+                        report.error(format!(
+                            "{}: ExecStart refers to {}, expected {}",
+                            path.display(),
+                            binary.display(),
+                            env!("CARGO_BIN_NAME")
+                        ));
+                        false
+                    }
+                    None => {
+                        report.error(format!(
+                            "{}: ExecStart binary {program:?} not found",
+                            path.display()
+                        ));
+                        false
+                    }
+                };
+                if is_valid && exec_valid {
+                    report.ok(format!("Systemd user unit at {}", path.display()));
+                }
+            }
+        }
+    }
+}
+
+/// Add whether the service could load its settings to `report`.
+///
+/// The service falls back to default settings for diagnostics if loading failed with `error`,
+/// but refuses to serve search providers.
+pub fn check_settings(report: &mut Report, error: Option<&anyhow::Error>) {
+    match error {
+        None => report.ok("Settings".to_string()),
+        Some(error) => report.error(format!("Invalid settings, using defaults: {error:#}")),
+    }
+}
+
+/// Add additional providers from settings whose object path clashes with another provider to
+/// `report`.
+///
+/// The service serves these providers at a unique object path instead, which their search
+/// provider files need to refer to.
+pub fn check_custom_providers(report: &mut Report, providers: &[CustomProvider]) {
+    for (provider, other) in clashing_obj_paths(providers) {
+        report.warning(format!(
+            "Object path of [{}] clashes with [{}], serving it at {OBJECT_PATH_PREFIX}/{} instead",
+            provider.group(),
+            other.group(),
+            provider.relative_obj_path
+        ));
+    }
+}
+
+/// Validate the installation of `providers` for `busname` in the given `locations`.
+pub fn validate_install(
+    locations: &InstallLocations,
+    providers: &[&ProviderDefinition],
+    busname: &str,
+) -> Report {
+    let mut report = Report::default();
+    check_search_providers(&mut report, &locations.data_dirs, providers, busname);
+    check_dbus_service(&mut report, &locations.data_dirs, busname);
+    check_systemd_unit(&mut report, &locations.unit_dirs, busname);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::PROVIDERS;
+    use crate::BUSNAME;
+    use similar_asserts::assert_eq;
+
+    /// Install all files from the source tree to `prefix`, like `make install` does.
+    fn install(prefix: &Path) -> InstallLocations {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let data_dir = prefix.join("share");
+        let providers_dir = data_dir.join("gnome-shell").join("search-providers");
+        let services_dir = data_dir.join("dbus-1").join("services");
+        let unit_dir = prefix.join("lib").join("systemd").join("user");
+        for dir in [&providers_dir, &services_dir, &unit_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        for entry in std::fs::read_dir(source.join("providers")).unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(&path, providers_dir.join(path.file_name().unwrap())).unwrap();
+        }
+        let service = format!("{BUSNAME}.service");
+        std::fs::copy(
+            source.join("dbus-1").join(&service),
+            services_dir.join(&service),
+        )
+        .unwrap();
+        // Point the unit to an absolute binary, since the test binary isn't in $PATH
+        let binary = prefix.join("bin").join(env!("CARGO_BIN_NAME"));
+        std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        std::fs::write(&binary, "").unwrap();
+        let unit = std::fs::read_to_string(source.join("systemd").join(SYSTEMD_UNIT))
+            .unwrap()
+            .replace(
+                concat!("ExecStart=", env!("CARGO_BIN_NAME")),
+                &format!("ExecStart={}", binary.display()),
+            );
+        std::fs::write(unit_dir.join(SYSTEMD_UNIT), unit).unwrap();
+        InstallLocations {
+            data_dirs: vec![data_dir],
+            unit_dirs: vec![unit_dir],
+        }
+    }
+
+    #[test]
+    fn validate_installed_source_tree() {
+        let prefix = glib::mkdtemp(std::env::temp_dir().join("install-XXXXXX")).unwrap();
+        let locations = install(&prefix);
+
+        let report = validate_install(&locations, &PROVIDERS.iter().collect::<Vec<_>>(), BUSNAME);
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|f| f.severity == Severity::Error)
+                .collect::<Vec<_>>(),
+            Vec::<&Finding>::new()
+        );
+
+        let ini = locations.data_dirs[0]
+            .join("gnome-shell")
+            .join("search-providers")
+            .join("de.swsnr.searchprovider.jetbrains.toolbox.clion.ini");
+        let contents = std::fs::read_to_string(&ini).unwrap();
+        std::fs::write(&ini, contents.replace("toolbox/clion", "toolbox/clang")).unwrap();
+        std::fs::remove_file(locations.unit_dirs[0].join(SYSTEMD_UNIT)).unwrap();
+        let report = validate_install(&locations, &PROVIDERS.iter().collect::<Vec<_>>(), BUSNAME);
+        assert_eq!(report.errors(), 2);
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|f| f.severity == Severity::Error)
+                .map(|f| f.message.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                format!(
+                    "{}: ObjectPath is /de/swsnr/searchprovider/jetbrains/toolbox/clang, \
+expected /de/swsnr/searchprovider/jetbrains/toolbox/clion",
+                    ini.display()
+                ),
+                format!("Systemd user unit {SYSTEMD_UNIT} not found"),
+            ]
+        );
+
+        std::fs::remove_dir_all(prefix).unwrap();
+    }
+
+    #[test]
+    fn report_clashing_object_paths() {
+        let settings = crate::settings::Settings::parse(
+            "[Provider foo-bar]\nConfigPrefix=Foo\n\n[Provider foo_bar]\nConfigPrefix=Foo\n",
+            PROVIDERS,
+        )
+        .unwrap();
+        let mut report = Report::default();
+        check_custom_providers(&mut report, &settings.custom_providers);
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "warning: Object path of [Provider foo_bar.desktop] clashes with [Provider foo-bar.desktop], \
+serving it at /de/swsnr/searchprovider/jetbrains/custom/foo_bar_2 instead"
+            ]
+        );
+    }
+
+    #[test]
+    fn report_invalid_settings() {
+        let mut report = Report::default();
+        check_settings(&mut report, None);
+        assert_eq!(report.errors(), 0);
+        check_settings(
+            &mut report,
+            Some(&anyhow::anyhow!("Invalid value for Lazy in [Options]")),
+        );
+        assert_eq!(report.errors(), 1);
+        assert_eq!(
+            report.findings[1].to_string(),
+            "error: Invalid settings, using defaults: Invalid value for Lazy in [Options]"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Watch recent projects files for changes.
+//!
+//! IDEs rewrite their recent projects file whenever the user opens a project, so we monitor
+//! these files, and invalidate the recent projects of a provider on changes, to reload them on the
+//! next search.  IDEs replace these files atomically, so we monitor the directories containing
+//! the files rather than the files themselves.  Writes often come in bursts; they all merge into
+//! a single reload.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use gio::prelude::*;
+use glib::thread_guard::ThreadGuard;
+use tracing::{event, Level};
+
+/// Whether `event` can change the contents of a file.
+fn is_change(event: gio::FileMonitorEvent) -> bool {
+    use gio::FileMonitorEvent::*;
+    matches!(
+        event,
+        ChangesDoneHint | Created | Deleted | MovedIn | MovedOut | Renamed
+    )
+}
+
+/// Watch recent projects files for changes.
+pub struct ProjectsWatcher {
+    /// Whether a watched file changed since we last checked.
+    changed: Arc<AtomicBool>,
+    /// The watched files by directory.
+    files: BTreeMap<PathBuf, Vec<OsString>>,
+    /// Monitors for all directories in `files`.
+    ///
+    /// File monitors are bound to the main context of the thread which created them.
+    monitors: Mutex<ThreadGuard<Vec<gio::FileMonitor>>>,
+}
+
+impl Default for ProjectsWatcher {
+    fn default() -> Self {
+        Self {
+            changed: Arc::default(),
+            files: BTreeMap::new(),
+            monitors: Mutex::new(ThreadGuard::new(Vec::new())),
+        }
+    }
+}
+
+impl Debug for ProjectsWatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProjectsWatcher")
+            .field("changed", &self.changed)
+            .field("files", &self.files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProjectsWatcher {
+    /// Watch `files`, and stop watching all other files.
+    ///
+    /// Keep the current monitors if `files` didn't change.  Skip directories we fail to monitor.
+    pub fn watch<'a, I: IntoIterator<Item = &'a Path>>(&mut self, files: I) {
+        let mut by_directory: BTreeMap<PathBuf, Vec<OsString>> = BTreeMap::new();
+        for file in files {
+            if let (Some(directory), Some(name)) = (file.parent(), file.file_name()) {
+                by_directory
+                    .entry(directory.to_path_buf())
+                    .or_default()
+                    .push(name.to_os_string());
+            }
+        }
+        if by_directory == self.files {
+            return;
+        }
+        let monitors = by_directory
+            .iter()
+            .filter_map(|(directory, names)| {
+                let monitor = gio::File::for_path(directory)
+                    .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+                    .map_err(|error| {
+                        event!(
+                            Level::WARN,
+                            "Failed to watch {}: {error}",
+                            directory.display()
+                        );
+                    })
+                    .ok()?;
+                event!(Level::DEBUG, "Watching {} for changes", directory.display());
+                let names = names.clone();
+                let changed = self.changed.clone();
+                monitor.connect_changed(move |_, file, other_file, event| {
+                    let is_watched = |file: &gio::File| {
+                        file.basename()
+                            .is_some_and(|name| names.iter().any(|n| *n == name.as_os_str()))
+                    };
+                    if is_change(event) && (is_watched(file) || other_file.is_some_and(is_watched))
+                    {
+                        event!(
+                            Level::DEBUG,
+                            "Recent projects file {} changed: {event:?}",
+                            file.parse_name()
+                        );
+                        changed.store(true, Ordering::SeqCst);
+                    }
+                });
+                Some(monitor)
+            })
+            .collect();
+        *self.monitors.get_mut().unwrap().get_mut() = monitors;
+        self.files = by_directory;
+    }
+
+    /// Whether any watched file changed since the last call.
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn detect_atomic_replacement_of_watched_file() {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let directory = glib::mkdtemp(std::env::temp_dir().join("options-XXXXXX")).unwrap();
+                let file = directory.join("recentProjects.xml");
+                std::fs::write(&file, "<application />").unwrap();
+
+                let mut watcher = ProjectsWatcher::default();
+                watcher.watch([file.as_path()]);
+                assert!(!watcher.take_changed());
+
+                let wait_for_change = |watcher: &ProjectsWatcher, timeout: Duration| {
+                    let deadline = Instant::now() + timeout;
+                    while Instant::now() < deadline {
+                        context.iteration(false);
+                        if watcher.take_changed() {
+                            return true;
+                        }
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    false
+                };
+
+                std::fs::write(directory.join("other.xml"), "<application />").unwrap();
+                let other_changed = wait_for_change(&watcher, Duration::from_millis(500));
+                let temp = directory.join("recentProjects.xml.tmp");
+                std::fs::write(&temp, "<application><component /></application>").unwrap();
+                std::fs::rename(&temp, &file).unwrap();
+                let watched_changed = wait_for_change(&watcher, Duration::from_secs(5));
+                drop(watcher);
+                std::fs::remove_dir_all(&directory).unwrap();
+                assert!(!other_changed);
+                assert!(watched_changed);
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Detect git worktrees of project directories.
+
+use std::path::{Path, PathBuf};
+
+use tracing::{event, Level};
+
+/// A git checkout of a project directory.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GitCheckout {
+    /// The common git directory of the repository.
+    ///
+    /// All worktrees of the same repository share the same common directory.
+    pub common_dir: PathBuf,
+
+    /// The branch checked out in this worktree, or `None` if `HEAD` is detached.
+    pub branch: Option<String>,
+}
+
+/// Parse the branch name from the contents of a `HEAD` file.
+///
+/// Return `None` if `HEAD` is detached, i.e. doesn't point to a branch.
+fn parse_head(head: &str) -> Option<String> {
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(ToString::to_string)
+}
+
+/// Find the git directory and the common git directory for the worktree at `directory`.
+///
+/// For the main worktree both are the `.git` directory; for linked worktrees `.git` is a file
+/// pointing to the git directory of the worktree, whose `commondir` file in turn points to the
+/// git directory of the main worktree.
+fn find_git_dirs(directory: &Path) -> Option<(PathBuf, PathBuf)> {
+    let dot_git = directory.join(".git");
+    if dot_git.is_dir() {
+        let common_dir = dot_git.canonicalize().ok()?;
+        Some((dot_git, common_dir))
+    } else {
+        let contents = std::fs::read_to_string(&dot_git).ok()?;
+        let git_dir = directory.join(contents.trim().strip_prefix("gitdir:")?.trim());
+        let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        Some((git_dir, common_dir.canonicalize().ok()?))
+    }
+}
+
+/// Inspect the git checkout at `directory`.
+///
+/// Return `None` if `directory` is no git checkout, or if we fail to read the git directory.
+pub fn inspect<P: AsRef<Path>>(directory: P) -> Option<GitCheckout> {
+    let (git_dir, common_dir) = find_git_dirs(directory.as_ref())?;
+    let head = match std::fs::read_to_string(git_dir.join("HEAD")) {
+        Ok(head) => head,
+        Err(error) => {
+            event!(
+                Level::DEBUG,
+                "Failed to read HEAD of {}: {error}",
+                git_dir.display()
+            );
+            return None;
+        }
+    };
+    Some(GitCheckout {
+        common_dir,
+        branch: parse_head(&head),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn parse_head_of_branch() {
+        assert_eq!(
+            parse_head("ref: refs/heads/feature/foo\n"),
+            Some("feature/foo".to_string())
+        );
+        assert_eq!(
+            parse_head("8f3c0e1d2b4a5c6d7e8f9a0b1c2d3e4f5a6b7c8d\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn inspect_main_and_linked_worktree() {
+        let root = glib::mkdtemp(std::env::temp_dir().join("worktree-XXXXXX")).unwrap();
+        let main = root.join("repo");
+        let main_git = main.join(".git");
+        let linked = root.join("repo-feature");
+        let linked_git = main_git.join("worktrees").join("repo-feature");
+        std::fs::create_dir_all(&linked_git).unwrap();
+        std::fs::create_dir_all(&linked).unwrap();
+        std::fs::write(main_git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(linked_git.join("HEAD"), "ref: refs/heads/feature\n").unwrap();
+        std::fs::write(linked_git.join("commondir"), "../..\n").unwrap();
+        std::fs::write(
+            linked.join(".git"),
+            format!("gitdir: {}\n", linked_git.display()),
+        )
+        .unwrap();
+
+        let main_checkout = inspect(&main).unwrap();
+        let linked_checkout = inspect(&linked).unwrap();
+        assert_eq!(main_checkout.branch, Some("main".to_string()));
+        assert_eq!(linked_checkout.branch, Some("feature".to_string()));
+        assert_eq!(main_checkout.common_dir, linked_checkout.common_dir);
+        assert_eq!(inspect(&root), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}